@@ -10,22 +10,19 @@ fn main() {
     println!("got name {:?}", bn);
 
     let op = bus::ObjectPath::from_bytes(b"/com/codyps/systemd_test\0").unwrap();
-    bus.add_object(op, |m| {
-        println!("message: {:?}", m);
-        Ok(())
-    })
-    .unwrap();
+    let _object = bus
+        .add_object(op, |m| {
+            println!("message: {:?}", m);
+            Ok(())
+        })
+        .unwrap();
     println!("added object: {:?}", op);
 
-    loop {
-        println!("wait?");
-        bus.wait(None).unwrap();
-        match bus.process().unwrap() {
-            Some(Some(m)) => println!("handled message: {:?}", m.as_ref()),
-            Some(None) => println!("made progress"),
-            None => println!("no progress"),
-        }
-    }
+    bus.run_until(|m| {
+        println!("unhandled message: {:?}", m);
+        false
+    })
+    .unwrap();
 }
 
 #[cfg(not(feature = "bus"))]
@@ -0,0 +1,74 @@
+#![warn(rust_2018_idioms)]
+
+//! Compare building a method call from scratch for every call against duplicating one built
+//! before, both for constructing the message alone and for the complete call.
+//!
+//! `cargo run --release --example bus-message-reuse [iterations]`
+
+#[cfg(feature = "bus")]
+fn main() {
+    use std::time::Instant;
+    use systemd::bus;
+    use utf8_cstr::Utf8CStr;
+
+    let iterations: u32 = std::env::args()
+        .nth(1)
+        .map(|n| n.parse().expect("iterations must be a number"))
+        .unwrap_or(10_000);
+
+    let mut bus = bus::Bus::default_system().unwrap();
+    let dest = bus::BusName::from_bytes(b"org.freedesktop.DBus\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/freedesktop/DBus\0").unwrap();
+    let interface = bus::InterfaceName::from_bytes(b"org.freedesktop.DBus\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"NameHasOwner\0").unwrap();
+    let name = Utf8CStr::from_bytes(b"org.freedesktop.DBus\0").unwrap();
+
+    let mut build = || {
+        let mut m = bus.new_method_call(dest, path, interface, member).unwrap();
+        m.append(name).unwrap();
+        m
+    };
+
+    // calling seals the message, so it can be duplicated afterwards
+    let mut template = build();
+    template.call(0).unwrap();
+
+    let report = |what: &str, start: Instant| {
+        let elapsed = start.elapsed();
+        println!(
+            "{:>24}: {:?} total, {:?} per iteration",
+            what,
+            elapsed,
+            elapsed / iterations
+        );
+    };
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        drop(build());
+    }
+    report("construct", start);
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        drop(template.duplicate_unsealed().unwrap());
+    }
+    report("duplicate", start);
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        build().call(0).unwrap();
+    }
+    report("construct and call", start);
+
+    let start = Instant::now();
+    for _ in 0..iterations {
+        template.duplicate_unsealed().unwrap().call(0).unwrap();
+    }
+    report("duplicate and call", start);
+}
+
+#[cfg(not(feature = "bus"))]
+fn main() {
+    println!("bus disabled");
+}
@@ -5,7 +5,7 @@ mod x {
     //! Follow future journal log messages and print up to 100 of them.
     use std::io::ErrorKind;
 
-    use systemd::journal::{self, JournalRecord, JournalSeek};
+    use systemd::journal::{self, JournalRecord};
     use systemd::Error;
 
     const KEY_UNIT: &str = "_SYSTEMD_UNIT";
@@ -21,10 +21,9 @@ mod x {
             .open()
             .expect("Could not open journal");
 
-        // Seek to end of current log to prevent old messages from being printed
-        reader
-            .seek(JournalSeek::Tail)
-            .expect("Could not seek to end of journal");
+        // Move to the most recent entry to prevent old messages from being printed. Only the
+        // positioning done by `tail()` is needed, `watch_all_elements()` does the iteration.
+        journal::tail(&mut reader, 0).expect("Could not seek to end of journal");
 
         // Print up to MAX_MESSAGES incoming messages
         let mut i = 0;
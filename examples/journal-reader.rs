@@ -3,7 +3,7 @@
 #[cfg(feature = "journal")]
 mod x {
     //! Follow future journal log messages and print up to 100 of them.
-    use systemd::journal::{self, JournalSeek};
+    use systemd::journal;
 
     const KEY_UNIT: &str = "_SYSTEMD_UNIT";
     const KEY_MESSAGE: &str = "MESSAGE";
@@ -18,45 +18,18 @@ mod x {
             .open()
             .expect("Could not open journal");
 
-        // Seek to end of current log to prevent old messages from being printed
-        reader
-            .seek(JournalSeek::Tail)
-            .expect("Could not seek to end of journal");
+        // Print up to MAX_MESSAGES incoming messages, skipping the ones already in the journal
+        for (i, record) in journal::tail(&mut reader, 0)?.enumerate() {
+            let record = record?;
+            println!("[{:?}] {:?}", record.get(KEY_UNIT), record.get(KEY_MESSAGE));
 
-        // JournalSeek::Tail goes to the position after the most recent entry so step back to
-        // point to the most recent entry.
-        reader.previous()?;
-
-        // Print up to MAX_MESSAGES incoming messages
-        let mut i = 0;
-        loop {
-            loop {
-                if reader.next()? == 0 {
-                    break;
-                }
-
-                let unit: Option<_> = reader.get_data(KEY_UNIT)?.and_then(|v| {
-                    v.value()
-                        .map(String::from_utf8_lossy)
-                        .map(|v| v.into_owned())
-                });
-                let message = reader.get_data(KEY_MESSAGE)?.and_then(|v| {
-                    v.value()
-                        .map(String::from_utf8_lossy)
-                        .map(|v| v.into_owned())
-                });
-
-                println!("[{:?}] {:?}", unit, message);
-
-                i += 1;
-                if i >= MAX_MESSAGES {
-                    eprintln!("done.");
-                    return Ok(());
-                }
+            if i + 1 >= MAX_MESSAGES {
+                eprintln!("done.");
+                break;
             }
-
-            reader.wait(None)?;
         }
+
+        Ok(())
     }
 }
 
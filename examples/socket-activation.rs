@@ -30,15 +30,16 @@
 
 #![warn(rust_2018_idioms)]
 
-use std::io::Write;
-use std::net::TcpStream;
-use systemd::daemon;
-
-fn handle_client(mut stream: TcpStream) {
+#[cfg(feature = "daemon")]
+fn handle_client(mut stream: std::net::TcpStream) {
+    use std::io::Write;
     stream.write_all(b"HI\n").unwrap();
 }
 
+#[cfg(feature = "daemon")]
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    use systemd::daemon;
+
     let lfds = daemon::listen_fds(false)?;
     if lfds.len() != 1 {
         panic!("Must have exactly 1 fd to listen on, got {}", lfds.len());
@@ -52,3 +53,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     Ok(())
 }
+
+#[cfg(not(feature = "daemon"))]
+fn main() {
+    println!("daemon disabled");
+}
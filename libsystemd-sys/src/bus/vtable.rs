@@ -1,7 +1,8 @@
 use super::super::{c_char, size_t};
 use super::{sd_bus_message_handler_t, sd_bus_property_get_t, sd_bus_property_set_t};
 use std::default::Default;
-use std::mem::{transmute, zeroed};
+use std::mem::{size_of, transmute, zeroed};
+use std::ptr;
 
 // XXX: check this repr, might vary based on platform type sizes
 #[derive(Clone, Copy, Debug)]
@@ -80,6 +81,53 @@ impl sd_bus_vtable {
     }
 }
 
+impl sd_bus_vtable {
+    fn with_data<T>(typ: SdBusVtableType, flags: u64, data: T) -> Self {
+        assert!(size_of::<T>() <= size_of::<[usize; 5]>());
+        let mut v = sd_bus_vtable {
+            type_and_flags: sd_bus_vtable::type_and_flags(typ as u32, flags),
+            ..Default::default()
+        };
+        unsafe { ptr::write(v.union_data.as_mut_ptr() as *mut T, data) };
+        v
+    }
+
+    /// The first entry of every vtable, equivalent to `SD_BUS_VTABLE_START(flags)`
+    ///
+    /// This uses the element size of this struct, which sd-bus treats as the (pre v242) format
+    /// without parameter names.
+    pub fn start(flags: u64) -> Self {
+        Self::with_data(
+            SdBusVtableType::Start,
+            flags,
+            sd_bus_table_start {
+                element_size: size_of::<sd_bus_vtable>(),
+            },
+        )
+    }
+
+    /// The last entry of every vtable, equivalent to `SD_BUS_VTABLE_END`
+    pub fn end() -> Self {
+        Self::with_data(SdBusVtableType::End, 0, ())
+    }
+
+    /// A property entry, equivalent to `SD_BUS_PROPERTY()` or `SD_BUS_WRITABLE_PROPERTY()`
+    /// depending on `typ`
+    pub fn property(typ: SdBusVtableType, flags: u64, property: sd_bus_table_property) -> Self {
+        Self::with_data(typ, flags, property)
+    }
+
+    /// A method entry, equivalent to `SD_BUS_METHOD_WITH_OFFSET()`
+    pub fn method(flags: u64, method: sd_bus_table_method) -> Self {
+        Self::with_data(SdBusVtableType::Method, flags, method)
+    }
+
+    /// A signal entry, equivalent to `SD_BUS_SIGNAL()`
+    pub fn signal(flags: u64, signal: sd_bus_table_signal) -> Self {
+        Self::with_data(SdBusVtableType::Signal, flags, signal)
+    }
+}
+
 #[test]
 fn vtable_bitfield() {
     let mut b: sd_bus_vtable = Default::default();
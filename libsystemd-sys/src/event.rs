@@ -1,6 +1,8 @@
 // automatically generated by rust-bindgen with manual adjustments
 
-use super::{c_char, c_int, c_void, clockid_t, pid_t, siginfo_t, signalfd_siginfo};
+use super::{
+    c_char, c_int, c_uint, c_void, clockid_t, inotify_event, pid_t, siginfo_t, signalfd_siginfo,
+};
 
 #[allow(non_camel_case_types)]
 pub enum sd_event {}
@@ -22,6 +24,10 @@ pub const SD_EVENT_PRIORITY_IMPORTANT: i32 = -100;
 pub const SD_EVENT_PRIORITY_NORMAL: i32 = 0;
 pub const SD_EVENT_PRIORITY_IDLE: i32 = 100;
 
+/// May be or'd into the signal number passed to `sd_event_add_signal()` to have the signal
+/// blocked automatically (since v253)
+pub const SD_EVENT_SIGNAL_PROCMASK: c_int = 1 << 30;
+
 #[allow(non_camel_case_types)]
 pub type sd_event_handler_t =
     Option<unsafe extern "C" fn(s: *mut sd_event_source, userdata: *mut c_void) -> c_int>;
@@ -54,6 +60,17 @@ pub type sd_event_child_handler_t = Option<
         userdata: *mut c_void,
     ) -> c_int,
 >;
+#[allow(non_camel_case_types)]
+pub type sd_event_inotify_handler_t = Option<
+    unsafe extern "C" fn(
+        s: *mut sd_event_source,
+        event: *const inotify_event,
+        userdata: *mut c_void,
+    ) -> c_int,
+>;
+#[allow(non_camel_case_types)]
+pub type sd_event_destroy_t = Option<unsafe extern "C" fn(userdata: *mut c_void)>;
+
 extern "C" {
     pub fn sd_event_default(e: *mut *mut sd_event) -> c_int;
     pub fn sd_event_new(e: *mut *mut sd_event) -> c_int;
@@ -76,6 +93,18 @@ extern "C" {
         callback: sd_event_time_handler_t,
         userdata: *mut c_void,
     ) -> c_int;
+    /// Since v247. See [`sd_event_add_time_relative(3)`][1]
+    ///
+    /// [1]: https://www.freedesktop.org/software/systemd/man/sd_event_add_time.html
+    pub fn sd_event_add_time_relative(
+        e: *mut sd_event,
+        s: *mut *mut sd_event_source,
+        clock: clockid_t,
+        usec: u64,
+        accuracy: u64,
+        callback: sd_event_time_handler_t,
+        userdata: *mut c_void,
+    ) -> c_int;
     pub fn sd_event_add_signal(
         e: *mut sd_event,
         s: *mut *mut sd_event_source,
@@ -91,6 +120,48 @@ extern "C" {
         callback: sd_event_child_handler_t,
         userdata: *mut c_void,
     ) -> c_int;
+    /// Since v245. See [`sd_event_add_child_pidfd(3)`][1]
+    ///
+    /// [1]: https://www.freedesktop.org/software/systemd/man/sd_event_add_child.html
+    pub fn sd_event_add_child_pidfd(
+        e: *mut sd_event,
+        s: *mut *mut sd_event_source,
+        pidfd: c_int,
+        options: c_int,
+        callback: sd_event_child_handler_t,
+        userdata: *mut c_void,
+    ) -> c_int;
+    /// Since v239. See [`sd_event_add_inotify(3)`][1]
+    ///
+    /// [1]: https://www.freedesktop.org/software/systemd/man/sd_event_add_inotify.html
+    pub fn sd_event_add_inotify(
+        e: *mut sd_event,
+        s: *mut *mut sd_event_source,
+        path: *const c_char,
+        mask: u32,
+        callback: sd_event_inotify_handler_t,
+        userdata: *mut c_void,
+    ) -> c_int;
+    /// Since v250. See [`sd_event_add_inotify_fd(3)`][1]
+    ///
+    /// [1]: https://www.freedesktop.org/software/systemd/man/sd_event_add_inotify.html
+    pub fn sd_event_add_inotify_fd(
+        e: *mut sd_event,
+        s: *mut *mut sd_event_source,
+        fd: c_int,
+        mask: u32,
+        callback: sd_event_inotify_handler_t,
+        userdata: *mut c_void,
+    ) -> c_int;
+    /// Since v254. See [`sd_event_add_memory_pressure(3)`][1]
+    ///
+    /// [1]: https://www.freedesktop.org/software/systemd/man/sd_event_add_memory_pressure.html
+    pub fn sd_event_add_memory_pressure(
+        e: *mut sd_event,
+        s: *mut *mut sd_event_source,
+        callback: sd_event_handler_t,
+        userdata: *mut c_void,
+    ) -> c_int;
     pub fn sd_event_add_defer(
         e: *mut sd_event,
         s: *mut *mut sd_event_source,
@@ -122,8 +193,24 @@ extern "C" {
     pub fn sd_event_get_exit_code(e: *mut sd_event, code: *mut c_int) -> c_int;
     pub fn sd_event_set_watchdog(e: *mut sd_event, b: c_int) -> c_int;
     pub fn sd_event_get_watchdog(e: *mut sd_event) -> c_int;
+    /// Since v231. See [`sd_event_get_iteration(3)`][1]
+    ///
+    /// [1]: https://www.freedesktop.org/software/systemd/man/sd_event_now.html
+    pub fn sd_event_get_iteration(e: *mut sd_event, ret: *mut u64) -> c_int;
+    /// Since v252. See [`sd_event_set_signal_exit(3)`][1]
+    ///
+    /// [1]: https://www.freedesktop.org/software/systemd/man/sd_event_set_signal_exit.html
+    pub fn sd_event_set_signal_exit(e: *mut sd_event, b: c_int) -> c_int;
+    /// Since v254. See [`sd_event_trim_memory(3)`][1]
+    ///
+    /// [1]: https://www.freedesktop.org/software/systemd/man/sd_event_add_memory_pressure.html
+    pub fn sd_event_trim_memory() -> c_int;
     pub fn sd_event_source_ref(s: *mut sd_event_source) -> *mut sd_event_source;
     pub fn sd_event_source_unref(s: *mut sd_event_source) -> *mut sd_event_source;
+    /// Since v243. See [`sd_event_source_disable_unref(3)`][1]
+    ///
+    /// [1]: https://www.freedesktop.org/software/systemd/man/sd_event_source_unref.html
+    pub fn sd_event_source_disable_unref(s: *mut sd_event_source) -> *mut sd_event_source;
     pub fn sd_event_source_get_event(s: *mut sd_event_source) -> *mut sd_event;
     pub fn sd_event_source_get_userdata(s: *mut sd_event_source) -> *mut c_void;
     pub fn sd_event_source_set_userdata(
@@ -159,4 +246,85 @@ extern "C" {
     pub fn sd_event_source_get_time_clock(s: *mut sd_event_source, clock: *mut clockid_t) -> c_int;
     pub fn sd_event_source_get_signal(s: *mut sd_event_source) -> c_int;
     pub fn sd_event_source_get_child_pid(s: *mut sd_event_source, pid: *mut pid_t) -> c_int;
+
+    // See sd_event_source_set_time(3)
+    // since v247
+    pub fn sd_event_source_set_time_relative(s: *mut sd_event_source, usec: u64) -> c_int;
+
+    // See sd_event_source_set_io_fd(3)
+    // since v237
+    pub fn sd_event_source_get_io_fd_own(s: *mut sd_event_source) -> c_int;
+    pub fn sd_event_source_set_io_fd_own(s: *mut sd_event_source, own: c_int) -> c_int;
+
+    // See sd_event_add_child(3)
+    // since v245
+    pub fn sd_event_source_get_child_pidfd(s: *mut sd_event_source) -> c_int;
+    pub fn sd_event_source_get_child_pidfd_own(s: *mut sd_event_source) -> c_int;
+    pub fn sd_event_source_set_child_pidfd_own(s: *mut sd_event_source, own: c_int) -> c_int;
+    pub fn sd_event_source_get_child_process_own(s: *mut sd_event_source) -> c_int;
+    pub fn sd_event_source_set_child_process_own(s: *mut sd_event_source, own: c_int) -> c_int;
+    pub fn sd_event_source_send_child_signal(
+        s: *mut sd_event_source,
+        sig: c_int,
+        si: *const siginfo_t,
+        flags: c_uint,
+    ) -> c_int;
+
+    // See sd_event_add_inotify(3)
+    // since v239
+    pub fn sd_event_source_get_inotify_mask(s: *mut sd_event_source, ret: *mut u32) -> c_int;
+
+    // See sd_event_source_set_destroy_callback(3)
+    // since v239
+    pub fn sd_event_source_set_destroy_callback(
+        s: *mut sd_event_source,
+        callback: sd_event_destroy_t,
+    ) -> c_int;
+    pub fn sd_event_source_get_destroy_callback(
+        s: *mut sd_event_source,
+        ret: *mut sd_event_destroy_t,
+    ) -> c_int;
+
+    // See sd_event_source_set_floating(3)
+    // since v240
+    pub fn sd_event_source_get_floating(s: *mut sd_event_source) -> c_int;
+    pub fn sd_event_source_set_floating(s: *mut sd_event_source, b: c_int) -> c_int;
+
+    // See sd_event_source_set_exit_on_failure(3)
+    // since v247
+    pub fn sd_event_source_get_exit_on_failure(s: *mut sd_event_source) -> c_int;
+    pub fn sd_event_source_set_exit_on_failure(s: *mut sd_event_source, b: c_int) -> c_int;
+
+    // See sd_event_source_set_ratelimit(3)
+    // since v248
+    pub fn sd_event_source_set_ratelimit(
+        s: *mut sd_event_source,
+        interval_usec: u64,
+        burst: c_uint,
+    ) -> c_int;
+    pub fn sd_event_source_get_ratelimit(
+        s: *mut sd_event_source,
+        ret_interval_usec: *mut u64,
+        ret_burst: *mut c_uint,
+    ) -> c_int;
+    pub fn sd_event_source_is_ratelimited(s: *mut sd_event_source) -> c_int;
+    // since v250
+    pub fn sd_event_source_set_ratelimit_expire_callback(
+        s: *mut sd_event_source,
+        callback: sd_event_handler_t,
+    ) -> c_int;
+    // since v254
+    pub fn sd_event_source_leave_ratelimit(s: *mut sd_event_source) -> c_int;
+
+    // See sd_event_add_memory_pressure(3)
+    // since v254
+    pub fn sd_event_source_set_memory_pressure_type(
+        s: *mut sd_event_source,
+        ty: *const c_char,
+    ) -> c_int;
+    pub fn sd_event_source_set_memory_pressure_period(
+        s: *mut sd_event_source,
+        threshold_usec: u64,
+        window_usec: u64,
+    ) -> c_int;
 }
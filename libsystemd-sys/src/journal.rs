@@ -1,7 +1,7 @@
 #![allow(non_camel_case_types)]
 
 use super::size_t;
-use super::{c_char, c_int, c_void, const_iovec};
+use super::{c_char, c_int, c_uint, c_void, const_iovec};
 
 pub const SD_JOURNAL_LOCAL_ONLY: c_int = 1 << 0;
 pub const SD_JOURNAL_RUNTIME_ONLY: c_int = 1 << 1;
@@ -25,6 +25,12 @@ extern "C" {
     // There are a bunch of other send methods, but for rust it doesn't make sense to call them
     // (we don't need to do c-style format strings)
 
+    pub fn sd_journal_stream_fd(
+        identifier: *const c_char,
+        priority: c_int,
+        level_prefix: c_int,
+    ) -> c_int;
+
     pub fn sd_journal_open(ret: *mut *mut sd_journal, flags: c_int) -> c_int;
     #[cfg(feature = "systemd_v245")]
     pub fn sd_journal_open_namespace(
@@ -42,6 +48,18 @@ extern "C" {
         path: *const *const c_char,
         flags: c_int,
     ) -> c_int;
+    // since v230
+    pub fn sd_journal_open_directory_fd(
+        ret: *mut *mut sd_journal,
+        fd: c_int,
+        flags: c_int,
+    ) -> c_int;
+    pub fn sd_journal_open_files_fd(
+        ret: *mut *mut sd_journal,
+        fds: *const c_int,
+        n_fds: c_uint,
+        flags: c_int,
+    ) -> c_int;
     pub fn sd_journal_close(j: *mut sd_journal);
 
     pub fn sd_journal_previous(j: *mut sd_journal) -> c_int;
@@ -63,7 +81,7 @@ extern "C" {
     pub fn sd_journal_get_data(
         j: *mut sd_journal,
         field: *const c_char,
-        data: *mut *mut u8,
+        data: *mut *const u8,
         l: *mut size_t,
     ) -> c_int;
     pub fn sd_journal_enumerate_data(
@@ -71,8 +89,18 @@ extern "C" {
         data: *mut *const u8,
         l: *mut size_t,
     ) -> c_int;
+    // since v246
+    pub fn sd_journal_enumerate_available_data(
+        j: *mut sd_journal,
+        data: *mut *const u8,
+        l: *mut size_t,
+    ) -> c_int;
     pub fn sd_journal_restart_data(j: *mut sd_journal);
 
+    // since v229
+    pub fn sd_journal_enumerate_fields(j: *mut sd_journal, field: *mut *const c_char) -> c_int;
+    pub fn sd_journal_restart_fields(j: *mut sd_journal);
+
     pub fn sd_journal_add_match(j: *mut sd_journal, data: *const c_void, size: size_t) -> c_int;
     pub fn sd_journal_add_disjunction(j: *mut sd_journal) -> c_int;
     pub fn sd_journal_add_conjunction(j: *mut sd_journal) -> c_int;
@@ -88,7 +116,7 @@ extern "C" {
     pub fn sd_journal_seek_realtime_usec(j: *mut sd_journal, usec: u64) -> c_int;
     pub fn sd_journal_seek_cursor(j: *mut sd_journal, cursor: *const c_char) -> c_int;
 
-    pub fn sd_journal_get_cursor(j: *mut sd_journal, cursor: *mut *const c_char) -> c_int;
+    pub fn sd_journal_get_cursor(j: *mut sd_journal, cursor: *mut *mut c_char) -> c_int;
     pub fn sd_journal_test_cursor(j: *mut sd_journal, cursor: *const c_char) -> c_int;
 
     pub fn sd_journal_get_cutoff_realtime_usec(
@@ -103,6 +131,13 @@ extern "C" {
         to: *mut u64,
     ) -> c_int;
 
+    // since v254
+    pub fn sd_journal_get_seqnum(
+        j: *mut sd_journal,
+        ret_seqnum: *mut u64,
+        ret_seqnum_id: *mut sd_id128_t,
+    ) -> c_int;
+
     pub fn sd_journal_get_usage(j: *mut sd_journal, bytes: *mut u64) -> c_int;
 
     pub fn sd_journal_query_unique(j: *mut sd_journal, field: *const c_char) -> c_int;
@@ -111,6 +146,12 @@ extern "C" {
         data: *mut *const c_void,
         l: *mut size_t,
     ) -> c_int;
+    // since v246
+    pub fn sd_journal_enumerate_available_unique(
+        j: *mut sd_journal,
+        data: *mut *const c_void,
+        l: *mut size_t,
+    ) -> c_int;
     pub fn sd_journal_restart_unique(j: *mut sd_journal);
 
     pub fn sd_journal_get_fd(j: *mut sd_journal) -> c_int;
@@ -120,6 +161,10 @@ extern "C" {
     pub fn sd_journal_wait(j: *mut sd_journal, timeout_usec: u64) -> c_int;
     pub fn sd_journal_reliable_fd(j: *mut sd_journal) -> c_int;
 
-    pub fn sd_journal_get_catalog(j: *mut sd_journal, text: *mut *const c_char) -> c_int;
-    pub fn sd_journal_get_catalog_for_message_id(id: sd_id128_t, ret: *mut *const c_char) -> c_int;
+    pub fn sd_journal_get_catalog(j: *mut sd_journal, text: *mut *mut c_char) -> c_int;
+    pub fn sd_journal_get_catalog_for_message_id(id: sd_id128_t, ret: *mut *mut c_char) -> c_int;
+
+    // since v229
+    pub fn sd_journal_has_runtime_files(j: *mut sd_journal) -> c_int;
+    pub fn sd_journal_has_persistent_files(j: *mut sd_journal) -> c_int;
 }
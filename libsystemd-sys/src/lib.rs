@@ -5,7 +5,9 @@
 
 #![warn(rust_2018_idioms)]
 
-pub use libc::{clockid_t, gid_t, iovec, pid_t, siginfo_t, signalfd_siginfo, size_t, uid_t};
+pub use libc::{
+    clockid_t, gid_t, inotify_event, iovec, pid_t, siginfo_t, signalfd_siginfo, size_t, uid_t,
+};
 pub use std::os::raw::{c_char, c_int, c_uint, c_void};
 
 pub mod daemon;
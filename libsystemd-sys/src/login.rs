@@ -20,9 +20,22 @@ extern "C" {
     pub fn sd_peer_get_slice(fd: c_int, slice: *mut *mut c_char) -> c_int;
     pub fn sd_peer_get_user_slice(fd: c_int, slice: *mut *mut c_char) -> c_int;
     pub fn sd_peer_get_machine_name(fd: c_int, machine: *mut *mut c_char) -> c_int;
-    pub fn sd_peer_get_cgroup(pid: pid_t, cgroup: *mut *mut c_char) -> c_int;
+    pub fn sd_peer_get_cgroup(fd: c_int, cgroup: *mut *mut c_char) -> c_int;
+
+    // since v254
+    pub fn sd_pidfd_get_session(pidfd: c_int, session: *mut *mut c_char) -> c_int;
+    pub fn sd_pidfd_get_owner_uid(pidfd: c_int, uid: *mut uid_t) -> c_int;
+    pub fn sd_pidfd_get_unit(pidfd: c_int, unit: *mut *mut c_char) -> c_int;
+    pub fn sd_pidfd_get_user_unit(pidfd: c_int, unit: *mut *mut c_char) -> c_int;
+    pub fn sd_pidfd_get_slice(pidfd: c_int, slice: *mut *mut c_char) -> c_int;
+    pub fn sd_pidfd_get_user_slice(pidfd: c_int, slice: *mut *mut c_char) -> c_int;
+    pub fn sd_pidfd_get_machine_name(pidfd: c_int, machine: *mut *mut c_char) -> c_int;
+    pub fn sd_pidfd_get_cgroup(pidfd: c_int, cgroup: *mut *mut c_char) -> c_int;
+
     pub fn sd_uid_get_state(uid: uid_t, state: *mut *mut c_char) -> c_int;
     pub fn sd_uid_get_display(uid: uid_t, session: *mut *mut c_char) -> c_int;
+    // since v256
+    pub fn sd_uid_get_login_time(uid: uid_t, usec: *mut u64) -> c_int;
     pub fn sd_uid_is_on_seat(uid: uid_t, require_active: c_int, seat: *const c_char) -> c_int;
     pub fn sd_uid_get_sessions(
         uid: uid_t,
@@ -38,6 +51,10 @@ extern "C" {
     pub fn sd_session_is_remote(session: *const c_char) -> c_int;
     pub fn sd_session_get_state(session: *const c_char, state: *mut *mut c_char) -> c_int;
     pub fn sd_session_get_uid(session: *const c_char, uid: *mut uid_t) -> c_int;
+    // since v254
+    pub fn sd_session_get_username(session: *const c_char, username: *mut *mut c_char) -> c_int;
+    pub fn sd_session_get_start_time(session: *const c_char, usec: *mut u64) -> c_int;
+    pub fn sd_session_get_leader(session: *const c_char, leader: *mut pid_t) -> c_int;
     pub fn sd_session_get_seat(session: *const c_char, seat: *mut *mut c_char) -> c_int;
     pub fn sd_session_get_service(session: *const c_char, service: *mut *mut c_char) -> c_int;
     pub fn sd_session_get_type(session: *const c_char, _type: *mut *mut c_char) -> c_int;
@@ -52,7 +69,7 @@ extern "C" {
         session: *const c_char,
         remote_user: *mut *mut c_char,
     ) -> c_int;
-    pub fn sd_session_get_tty(session: *const c_char, display: *mut *mut c_char) -> c_int;
+    pub fn sd_session_get_tty(session: *const c_char, tty: *mut *mut c_char) -> c_int;
     pub fn sd_session_get_vt(session: *const c_char, vtnr: *mut c_uint) -> c_int;
     pub fn sd_seat_get_active(
         seat: *const c_char,
@@ -16,22 +16,103 @@
 //    than what is possible with sd-bus directly.
 
 //use enumflags2_derive::EnumFlags;
+use cstr_argument::CStrArgument;
 use ffi::{c_char, c_int, c_void, pid_t};
 use foreign_types::{foreign_type, ForeignType, ForeignTypeRef};
-use std::ffi::CStr;
+use std::borrow::Borrow;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
 use std::marker::PhantomData;
-use std::mem::{forget, MaybeUninit};
-use std::ops::Deref;
+use std::mem::{forget, ManuallyDrop, MaybeUninit};
+use std::ops::{Deref, DerefMut};
 use std::os::unix::io::AsRawFd;
 use std::ptr;
 use std::result;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 use std::{fmt, str};
 
+#[cfg(feature = "event")]
+use super::event::{Event, EventRef};
 use super::usec_from_duration;
 use utf8_cstr::Utf8CStr;
 
 pub mod types;
+pub use self::types::{AnyVariant, SharedFd, Value, Variant};
+
+/// Append values of any type implementing `serde::Serialize` to messages and read
+/// `serde::Deserialize` ones from them, so complex arguments can be plain Rust structs with
+/// `#[derive(Serialize, Deserialize)]`.
+#[cfg(feature = "serde")]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "serde")))]
+pub mod serde;
+
+/// Process a bus from a tokio runtime, with `async` method calls.
+#[cfg(feature = "tokio")]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "tokio")))]
+pub mod nonblock;
+
+/// Export the methods of a type as a bus interface, by implementing [`Interface`] for it.
+///
+/// Placed on an `impl` block, with the name of the interface:
+///
+/// - functions taking `&self` become methods, named in `CamelCase` (`set_level` is `SetLevel`).
+///   Arguments are read with [`MessageIter::next()`] and the returned value is the reply, with
+///   tuples sent as multiple values. Errors of functions returning a `Result` are sent as the
+///   reply instead, so they must convert into [`Error`].
+/// - `#[dbus_interface(property)]` getters become properties. A property `set_` function next to
+///   one makes it writable. `change = "none" | "const" | "emits_change" | "emits_invalidation"`
+///   selects the [`PropertyChange`], `emits_change` by default.
+/// - `#[dbus_interface(signal)]` functions, without a body, declare signals. The generated body
+///   sends the signal from the object at the given path with the remaining arguments.
+/// - `#[dbus_interface(name = "..")]` overrides the member name, `#[dbus_interface(skip)]` leaves
+///   a function out. Functions without `self` are left out as well.
+///
+/// `auto_emit` next to the interface name (`#[dbus_interface(name = "..", auto_emit)]`) signals
+/// the properties set through the bus, see [`Vtable::auto_emit_changes()`].
+///
+/// The generated code refers to this crate as `::systemd`.
+///
+/// ```no_run
+/// use std::sync::atomic::{AtomicU32, Ordering};
+/// use std::sync::Arc;
+/// use systemd::bus::{self, dbus_interface, BusRef, ObjectPath};
+///
+/// #[derive(Default)]
+/// struct Counter {
+///     value: AtomicU32,
+/// }
+///
+/// #[dbus_interface(name = "org.example.Counter")]
+/// impl Counter {
+///     fn add(&self, n: u32) -> u32 {
+///         self.value.fetch_add(n, Ordering::SeqCst) + n
+///     }
+///
+///     #[dbus_interface(property)]
+///     fn value(&self) -> u32 {
+///         self.value.load(Ordering::SeqCst)
+///     }
+///
+///     #[dbus_interface(property)]
+///     fn set_value(&self, value: u32) {
+///         self.value.store(value, Ordering::SeqCst)
+///     }
+///
+///     #[dbus_interface(signal)]
+///     fn overflowed(bus: &mut BusRef, path: &ObjectPath, value: u32) -> bus::Result<()> {}
+/// }
+///
+/// let mut bus = bus::Bus::default_system().unwrap();
+/// let path = ObjectPath::from_bytes(b"/org/example/Counter\0").unwrap();
+/// let _slot = bus.add_interface(path, Arc::new(Counter::default())).unwrap();
+/// Counter::overflowed(&mut bus, path, 0).unwrap();
+/// ```
+#[cfg(feature = "macros")]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "macros")))]
+pub use systemd_macros::dbus_interface;
 
 /**
  * Result type for dbus calls that contains errors returned by remote services (and local errors as
@@ -46,6 +127,200 @@ pub mod types;
  */
 pub type Result<T> = result::Result<T, Error>;
 
+/// Implements `Display` and `Borrow<CStr>` for the validated name types, which are all thin
+/// wrappers around a `CStr` holding ASCII.
+macro_rules! name_type_impls {
+    ($($t:ty),*) => {$(
+        impl fmt::Display for $t {
+            fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+                // validated names are ASCII
+                fmt.write_str(str::from_utf8(self.inner.to_bytes()).map_err(|_| fmt::Error)?)
+            }
+        }
+
+        impl Borrow<CStr> for $t {
+            fn borrow(&self) -> &CStr {
+                &self.inner
+            }
+        }
+    )*};
+}
+
+name_type_impls!(ObjectPath, InterfaceName, BusName, MemberName, Signature);
+
+/// Defines owned versions of the validated name types, holding a `CString` which is checked with
+/// the `from_bytes()` of the borrowed type.
+macro_rules! owned_name_types {
+    ($($(#[$attr:meta])* $owned:ident => $t:ident,)*) => {$(
+        $(#[$attr])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $owned {
+            inner: CString,
+        }
+
+        impl $owned {
+            /// Validate `name`, given without a trailing nul.
+            pub fn new<T: Into<Vec<u8>>>(name: T) -> result::Result<Self, &'static str> {
+                let inner = CString::new(name).map_err(|_| "Name must not contain '\\0' bytes")?;
+                $t::from_bytes(inner.as_bytes_with_nul())?;
+                Ok($owned { inner })
+            }
+        }
+
+        impl Deref for $owned {
+            type Target = $t;
+            #[inline]
+            fn deref(&self) -> &$t {
+                unsafe { $t::from_bytes_unchecked(self.inner.as_bytes_with_nul()) }
+            }
+        }
+
+        impl Borrow<$t> for $owned {
+            #[inline]
+            fn borrow(&self) -> &$t {
+                self
+            }
+        }
+
+        impl AsRef<$t> for $owned {
+            #[inline]
+            fn as_ref(&self) -> &$t {
+                self
+            }
+        }
+
+        impl From<&$t> for $owned {
+            #[inline]
+            fn from(name: &$t) -> Self {
+                $owned {
+                    inner: name.inner.to_owned(),
+                }
+            }
+        }
+
+        impl From<$owned> for CString {
+            #[inline]
+            fn from(name: $owned) -> Self {
+                name.inner
+            }
+        }
+
+        impl TryFrom<&str> for $owned {
+            type Error = &'static str;
+            #[inline]
+            fn try_from(name: &str) -> result::Result<Self, Self::Error> {
+                Self::new(name)
+            }
+        }
+
+        impl TryFrom<String> for $owned {
+            type Error = &'static str;
+            #[inline]
+            fn try_from(name: String) -> result::Result<Self, Self::Error> {
+                Self::new(name)
+            }
+        }
+
+        impl str::FromStr for $owned {
+            type Err = &'static str;
+            #[inline]
+            fn from_str(name: &str) -> result::Result<Self, Self::Err> {
+                Self::new(name)
+            }
+        }
+
+        impl fmt::Display for $owned {
+            fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&**self, fmt)
+            }
+        }
+    )*};
+}
+
+owned_name_types! {
+    /// An owned [`ObjectPath`]
+    ObjectPathBuf => ObjectPath,
+    /// An owned [`InterfaceName`]
+    InterfaceNameBuf => InterfaceName,
+    /// An owned [`BusName`]
+    BusNameBuf => BusName,
+    /// An owned [`MemberName`]
+    MemberNameBuf => MemberName,
+    /// An owned [`Signature`]
+    SignatureBuf => Signature,
+}
+
+/// A `&'static` [`ObjectPath`](bus::ObjectPath) from a string literal, validated at compile time.
+///
+/// ```
+/// let path: &systemd::bus::ObjectPath = systemd::path!("/org/freedesktop/systemd1");
+/// ```
+///
+/// Invalid paths fail to compile:
+///
+/// ```compile_fail
+/// let path = systemd::path!("/org/freedesktop/");
+/// ```
+#[macro_export]
+macro_rules! path {
+    ($path:expr) => {{
+        const PATH: &$crate::bus::ObjectPath =
+            match $crate::bus::ObjectPath::from_bytes(concat!($path, "\0").as_bytes()) {
+                Ok(p) => p,
+                Err(e) => panic!("{}", e),
+            };
+        PATH
+    }};
+}
+
+/// A `&'static` [`InterfaceName`](bus::InterfaceName) from a string literal, validated at compile
+/// time.
+///
+/// ```
+/// let interface: &systemd::bus::InterfaceName =
+///     systemd::interface!("org.freedesktop.systemd1.Manager");
+/// ```
+///
+/// Invalid names fail to compile:
+///
+/// ```compile_fail
+/// let interface = systemd::interface!("systemd1");
+/// ```
+#[macro_export]
+macro_rules! interface {
+    ($interface:expr) => {{
+        const INTERFACE: &$crate::bus::InterfaceName =
+            match $crate::bus::InterfaceName::from_bytes(concat!($interface, "\0").as_bytes()) {
+                Ok(i) => i,
+                Err(e) => panic!("{}", e),
+            };
+        INTERFACE
+    }};
+}
+
+/// A `&'static` [`MemberName`](bus::MemberName) from a string literal, validated at compile time.
+///
+/// ```
+/// let member: &systemd::bus::MemberName = systemd::member!("StartUnit");
+/// ```
+///
+/// Invalid names fail to compile:
+///
+/// ```compile_fail
+/// let member = systemd::member!("Start.Unit");
+/// ```
+#[macro_export]
+macro_rules! member {
+    ($member:expr) => {{
+        const MEMBER: &$crate::bus::MemberName =
+            match $crate::bus::MemberName::from_bytes(concat!($member, "\0").as_bytes()) {
+                Ok(m) => m,
+                Err(e) => panic!("{}", e),
+            };
+        MEMBER
+    }};
+}
+
 /**
  * A wrapper which promises it always holds a valid dbus object path
  *
@@ -58,7 +333,7 @@ pub type Result<T> = result::Result<T, Error>;
  * - A trailing '/' character is not allowed unless the path is the root path
  * - Further, sd-bus additionally requires nul ('\0') termination of paths.
  */
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct ObjectPath {
     inner: CStr,
 }
@@ -68,7 +343,7 @@ impl ObjectPath {
      * Create a path reference from a u8 slice. Performs all checking needed to ensure requirements
      * are met.
      */
-    pub fn from_bytes(b: &[u8]) -> result::Result<&ObjectPath, &'static str> {
+    pub const fn from_bytes(b: &[u8]) -> result::Result<&ObjectPath, &'static str> {
         if b.is_empty() {
             return Err("Path must have at least 1 character ('/')");
         }
@@ -77,9 +352,12 @@ impl ObjectPath {
             return Err("Path must begin with '/'");
         }
 
-        for w in b.windows(2) {
-            let prev = w[0];
-            let c = w[1];
+        // a `while` loop instead of `windows()` so this can be used in constants, see `path!()`
+        let mut i = 1;
+        while i < b.len() {
+            let prev = b[i - 1];
+            let c = b[i];
+            i += 1;
 
             match c {
                 b'/' => {
@@ -111,7 +389,7 @@ impl ObjectPath {
     /// - `b` must be nul (`'\0'`) terminated
     /// - `b` must be a valid object path string
     #[inline]
-    pub unsafe fn from_bytes_unchecked(b: &[u8]) -> &ObjectPath {
+    pub const unsafe fn from_bytes_unchecked(b: &[u8]) -> &ObjectPath {
         &*(b as *const [u8] as *const ObjectPath)
     }
 
@@ -122,7 +400,7 @@ impl ObjectPath {
     /// - `b` must be a valid object path string
     #[inline]
     pub unsafe fn from_ptr_unchecked<'b>(b: *const c_char) -> &'b ObjectPath {
-        Self::from_bytes_unchecked(CStr::from_ptr(b).to_bytes())
+        Self::from_bytes_unchecked(CStr::from_ptr(b).to_bytes_with_nul())
     }
 }
 
@@ -146,12 +424,26 @@ fn t_path() {
     ObjectPath::from_bytes(b"/hello/goodbye/013/4?/HA\0")
         .err()
         .unwrap();
+
+    let p = ObjectPathBuf::new("/hello/goodbye").unwrap();
+    assert_eq!(&*p, ObjectPath::from_bytes(b"/hello/goodbye\0").unwrap());
+    assert_eq!(p.to_string(), "/hello/goodbye");
+    assert_eq!(
+        ObjectPathBuf::try_from(String::from("/hello/goodbye")),
+        Ok(p)
+    );
+    ObjectPathBuf::new("/hello/").err().unwrap();
+    ObjectPathBuf::new("/hello\0/goodbye").err().unwrap();
+    assert_eq!(
+        path!("/hello"),
+        ObjectPath::from_bytes(b"/hello\0").unwrap()
+    );
 }
 
 /**
  * A wrapper which promises it always holds a validated dbus interface name
  */
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct InterfaceName {
     inner: CStr,
 }
@@ -172,7 +464,7 @@ impl InterfaceName {
      *  Interface names must not being with a '.' character
      * sd-bus additionally requires nul ('\0') termination of the interface name.
      */
-    pub fn from_bytes(b: &[u8]) -> result::Result<&InterfaceName, &'static str> {
+    pub const fn from_bytes(b: &[u8]) -> result::Result<&InterfaceName, &'static str> {
         if b.is_empty() {
             return Err("Name must have more than 0 characters");
         }
@@ -186,9 +478,13 @@ impl InterfaceName {
         }
 
         let mut periods = 0;
-        for w in b.windows(2) {
-            let prev = w[0];
-            let c = w[1];
+        // a `while` loop instead of `windows()` so this can be used in constants, see
+        // `interface!()`
+        let mut i = 1;
+        while i < b.len() {
+            let prev = b[i - 1];
+            let c = b[i];
+            i += 1;
             match c {
                 b'.' => {
                     if prev == b'.' {
@@ -233,7 +529,7 @@ impl InterfaceName {
     ///  - `b` must be a nul terminated string
     ///  - `b` must contain a valid interface string
     #[inline]
-    pub unsafe fn from_bytes_unchecked(b: &[u8]) -> &InterfaceName {
+    pub const unsafe fn from_bytes_unchecked(b: &[u8]) -> &InterfaceName {
         &*(b as *const [u8] as *const InterfaceName)
     }
 
@@ -265,9 +561,13 @@ fn t_interface() {
     InterfaceName::from_bytes(b"A.Z.xar.yfds.d3490\0").unwrap();
     InterfaceName::from_bytes(b"a.b.c\0").unwrap();
     InterfaceName::from_bytes(b"a.b.c?\0").err().unwrap();
+
+    let i: InterfaceNameBuf = "a.b.c".parse().unwrap();
+    assert_eq!(&*i, interface!("a.b.c"));
+    InterfaceNameBuf::new("a").err().unwrap();
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct BusName {
     inner: CStr,
 }
@@ -371,7 +671,7 @@ impl BusName {
     /// - `b` must be a valid bus name string
     #[inline]
     pub unsafe fn from_ptr_unchecked<'a>(b: *const c_char) -> &'a Self {
-        Self::from_bytes_unchecked(CStr::from_ptr(b).to_bytes())
+        Self::from_bytes_unchecked(CStr::from_ptr(b).to_bytes_with_nul())
     }
 }
 
@@ -393,9 +693,14 @@ fn t_busname() {
     BusName::from_bytes(b"a.b-c.0a\0").err().unwrap();
     BusName::from_bytes(b":a.b-c\0").unwrap();
     BusName::from_bytes(b":a.b-c.1\0").unwrap();
+
+    let n = BusNameBuf::new(":1.42").unwrap();
+    assert_eq!(CString::from(n.clone()).as_bytes(), b":1.42");
+    assert_eq!(BusNameBuf::from(&*n), n);
+    BusNameBuf::try_from("a").err().unwrap();
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct MemberName {
     inner: CStr,
 }
@@ -415,7 +720,7 @@ impl MemberName {
      *
      * sd-bus additionally requires nul ('\0') termination of the bus name.
      */
-    pub fn from_bytes(b: &[u8]) -> result::Result<&Self, &'static str> {
+    pub const fn from_bytes(b: &[u8]) -> result::Result<&Self, &'static str> {
         if b.len() < 2 {
             return Err("Name must have more than 0 characters");
         }
@@ -431,8 +736,12 @@ impl MemberName {
             _ => return Err("Must begin with '[A-Z][a-z]_'"),
         }
 
-        for c in b {
-            match *c {
+        // a `while` loop instead of a `for` loop so this can be used in constants, see `member!()`
+        let mut i = 0;
+        while i < b.len() {
+            let c = b[i];
+            i += 1;
+            match c {
                 b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'_' => {
                     // Ok
                 }
@@ -452,7 +761,7 @@ impl MemberName {
     ///
     /// `b` must be a valid c-string (ie: it must be `\0` (nul) terminated).
     #[inline]
-    pub unsafe fn from_bytes_unchecked(b: &[u8]) -> &Self {
+    pub const unsafe fn from_bytes_unchecked(b: &[u8]) -> &Self {
         &*(b as *const [u8] as *const MemberName)
     }
 
@@ -461,7 +770,7 @@ impl MemberName {
     /// `b` must point to a valid c-string, with lifetime at least `'a`
     #[inline]
     pub unsafe fn from_ptr_unchecked<'a>(b: *const c_char) -> &'a Self {
-        Self::from_bytes_unchecked(CStr::from_ptr(b).to_bytes())
+        Self::from_bytes_unchecked(CStr::from_ptr(b).to_bytes_with_nul())
     }
 }
 
@@ -481,6 +790,163 @@ fn t_member_name() {
     MemberName::from_bytes(b"abc").err().unwrap();
     MemberName::from_bytes(b"\0").err().unwrap();
     MemberName::from_bytes(b"a\0").unwrap();
+
+    let m: MemberNameBuf = "StartUnit".parse().unwrap();
+    assert_eq!(&*m, member!("StartUnit"));
+    MemberNameBuf::new("Start.Unit").err().unwrap();
+}
+
+/**
+ * A wrapper which promises it always holds a valid dbus type signature
+ *
+ * Requirements (from dbus spec 0.26):
+ *
+ * - must only consist of complete types, made of the basic type codes 'ybnqiuxtdhsog', the
+ *   variant code 'v' and the container codes 'a', '(' ')' and '{' '}'
+ * - structs must have at least 1 field
+ * - dict entries must only occur as the element type of an array, and must hold a basic type key
+ *   and a single value
+ * - arrays and structs may each be nested at most 32 deep
+ * - must not exceed 255 bytes
+ * - sd-bus additionally requires nul ('\0') termination of signatures.
+ */
+#[derive(Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Signature {
+    inner: CStr,
+}
+
+/// Maximum nesting of arrays, and separately of structs, in a signature
+const SIGNATURE_MAX_DEPTH: usize = 32;
+
+fn is_basic_type(c: u8) -> bool {
+    b"ybnqiuxtdhsog".contains(&c)
+}
+
+/// Parse the single complete type starting at `i`, returning the index following it
+fn signature_complete_type(
+    b: &[u8],
+    i: usize,
+    arrays: usize,
+    structs: usize,
+) -> result::Result<usize, &'static str> {
+    match b.get(i) {
+        None => Err("Incomplete type in signature"),
+        Some(&c) if is_basic_type(c) || c == b'v' => Ok(i + 1),
+        Some(b'a') => {
+            if arrays == SIGNATURE_MAX_DEPTH {
+                return Err("Arrays nested too deeply in signature");
+            }
+            if b.get(i + 1) != Some(&b'{') {
+                return signature_complete_type(b, i + 1, arrays + 1, structs);
+            }
+            if structs == SIGNATURE_MAX_DEPTH {
+                return Err("Structs nested too deeply in signature");
+            }
+            match b.get(i + 2) {
+                Some(&c) if is_basic_type(c) => {}
+                _ => return Err("Dict entry key must be a basic type"),
+            }
+            let j = signature_complete_type(b, i + 3, arrays + 1, structs + 1)?;
+            if b.get(j) != Some(&b'}') {
+                return Err("Dict entry must have exactly 2 types");
+            }
+            Ok(j + 1)
+        }
+        Some(b'(') => {
+            if structs == SIGNATURE_MAX_DEPTH {
+                return Err("Structs nested too deeply in signature");
+            }
+            if b.get(i + 1) == Some(&b')') {
+                return Err("Struct must have at least 1 field");
+            }
+            let mut j = i + 1;
+            while b.get(j) != Some(&b')') {
+                j = signature_complete_type(b, j, arrays, structs + 1)?;
+            }
+            Ok(j + 1)
+        }
+        Some(b'{') => Err("Dict entry outside of array in signature"),
+        Some(_) => Err("Invalid character in signature"),
+    }
+}
+
+impl Signature {
+    /**
+     * Create a signature reference from a u8 slice. Performs all checking needed to ensure
+     * requirements are met.
+     */
+    pub fn from_bytes(b: &[u8]) -> result::Result<&Self, &'static str> {
+        let sig = match b.split_last() {
+            Some((b'\0', sig)) => sig,
+            _ => return Err("Signature must be terminated in a '\\0' byte (for use by sd-bus)"),
+        };
+
+        if sig.len() > 255 {
+            return Err("Must be shorter than 256 characters");
+        }
+
+        let mut i = 0;
+        while i < sig.len() {
+            i = signature_complete_type(sig, i, 0, 0)?;
+        }
+
+        Ok(unsafe { Self::from_bytes_unchecked(b) })
+    }
+
+    /// # Safety
+    ///
+    /// - `b` must be nul (`'\0'`) terminated
+    /// - `b` must be a valid signature
+    #[inline]
+    pub unsafe fn from_bytes_unchecked(b: &[u8]) -> &Self {
+        &*(b as *const [u8] as *const Signature)
+    }
+
+    /// # Safety
+    ///
+    /// - `b` must have a lifetime of at least `'a`
+    /// - `b` must be nul (`'\0'`) terminated
+    /// - `b` must be a valid signature
+    #[inline]
+    pub unsafe fn from_ptr_unchecked<'a>(b: *const c_char) -> &'a Self {
+        Self::from_bytes_unchecked(CStr::from_ptr(b).to_bytes_with_nul())
+    }
+}
+
+impl Deref for Signature {
+    type Target = CStr;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[test]
+fn t_signature() {
+    Signature::from_bytes(b"\0").unwrap();
+    Signature::from_bytes(b"s").err().unwrap();
+    Signature::from_bytes(b"sa{sv}(iu)ag\0").unwrap();
+    Signature::from_bytes(b"a(sa(ux))\0").unwrap();
+    Signature::from_bytes(b"a\0").err().unwrap();
+    Signature::from_bytes(b"()\0").err().unwrap();
+    Signature::from_bytes(b"(s\0").err().unwrap();
+    Signature::from_bytes(b"{sv}\0").err().unwrap();
+    Signature::from_bytes(b"a{vs}\0").err().unwrap();
+    Signature::from_bytes(b"a{sss}\0").err().unwrap();
+    Signature::from_bytes(b"z\0").err().unwrap();
+    Signature::from_bytes(b"s\0s\0").err().unwrap();
+
+    let deep = format!("{}y\0", "a".repeat(32));
+    Signature::from_bytes(deep.as_bytes()).unwrap();
+    let deep = format!("{}y\0", "a".repeat(33));
+    Signature::from_bytes(deep.as_bytes()).err().unwrap();
+    let deep = format!("{}y{}\0", "(".repeat(33), ")".repeat(33));
+    Signature::from_bytes(deep.as_bytes()).err().unwrap();
+    let long = format!("{}\0", "y".repeat(256));
+    Signature::from_bytes(long.as_bytes()).err().unwrap();
+
+    assert_eq!(SignatureBuf::new("a{sv}").unwrap().to_bytes(), b"a{sv}");
+    SignatureBuf::new("{sv}").err().unwrap();
 }
 
 /*
@@ -524,6 +990,21 @@ pub enum NameFlags {
 }
 */
 
+/// Flag for [`BusRef::request_name()`]: take the name over from its current owner, if that
+/// owner requested it with [`NAME_ALLOW_REPLACEMENT`].
+#[cfg(feature = "bus-service")]
+pub const NAME_REPLACE_EXISTING: u64 = 1 << 0;
+
+/// Flag for [`BusRef::request_name()`]: let other peers take the name over with
+/// [`NAME_REPLACE_EXISTING`].
+#[cfg(feature = "bus-service")]
+pub const NAME_ALLOW_REPLACEMENT: u64 = 1 << 1;
+
+/// Flag for [`BusRef::request_name()`]: if the name can't be acquired now, wait in the queue of
+/// the name until it is released.
+#[cfg(feature = "bus-service")]
+pub const NAME_QUEUE: u64 = 1 << 2;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 #[repr(u8)]
 pub enum MessageType {
@@ -534,6 +1015,16 @@ pub enum MessageType {
 }
 
 impl MessageType {
+    /// The name of the type in match rules (`type='...'`)
+    fn match_name(self) -> &'static str {
+        match self {
+            MessageType::MethodCall => "method_call",
+            MessageType::MethodReturn => "method_return",
+            MessageType::MethodError => "error",
+            MessageType::Signal => "signal",
+        }
+    }
+
     pub fn from_raw(raw: u8) -> Self {
         match raw as c_int {
             ffi::bus::SD_BUS_MESSAGE_METHOD_CALL => MessageType::MethodCall,
@@ -582,6 +1073,162 @@ impl RawError {
     }
 }
 
+/// A classification of [`Error`]s by their D-Bus error name, returned by [`Error::kind()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// No reply arrived in time, see [`Error::is_timeout()`]
+    Timeout,
+    /// Nothing provides the called service, see [`Error::is_service_unknown()`]
+    ServiceUnknown,
+    /// The called object doesn't exist (`org.freedesktop.DBus.Error.UnknownObject`)
+    UnknownObject,
+    /// The called method doesn't exist, see [`Error::is_unknown_method()`]
+    UnknownMethod,
+    /// The caller isn't permitted to perform the operation, see [`Error::is_access_denied()`]
+    AccessDenied,
+    /// Any other error
+    Other,
+}
+
+/// How long sd-bus waits for the reply to a method call by default.
+///
+/// The `SYSTEMD_BUS_TIMEOUT` environment variable overrides this, see [`default_call_timeout()`].
+pub const DEFAULT_CALL_TIMEOUT: Duration = Duration::from_secs(25);
+
+/// The timeout sd-bus applies to method calls of connections which don't set their own (with
+/// [`BusBuilder::method_call_timeout()`] or [`BusRef::set_method_call_timeout()`]).
+///
+/// Like sd-bus, this is taken from the `SYSTEMD_BUS_TIMEOUT` environment variable when it holds a
+/// non-zero time span (like `90s` or `2min`, a plain number is in seconds), and is
+/// [`DEFAULT_CALL_TIMEOUT`] otherwise.
+pub fn default_call_timeout() -> Duration {
+    std::env::var("SYSTEMD_BUS_TIMEOUT")
+        .ok()
+        .and_then(|t| parse_timespan(&t))
+        .filter(|t| *t != Duration::from_secs(0))
+        .unwrap_or(DEFAULT_CALL_TIMEOUT)
+}
+
+/// Parse a time span in the format of `systemd.time(7)`, like `1min 30s`.
+fn parse_timespan(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    if s.is_empty() {
+        return None;
+    }
+    let mut rest = s;
+    let mut usec = 0f64;
+    while !rest.is_empty() {
+        let n = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let value: f64 = rest[..n].parse().ok()?;
+        rest = rest[n..].trim_start();
+        let u = rest
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..u] {
+            "usec" | "us" | "µs" => 1u64,
+            "msec" | "ms" => 1_000,
+            "" | "seconds" | "second" | "sec" | "s" => 1_000_000,
+            "minutes" | "minute" | "min" | "m" => 60_000_000,
+            "hours" | "hour" | "hr" | "h" => 3_600_000_000,
+            "days" | "day" | "d" => 86_400_000_000,
+            "weeks" | "week" | "w" => 604_800_000_000,
+            _ => return None,
+        };
+        usec += value * unit as f64;
+        rest = rest[u..].trim_start();
+    }
+    Some(Duration::from_micros(usec as u64))
+}
+
+#[test]
+fn t_parse_timespan() {
+    assert_eq!(parse_timespan("25"), Some(Duration::from_secs(25)));
+    assert_eq!(parse_timespan("500ms"), Some(Duration::from_millis(500)));
+    assert_eq!(parse_timespan("1min 30s"), Some(Duration::from_secs(90)));
+    assert_eq!(parse_timespan("1.5h"), Some(Duration::from_secs(5400)));
+    assert_eq!(parse_timespan(" 2 min "), Some(Duration::from_secs(120)));
+    assert_eq!(parse_timespan("infinity"), None);
+    assert_eq!(parse_timespan("5 parsecs"), None);
+    assert_eq!(parse_timespan(""), None);
+}
+
+/// How [`BusRef::call_method_with_policy()`] retries calls failing with transient errors.
+///
+/// By default calls failing with [`ErrorKind::Timeout`] or [`ErrorKind::ServiceUnknown`] are
+/// retried up to 3 times, waiting 100ms before the first retry and twice as long before each
+/// following one.
+///
+/// A call which timed out may still have been carried out by the destination, so only use a
+/// policy retrying `Timeout` for calls which are safe to repeat, like reading properties.
+///
+/// ```no_run
+/// # use systemd::bus::{CallPolicy, ErrorKind};
+/// # use std::time::Duration;
+/// // keep trying for a service which is still starting up
+/// let policy = CallPolicy::new()
+///     .retries(5)
+///     .retry_on(&[ErrorKind::ServiceUnknown])
+///     .wait_for_activation(Duration::from_secs(10));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallPolicy {
+    retries: u32,
+    backoff: Duration,
+    retry_on: Vec<ErrorKind>,
+    activation_timeout: Option<Duration>,
+}
+
+impl Default for CallPolicy {
+    fn default() -> Self {
+        CallPolicy {
+            retries: 3,
+            backoff: Duration::from_millis(100),
+            retry_on: vec![ErrorKind::Timeout, ErrorKind::ServiceUnknown],
+            activation_timeout: None,
+        }
+    }
+}
+
+impl CallPolicy {
+    /// The default policy
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Retry at most `retries` times, so the call is made up to `retries + 1` times.
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Wait `backoff` before the first retry. The delay doubles for every further retry.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Only retry calls failing with errors of these kinds.
+    pub fn retry_on(mut self, kinds: &[ErrorKind]) -> Self {
+        self.retry_on = kinds.to_vec();
+        self
+    }
+
+    /// Before retrying a call that failed with [`ErrorKind::ServiceUnknown`], wait up to `timeout`
+    /// for the destination name to be acquired on the bus instead of backing off.
+    ///
+    /// The bus driver is asked whether the name has an owner every backoff interval. This helps
+    /// with services which are started by other means than bus activation, and is only useful if
+    /// `ServiceUnknown` errors are retried.
+    pub fn wait_for_activation(mut self, timeout: Duration) -> Self {
+        self.activation_timeout = Some(timeout);
+        self
+    }
+}
+
 pub struct Error {
     raw: RawError,
     name_len: usize,
@@ -630,6 +1277,79 @@ impl Error {
         }
     }
 
+    /// Returns `true` if the error has the D-Bus error name `name`.
+    ///
+    /// This corresponds to [`sd_bus_error_has_name`]
+    ///
+    /// [`sd_bus_error_has_name`]: https://www.freedesktop.org/software/systemd/man/sd_bus_error_has_name.html
+    #[inline]
+    pub fn has_name<S: CStrArgument>(&self, name: S) -> bool {
+        let name = name.into_cstr();
+        unsafe { ffi::bus::sd_bus_error_has_name(self.as_ptr(), name.as_ref().as_ptr()) != 0 }
+    }
+
+    /// Returns `true` if the called method does not exist
+    /// (`org.freedesktop.DBus.Error.UnknownMethod`).
+    #[inline]
+    pub fn is_unknown_method(&self) -> bool {
+        self.has_name("org.freedesktop.DBus.Error.UnknownMethod\0")
+    }
+
+    /// Returns `true` if the caller is not permitted to perform the operation
+    /// (`org.freedesktop.DBus.Error.AccessDenied`).
+    #[inline]
+    pub fn is_access_denied(&self) -> bool {
+        self.has_name("org.freedesktop.DBus.Error.AccessDenied\0")
+    }
+
+    /// Returns `true` if nothing on the bus provides the called service
+    /// (`org.freedesktop.DBus.Error.ServiceUnknown` or `org.freedesktop.DBus.Error.NameHasNoOwner`),
+    /// like when calling the service manager in a container which doesn't run systemd.
+    #[inline]
+    pub fn is_service_unknown(&self) -> bool {
+        self.has_name("org.freedesktop.DBus.Error.ServiceUnknown\0")
+            || self.has_name("org.freedesktop.DBus.Error.NameHasNoOwner\0")
+    }
+
+    /// Returns `true` if the operation timed out.
+    ///
+    /// This covers both `org.freedesktop.DBus.Error.Timeout` and
+    /// `org.freedesktop.DBus.Error.NoReply`, which sd-bus uses when a method call receives no reply
+    /// before its timeout elapses.
+    #[inline]
+    pub fn is_timeout(&self) -> bool {
+        self.has_name("org.freedesktop.DBus.Error.Timeout\0")
+            || self.has_name("org.freedesktop.DBus.Error.NoReply\0")
+    }
+
+    /// Classify the error by its name.
+    pub fn kind(&self) -> ErrorKind {
+        if self.is_timeout() {
+            ErrorKind::Timeout
+        } else if self.is_service_unknown() {
+            ErrorKind::ServiceUnknown
+        } else if self.has_name("org.freedesktop.DBus.Error.UnknownObject\0") {
+            ErrorKind::UnknownObject
+        } else if self.is_unknown_method() {
+            ErrorKind::UnknownMethod
+        } else if self.is_access_denied() {
+            ErrorKind::AccessDenied
+        } else {
+            ErrorKind::Other
+        }
+    }
+
+    /// The errno value corresponding to the error, as sent with
+    /// [`MessageRef::reply_errno()`]. Names without a known mapping result in `EIO`.
+    ///
+    /// This corresponds to [`sd_bus_error_get_errno`]
+    ///
+    /// [`sd_bus_error_get_errno`]: https://www.freedesktop.org/software/systemd/man/sd_bus_error.html
+    #[inline]
+    pub fn errno(&self) -> c_int {
+        unsafe { ffi::bus::sd_bus_error_get_errno(self.as_ptr()) }
+    }
+
     fn as_ptr(&self) -> *const ffi::bus::sd_bus_error {
         self.raw.as_ptr()
     }
@@ -650,6 +1370,21 @@ impl ::std::error::Error for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    /// Convert a local failure into a bus error, mapping the errno to a D-Bus error name the same
+    /// way [`sd_bus_error_set_errno`] does.
+    ///
+    /// [`sd_bus_error_set_errno`]: https://www.freedesktop.org/software/systemd/man/sd_bus_error.html
+    fn from(e: std::io::Error) -> Self {
+        let mut raw = RawError::new();
+        let errno = e.raw_os_error().filter(|&x| x != 0).unwrap_or(libc::EIO);
+        unsafe {
+            ffi::bus::sd_bus_error_set_errno(raw.as_mut_ptr(), errno);
+            Error::from_raw(raw)
+        }
+    }
+}
+
 impl fmt::Debug for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.debug_struct("Error")
@@ -703,6 +1438,15 @@ impl RawError {
         }
     }
 
+    /// Like `into_result()`, but also fails if `r` (the return value of the sd-bus call which
+    /// was handed this error) is negative and no error was filled in.
+    fn into_result_with(mut self, r: c_int) -> Result<()> {
+        if r < 0 && !self.is_set() {
+            unsafe { ffi::bus::sd_bus_error_set_errno(&mut self.inner, r) };
+        }
+        self.into_result()
+    }
+
     fn with(name: &Utf8CStr, message: Option<&Utf8CStr>) -> Self {
         let mut v: Self = Default::default();
         v.set(name, message);
@@ -817,6 +1561,41 @@ fn t_raw_error() {
     let _raw = RawError::new().set(name, Some(message));
 }
 
+#[test]
+fn t_error_has_name() {
+    let e = Error::new(
+        Utf8CStr::from_bytes(b"org.freedesktop.DBus.Error.NoReply\0").unwrap(),
+        None,
+    );
+    assert!(e.has_name("org.freedesktop.DBus.Error.NoReply"));
+    assert!(!e.has_name("org.freedesktop.DBus.Error.Failed"));
+    assert!(e.is_timeout());
+    assert!(!e.is_unknown_method());
+    assert!(!e.is_access_denied());
+
+    let e: Error = std::io::Error::from_raw_os_error(libc::EACCES).into();
+    assert!(e.is_access_denied());
+    assert!(!e.is_service_unknown());
+
+    let e = Error::new(
+        Utf8CStr::from_bytes(b"org.freedesktop.DBus.Error.ServiceUnknown\0").unwrap(),
+        None,
+    );
+    assert!(e.is_service_unknown());
+    assert_eq!(e.kind(), ErrorKind::ServiceUnknown);
+
+    let e = Error::new(
+        Utf8CStr::from_bytes(b"org.freedesktop.DBus.Error.NoReply\0").unwrap(),
+        None,
+    );
+    assert_eq!(e.kind(), ErrorKind::Timeout);
+    let e = Error::new(
+        Utf8CStr::from_bytes(b"org.freedesktop.DBus.Error.Failed\0").unwrap(),
+        None,
+    );
+    assert_eq!(e.kind(), ErrorKind::Other);
+}
+
 /* XXX: fixme: return code does have meaning! */
 extern "C" fn raw_message_handler<F>(
     msg: *mut ffi::bus::sd_bus_message,
@@ -826,7 +1605,8 @@ extern "C" fn raw_message_handler<F>(
 where
     F: Fn(&mut MessageRef) -> Result<()>,
 {
-    let m: Box<F> = unsafe { Box::from_raw(userdata as *mut F) };
+    // the closure is owned by the slot and freed by `raw_destroy_cb_message_handler`
+    let m: &F = unsafe { &*(userdata as *const F) };
     let e = m(unsafe { MessageRef::from_ptr_mut(msg) });
 
     match e {
@@ -853,25 +1633,500 @@ where
 }
 
 foreign_type! {
-    pub unsafe type Bus {
-        type CType = ffi::bus::sd_bus;
-        fn drop = ffi::bus::sd_bus_unref;
-        fn clone = ffi::bus::sd_bus_ref;
+    /// A registration of a callback on a bus, such as a match installed with
+    /// [`BusRef::add_match()`].
+    ///
+    /// Dropping the `Slot` unregisters the callback.
+    ///
+    /// This corresponds to [`sd_bus_slot`].
+    ///
+    /// [`sd_bus_slot`]: https://www.freedesktop.org/software/systemd/man/sd_bus_slot_ref.html
+    #[must_use = "dropping the Slot unregisters the callback"]
+    pub unsafe type Slot {
+        type CType = ffi::bus::sd_bus_slot;
+        fn drop = ffi::bus::sd_bus_slot_unref;
     }
 }
 
-impl Bus {
-    #[inline]
-    pub fn default() -> crate::Result<Bus> {
-        let mut b = MaybeUninit::uninit();
-        sd_try!(ffi::bus::sd_bus_default(b.as_mut_ptr()));
-        Ok(unsafe { Bus::from_ptr(b.assume_init()) })
+impl Slot {
+    /// Hand the slot over to the bus, keeping the callback registered until the bus connection
+    /// is closed (or the callback is otherwise finished, like for replies to [`MessageRef::call_async()`]).
+    ///
+    /// This is the same as [`SlotRef::set_floating()`] followed by dropping the `Slot`.
+    pub fn leak(self) -> super::Result<()> {
+        self.set_floating(true)
     }
+}
 
+impl SlotRef {
+    /// Whether the slot is owned by the bus rather than by references to it, see
+    /// [`SlotRef::set_floating()`].
+    ///
+    /// This corresponds to [`sd_bus_slot_get_floating`]
+    ///
+    /// [`sd_bus_slot_get_floating`]: https://www.freedesktop.org/software/systemd/man/sd_bus_slot_set_floating.html
     #[inline]
-    pub fn default_user() -> crate::Result<Bus> {
-        let mut b = MaybeUninit::uninit();
-        sd_try!(ffi::bus::sd_bus_default_user(b.as_mut_ptr()));
+    pub fn floating(&self) -> super::Result<bool> {
+        Ok(sd_try!(ffi::bus::sd_bus_slot_get_floating(self.as_ptr())) > 0)
+    }
+
+    /// Make the slot floating (owned by the bus): dropping the `Slot` then no longer unregisters
+    /// the callback.
+    ///
+    /// Fails with `ESTALE` if the bus connection is already gone.
+    ///
+    /// This corresponds to [`sd_bus_slot_set_floating`]
+    ///
+    /// [`sd_bus_slot_set_floating`]: https://www.freedesktop.org/software/systemd/man/sd_bus_slot_set_floating.html
+    #[inline]
+    pub fn set_floating(&self, floating: bool) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_slot_set_floating(
+            self.as_ptr(),
+            floating as c_int
+        ));
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SlotRef {
+    fn fmt(&self, fmtr: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmtr.debug_struct("Slot")
+            .field("raw", &self.as_ptr())
+            .finish()
+    }
+}
+
+/// Whether an error returned by [`BusRef::process()`] or [`BusRef::wait()`] means the connection
+/// is gone.
+fn is_disconnect(e: &std::io::Error) -> bool {
+    matches!(
+        e.raw_os_error(),
+        Some(libc::ECONNRESET) | Some(libc::ENOTCONN)
+    )
+}
+
+/// The properties of an interface by name, with their values unwrapped from the variants they
+/// are sent in.
+pub type Properties = BTreeMap<String, Value>;
+
+/// The interfaces of an object, with their properties.
+pub type Interfaces = BTreeMap<InterfaceNameBuf, Properties>;
+
+/// The objects managed by an object manager, as returned by [`BusRef::get_managed_objects()`].
+pub type ManagedObjects = BTreeMap<ObjectPathBuf, Interfaces>;
+
+fn object_manager_interface() -> &'static InterfaceName {
+    InterfaceName::from_bytes(b"org.freedesktop.DBus.ObjectManager\0").unwrap()
+}
+
+fn properties_interface() -> &'static InterfaceName {
+    InterfaceName::from_bytes(b"org.freedesktop.DBus.Properties\0").unwrap()
+}
+
+/// Append a bus name as a string argument.
+fn append_name(m: &mut MessageRef, name: &CStr) -> crate::Result<()> {
+    // names are validated to be ASCII
+    m.append(name.to_str().unwrap())
+}
+
+fn cstr(b: &'static [u8]) -> &'static CStr {
+    CStr::from_bytes_with_nul(b).unwrap()
+}
+
+fn bad_message() -> std::io::Error {
+    std::io::Error::from_raw_os_error(libc::EBADMSG)
+}
+
+/// Read an `a{sv}` property map.
+fn read_properties(i: &mut MessageIter<'_>) -> crate::Result<Properties> {
+    let mut properties = BTreeMap::new();
+    if !i.enter_container(b'a', cstr(b"{sv}\0"))? {
+        return Err(bad_message());
+    }
+    while i.enter_container(b'e', cstr(b"sv\0"))? {
+        let name = i.next::<String>()?.ok_or_else(bad_message)?;
+        let value = match Value::read(i)? {
+            Some(Value::Variant(v)) => *v,
+            _ => return Err(bad_message()),
+        };
+        i.exit_container()?;
+        properties.insert(name, value);
+    }
+    i.exit_container()?;
+    Ok(properties)
+}
+
+/// Read an `a{sa{sv}}` map of interfaces and their properties.
+fn read_interfaces(i: &mut MessageIter<'_>) -> crate::Result<Interfaces> {
+    let mut interfaces = BTreeMap::new();
+    if !i.enter_container(b'a', cstr(b"{sa{sv}}\0"))? {
+        return Err(bad_message());
+    }
+    while i.enter_container(b'e', cstr(b"sa{sv}\0"))? {
+        let name = i.next::<&str>()?.ok_or_else(bad_message)?;
+        let name = InterfaceNameBuf::new(name).map_err(|_| bad_message())?;
+        let properties = read_properties(i)?;
+        i.exit_container()?;
+        interfaces.insert(name, properties);
+    }
+    i.exit_container()?;
+    Ok(interfaces)
+}
+
+/// Identifies a signal: its interface and name, and optionally the sender and object emitting it.
+///
+/// The same definition produces the match rule used to subscribe to the signal
+/// ([`SignalDef::match_rule()`]) and checks messages against it ([`SignalDef::matches()`]), so the
+/// two can't drift apart.
+///
+/// ```no_run
+/// use systemd::bus::{Bus, BusName, InterfaceName, MemberName, SignalDef};
+/// let bus = Bus::default_system().unwrap();
+/// let def = SignalDef::new(
+///     InterfaceName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+///     MemberName::from_bytes(b"NameOwnerChanged\0").unwrap(),
+/// )
+/// .sender(BusName::from_bytes(b"org.freedesktop.DBus\0").unwrap());
+/// let _slot = def
+///     .add_match(&bus, |m| {
+///         println!("{:?}", m.member());
+///         Ok(())
+///     })
+///     .unwrap();
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct SignalDef<'a> {
+    sender: Option<&'a BusName>,
+    path: Option<&'a ObjectPath>,
+    interface: &'a InterfaceName,
+    member: &'a MemberName,
+}
+
+impl<'a> SignalDef<'a> {
+    /// A signal `member` of `interface`, from any sender and object.
+    pub fn new(interface: &'a InterfaceName, member: &'a MemberName) -> Self {
+        SignalDef {
+            sender: None,
+            path: None,
+            interface,
+            member,
+        }
+    }
+
+    /// Only match signals sent by `sender`.
+    pub fn sender(mut self, sender: &'a BusName) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    /// Only match signals emitted by the object at `path`.
+    pub fn path(mut self, path: &'a ObjectPath) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    /// The match rule selecting this signal, in the format of [`sd_bus_add_match`].
+    ///
+    /// [`sd_bus_add_match`]: https://www.freedesktop.org/software/systemd/man/sd_bus_add_match.html
+    pub fn match_rule(&self) -> CString {
+        let mut rule = MatchRule::new()
+            .type_(MessageType::Signal)
+            .interface(self.interface)
+            .member(self.member);
+        if let Some(sender) = self.sender {
+            rule = rule.sender(sender);
+        }
+        if let Some(path) = self.path {
+            rule = rule.path(path);
+        }
+        rule.to_cstring()
+    }
+
+    /// Whether `m` is this signal.
+    ///
+    /// The sender is not compared, as signals carry the unique name of the sender rather than
+    /// the well known name the definition usually refers to; the bus daemon does that check when
+    /// routing signals for a match rule.
+    pub fn matches(&self, m: &MessageRef) -> bool {
+        let is_signal = unsafe {
+            ffi::bus::sd_bus_message_is_signal(
+                m.as_ptr(),
+                self.interface.as_ptr(),
+                self.member.as_ptr(),
+            )
+        };
+        is_signal > 0
+            && match self.path {
+                Some(path) => m.path() == Some(&**path),
+                None => true,
+            }
+    }
+
+    /// Subscribe to the signal, calling `callback` for each occurrence.
+    ///
+    /// Messages which don't match the definition (see [`SignalDef::matches()`]) are not passed
+    /// to `callback`. The subscription lasts until the returned [`Slot`] is dropped.
+    pub fn add_match<F>(&self, bus: &BusRef, callback: F) -> super::Result<Slot>
+    where
+        F: Fn(&mut MessageRef) -> Result<()> + Send + Sync + 'static,
+    {
+        let def = OwnedSignalDef::from(self);
+        bus.add_match(self.match_rule(), move |m| {
+            if def.as_def().matches(m) {
+                callback(m)
+            } else {
+                Ok(())
+            }
+        })
+    }
+}
+
+/// A match rule selecting messages by their header fields, to be installed with
+/// [`BusRef::add_match()`].
+///
+/// Each component which is set restricts the messages which match, a rule without any matches all
+/// messages (which are routed to the connection).
+///
+/// ```no_run
+/// use systemd::bus::{Bus, BusName, InterfaceName, MatchRule, MessageType, ObjectPath};
+/// let bus = Bus::default_system().unwrap();
+/// let rule = MatchRule::new()
+///     .type_(MessageType::Signal)
+///     .sender(BusName::from_bytes(b"org.freedesktop.systemd1\0").unwrap())
+///     .path_namespace(ObjectPath::from_bytes(b"/org/freedesktop/systemd1/unit\0").unwrap())
+///     .interface(InterfaceName::from_bytes(b"org.freedesktop.DBus.Properties\0").unwrap());
+/// let _slot = bus
+///     .add_match(&rule, |m| {
+///         println!("{:?}", m.path());
+///         Ok(())
+///     })
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MatchRule<'a> {
+    type_: Option<MessageType>,
+    sender: Option<&'a BusName>,
+    path: Option<&'a ObjectPath>,
+    path_namespace: Option<&'a ObjectPath>,
+    interface: Option<&'a InterfaceName>,
+    member: Option<&'a MemberName>,
+    arg0: Option<&'a str>,
+    eavesdrop: bool,
+}
+
+impl<'a> MatchRule<'a> {
+    /// A rule matching all messages
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only match messages of type `type_`
+    pub fn type_(mut self, type_: MessageType) -> Self {
+        self.type_ = Some(type_);
+        self
+    }
+
+    /// Only match messages sent by `sender`
+    pub fn sender(mut self, sender: &'a BusName) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    /// Only match messages for (or from, for signals) the object at `path`
+    pub fn path(mut self, path: &'a ObjectPath) -> Self {
+        self.path = Some(path);
+        self
+    }
+
+    /// Only match messages for the object at `path` or any object below it
+    pub fn path_namespace(mut self, path: &'a ObjectPath) -> Self {
+        self.path_namespace = Some(path);
+        self
+    }
+
+    /// Only match messages of `interface`
+    pub fn interface(mut self, interface: &'a InterfaceName) -> Self {
+        self.interface = Some(interface);
+        self
+    }
+
+    /// Only match messages with the method or signal name `member`
+    pub fn member(mut self, member: &'a MemberName) -> Self {
+        self.member = Some(member);
+        self
+    }
+
+    /// Only match messages whose first argument is the string `arg0`
+    ///
+    /// If `arg0` contains an apostrophe, it can't also contain commas or backslashes, as these
+    /// can't be expressed in a way both sd-bus and the bus daemon understand.
+    ///
+    /// Fails if `arg0` contains a nul byte, which a match rule can't express.
+    pub fn arg0(mut self, arg0: &'a str) -> crate::Result<Self> {
+        if arg0.contains('\0') {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "arg0 of a match rule can't contain nul bytes",
+            ));
+        }
+        self.arg0 = Some(arg0);
+        Ok(self)
+    }
+
+    /// Also match messages which are not addressed to this connection. Only some bus daemons
+    /// allow this, and only for privileged connections.
+    pub fn eavesdrop(mut self, eavesdrop: bool) -> Self {
+        self.eavesdrop = eavesdrop;
+        self
+    }
+
+    /// The rule as a string in the format of [`sd_bus_add_match`].
+    ///
+    /// [`sd_bus_add_match`]: https://www.freedesktop.org/software/systemd/man/sd_bus_add_match.html
+    pub fn to_cstring(&self) -> CString {
+        let mut r = Vec::new();
+        let mut add = |key: &str, value: &[u8]| {
+            if !r.is_empty() {
+                r.push(b',');
+            }
+            r.extend_from_slice(key.as_bytes());
+            r.push(b'=');
+            if value.contains(&b'\'') {
+                // quoted values can't contain apostrophes, and sd-bus doesn't accept mixing quoted
+                // and unquoted parts, so write the value unquoted with the apostrophes escaped
+                for &b in value {
+                    if b == b'\'' {
+                        r.push(b'\\');
+                    }
+                    r.push(b);
+                }
+            } else {
+                r.push(b'\'');
+                r.extend_from_slice(value);
+                r.push(b'\'');
+            }
+        };
+        if let Some(type_) = self.type_ {
+            add("type", type_.match_name().as_bytes());
+        }
+        if let Some(sender) = self.sender {
+            add("sender", sender.to_bytes());
+        }
+        if let Some(path) = self.path {
+            add("path", path.to_bytes());
+        }
+        if let Some(path) = self.path_namespace {
+            add("path_namespace", path.to_bytes());
+        }
+        if let Some(interface) = self.interface {
+            add("interface", interface.to_bytes());
+        }
+        if let Some(member) = self.member {
+            add("member", member.to_bytes());
+        }
+        if let Some(arg0) = self.arg0 {
+            add("arg0", arg0.as_bytes());
+        }
+        if self.eavesdrop {
+            add("eavesdrop", b"true");
+        }
+        // names are validated and `arg0()` rejects nul bytes
+        CString::new(r).unwrap()
+    }
+}
+
+impl<'a, 'b> CStrArgument for &'b MatchRule<'a> {
+    type Output = CString;
+
+    fn try_into_cstr(self) -> result::Result<Self::Output, cstr_argument::NulError<Self>> {
+        Ok(self.to_cstring())
+    }
+}
+
+impl fmt::Display for MatchRule<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_cstring().to_string_lossy())
+    }
+}
+
+#[test]
+fn t_match_rule() {
+    assert_eq!(MatchRule::new().to_cstring().as_bytes(), b"");
+    let rule = MatchRule::new()
+        .type_(MessageType::MethodError)
+        .sender(BusName::from_bytes(b"org.example\0").unwrap())
+        .path(ObjectPath::from_bytes(b"/a\0").unwrap())
+        .path_namespace(ObjectPath::from_bytes(b"/b\0").unwrap())
+        .interface(InterfaceName::from_bytes(b"org.example.I\0").unwrap())
+        .member(MemberName::from_bytes(b"M\0").unwrap())
+        .arg0("it's")
+        .unwrap()
+        .eavesdrop(true);
+    assert_eq!(
+        rule.to_string(),
+        "type='error',sender='org.example',path='/a',path_namespace='/b',\
+         interface='org.example.I',member='M',arg0=it\\'s,eavesdrop='true'"
+    );
+    assert_eq!(
+        MatchRule::new().arg0("a\0b").unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+}
+
+/// A [`SignalDef`] which can be moved into callbacks.
+struct OwnedSignalDef {
+    path: Option<CString>,
+    interface: CString,
+    member: CString,
+}
+
+impl OwnedSignalDef {
+    fn as_def(&self) -> SignalDef<'_> {
+        // the names were copied from validated ones
+        unsafe {
+            SignalDef {
+                sender: None,
+                path: self
+                    .path
+                    .as_ref()
+                    .map(|p| ObjectPath::from_bytes_unchecked(p.as_bytes_with_nul())),
+                interface: InterfaceName::from_bytes_unchecked(self.interface.as_bytes_with_nul()),
+                member: MemberName::from_bytes_unchecked(self.member.as_bytes_with_nul()),
+            }
+        }
+    }
+}
+
+impl<'a> From<&SignalDef<'a>> for OwnedSignalDef {
+    fn from(d: &SignalDef<'a>) -> Self {
+        OwnedSignalDef {
+            path: d.path.map(|p| CString::from(&**p)),
+            interface: CString::from(&**d.interface),
+            member: CString::from(&**d.member),
+        }
+    }
+}
+
+foreign_type! {
+    pub unsafe type Bus {
+        type CType = ffi::bus::sd_bus;
+        fn drop = ffi::bus::sd_bus_unref;
+        fn clone = ffi::bus::sd_bus_ref;
+    }
+}
+
+impl Bus {
+    #[inline]
+    pub fn default() -> crate::Result<Bus> {
+        let mut b = MaybeUninit::uninit();
+        sd_try!(ffi::bus::sd_bus_default(b.as_mut_ptr()));
+        Ok(unsafe { Bus::from_ptr(b.assume_init()) })
+    }
+
+    #[inline]
+    pub fn default_user() -> crate::Result<Bus> {
+        let mut b = MaybeUninit::uninit();
+        sd_try!(ffi::bus::sd_bus_default_user(b.as_mut_ptr()));
         Ok(unsafe { Bus::from_ptr(b.assume_init()) })
     }
 
@@ -881,6 +2136,290 @@ impl Bus {
         sd_try!(ffi::bus::sd_bus_default_system(b.as_mut_ptr()));
         Ok(unsafe { Bus::from_ptr(b.assume_init()) })
     }
+
+    /// Like [`Bus::default_system()`], but returns `None` if there is no system bus to connect
+    /// to: its socket doesn't exist or nothing listens on it, as in many containers.
+    ///
+    /// This allows tools to fall back to code paths which don't need the bus, while still
+    /// reporting other failures (like running out of file descriptors). The clients in
+    /// [`manager`](crate::manager) and [`login1`](crate::login1) take an existing connection,
+    /// so this is the place to decide whether to use them at all.
+    ///
+    /// ```no_run
+    /// use systemd::{bus::Bus, manager};
+    /// match Bus::try_default_system().unwrap() {
+    ///     Some(mut bus) => manager::reload(&mut bus).unwrap(),
+    ///     None => eprintln!("no system bus, not reloading"),
+    /// }
+    /// ```
+    pub fn try_default_system() -> super::Result<Option<Bus>> {
+        no_bus_to_none(Bus::default_system())
+    }
+
+    /// Like [`Bus::default_user()`], but returns `None` if there is no user bus to connect to,
+    /// including when `$XDG_RUNTIME_DIR` isn't set (for example in a `su` session).
+    pub fn try_default_user() -> super::Result<Option<Bus>> {
+        no_bus_to_none(Bus::default_user())
+    }
+}
+
+/// Turn the errors of connecting to a bus which doesn't exist into `None`
+fn no_bus_to_none(r: crate::Result<Bus>) -> super::Result<Option<Bus>> {
+    match r {
+        Ok(b) => Ok(Some(b)),
+        Err(e)
+            if matches!(
+                e.raw_os_error(),
+                Some(libc::ENOENT) | Some(libc::ECONNREFUSED) | Some(libc::ENOMEDIUM)
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+impl Bus {
+    /// Convert into a [`BusConnection`] which flushes and closes the connection when dropped,
+    /// instead of only dropping a reference to it.
+    ///
+    /// Note that closing affects every holder of the underlying connection, so this should not be
+    /// used on the shared connections returned by [`Bus::default()`] and friends while other
+    /// code may still use them.
+    #[inline]
+    pub fn into_flush_close(self) -> BusConnection {
+        BusConnection {
+            bus: ManuallyDrop::new(self),
+            flush_close: true,
+        }
+    }
+}
+
+/// Which bus a [`BusBuilder`] connects to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum BusKind {
+    Default,
+    User,
+    System,
+    Address(CString),
+}
+
+/// Open a new (non-shared) connection to a bus, using custom options.
+///
+/// # Examples
+///
+/// ```no_run
+/// use systemd::bus;
+/// let b = bus::BusBuilder::system()
+///     .description("my-service")
+///     .flush_close_on_drop(true)
+///     .open()
+///     .unwrap();
+/// // ... queue signals ...
+/// drop(b); // queued messages are written out before the connection is closed
+/// ```
+#[derive(Clone, Debug)]
+pub struct BusBuilder {
+    kind: BusKind,
+    description: Option<CString>,
+    flush_close_on_drop: bool,
+    method_call_timeout: Option<Duration>,
+}
+
+impl Default for BusBuilder {
+    /// Connect to the user bus when running in a user session, and to the system bus otherwise.
+    ///
+    /// This corresponds to [`sd_bus_open`]
+    ///
+    /// [`sd_bus_open`]: https://www.freedesktop.org/software/systemd/man/sd_bus_open.html
+    fn default() -> Self {
+        Self::with_kind(BusKind::Default)
+    }
+}
+
+impl BusBuilder {
+    /// Connect to the user bus.
+    ///
+    /// This corresponds to [`sd_bus_open_user`]
+    ///
+    /// [`sd_bus_open_user`]: https://www.freedesktop.org/software/systemd/man/sd_bus_open_user.html
+    pub fn user() -> Self {
+        Self::with_kind(BusKind::User)
+    }
+
+    /// Connect to the system bus.
+    ///
+    /// This corresponds to [`sd_bus_open_system`]
+    ///
+    /// [`sd_bus_open_system`]: https://www.freedesktop.org/software/systemd/man/sd_bus_open_system.html
+    pub fn system() -> Self {
+        Self::with_kind(BusKind::System)
+    }
+
+    /// Connect to the bus at `address`, in the D-Bus address format (like
+    /// `unix:path=/run/user/1000/bus`, see [`login::user_bus_address()`]).
+    ///
+    /// This corresponds to [`sd_bus_set_address`]
+    ///
+    /// [`login::user_bus_address()`]: crate::login::user_bus_address
+    /// [`sd_bus_set_address`]: https://www.freedesktop.org/software/systemd/man/sd_bus_set_address.html
+    pub fn address<S: CStrArgument>(address: S) -> Self {
+        Self::with_kind(BusKind::Address(address.into_cstr().as_ref().to_owned()))
+    }
+
+    fn with_kind(kind: BusKind) -> Self {
+        BusBuilder {
+            kind,
+            description: None,
+            flush_close_on_drop: false,
+            method_call_timeout: None,
+        }
+    }
+
+    /// Set a description of the connection, used in debug and log messages.
+    ///
+    /// This corresponds to [`sd_bus_set_description`]
+    ///
+    /// [`sd_bus_set_description`]: https://www.freedesktop.org/software/systemd/man/sd_bus_set_description.html
+    pub fn description<S: CStrArgument>(&mut self, description: S) -> &mut Self {
+        self.description = Some(description.into_cstr().as_ref().to_owned());
+        self
+    }
+
+    /// When `true`, dropping the returned [`BusConnection`] flushes queued outgoing messages and
+    /// closes the connection ([`sd_bus_flush_close_unref`]) instead of only dropping a reference
+    /// to it ([`sd_bus_unref`]).
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`sd_bus_flush_close_unref`]: https://www.freedesktop.org/software/systemd/man/sd_bus_new.html
+    /// [`sd_bus_unref`]: https://www.freedesktop.org/software/systemd/man/sd_bus_new.html
+    pub fn flush_close_on_drop(&mut self, flush_close: bool) -> &mut Self {
+        self.flush_close_on_drop = flush_close;
+        self
+    }
+
+    /// How long to wait for the replies to method calls made with a timeout of `0`, like
+    /// [`BusRef::call_method()`] does.
+    ///
+    /// Defaults to [`default_call_timeout()`].
+    ///
+    /// This corresponds to [`sd_bus_set_method_call_timeout`]
+    ///
+    /// [`sd_bus_set_method_call_timeout`]: https://www.freedesktop.org/software/systemd/man/sd_bus_set_method_call_timeout.html
+    pub fn method_call_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.method_call_timeout = Some(timeout);
+        self
+    }
+
+    /// Open the connection.
+    pub fn open(&self) -> crate::Result<BusConnection> {
+        let mut b = MaybeUninit::uninit();
+        match &self.kind {
+            BusKind::Default => sd_try!(ffi::bus::sd_bus_open(b.as_mut_ptr())),
+            BusKind::User => sd_try!(ffi::bus::sd_bus_open_user(b.as_mut_ptr())),
+            BusKind::System => sd_try!(ffi::bus::sd_bus_open_system(b.as_mut_ptr())),
+            BusKind::Address(_) => sd_try!(ffi::bus::sd_bus_new(b.as_mut_ptr())),
+        };
+        let bus = unsafe { Bus::from_ptr(b.assume_init()) };
+        if let BusKind::Address(address) = &self.kind {
+            sd_try!(ffi::bus::sd_bus_set_address(bus.as_ptr(), address.as_ptr()));
+            sd_try!(ffi::bus::sd_bus_set_bus_client(bus.as_ptr(), 1));
+            sd_try!(ffi::bus::sd_bus_start(bus.as_ptr()));
+        }
+        if let Some(d) = &self.description {
+            sd_try!(ffi::bus::sd_bus_set_description(bus.as_ptr(), d.as_ptr()));
+        }
+        if let Some(t) = self.method_call_timeout {
+            sd_try!(ffi::bus::sd_bus_set_method_call_timeout(
+                bus.as_ptr(),
+                usec_from_duration(t)
+            ));
+        }
+        Ok(BusConnection {
+            bus: ManuallyDrop::new(bus),
+            flush_close: self.flush_close_on_drop,
+        })
+    }
+}
+
+/// Features and setup of a bus connection, as reported by [`BusRef::capabilities()`].
+///
+/// Transports differ in what they support (for example, connections to remote machines can't
+/// pass file descriptors), so this allows choosing code paths up front instead of failing when a
+/// message is sent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BusCapabilities {
+    /// File descriptors can be sent and received (`sd_bus_can_send(bus, 'h')`)
+    pub fds: bool,
+    /// Credentials attached to received messages, see [`BusRef::creds_mask()`]
+    pub creds_mask: u64,
+    /// Connected to a message bus, rather than directly to a peer
+    pub bus_client: bool,
+    /// Acting as the server side of a direct connection
+    pub server: bool,
+    /// The connection authenticated anonymously
+    pub anonymous: bool,
+    /// All peers are trusted, so access checks are skipped
+    pub trusted: bool,
+    /// The connection is in monitor mode, only receiving copies of messages
+    pub monitor: bool,
+}
+
+/// An owned [`Bus`] whose behaviour on drop is configurable.
+///
+/// Created by [`BusBuilder::open()`] and [`Bus::into_flush_close()`]. Dereferences to
+/// [`BusRef`], so it can be used wherever a bus is needed.
+pub struct BusConnection {
+    bus: ManuallyDrop<Bus>,
+    flush_close: bool,
+}
+
+impl BusConnection {
+    /// Select whether dropping flushes and closes the connection, see
+    /// [`BusBuilder::flush_close_on_drop()`].
+    pub fn set_flush_close_on_drop(&mut self, flush_close: bool) {
+        self.flush_close = flush_close;
+    }
+
+    /// Return the underlying [`Bus`], which only drops a reference when it is dropped.
+    pub fn into_bus(self) -> Bus {
+        let mut this = ManuallyDrop::new(self);
+        unsafe { ManuallyDrop::take(&mut this.bus) }
+    }
+}
+
+impl Deref for BusConnection {
+    type Target = BusRef;
+
+    fn deref(&self) -> &BusRef {
+        &self.bus
+    }
+}
+
+impl DerefMut for BusConnection {
+    fn deref_mut(&mut self) -> &mut BusRef {
+        &mut self.bus
+    }
+}
+
+impl Drop for BusConnection {
+    fn drop(&mut self) {
+        if self.flush_close {
+            unsafe { ffi::bus::sd_bus_flush_close_unref(self.bus.as_ptr()) };
+        } else {
+            unsafe { ManuallyDrop::drop(&mut self.bus) };
+        }
+    }
+}
+
+impl fmt::Debug for BusConnection {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("BusConnection")
+            .field("bus", &**self.bus)
+            .field("flush_close", &self.flush_close)
+            .finish()
+    }
 }
 
 impl fmt::Debug for BusRef {
@@ -1026,11 +2565,62 @@ impl BusRef {
         )) > 0)
     }
 
-    /// Get the unique name (address) of this connection to this `Bus`.
+    /// Process the connection until `pred` returns `true` for a message, returning that message.
     ///
+    /// This repeatedly calls [`BusRef::process()`], and [`BusRef::wait()`] whenever there is
+    /// nothing left to process. `pred` sees the messages `process()` returns, which are those not
+    /// handled by any callback (method calls to objects, matches, ...); callbacks are invoked as
+    /// usual while this runs.
     ///
+    /// Returns `None` if the connection was closed.
+    /// Waiting which is interrupted by a signal (`EINTR`) is retried.
     ///
-    /// This corresponds to [`sd_bus_get_unique_name`].
+    /// ```no_run
+    /// use systemd::bus;
+    /// let mut bus = bus::Bus::default().unwrap();
+    /// // ... add objects and matches ...
+    /// if let Some(m) = bus.run_until(|m| m.member().is_some()).unwrap() {
+    ///     println!("unhandled call of {:?}", m.member());
+    /// }
+    /// ```
+    pub fn run_until<F>(&mut self, mut pred: F) -> super::Result<Option<Message>>
+    where
+        F: FnMut(&mut MessageRef) -> bool,
+    {
+        loop {
+            let r = match self.process() {
+                Ok(Some(Some(mut m))) => {
+                    if pred(&mut m) {
+                        return Ok(Some(m));
+                    }
+                    continue;
+                }
+                // progress was made, more may be pending
+                Ok(Some(None)) => continue,
+                Ok(None) => self.wait(None).map(|_| ()),
+                Err(e) => Err(e),
+            };
+            match r {
+                Ok(()) => {}
+                Err(e) if e.raw_os_error() == Some(libc::EINTR) => {}
+                Err(e) if is_disconnect(&e) => return Ok(None),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Process the connection until it is closed, dispatching messages to callbacks.
+    ///
+    /// Messages not handled by any callback are dropped. See [`BusRef::run_until()`].
+    pub fn run_forever(&mut self) -> super::Result<()> {
+        self.run_until(|_| false).map(|_| ())
+    }
+
+    /// Get the unique name (address) of this connection to this `Bus`.
+    ///
+    ///
+    ///
+    /// This corresponds to [`sd_bus_get_unique_name`].
     ///
     /// [`sd_bus_get_unique_name`]: https://www.freedesktop.org/software/systemd/man/sd_bus_get_unique_name.html
     #[inline]
@@ -1093,13 +2683,94 @@ impl BusRef {
         Ok(ret)
     }
 
-    /*
-    pub fn event(&self) -> super::Result<Event>
-    {
+    /// The credentials which are attached to messages received on this connection, as a mask of
+    /// `SD_BUS_CREDS_*` flags.
+    ///
+    /// This corresponds to [`sd_bus_get_creds_mask`]
+    ///
+    /// [`sd_bus_get_creds_mask`]: https://www.freedesktop.org/software/systemd/man/sd_bus_negotiate_fds.html
+    pub fn creds_mask(&self) -> super::Result<u64> {
+        let mut ret = Default::default();
+        sd_try!(ffi::bus::sd_bus_get_creds_mask(self.as_ptr(), &mut ret));
+        Ok(ret)
+    }
 
+    /// Report the features negotiated for this connection, and how it is set up.
+    ///
+    /// As negotiation happens while connecting, this waits for the connection to be established
+    /// if it isn't yet.
+    pub fn capabilities(&self) -> super::Result<BusCapabilities> {
+        let flag = |r: c_int| crate::ffi_result(r).map(|r| r > 0);
+        let b = self.as_ptr();
+        unsafe {
+            Ok(BusCapabilities {
+                fds: flag(ffi::bus::sd_bus_can_send(b, b'h' as c_char))?,
+                creds_mask: self.creds_mask()?,
+                bus_client: flag(ffi::bus::sd_bus_is_bus_client(b))?,
+                server: flag(ffi::bus::sd_bus_is_server(b))?,
+                anonymous: flag(ffi::bus::sd_bus_is_anonymous(b))?,
+                trusted: flag(ffi::bus::sd_bus_is_trusted(b))?,
+                monitor: flag(ffi::bus::sd_bus_is_monitor(b))?,
+            })
+        }
+    }
+
+    /// Attach the bus to `event`, so that running the event loop processes the bus instead of
+    /// calling [`BusRef::process()`] and [`BusRef::wait()`] by hand.
+    ///
+    /// `priority` is that of the event sources dispatching the bus, lower values are dispatched
+    /// first and `0` is the normal priority. The bus holds a reference to the event loop until
+    /// it is detached with [`BusRef::detach_event()`] or dropped.
+    ///
+    /// This corresponds to [`sd_bus_attach_event`].
+    ///
+    /// [`sd_bus_attach_event`]: https://www.freedesktop.org/software/systemd/man/sd_bus_attach_event.html
+    #[cfg(feature = "event")]
+    #[inline]
+    pub fn attach_event(&mut self, event: &EventRef, priority: c_int) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_attach_event(
+            self.as_ptr(),
+            event.as_ptr(),
+            priority
+        ));
+        Ok(())
+    }
+
+    /// Detach the bus from the event loop it was attached to with [`BusRef::attach_event()`],
+    /// if any.
+    ///
+    /// This corresponds to [`sd_bus_detach_event`].
+    ///
+    /// [`sd_bus_detach_event`]: https://www.freedesktop.org/software/systemd/man/sd_bus_attach_event.html
+    #[cfg(feature = "event")]
+    #[inline]
+    pub fn detach_event(&mut self) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_detach_event(self.as_ptr()));
+        Ok(())
+    }
+
+    /// Returns the event loop the bus is attached to, if any.
+    ///
+    /// This corresponds to [`sd_bus_get_event`].
+    ///
+    /// [`sd_bus_get_event`]: https://www.freedesktop.org/software/systemd/man/sd_bus_attach_event.html
+    #[cfg(feature = "event")]
+    pub fn event(&self) -> Option<Event> {
+        let e = unsafe { ffi::bus::sd_bus_get_event(self.as_ptr()) };
+        if e.is_null() {
+            None
+        } else {
+            Some(unsafe { EventRef::from_ptr(e) }.to_owned())
+        }
     }
-    */
 
+    /// How long method calls made with a timeout of `0` wait for their reply, in microseconds.
+    ///
+    /// Unless set, this is [`default_call_timeout()`].
+    ///
+    /// This corresponds to [`sd_bus_get_method_call_timeout`]
+    ///
+    /// [`sd_bus_get_method_call_timeout`]: https://www.freedesktop.org/software/systemd/man/sd_bus_set_method_call_timeout.html
     pub fn method_call_timeout(&self) -> super::Result<u64> {
         let mut ret = Default::default();
         sd_try!(ffi::bus::sd_bus_get_method_call_timeout(
@@ -1109,12 +2780,82 @@ impl BusRef {
         Ok(ret)
     }
 
+    /// Set how long method calls made with a timeout of `0` wait for their reply.
+    ///
+    /// This corresponds to [`sd_bus_set_method_call_timeout`]
+    ///
+    /// [`sd_bus_set_method_call_timeout`]: https://www.freedesktop.org/software/systemd/man/sd_bus_set_method_call_timeout.html
+    pub fn set_method_call_timeout(&mut self, timeout: Duration) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_set_method_call_timeout(
+            self.as_ptr(),
+            usec_from_duration(timeout)
+        ));
+        Ok(())
+    }
+
     pub fn bus_id(&self) -> super::Result<super::id128::Id128> {
         let mut id: super::id128::Id128 = Default::default();
         crate::ffi_result(unsafe { ffi::bus::sd_bus_get_bus_id(self.as_ptr(), id.as_raw_mut()) })?;
         Ok(id)
     }
 
+    /// Call `f` with the message currently being dispatched on this bus, or `None` outside of a
+    /// callback.
+    ///
+    /// This lets code running inside a callback (method handlers, matches, ...) reach the
+    /// in-flight message without having it passed down explicitly, for example for logging. The
+    /// message is only lent to `f`, so it can't be kept past the dispatch of the callback.
+    ///
+    /// This corresponds to [`sd_bus_get_current_message`].
+    ///
+    /// [`sd_bus_get_current_message`]: https://www.freedesktop.org/software/systemd/man/sd_bus_get_current_message.html
+    pub fn with_current_message<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(Option<&MessageRef>) -> R,
+    {
+        let m = unsafe { ffi::bus::sd_bus_get_current_message(self.as_ptr()) };
+        if m.is_null() {
+            return f(None);
+        }
+        // keep the message alive even if `f` manages to end the dispatch
+        let m = unsafe { MessageRef::from_ptr(m) }.to_owned();
+        f(Some(&m))
+    }
+
+    /// Returns the slot whose callback is currently being dispatched on this bus, or `None`
+    /// outside of a callback.
+    ///
+    /// The returned `Slot` holds its own reference: dropping it does not unregister the callback.
+    ///
+    /// This corresponds to [`sd_bus_get_current_slot`].
+    ///
+    /// [`sd_bus_get_current_slot`]: https://www.freedesktop.org/software/systemd/man/sd_bus_get_current_slot.html
+    pub fn current_slot(&self) -> Option<Slot> {
+        let s = unsafe { ffi::bus::sd_bus_get_current_slot(self.as_ptr()) };
+        if s.is_null() {
+            return None;
+        }
+        Some(unsafe { Slot::from_ptr(ffi::bus::sd_bus_slot_ref(s)) })
+    }
+
+    /// Returns the userdata of the callback currently being dispatched on this bus, or `None`
+    /// outside of a callback (or if it has none).
+    ///
+    /// This corresponds to [`sd_bus_get_current_userdata`].
+    ///
+    /// # Safety
+    ///
+    /// The userdata must point to a `T` which lives as long as the returned reference. For
+    /// callbacks registered through this crate it points at state private to the crate (like
+    /// the boxed closure), so this is only useful for callbacks registered through `libsystemd-sys`
+    /// directly.
+    ///
+    /// [`sd_bus_get_current_userdata`]: https://www.freedesktop.org/software/systemd/man/sd_bus_get_current_userdata.html
+    pub unsafe fn current_userdata<T>(&self) -> Option<&T> {
+        let u = ffi::bus::sd_bus_get_current_userdata(self.as_ptr());
+        (u as *const T).as_ref()
+    }
+
     ///
     /// This corresponds to [`sd_bus_message_new_signal`].
     ///
@@ -1162,6 +2903,432 @@ impl BusRef {
         Ok(unsafe { Message::from_ptr(m) })
     }
 
+    /// Call a method and wait for the reply, using `append` to add the arguments to the method
+    /// call message.
+    ///
+    /// This is the equivalent of [`sd_bus_call_method`], which can't be wrapped directly as it
+    /// takes its arguments as varargs.
+    ///
+    /// [`sd_bus_call_method`]: https://www.freedesktop.org/software/systemd/man/sd_bus_call_method.html
+    pub fn call_method<F>(
+        &mut self,
+        dest: &BusName,
+        path: &ObjectPath,
+        interface: &InterfaceName,
+        member: &MemberName,
+        append: F,
+    ) -> Result<Message>
+    where
+        F: FnOnce(&mut MessageRef) -> crate::Result<()>,
+    {
+        let mut m = self.new_method_call(dest, path, interface, member)?;
+        append(&mut m)?;
+        m.call(0)
+    }
+
+    /// Call a method with the values in `args` as its arguments and wait for the reply.
+    ///
+    /// `args` is a tuple of the arguments, or `()` for none.
+    ///
+    /// ```no_run
+    /// # use systemd::bus;
+    /// let mut b = bus::Bus::default_system().unwrap();
+    /// let mut reply = b
+    ///     .call_method_args(
+    ///         bus::BusName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+    ///         bus::ObjectPath::from_bytes(b"/\0").unwrap(),
+    ///         bus::InterfaceName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+    ///         bus::MemberName::from_bytes(b"GetNameOwner\0").unwrap(),
+    ///         ("org.freedesktop.systemd1",),
+    ///     )
+    ///     .unwrap();
+    /// let owner: String = reply.iter().unwrap().next().unwrap().unwrap();
+    /// ```
+    pub fn call_method_args<A: types::ToSdBusMessageArgs>(
+        &mut self,
+        dest: &BusName,
+        path: &ObjectPath,
+        interface: &InterfaceName,
+        member: &MemberName,
+        args: A,
+    ) -> Result<Message> {
+        self.call_method(dest, path, interface, member, |m| m.append_tuple(args))
+    }
+
+    /// Call a method with the values in `args` as its arguments without waiting for the reply,
+    /// which is passed to `callback` once it arrives, see [`MessageRef::call_async()`].
+    ///
+    /// Dropping the returned [`Slot`] before the reply arrives cancels the call.
+    ///
+    /// This is the equivalent of [`sd_bus_call_method_async`].
+    ///
+    /// [`sd_bus_call_method_async`]: https://www.freedesktop.org/software/systemd/man/sd_bus_call_method.html
+    pub fn call_method_async<A, F>(
+        &mut self,
+        dest: &BusName,
+        path: &ObjectPath,
+        interface: &InterfaceName,
+        member: &MemberName,
+        args: A,
+        callback: F,
+    ) -> Result<Slot>
+    where
+        A: types::ToSdBusMessageArgs,
+        F: Fn(&mut MessageRef) -> Result<()> + 'static + Sync + Send,
+    {
+        let mut m = self.new_method_call(dest, path, interface, member)?;
+        m.append_tuple(args)?;
+        Ok(m.call_async(callback, 0)?)
+    }
+
+    /// Like [`BusRef::call_method()`], retrying calls which fail with the errors `policy` selects.
+    ///
+    /// `append` is called to build a new method call message for every attempt. Only the error of
+    /// the last attempt is returned.
+    pub fn call_method_with_policy<F>(
+        &mut self,
+        dest: &BusName,
+        path: &ObjectPath,
+        interface: &InterfaceName,
+        member: &MemberName,
+        policy: &CallPolicy,
+        append: F,
+    ) -> Result<Message>
+    where
+        F: Fn(&mut MessageRef) -> crate::Result<()>,
+    {
+        let mut delay = policy.backoff;
+        let mut attempt = 0;
+        loop {
+            let e = match self.call_method(dest, path, interface, member, &append) {
+                Ok(m) => return Ok(m),
+                Err(e) => e,
+            };
+            let kind = e.kind();
+            if attempt >= policy.retries || !policy.retry_on.contains(&kind) {
+                return Err(e);
+            }
+            attempt += 1;
+
+            match policy.activation_timeout {
+                Some(timeout) if kind == ErrorKind::ServiceUnknown => {
+                    self.wait_for_name(dest, timeout, policy.backoff)?;
+                }
+                _ => {
+                    std::thread::sleep(delay);
+                    delay = delay.checked_mul(2).unwrap_or(delay);
+                }
+            }
+        }
+    }
+
+    /// Poll the bus driver every `interval` until `name` has an owner or `timeout` elapsed.
+    fn wait_for_name(
+        &mut self,
+        name: &BusName,
+        timeout: Duration,
+        interval: Duration,
+    ) -> Result<()> {
+        let deadline = std::time::Instant::now() + timeout;
+        let name = Utf8CStr::from_bytes(name.to_bytes_with_nul()).unwrap();
+        loop {
+            let mut r = self.call_method(
+                BusName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+                ObjectPath::from_bytes(b"/org/freedesktop/DBus\0").unwrap(),
+                InterfaceName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+                MemberName::from_bytes(b"NameHasOwner\0").unwrap(),
+                |m| m.append(name),
+            )?;
+            if r.iter()?.next::<bool>()? == Some(true) {
+                return Ok(());
+            }
+            let now = std::time::Instant::now();
+            if now >= deadline {
+                return Ok(());
+            }
+            std::thread::sleep(interval.min(deadline - now));
+        }
+    }
+
+    /// Retrieve a property of a basic type (integer, float, bool) from a remote object.
+    ///
+    /// This corresponds to [`sd_bus_get_property_trivial`]
+    ///
+    /// [`sd_bus_get_property_trivial`]: https://www.freedesktop.org/software/systemd/man/sd_bus_get_property_trivial.html
+    #[inline]
+    pub fn get_property_trivial<T: types::SdBusMessageDirect>(
+        &mut self,
+        dest: &BusName,
+        path: &ObjectPath,
+        interface: &InterfaceName,
+        member: &MemberName,
+    ) -> Result<T> {
+        let mut v = MaybeUninit::<T>::uninit();
+        let mut e = RawError::new();
+        let r = unsafe {
+            ffi::bus::sd_bus_get_property_trivial(
+                self.as_ptr(),
+                dest.as_ptr(),
+                path.as_ptr(),
+                interface.as_ptr(),
+                member.as_ptr(),
+                e.as_mut_ptr(),
+                T::dbus_type() as c_char,
+                v.as_mut_ptr() as *mut c_void,
+            )
+        };
+        e.into_result_with(r).map(|_| unsafe { v.assume_init() })
+    }
+
+    /// Retrieve a string property from a remote object.
+    ///
+    /// This corresponds to [`sd_bus_get_property_string`]
+    ///
+    /// [`sd_bus_get_property_string`]: https://www.freedesktop.org/software/systemd/man/sd_bus_get_property_string.html
+    #[inline]
+    pub fn get_property_string(
+        &mut self,
+        dest: &BusName,
+        path: &ObjectPath,
+        interface: &InterfaceName,
+        member: &MemberName,
+    ) -> Result<String> {
+        let mut v = ptr::null_mut();
+        let mut e = RawError::new();
+        let r = unsafe {
+            ffi::bus::sd_bus_get_property_string(
+                self.as_ptr(),
+                dest.as_ptr(),
+                path.as_ptr(),
+                interface.as_ptr(),
+                member.as_ptr(),
+                e.as_mut_ptr(),
+                &mut v,
+            )
+        };
+        e.into_result_with(r)
+            .map(|_| unsafe { super::free_cstring(v) }.unwrap_or_default())
+    }
+
+    /// Retrieve the property `member` of `interface` from the object at `path` on `dest`.
+    ///
+    /// The property must be of type `T`, reading one of another type fails with `ENXIO`. Use
+    /// [`BusRef::get_all_properties()`] for properties of unknown types.
+    ///
+    /// This calls `org.freedesktop.DBus.Properties.Get` and waits for the reply.
+    ///
+    /// ```no_run
+    /// # use systemd::bus;
+    /// let mut b = bus::Bus::default_system().unwrap();
+    /// let version: String = b
+    ///     .get_property(
+    ///         bus::BusName::from_bytes(b"org.freedesktop.systemd1\0").unwrap(),
+    ///         bus::ObjectPath::from_bytes(b"/org/freedesktop/systemd1\0").unwrap(),
+    ///         bus::InterfaceName::from_bytes(b"org.freedesktop.systemd1.Manager\0").unwrap(),
+    ///         bus::MemberName::from_bytes(b"Version\0").unwrap(),
+    ///     )
+    ///     .unwrap();
+    /// ```
+    pub fn get_property<T>(
+        &mut self,
+        dest: &BusName,
+        path: &ObjectPath,
+        interface: &InterfaceName,
+        member: &MemberName,
+    ) -> Result<T>
+    where
+        T: for<'a> types::FromSdBusMessage<'a> + types::SdBusSignature,
+    {
+        let mut reply = self.call_method(
+            dest,
+            path,
+            properties_interface(),
+            MemberName::from_bytes(b"Get\0").unwrap(),
+            |m| {
+                append_name(m, interface)?;
+                append_name(m, member)
+            },
+        )?;
+        let mut i = reply.iter()?;
+        match i.next::<Variant<T>>()? {
+            Some(Variant(v)) => Ok(v),
+            None => Err(bad_message().into()),
+        }
+    }
+
+    /// Set the property `member` of `interface` of the object at `path` on `dest` to `value`.
+    ///
+    /// This calls `org.freedesktop.DBus.Properties.Set` and waits for the reply.
+    pub fn set_property<T>(
+        &mut self,
+        dest: &BusName,
+        path: &ObjectPath,
+        interface: &InterfaceName,
+        member: &MemberName,
+        value: T,
+    ) -> Result<()>
+    where
+        T: types::ToSdBusMessage + types::SdBusSignature,
+    {
+        self.call_method(
+            dest,
+            path,
+            properties_interface(),
+            MemberName::from_bytes(b"Set\0").unwrap(),
+            |m| {
+                append_name(m, interface)?;
+                append_name(m, member)?;
+                m.append(Variant(value))
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Retrieve all properties of `interface` from the object at `path` on `dest`, with their
+    /// values.
+    ///
+    /// This calls `org.freedesktop.DBus.Properties.GetAll` and waits for the reply.
+    pub fn get_all_properties(
+        &mut self,
+        dest: &BusName,
+        path: &ObjectPath,
+        interface: &InterfaceName,
+    ) -> Result<Properties> {
+        let mut reply = self.call_method(
+            dest,
+            path,
+            properties_interface(),
+            MemberName::from_bytes(b"GetAll\0").unwrap(),
+            |m| append_name(m, interface),
+        )?;
+        Ok(read_properties(&mut reply.iter()?)?)
+    }
+
+    /// Fetch the introspection XML of the object at `path` on `dest`.
+    ///
+    /// For objects registered with [`BusRef::add_object_vtable()`], sd-bus generates this from the
+    /// registered vtables, along with the standard `org.freedesktop.DBus.Introspectable`,
+    /// `org.freedesktop.DBus.Properties` and `org.freedesktop.DBus.Peer` interfaces.
+    ///
+    /// This calls `org.freedesktop.DBus.Introspectable.Introspect` and waits for the reply.
+    pub fn introspect(&mut self, dest: &BusName, path: &ObjectPath) -> Result<String> {
+        let mut reply = self.call_method(
+            dest,
+            path,
+            InterfaceName::from_bytes(b"org.freedesktop.DBus.Introspectable\0").unwrap(),
+            MemberName::from_bytes(b"Introspect\0").unwrap(),
+            |_| Ok(()),
+        )?;
+        let mut iter = reply.iter()?;
+        match iter.next::<&Utf8CStr>()? {
+            Some(xml) => Ok(xml.to_string()),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Introspect reply has no XML data",
+            )
+            .into()),
+        }
+    }
+
+    /// Fetch all objects below `path` (including itself) managed by the object manager at `path`,
+    /// with all their interfaces and the values of their properties.
+    ///
+    /// This calls `org.freedesktop.DBus.ObjectManager.GetManagedObjects`. Use
+    /// [`BusRef::on_interfaces_added()`] and [`BusRef::on_interfaces_removed()`] to track changes
+    /// afterwards.
+    pub fn get_managed_objects(
+        &mut self,
+        dest: &BusName,
+        path: &ObjectPath,
+    ) -> Result<ManagedObjects> {
+        let mut reply = self.call_method(
+            dest,
+            path,
+            object_manager_interface(),
+            MemberName::from_bytes(b"GetManagedObjects\0").unwrap(),
+            |_| Ok(()),
+        )?;
+        let mut i = reply.iter()?;
+        let mut objects = BTreeMap::new();
+        if !i.enter_container(b'a', cstr(b"{oa{sa{sv}}}\0"))? {
+            return Err(bad_message().into());
+        }
+        while i.enter_container(b'e', cstr(b"oa{sa{sv}}\0"))? {
+            let path = i.next::<&ObjectPath>()?.ok_or_else(bad_message)?;
+            let path = ObjectPathBuf::from(path);
+            let interfaces = read_interfaces(&mut i)?;
+            i.exit_container()?;
+            objects.insert(path, interfaces);
+        }
+        i.exit_container()?;
+        Ok(objects)
+    }
+
+    /// Call `callback` with the object path and new interfaces (with their properties) whenever
+    /// the object manager at `path` of `sender` announces an object or new interfaces of one
+    /// (the `org.freedesktop.DBus.ObjectManager.InterfacesAdded` signal).
+    ///
+    /// The match stays installed until the returned [`Slot`] is dropped.
+    pub fn on_interfaces_added<F>(
+        &self,
+        sender: &BusName,
+        path: &ObjectPath,
+        callback: F,
+    ) -> super::Result<Slot>
+    where
+        F: Fn(&ObjectPath, Interfaces) + Send + Sync + 'static,
+    {
+        SignalDef::new(
+            object_manager_interface(),
+            MemberName::from_bytes(b"InterfacesAdded\0").unwrap(),
+        )
+        .sender(sender)
+        .path(path)
+        .add_match(self, move |m| {
+            let mut i = m.iter()?;
+            let path = i.next::<&ObjectPath>()?.ok_or_else(bad_message)?;
+            let interfaces = read_interfaces(&mut i)?;
+            callback(path, interfaces);
+            Ok(())
+        })
+    }
+
+    /// Call `callback` with the object path and the names of the removed interfaces whenever the
+    /// object manager at `path` of `sender` announces the removal of interfaces or a whole object
+    /// (the `org.freedesktop.DBus.ObjectManager.InterfacesRemoved` signal).
+    ///
+    /// The match stays installed until the returned [`Slot`] is dropped.
+    pub fn on_interfaces_removed<F>(
+        &self,
+        sender: &BusName,
+        path: &ObjectPath,
+        callback: F,
+    ) -> super::Result<Slot>
+    where
+        F: Fn(&ObjectPath, Vec<InterfaceNameBuf>) + Send + Sync + 'static,
+    {
+        SignalDef::new(
+            object_manager_interface(),
+            MemberName::from_bytes(b"InterfacesRemoved\0").unwrap(),
+        )
+        .sender(sender)
+        .path(path)
+        .add_match(self, move |m| {
+            let mut i = m.iter()?;
+            let path = i.next::<&ObjectPath>()?.ok_or_else(bad_message)?;
+            let mut interfaces = Vec::new();
+            if !i.enter_container(b'a', cstr(b"s\0"))? {
+                return Err(bad_message().into());
+            }
+            while let Some(name) = i.next::<&str>()? {
+                interfaces.push(InterfaceNameBuf::new(name).map_err(|_| bad_message())?);
+            }
+            i.exit_container()?;
+            callback(path, interfaces);
+            Ok(())
+        })
+    }
+
     // new_method_errno
 
     // TODO: consider using a guard object for name handling
@@ -1171,6 +3338,7 @@ impl BusRef {
     /// This corresponds to [`sd_bus_request_name`]
     ///
     /// [`sd_bus_request_name`]: https://www.freedesktop.org/software/systemd/man/sd_bus_request_name.html
+    #[cfg(feature = "bus-service")]
     #[inline]
     pub fn request_name(&mut self, name: &BusName, flags: u64) -> super::Result<()> {
         sd_try!(ffi::bus::sd_bus_request_name(
@@ -1181,13 +3349,22 @@ impl BusRef {
         Ok(())
     }
 
+    /// Request `name` without waiting for the reply, calling `callback` with it instead.
+    ///
+    /// Dropping the returned [`Slot`] before the reply arrives means `callback` isn't called, use
+    /// [`Slot::leak()`] if the result isn't of interest.
+    ///
+    /// This corresponds to [`sd_bus_request_name_async`]
+    ///
+    /// [`sd_bus_request_name_async`]: https://www.freedesktop.org/software/systemd/man/sd_bus_request_name.html
+    #[cfg(feature = "bus-service")]
     #[inline]
     pub fn request_name_async<F>(
         &mut self,
         name: &BusName,
         flags: u64,
         callback: F,
-    ) -> super::Result<()>
+    ) -> super::Result<Slot>
     where
         F: Fn(&mut MessageRef) -> Result<()> + Send + Sync + 'static,
     {
@@ -1211,21 +3388,18 @@ impl BusRef {
         } {
             Err(e) => {
                 // try not to leak
-                unsafe { Box::from_raw(b) };
+                drop(unsafe { Box::from_raw(b) });
                 Err(e)
             }
-            Ok(_) => {
-                unsafe {
-                    ffi::bus::sd_bus_slot_set_destroy_callback(slot, Some(d));
-                    // we don't want to take care of this one, let the bus handle it
-                    ffi::bus::sd_bus_slot_set_floating(slot, 1);
-                }
-                Ok(())
-            }
+            Ok(_) => unsafe {
+                ffi::bus::sd_bus_slot_set_destroy_callback(slot, Some(d));
+                Ok(Slot::from_ptr(slot))
+            },
         }
     }
 
     /// This blocks. To get async behavior, use `request_name` directly.
+    #[cfg(feature = "bus-service")]
     #[inline]
     pub fn release_name(&self, name: &BusName) -> super::Result<()> {
         sd_try!(ffi::bus::sd_bus_release_name(
@@ -1235,11 +3409,17 @@ impl BusRef {
         Ok(())
     }
 
+    /// Call `callback` for the messages sent to the object at `path`.
+    ///
+    /// The object stays registered until the returned [`Slot`] is dropped, or for as long as the
+    /// bus connection exists once it is [leaked](Slot::leak).
+    ///
     /// This corresponds to [`sd_bus_add_object`]
     ///
     /// [`sd_bus_add_object`]: https://www.freedesktop.org/software/systemd/man/sd_bus_add_object.html
+    #[cfg(feature = "bus-service")]
     #[inline]
-    pub fn add_object<F>(&self, path: &ObjectPath, callback: F) -> super::Result<()>
+    pub fn add_object<F>(&self, path: &ObjectPath, callback: F) -> super::Result<Slot>
     where
         F: Fn(&mut MessageRef) -> Result<()> + Send + Sync + 'static,
     {
@@ -1261,44 +3441,352 @@ impl BusRef {
             )
         }) {
             Err(e) => {
-                unsafe { Box::from_raw(b) };
+                drop(unsafe { Box::from_raw(b) });
                 Err(e)
             }
-            Ok(_) => {
-                unsafe {
-                    ffi::bus::sd_bus_slot_set_destroy_callback(slot, Some(d));
-                    ffi::bus::sd_bus_slot_set_floating(slot, 1);
-                }
-                Ok(())
-            }
+            Ok(_) => unsafe {
+                ffi::bus::sd_bus_slot_set_destroy_callback(slot, Some(d));
+                Ok(Slot::from_ptr(slot))
+            },
         }
     }
 
-    #[inline]
-    pub fn add_object_manager(&self, path: &ObjectPath) -> super::Result<()> {
-        sd_try!(ffi::bus::sd_bus_add_object_manager(
-            self.as_ptr(),
-            ptr::null_mut(),
-            &*path as *const _ as *const _
+    /// Call `callback` for the messages sent to any object below `prefix` that hasn't been
+    /// registered more specifically, as well as to `prefix` itself.
+    ///
+    /// The objects stay registered until the returned [`Slot`] is dropped.
+    ///
+    /// This corresponds to [`sd_bus_add_fallback`]
+    ///
+    /// [`sd_bus_add_fallback`]: https://www.freedesktop.org/software/systemd/man/sd_bus_add_object.html
+    #[cfg(feature = "bus-service")]
+    pub fn add_fallback<F>(&self, prefix: &ObjectPath, callback: F) -> super::Result<Slot>
+    where
+        F: Fn(&mut MessageRef) -> Result<()> + Send + Sync + 'static,
+    {
+        let f: extern "C" fn(
+            *mut ffi::bus::sd_bus_message,
+            *mut c_void,
+            *mut ffi::bus::sd_bus_error,
+        ) -> c_int = raw_message_handler::<F>;
+        let d: extern "C" fn(*mut c_void) = raw_destroy_cb_message_handler::<F>;
+        let mut slot = ptr::null_mut();
+        let b = Box::into_raw(Box::new(callback));
+        match crate::ffi_result(unsafe {
+            ffi::bus::sd_bus_add_fallback(
+                self.as_ptr(),
+                &mut slot,
+                prefix.as_ptr(),
+                Some(f),
+                b as *mut c_void,
+            )
+        }) {
+            Err(e) => {
+                drop(unsafe { Box::from_raw(b) });
+                Err(e)
+            }
+            Ok(_) => unsafe {
+                ffi::bus::sd_bus_slot_set_destroy_callback(slot, Some(d));
+                Ok(Slot::from_ptr(slot))
+            },
+        }
+    }
+
+    /// Emit a single `org.freedesktop.DBus.Properties.PropertiesChanged` signal for the
+    /// properties `names` of `interface` on the object at `path`.
+    ///
+    /// The new values are read from the properties of the vtable registered for `interface`
+    /// (properties flagged to only invalidate are listed without a value), so this fails if the
+    /// object has no such vtable. Emitting nothing for an empty `names` isn't an error.
+    ///
+    /// This corresponds to [`sd_bus_emit_properties_changed_strv`]
+    ///
+    /// [`sd_bus_emit_properties_changed_strv`]: https://www.freedesktop.org/software/systemd/man/sd_bus_emit_signal.html
+    #[cfg(feature = "bus-service")]
+    pub fn emit_properties_changed(
+        &self,
+        path: &ObjectPath,
+        interface: &InterfaceName,
+        names: &[&MemberName],
+    ) -> super::Result<()> {
+        if names.is_empty() {
+            return Ok(());
+        }
+        // the names are only read, sd-bus takes them as `char **` for no reason
+        let mut strv: Vec<*mut c_char> = names.iter().map(|n| n.as_ptr() as *mut _).collect();
+        strv.push(ptr::null_mut());
+        sd_try!(ffi::bus::sd_bus_emit_properties_changed_strv(
+            self.as_ptr(),
+            path.as_ptr(),
+            interface.as_ptr(),
+            strv.as_mut_ptr()
         ));
         Ok(())
     }
 
-    // pub fn add_object_vtable<T: Any + 'static>(&self,
-    //                                           path: ObjectPath,
-    //                                           interface: InterfaceName,
-    //                                           vtable: Vtable<T>,
-    //                                           userdata: T)
-    //                                           -> super::Result<()> {
-    //    let u = Box::into_raw(Box::new(userdata));
-    //    sd_try!(ffi::bus::sd_bus_add_object_vtable(self.raw,
-    //                                               ptr::null_mut(),
-    //                                               path.as_ptr() as *const _,
-    //                                               interface.as_ptr() as *const _,
-    //                                               vtable.as_ptr(),
-    //                                               Box::into_raw(Box::new(T))));
-    //    Ok(())
-    // }
+    /// Call `callback` for every message received on the bus, before it is dispatched to matches
+    /// and objects.
+    ///
+    /// A filter returning an error stops the message from being dispatched any further, and a
+    /// method call is answered with the error. This allows rejecting calls early, for example ones
+    /// exceeding a size limit checked with [`MessageRef::body_size()`].
+    ///
+    /// The filter stays installed until the returned [`Slot`] is dropped.
+    ///
+    /// This corresponds to [`sd_bus_add_filter`]
+    ///
+    /// [`sd_bus_add_filter`]: https://www.freedesktop.org/software/systemd/man/sd_bus_add_match.html
+    pub fn add_filter<F>(&self, callback: F) -> super::Result<Slot>
+    where
+        F: Fn(&mut MessageRef) -> Result<()> + Send + Sync + 'static,
+    {
+        let f: extern "C" fn(
+            *mut ffi::bus::sd_bus_message,
+            *mut c_void,
+            *mut ffi::bus::sd_bus_error,
+        ) -> c_int = raw_message_handler::<F>;
+        let d: extern "C" fn(*mut c_void) = raw_destroy_cb_message_handler::<F>;
+        let mut slot = ptr::null_mut();
+        let b = Box::into_raw(Box::new(callback));
+        match crate::ffi_result(unsafe {
+            ffi::bus::sd_bus_add_filter(self.as_ptr(), &mut slot, Some(f), b as *mut c_void)
+        }) {
+            Err(e) => {
+                drop(unsafe { Box::from_raw(b) });
+                Err(e)
+            }
+            Ok(_) => unsafe {
+                ffi::bus::sd_bus_slot_set_destroy_callback(slot, Some(d));
+                Ok(Slot::from_ptr(slot))
+            },
+        }
+    }
+
+    /// Add the sizes of the bodies of all messages received on the bus to `counters`, using a
+    /// filter (see [`BusRef::add_filter()`]).
+    ///
+    /// Every message is walked with [`MessageRef::body_size()`], so this has a cost proportional to
+    /// the traffic on the bus. Counting stops when the returned [`Slot`] is dropped.
+    pub fn add_size_counters(&self, counters: Arc<SizeCounters>) -> super::Result<Slot> {
+        self.add_filter(move |m| {
+            counters.add(m.body_size()?);
+            Ok(())
+        })
+    }
+
+    /// Install a match rule, calling `callback` for every message matching it.
+    ///
+    /// `rule` is either a string in the match rule format or a [`MatchRule`].
+    ///
+    /// The match stays installed until the returned [`Slot`] is dropped.
+    ///
+    /// This corresponds to [`sd_bus_add_match`]
+    ///
+    /// [`sd_bus_add_match`]: https://www.freedesktop.org/software/systemd/man/sd_bus_add_match.html
+    pub fn add_match<S, F>(&self, rule: S, callback: F) -> super::Result<Slot>
+    where
+        S: CStrArgument,
+        F: Fn(&mut MessageRef) -> Result<()> + Send + Sync + 'static,
+    {
+        let f: extern "C" fn(
+            *mut ffi::bus::sd_bus_message,
+            *mut c_void,
+            *mut ffi::bus::sd_bus_error,
+        ) -> c_int = raw_message_handler::<F>;
+        let d: extern "C" fn(*mut c_void) = raw_destroy_cb_message_handler::<F>;
+        let rule = rule.into_cstr();
+        let mut slot = ptr::null_mut();
+        let b = Box::into_raw(Box::new(callback));
+        match crate::ffi_result(unsafe {
+            ffi::bus::sd_bus_add_match(
+                self.as_ptr(),
+                &mut slot,
+                rule.as_ref().as_ptr(),
+                Some(f),
+                b as *mut c_void,
+            )
+        }) {
+            Err(e) => {
+                drop(unsafe { Box::from_raw(b) });
+                Err(e)
+            }
+            Ok(_) => unsafe {
+                ffi::bus::sd_bus_slot_set_destroy_callback(slot, Some(d));
+                Ok(Slot::from_ptr(slot))
+            },
+        }
+    }
+
+    #[cfg(feature = "bus-service")]
+    #[inline]
+    pub fn add_object_manager(&self, path: &ObjectPath) -> super::Result<()> {
+        sd_try!(ffi::bus::sd_bus_add_object_manager(
+            self.as_ptr(),
+            ptr::null_mut(),
+            &*path as *const _ as *const _
+        ));
+        Ok(())
+    }
+
+    /// Export the members in `vtable` as `interface` of the object at `path`.
+    ///
+    /// The object stays exported until the returned [`Slot`] is dropped. sd-bus also provides the
+    /// `org.freedesktop.DBus.Properties`, `org.freedesktop.DBus.Introspectable` and
+    /// `org.freedesktop.DBus.Peer` interfaces for objects registered this way, see
+    /// [`BusRef::introspect()`] for fetching the resulting introspection data.
+    ///
+    /// This corresponds to [`sd_bus_add_object_vtable`]
+    ///
+    /// [`sd_bus_add_object_vtable`]: https://www.freedesktop.org/software/systemd/man/sd_bus_add_object_vtable.html
+    #[cfg(feature = "bus-service")]
+    pub fn add_object_vtable(
+        &self,
+        path: &ObjectPath,
+        interface: &InterfaceName,
+        vtable: Vtable,
+    ) -> super::Result<Slot> {
+        let d: extern "C" fn(*mut c_void) = raw_destroy_vtable;
+        let mut vtable = Box::new(vtable);
+        vtable.build_entries();
+        let mut slot = ptr::null_mut();
+        let entries = vtable.entries.as_ptr();
+        let b = Box::into_raw(vtable);
+        match crate::ffi_result(unsafe {
+            ffi::bus::sd_bus_add_object_vtable(
+                self.as_ptr(),
+                &mut slot,
+                path.as_ptr(),
+                interface.as_ptr(),
+                entries,
+                b as *mut c_void,
+            )
+        }) {
+            Err(e) => {
+                drop(unsafe { Box::from_raw(b) });
+                Err(e)
+            }
+            Ok(_) => unsafe {
+                ffi::bus::sd_bus_slot_set_destroy_callback(slot, Some(d));
+                Ok(Slot::from_ptr(slot))
+            },
+        }
+    }
+
+    /// Export `object` at `path`, with the members of its [`Interface`].
+    ///
+    /// The object stays exported until the returned [`Slot`] is dropped.
+    #[cfg(feature = "bus-service")]
+    pub fn add_interface<I: Interface>(
+        &self,
+        path: &ObjectPath,
+        object: std::sync::Arc<I>,
+    ) -> super::Result<Slot> {
+        self.add_object_vtable(path, I::name(), I::vtable(object)?)
+    }
+
+    /// Export the members in `vtable` as `interface` of every object below `prefix` for which
+    /// `find` returns `true`, such as one object per device.
+    ///
+    /// The same `vtable` serves all of the objects: its property handlers can tell them apart by
+    /// the path of the call being handled, available from [`BusRef::with_current_message()`] of
+    /// the bus the reply belongs to. Combine this with [`BusRef::add_node_enumerator()`] to make the
+    /// objects show up in the introspection data of `prefix`.
+    ///
+    /// The objects stay exported until the returned [`Slot`] is dropped.
+    ///
+    /// This corresponds to [`sd_bus_add_fallback_vtable`]
+    ///
+    /// [`sd_bus_add_fallback_vtable`]: https://www.freedesktop.org/software/systemd/man/sd_bus_add_object_vtable.html
+    #[cfg(feature = "bus-service")]
+    pub fn add_fallback_vtable<F>(
+        &self,
+        prefix: &ObjectPath,
+        interface: &InterfaceName,
+        vtable: Vtable,
+        find: F,
+    ) -> super::Result<Slot>
+    where
+        F: Fn(&ObjectPath) -> Result<bool> + Send + Sync + 'static,
+    {
+        let f: unsafe extern "C" fn(
+            *mut ffi::bus::sd_bus,
+            *const c_char,
+            *const c_char,
+            *mut c_void,
+            *mut *mut c_void,
+            *mut ffi::bus::sd_bus_error,
+        ) -> c_int = raw_fallback_find::<F>;
+        let d: extern "C" fn(*mut c_void) = raw_destroy_fallback_vtable::<F>;
+        let mut fallback = Box::new(FallbackVtable { vtable, find });
+        fallback.vtable.build_entries();
+        let mut slot = ptr::null_mut();
+        let entries = fallback.vtable.entries.as_ptr();
+        let b = Box::into_raw(fallback);
+        match crate::ffi_result(unsafe {
+            ffi::bus::sd_bus_add_fallback_vtable(
+                self.as_ptr(),
+                &mut slot,
+                prefix.as_ptr(),
+                interface.as_ptr(),
+                entries,
+                Some(f),
+                b as *mut c_void,
+            )
+        }) {
+            Err(e) => {
+                drop(unsafe { Box::from_raw(b) });
+                Err(e)
+            }
+            Ok(_) => unsafe {
+                ffi::bus::sd_bus_slot_set_destroy_callback(slot, Some(d));
+                Ok(Slot::from_ptr(slot))
+            },
+        }
+    }
+
+    /// Call `callback` with `path` to list the objects below it when they are introspected or
+    /// enumerated by an object manager.
+    ///
+    /// `callback` returns the full paths of the objects. It stays registered until the returned
+    /// [`Slot`] is dropped.
+    ///
+    /// This corresponds to [`sd_bus_add_node_enumerator`]
+    ///
+    /// [`sd_bus_add_node_enumerator`]: https://www.freedesktop.org/software/systemd/man/sd_bus_add_node_enumerator.html
+    #[cfg(feature = "bus-service")]
+    pub fn add_node_enumerator<F>(&self, path: &ObjectPath, callback: F) -> super::Result<Slot>
+    where
+        F: Fn(&ObjectPath) -> Result<Vec<ObjectPathBuf>> + Send + Sync + 'static,
+    {
+        let f: unsafe extern "C" fn(
+            *mut ffi::bus::sd_bus,
+            *const c_char,
+            *mut c_void,
+            *mut *mut *mut c_char,
+            *mut ffi::bus::sd_bus_error,
+        ) -> c_int = raw_node_enumerator::<F>;
+        let d: extern "C" fn(*mut c_void) = raw_destroy_node_enumerator::<F>;
+        let mut slot = ptr::null_mut();
+        let b = Box::into_raw(Box::new(callback));
+        match crate::ffi_result(unsafe {
+            ffi::bus::sd_bus_add_node_enumerator(
+                self.as_ptr(),
+                &mut slot,
+                path.as_ptr(),
+                Some(f),
+                b as *mut c_void,
+            )
+        }) {
+            Err(e) => {
+                drop(unsafe { Box::from_raw(b) });
+                Err(e)
+            }
+            Ok(_) => unsafe {
+                ffi::bus::sd_bus_slot_set_destroy_callback(slot, Some(d));
+                Ok(Slot::from_ptr(slot))
+            },
+        }
+    }
 
     // emit_signal
     // emit_properties_changed
@@ -1355,12 +3843,153 @@ foreign_type! {
     }
 }
 
+/// Size of a message body, computed by [`MessageRef::body_size()`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BodySize {
+    /// Size of the body in the D-Bus wire format, in bytes
+    pub bytes: usize,
+    /// Number of file descriptors referred to (values of type `h`)
+    pub fds: usize,
+}
+
+/// Totals of the messages received on a connection, kept by [`BusRef::add_size_counters()`].
+///
+/// Shared with the filter counting them, so they can be read while the bus is processed.
+#[derive(Debug, Default)]
+pub struct SizeCounters {
+    messages: AtomicUsize,
+    bytes: AtomicUsize,
+    fds: AtomicUsize,
+}
+
+impl SizeCounters {
+    /// Number of messages counted
+    pub fn messages(&self) -> usize {
+        self.messages.load(Ordering::Relaxed)
+    }
+
+    /// Sum of the body sizes of the messages counted
+    pub fn total(&self) -> BodySize {
+        BodySize {
+            bytes: self.bytes.load(Ordering::Relaxed),
+            fds: self.fds.load(Ordering::Relaxed),
+        }
+    }
+
+    fn add(&self, size: BodySize) {
+        self.messages.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(size.bytes, Ordering::Relaxed);
+        self.fds.fetch_add(size.fds, Ordering::Relaxed);
+    }
+}
+
+/// Alignment of a D-Bus type in the wire format.
+fn dbus_alignment(t: u8) -> usize {
+    match t {
+        b'n' | b'q' => 2,
+        b'b' | b'i' | b'u' | b'h' | b's' | b'o' | b'a' => 4,
+        b'x' | b't' | b'd' | b'r' | b'e' | b'(' | b'{' => 8,
+        _ => 1,
+    }
+}
+
+/// Size of a fixed size D-Bus type in the wire format, 0 for others.
+fn dbus_fixed_size(t: u8) -> usize {
+    match t {
+        b'y' => 1,
+        b'n' | b'q' => 2,
+        b'b' | b'i' | b'u' | b'h' => 4,
+        b'x' | b't' | b'd' => 8,
+        _ => 0,
+    }
+}
+
+/// Add the sizes of the values from the read position to the end of the current container.
+fn walk_body(m: *mut ffi::bus::sd_bus_message, size: &mut BodySize) -> crate::Result<()> {
+    fn align(n: usize, a: usize) -> usize {
+        (n + a - 1) & !(a - 1)
+    }
+
+    loop {
+        let mut t: c_char = 0;
+        let mut contents: *const c_char = ptr::null();
+        if sd_try!(ffi::bus::sd_bus_message_peek_type(m, &mut t, &mut contents)) == 0 {
+            return Ok(());
+        }
+        let t = t as u8;
+        size.bytes = align(size.bytes, dbus_alignment(t));
+        match t {
+            b'a' | b'v' | b'r' | b'e' => {
+                let contents = unsafe { CStr::from_ptr(contents) };
+                let c = contents.to_bytes();
+                if t == b'a' {
+                    // the length, then padding to the first element even if there is none
+                    size.bytes = align(size.bytes + 4, dbus_alignment(c[0]));
+                    let fixed = dbus_fixed_size(c[0]);
+                    if c.len() == 1 && fixed > 0 {
+                        let mut p = ptr::null();
+                        let mut n = 0;
+                        sd_try!(ffi::bus::sd_bus_message_read_array(
+                            m,
+                            c[0] as c_char,
+                            &mut p,
+                            &mut n
+                        ));
+                        size.bytes += n;
+                        if c[0] == b'h' {
+                            size.fds += n / fixed;
+                        }
+                        continue;
+                    }
+                } else if t == b'v' {
+                    // the signature of the value
+                    size.bytes += c.len() + 2;
+                }
+                sd_try!(ffi::bus::sd_bus_message_enter_container(
+                    m,
+                    t as c_char,
+                    contents.as_ptr()
+                ));
+                walk_body(m, size)?;
+                sd_try!(ffi::bus::sd_bus_message_exit_container(m));
+            }
+            b's' | b'o' | b'g' => {
+                let mut v: *const c_char = ptr::null();
+                sd_try!(ffi::bus::sd_bus_message_read_basic(
+                    m,
+                    t as c_char,
+                    &mut v as *mut _ as *mut c_void
+                ));
+                let len = unsafe { CStr::from_ptr(v) }.to_bytes().len();
+                // length (4 bytes, or 1 for signatures) and nul terminator
+                size.bytes += if t == b'g' { 1 } else { 4 } + len + 1;
+            }
+            _ => {
+                let mut v = 0u64;
+                sd_try!(ffi::bus::sd_bus_message_read_basic(
+                    m,
+                    t as c_char,
+                    &mut v as *mut u64 as *mut c_void
+                ));
+                size.bytes += dbus_fixed_size(t);
+                if t == b'h' {
+                    size.fds += 1;
+                }
+            }
+        }
+    }
+}
+
 /// An iterator over the elements of a `Message`, use this to read data out of a message.
 ///
 /// Note: we're using a concrete type here instead of a reference to allow us to handle lifetimes
 /// properly.
 pub struct MessageIter<'a> {
     raw: *mut ffi::bus::sd_bus_message,
+    // number of complete types read from the current container so far
+    consumed: usize,
+    // for each entered container, its type and the `consumed` count of its parent
+    containers: Vec<(u8, usize)>,
     life: PhantomData<&'a MessageRef>,
 }
 
@@ -1504,11 +4133,70 @@ impl MessageRef {
     ///
     /// [`sd_bus_message_get_signature`]: https://www.freedesktop.org/software/systemd/man/sd_bus_message_get_signature.html
     // XXX: doesn't allow partial signatures
-    pub fn signature(&self) -> &CStr {
+    pub fn signature(&self) -> &Signature {
         let p = unsafe { ffi::bus::sd_bus_message_get_signature(self.as_ptr(), 1) };
         assert!(!p.is_null());
 
-        unsafe { CStr::from_ptr(p) }
+        // sd-bus only builds messages with valid signatures, and rejects received ones without
+        unsafe { Signature::from_ptr_unchecked(p) }
+    }
+
+    /// Whether the signature of the complete message is `signature`.
+    ///
+    /// This corresponds to [`sd_bus_message_has_signature`]
+    ///
+    /// [`sd_bus_message_has_signature`]: https://www.freedesktop.org/software/systemd/man/sd_bus_message_has_signature.html
+    pub fn has_signature(&self, signature: &Signature) -> bool {
+        unsafe { ffi::bus::sd_bus_message_has_signature(self.as_ptr(), signature.as_ptr()) > 0 }
+    }
+
+    /// Create a new, unsealed method call with the same destination, path, interface, member,
+    /// flags and arguments as this (sealed) method call.
+    ///
+    /// A sealed message can't be sent again, so repeating a call needs a new message each time.
+    /// Duplicating a message built once copies its arguments instead of appending (and validating)
+    /// each of them again. Whether that is cheaper depends on the arguments, the
+    /// `bus-message-reuse` example compares both approaches.
+    ///
+    /// Fails with `EINVAL` if this isn't a method call and with `EPERM` if it isn't sealed yet.
+    ///
+    /// This uses [`sd_bus_message_copy`].
+    ///
+    /// [`sd_bus_message_copy`]: https://www.freedesktop.org/software/systemd/man/sd_bus_message_copy.html
+    pub fn duplicate_unsealed(&mut self) -> super::Result<Message> {
+        if self.type_() != MessageType::MethodCall {
+            return Err(std::io::Error::from_raw_os_error(libc::EINVAL));
+        }
+        let raw = self.as_ptr();
+        // positions the message for reading, and fails if it isn't sealed
+        sd_try!(ffi::bus::sd_bus_message_rewind(raw, 1));
+
+        let mut m = MaybeUninit::uninit();
+        sd_try!(ffi::bus::sd_bus_message_new_method_call(
+            ffi::bus::sd_bus_message_get_bus(raw),
+            m.as_mut_ptr(),
+            ffi::bus::sd_bus_message_get_destination(raw),
+            ffi::bus::sd_bus_message_get_path(raw),
+            ffi::bus::sd_bus_message_get_interface(raw),
+            ffi::bus::sd_bus_message_get_member(raw)
+        ));
+        let m = unsafe { Message::from_ptr(m.assume_init()) };
+        sd_try!(ffi::bus::sd_bus_message_set_expect_reply(
+            m.as_ptr(),
+            ffi::bus::sd_bus_message_get_expect_reply(raw)
+        ));
+        sd_try!(ffi::bus::sd_bus_message_set_auto_start(
+            m.as_ptr(),
+            ffi::bus::sd_bus_message_get_auto_start(raw)
+        ));
+        sd_try!(
+            ffi::bus::sd_bus_message_set_allow_interactive_authorization(
+                m.as_ptr(),
+                ffi::bus::sd_bus_message_get_allow_interactive_authorization(raw)
+            )
+        );
+        sd_try!(ffi::bus::sd_bus_message_copy(m.as_ptr(), raw, 1));
+        Ok(m)
     }
 
     /// This corresponds to [`sd_bus_message_is_empty`]
@@ -1668,9 +4356,9 @@ impl MessageRef {
     }
 
     /// Use this message to call a dbus method. Blocks until a reply is received or `usec`
-    /// microseconds elapse (ie: this times out)
+    /// microseconds elapse (ie: this times out). A `usec` of `0` uses the
+    /// [`BusRef::method_call_timeout()`] of the bus.
     ///
-    /// XXX: document blocking forever
     /// Seals `self`.
     ///
     ///
@@ -1694,6 +4382,13 @@ impl MessageRef {
             .map(|_| unsafe { Message::from_ptr(r.assume_init()) })
     }
 
+    /// Like [`MessageRef::call()`], waiting up to `timeout` for the reply.
+    #[inline]
+    pub fn call_with_timeout(&mut self, timeout: Duration) -> Result<Message> {
+        // `0` would select the default of the bus instead
+        self.call(usec_from_duration(timeout).max(1))
+    }
+
     // XXX: we may need to move this, unclear we have the right lifetime here (we're being too
     // strict)
     //
@@ -1703,11 +4398,14 @@ impl MessageRef {
     /// XXX: document how timeout affects this
     /// Seals `self`.
     ///
+    /// Dropping the returned [`Slot`] before the reply arrives cancels the call (the callback is
+    /// not invoked). Use [`Slot::leak()`] to keep it pending without holding on to the `Slot`.
+    ///
     /// This corresponds to [`sd_bus_call_async`]
     ///
     /// [`sd_bus_call_async`]: https://www.freedesktop.org/software/systemd/man/sd_bus_call_async.html
     #[inline]
-    pub fn call_async<F>(&mut self, callback: F, usec: u64) -> super::Result<()>
+    pub fn call_async<F>(&mut self, callback: F, usec: u64) -> super::Result<Slot>
     where
         F: Fn(&mut MessageRef) -> Result<()> + 'static + Sync + Send,
     {
@@ -1731,23 +4429,20 @@ impl MessageRef {
         }) {
             Err(e) => {
                 // try not to leak
-                unsafe { Box::from_raw(b) };
+                drop(unsafe { Box::from_raw(b) });
                 Err(e)
             }
-            Ok(_) => {
-                unsafe {
-                    ffi::bus::sd_bus_slot_set_destroy_callback(slot, Some(d));
-                    // we don't want to take care of this one, let the bus handle it
-                    ffi::bus::sd_bus_slot_set_floating(slot, 1);
-                }
-                Ok(())
-            }
+            Ok(_) => unsafe {
+                ffi::bus::sd_bus_slot_set_destroy_callback(slot, Some(d));
+                Ok(Slot::from_ptr(slot))
+            },
         }
     }
 
     /// This corresponds to [`sd_bus_message_new_method_error`]
     ///
     /// [`sd_bus_message_new_method_error`]: https://www.freedesktop.org/software/systemd/man/sd_bus_message_new_method_error.html
+    #[cfg(feature = "bus-service")]
     #[inline]
     pub fn new_method_error(&mut self, error: &Error) -> crate::Result<Message> {
         let mut m = MaybeUninit::uninit();
@@ -1762,6 +4457,7 @@ impl MessageRef {
     /// This corresponds to [`sd_bus_message_new_method_return`]
     ///
     /// [`sd_bus_message_new_method_return`]: https://www.freedesktop.org/software/systemd/man/sd_bus_message_new_method_return.html
+    #[cfg(feature = "bus-service")]
     #[inline]
     pub fn new_method_return(&mut self) -> crate::Result<Message> {
         let mut m = MaybeUninit::uninit();
@@ -1772,6 +4468,63 @@ impl MessageRef {
         Ok(unsafe { Message::from_ptr(m.assume_init()) })
     }
 
+    /// Reply to this method call with an error built from `errno`.
+    ///
+    /// The error name is derived from `errno` the way sd-bus maps errno values to D-Bus errors
+    /// (for example, `ENOENT` becomes `org.freedesktop.DBus.Error.FileNotFound`). The error
+    /// message is the description of `errno`, prefixed with `context` unless it is empty.
+    ///
+    /// This corresponds to [`sd_bus_reply_method_errnof`], with the message formatted as
+    /// `"<context>: %m"`.
+    ///
+    /// [`sd_bus_reply_method_errnof`]: https://www.freedesktop.org/software/systemd/man/sd_bus_reply_method_error.html
+    #[cfg(feature = "bus-service")]
+    pub fn reply_errno(&mut self, errno: i32, context: &str) -> Result<()> {
+        if context.is_empty() {
+            sd_try!(ffi::bus::sd_bus_reply_method_errno(
+                self.as_ptr(),
+                errno,
+                ptr::null()
+            ));
+            return Ok(());
+        }
+        let context = CString::new(context)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        sd_try!(ffi::bus::sd_bus_reply_method_errnof(
+            self.as_ptr(),
+            errno,
+            b"%s: %m\0".as_ptr() as *const c_char,
+            context.as_ptr()
+        ));
+        Ok(())
+    }
+
+    /// Reply to this method call with `err`, using [`MessageRef::reply_errno()`].
+    ///
+    /// Errors carrying an OS error code are sent with that code and its description. Other errors
+    /// are sent as `EIO`, with their own description as context.
+    ///
+    /// This allows method handlers to report failures of I/O operations:
+    ///
+    /// ```no_run
+    /// use systemd::bus;
+    /// let bus = bus::Bus::default().unwrap();
+    /// let path = bus::ObjectPath::from_bytes(b"/org/example/Config\0").unwrap();
+    /// let _object = bus
+    ///     .add_object(path, |m| match std::fs::read("/etc/example.conf") {
+    ///         Ok(_) => Ok(()),
+    ///         Err(err) => m.reply_errno_from(&err),
+    ///     })
+    ///     .unwrap();
+    /// ```
+    #[cfg(feature = "bus-service")]
+    pub fn reply_errno_from(&mut self, err: &std::io::Error) -> Result<()> {
+        match err.raw_os_error() {
+            Some(errno) => self.reply_errno(errno, ""),
+            None => self.reply_errno(libc::EIO, &err.to_string()),
+        }
+    }
+
     /// Raw access to append data to this message
     /// Will fail if the message is sealed
     ///
@@ -1798,29 +4551,121 @@ impl MessageRef {
         Ok(())
     }
 
+    /// Append an array of fixed size basic values (like `ay` for a `&[u8]`) in one go, rather
+    /// than element by element into an opened array container.
+    ///
+    /// Will fail if the message is sealed
+    ///
+    /// This corresponds to [`sd_bus_message_append_array`]
+    ///
+    /// [`sd_bus_message_append_array`]: https://www.freedesktop.org/software/systemd/man/sd_bus_message_append_array.html
+    #[inline]
+    pub fn append_array<T: types::SdBusMessageDirect>(&mut self, v: &[T]) -> crate::Result<()> {
+        sd_try!(ffi::bus::sd_bus_message_append_array(
+            self.as_ptr(),
+            T::dbus_type() as c_char,
+            v.as_ptr() as *const c_void,
+            std::mem::size_of_val(v)
+        ));
+        Ok(())
+    }
+
     /// Append a value to the message
+    ///
+    /// Tuples are appended as a single D-Bus struct, use [`MessageRef::append_tuple()`] to append
+    /// their elements as separate arguments instead.
     #[inline]
     pub fn append<V: types::ToSdBusMessage>(&mut self, v: V) -> crate::Result<()> {
         v.to_message(self)
     }
 
+    /// Append each element of the tuple `args` to the message in order, as separate arguments
+    ///
+    /// ```no_run
+    /// # use systemd::bus;
+    /// # use utf8_cstr::Utf8CStr;
+    /// # fn f(m: &mut bus::MessageRef) -> systemd::Result<()> {
+    /// let name = Utf8CStr::from_bytes(b"foo.service\0").unwrap();
+    /// let mode = Utf8CStr::from_bytes(b"replace\0").unwrap();
+    /// m.append_tuple((name, mode, 0u64))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[inline]
+    pub fn append_tuple<T: types::ToSdBusMessageArgs>(&mut self, args: T) -> crate::Result<()> {
+        args.to_message_args(self)
+    }
+
+    /// Open a container (array `a`, struct `r`, variant `v` or dict entry `e`) with the given
+    /// `contents` signature. Subsequent appends go into the container until it is closed with
+    /// [`MessageRef::close_container()`].
+    ///
+    /// `contents` is usually a [`Signature`]. It is a `&CStr` because the contents of an array of
+    /// dict entries (`{sv}` for `a{sv}`) aren't a complete signature on their own.
+    ///
+    /// Will fail if the message is sealed
+    ///
+    /// This corresponds to [`sd_bus_message_open_container`]
+    ///
+    /// [`sd_bus_message_open_container`]: https://www.freedesktop.org/software/systemd/man/sd_bus_message_open_container.html
+    #[inline]
+    pub fn open_container(&mut self, dbus_type: u8, contents: &CStr) -> crate::Result<()> {
+        sd_try!(ffi::bus::sd_bus_message_open_container(
+            self.as_ptr(),
+            dbus_type as c_char,
+            contents.as_ptr()
+        ));
+        Ok(())
+    }
+
+    /// Close the container most recently opened with [`MessageRef::open_container()`].
+    ///
+    /// This corresponds to [`sd_bus_message_close_container`]
+    ///
+    /// [`sd_bus_message_close_container`]: https://www.freedesktop.org/software/systemd/man/sd_bus_message_open_container.html
+    #[inline]
+    pub fn close_container(&mut self) -> crate::Result<()> {
+        sd_try!(ffi::bus::sd_bus_message_close_container(self.as_ptr()));
+        Ok(())
+    }
+
+    /// Compute the size of the message body in the D-Bus wire format and count the file
+    /// descriptors it refers to, so a service can reject oversized requests before processing
+    /// them.
+    ///
+    /// sd-bus doesn't expose either, so this walks the whole body. Values aren't copied, and
+    /// arrays of fixed size types are skipped over in one go.
+    ///
+    /// [`BusRef::add_size_counters()`] keeps totals of the messages received on a connection.
+    ///
+    /// Requires that message is sealed.
+    pub fn body_size(&mut self) -> crate::Result<BodySize> {
+        let m = self.as_ptr();
+        sd_try!(ffi::bus::sd_bus_message_rewind(m, 1));
+        let mut size = BodySize::default();
+        let r = walk_body(m, &mut size);
+        sd_try!(ffi::bus::sd_bus_message_rewind(m, 1));
+        r.map(|_| size)
+    }
+
     /// Get an iterator over the message. This iterator really exists with in the `Message` itself,
     /// so we can only hand out one at a time.
     ///
     /// Ideally, handing this iterator out wouldn't prevent the use of other non-iterator
     /// accessors, but right now it does (unless you bypass `borrowck` using `unsafe{}`)
     ///
+    /// The iterator always starts at the beginning of the message body, even if a previous
+    /// iterator read from it.
+    ///
     /// Requires that message is sealed.
     #[inline]
     pub fn iter(&mut self) -> crate::Result<MessageIter<'_>> {
-        /* probe the `Message` to check if we can iterate on it */
-        sd_try!(ffi::bus::sd_bus_message_peek_type(
-            self.as_ptr(),
-            ptr::null_mut(),
-            ptr::null_mut()
-        ));
+        /* also checks that the `Message` is sealed, so we can iterate on it */
+        sd_try!(ffi::bus::sd_bus_message_rewind(self.as_ptr(), 1));
         Ok(MessageIter {
             raw: self.as_ptr(),
+            consumed: 0,
+            containers: Vec::new(),
             life: PhantomData,
         })
     }
@@ -1868,12 +4713,65 @@ impl<'a> MessageIter<'a> {
             dbus_type as c_char,
             v.as_mut_ptr() as *mut _,
         )) {
-            Ok(1) => Ok(Some(cons(v.assume_init()))),
+            Ok(1) => {
+                self.consumed += 1;
+                Ok(Some(cons(v.assume_init())))
+            }
             Ok(_) => Ok(None),
+            // sd-bus reports the end of the body (as opposed to the end of an array) as a type
+            // mismatch
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) && self.at_end(false)? => Ok(None),
             Err(e) => Err(e),
         }
     }
 
+    /// Read an array of fixed size basic values (like `ay`) without copying it: the returned
+    /// slice points into the message.
+    ///
+    /// Returns `Ok(None)` at the end of the body or of the current container.
+    ///
+    /// This corresponds to [`sd_bus_message_read_array`]
+    ///
+    /// [`sd_bus_message_read_array`]: https://www.freedesktop.org/software/systemd/man/sd_bus_message_read_array.html
+    pub fn read_array<T: types::SdBusMessageDirect + 'a>(
+        &mut self,
+    ) -> crate::Result<Option<&'a [T]>> {
+        let mut p = ptr::null();
+        let mut size = 0;
+        match crate::ffi_result(unsafe {
+            ffi::bus::sd_bus_message_read_array(
+                self.as_mut_ptr(),
+                T::dbus_type() as c_char,
+                &mut p,
+                &mut size,
+            )
+        }) {
+            Ok(1) => {}
+            Ok(_) => return Ok(None),
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) && self.at_end(false)? => {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        }
+        self.consumed += 1;
+
+        // the pointer may be null for empty arrays
+        if size == 0 {
+            return Ok(Some(&[]));
+        }
+        // sd-bus aligns array elements in the message to their size, check rather than trust it
+        let (len, partial) = (
+            size / std::mem::size_of::<T>(),
+            size % std::mem::size_of::<T>(),
+        );
+        if p as usize & (std::mem::align_of::<T>() - 1) != 0 || partial != 0 {
+            return Err(std::io::Error::from_raw_os_error(libc::EBADMSG));
+        }
+        Ok(Some(unsafe {
+            std::slice::from_raw_parts(p as *const T, len)
+        }))
+    }
+
     /// This needs to be `&mut` as the `&str` will be invalid after either of:
     ///  - self is dropped
     ///  - sd_bus_message_peek_type is called a second time
@@ -1895,9 +4793,13 @@ impl<'a> MessageIter<'a> {
     pub fn peek_type(&mut self) -> crate::Result<(c_char, &str)> {
         let mut t = MaybeUninit::<c_char>::uninit();
         let mut cont = MaybeUninit::<*const c_char>::uninit();
-        crate::ffi_result(unsafe {
+        let r = crate::ffi_result(unsafe {
             ffi::bus::sd_bus_message_peek_type(self.as_mut_ptr(), t.as_mut_ptr(), cont.as_mut_ptr())
         })?;
+        if r == 0 {
+            // at the end, nothing was stored
+            return Ok((0, ""));
+        }
 
         let cont = unsafe { cont.assume_init() };
         let s = if cont.is_null() {
@@ -1911,40 +4813,774 @@ impl<'a> MessageIter<'a> {
         Ok((t, s))
     }
 
+    /// Returns true if all of the body has been read.
+    ///
+    /// If `complete` is false, only checks if the end of the currently open container has been
+    /// reached.
+    ///
+    /// This corresponds to [`sd_bus_message_at_end`]
+    ///
+    /// [`sd_bus_message_at_end`]: https://www.freedesktop.org/software/systemd/man/sd_bus_message_at_end.html
+    #[inline]
+    pub fn at_end(&mut self, complete: bool) -> crate::Result<bool> {
+        let r = sd_try!(ffi::bus::sd_bus_message_at_end(
+            self.as_mut_ptr(),
+            complete as c_int
+        ));
+        Ok(r > 0)
+    }
+
+    /// Enter the container (array `a`, struct `r`, variant `v` or dict entry `e`) at the current
+    /// position, which must have the given `contents` signature. Subsequent reads come from the
+    /// container until [`MessageIter::exit_container()`] is called.
+    ///
+    /// As with [`MessageRef::open_container()`], `contents` is usually a [`Signature`].
+    ///
+    /// Returns `false` if there is nothing left to enter: the end of the body, or of the array
+    /// being read, has been reached.
+    ///
+    /// This corresponds to [`sd_bus_message_enter_container`]
+    ///
+    /// [`sd_bus_message_enter_container`]: https://www.freedesktop.org/software/systemd/man/sd_bus_message_open_container.html
+    pub fn enter_container(&mut self, dbus_type: u8, contents: &CStr) -> crate::Result<bool> {
+        match crate::ffi_result(unsafe {
+            ffi::bus::sd_bus_message_enter_container(
+                self.as_mut_ptr(),
+                dbus_type as c_char,
+                contents.as_ptr(),
+            )
+        }) {
+            Ok(0) => Ok(false),
+            Ok(_) => {
+                self.containers.push((dbus_type, self.consumed));
+                self.consumed = 0;
+                Ok(true)
+            }
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) && self.at_end(false)? => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Leave the container most recently entered with [`MessageIter::enter_container()`],
+    /// skipping any of its contents which haven't been read.
+    ///
+    /// This corresponds to [`sd_bus_message_exit_container`]
+    ///
+    /// [`sd_bus_message_exit_container`]: https://www.freedesktop.org/software/systemd/man/sd_bus_message_open_container.html
+    pub fn exit_container(&mut self) -> crate::Result<()> {
+        let (_, consumed) = match self.containers.last() {
+            Some(c) => *c,
+            None => return Err(std::io::Error::from_raw_os_error(libc::ENXIO)),
+        };
+        // sd_bus_message_exit_container() may fail if elements are left, skip them first
+        while !self.at_end(false)? {
+            sd_try!(ffi::bus::sd_bus_message_skip(
+                self.as_mut_ptr(),
+                ptr::null()
+            ));
+        }
+        sd_try!(ffi::bus::sd_bus_message_exit_container(self.as_mut_ptr()));
+        self.containers.pop();
+        self.consumed = consumed + 1;
+        Ok(())
+    }
+
+    /// The signature of the part of the current container (or the body, outside of containers)
+    /// which hasn't been read yet.
+    ///
+    /// Inside an array, this is the element type until the end of the array is reached. This is
+    /// empty at the end of the container.
+    pub fn remaining_signature(&self) -> &Signature {
+        let sig = unsafe { CStr::from_ptr(ffi::bus::sd_bus_message_get_signature(self.raw, 0)) };
+        let b = sig.to_bytes_with_nul();
+        let in_array = matches!(self.containers.last(), Some((b'a', _)));
+        if in_array {
+            let at_end = unsafe { ffi::bus::sd_bus_message_at_end(self.raw, 0) };
+            let i = if at_end == 0 { 0 } else { b.len() - 1 };
+            return unsafe { Signature::from_bytes_unchecked(&b[i..]) };
+        }
+        let mut i = 0;
+        for _ in 0..self.consumed {
+            // sd-bus only hands out valid signatures, and we never read past their end
+            i = signature_complete_type(b, i, 0, 0).unwrap_or(b.len() - 1);
+        }
+        unsafe { Signature::from_bytes_unchecked(&b[i..]) }
+    }
+
+    /// Read the next element of the body.
+    ///
+    /// Returns `Ok(None)` once the end of the body is reached, and an error if the next element
+    /// doesn't have the type `V` expects (the iterator doesn't advance in that case). This allows
+    /// reading until the body is exhausted:
+    ///
+    /// ```no_run
+    /// # use systemd::bus;
+    /// # fn f(m: &mut bus::MessageRef) -> systemd::Result<()> {
+    /// let mut i = m.iter()?;
+    /// while let Some(v) = i.next::<u32>()? {
+    ///     println!("{}", v);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
     // XXX: handle containers
     // FIXME: consider renaming
     #[allow(clippy::should_implement_trait)]
-    pub fn next<V: types::FromSdBusMessage<'a>>(&'a mut self) -> crate::Result<Option<V>> {
+    pub fn next<V: types::FromSdBusMessage<'a>>(&mut self) -> crate::Result<Option<V>> {
         V::from_message(self)
     }
 }
 
-/*
-struct Vtable;
-struct VtableBuilder<T> {
-    Vec<ffi::bus::sd_bus_vtable>,
+/// How changes to a property exported through a [`Vtable`] are announced to clients.
+///
+/// This selects the `org.freedesktop.DBus.Property.EmitsChangedSignal` annotation in the
+/// introspection data and what [`BusRef::emit_properties_changed()`] sends for the property.
+#[cfg(feature = "bus-service")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyChange {
+    /// Changes aren't signalled, clients need to re-read the property.
+    None,
+    /// The value never changes during the lifetime of the object. Can't be used on writable
+    /// properties.
+    Const,
+    /// Changes are signalled with `PropertiesChanged` including the new value.
+    EmitsChange,
+    /// Changes are signalled with `PropertiesChanged` listing the property as invalidated, without
+    /// the new value.
+    EmitsInvalidation,
 }
 
-type PropertyGet<T> = fn(Bus, ObjectPath, InterfaceName, MessageRef, &mut T, &mut Error) -> c_int;
-type PropertySet<T> = fn(Bus, ObjectPath, InterfaceName, MessageRef, &mut T, &mut Error) -> c_int;
+#[cfg(feature = "bus-service")]
+impl PropertyChange {
+    fn flags(self) -> u64 {
+        use ffi::bus::vtable::SdBusVtableFlag;
+        match self {
+            PropertyChange::None => 0,
+            PropertyChange::Const => SdBusVtableFlag::PropertyConst as u64,
+            PropertyChange::EmitsChange => SdBusVtableFlag::PropertyEmitsChange as u64,
+            PropertyChange::EmitsInvalidation => SdBusVtableFlag::PropertyEmitsInvalidation as u64,
+        }
+    }
+}
+
+#[cfg(feature = "bus-service")]
+type PropertyGetter = Box<dyn Fn(&mut MessageRef) -> Result<()> + Send + Sync + 'static>;
+
+#[cfg(feature = "bus-service")]
+type PropertySetter = Box<dyn Fn(&mut MessageIter<'_>) -> Result<()> + Send + Sync + 'static>;
+
+#[cfg(feature = "bus-service")]
+type MethodHandler = Box<dyn Fn(&mut MessageRef) -> Result<()> + Send + Sync + 'static>;
+
+#[cfg(feature = "bus-service")]
+struct VtableMethod {
+    member: CString,
+    signature: CString,
+    result: CString,
+    handler: MethodHandler,
+}
 
+#[cfg(feature = "bus-service")]
+struct VtableSignal {
+    member: CString,
+    signature: CString,
+}
+
+#[cfg(feature = "bus-service")]
+struct VtableProperty {
+    member: CString,
+    signature: CString,
+    change: PropertyChange,
+    get: PropertyGetter,
+    set: Option<PropertySetter>,
+}
+
+/// The members of one interface of an object, registered with [`BusRef::add_object_vtable()`].
+///
+/// Properties are backed by closures: getters append the current value to the reply message
+/// passed to them, setters read the new value from an iterator positioned at it. Methods are
+/// closures as well, which are passed the call and reply to it. Signals are only declared, for the
+/// introspection data, and sent with [`BusRef::new_signal()`]. sd-bus takes care of the
+/// `org.freedesktop.DBus.Properties` calls and the introspection data.
+///
+/// ```no_run
+/// # use systemd::bus::{self, PropertyChange, Vtable};
+/// # use utf8_cstr::Utf8CStr;
+/// # use std::sync::atomic::{AtomicU32, Ordering};
+/// # use std::sync::Arc;
+/// let level = Arc::new(AtomicU32::new(0));
+/// let l = level.clone();
+/// let vtable = Vtable::new()
+///     .property(
+///         bus::MemberName::from_bytes(b"Version\0").unwrap(),
+///         bus::Signature::from_bytes(b"s\0").unwrap(),
+///         PropertyChange::Const,
+///         |reply| Ok(reply.append(Utf8CStr::from_bytes(b"1.0\0").unwrap())?),
+///     )
+///     .writable_property(
+///         bus::MemberName::from_bytes(b"Level\0").unwrap(),
+///         bus::Signature::from_bytes(b"u\0").unwrap(),
+///         PropertyChange::EmitsChange,
+///         move |reply| Ok(reply.append(l.load(Ordering::SeqCst))?),
+///         move |value| {
+///             let v = value.next::<u32>()?;
+///             level.store(v.unwrap_or(0), Ordering::SeqCst);
+///             Ok(())
+///         },
+///     );
+/// ```
+#[cfg(feature = "bus-service")]
+#[derive(Default)]
+pub struct Vtable {
+    methods: Vec<VtableMethod>,
+    signals: Vec<VtableSignal>,
+    properties: Vec<VtableProperty>,
+    entries: Vec<ffi::bus::sd_bus_vtable>,
+    auto_emit: bool,
+}
 
-impl VtableBuilder {
-    fn method(mut self, member: &str, signature: &str, result: &str, handler: MessageHandler) {
-        /* verify */
-        /* track */
+#[cfg(feature = "bus-service")]
+impl Vtable {
+    /// An empty vtable
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
     }
 
-    fn property(mut self, member: &str, signature: &str, get: PropertyGet) {
+    /// Add a read-only property, with `get` appending its value (of type `signature`) to the
+    /// reply.
+    ///
+    /// This corresponds to `SD_BUS_PROPERTY()`.
+    pub fn property<G>(
+        mut self,
+        member: &MemberName,
+        signature: &Signature,
+        change: PropertyChange,
+        get: G,
+    ) -> Self
+    where
+        G: Fn(&mut MessageRef) -> Result<()> + Send + Sync + 'static,
+    {
+        self.properties.push(VtableProperty {
+            member: (**member).to_owned(),
+            signature: (**signature).to_owned(),
+            change,
+            get: Box::new(get),
+            set: None,
+        });
+        self
     }
 
-    fn property_writable(mut self, member: &str, signature: &str, get: PropertyGet, set: PropertySet) {
+    /// Add a writable property, with `get` appending its value (of type `signature`) to the reply
+    /// and `set` reading the new value from the iterator it is given.
+    ///
+    /// This corresponds to `SD_BUS_WRITABLE_PROPERTY()`.
+    pub fn writable_property<G, S>(
+        mut self,
+        member: &MemberName,
+        signature: &Signature,
+        change: PropertyChange,
+        get: G,
+        set: S,
+    ) -> Self
+    where
+        G: Fn(&mut MessageRef) -> Result<()> + Send + Sync + 'static,
+        S: Fn(&mut MessageIter<'_>) -> Result<()> + Send + Sync + 'static,
+    {
+        self.properties.push(VtableProperty {
+            member: (**member).to_owned(),
+            signature: (**signature).to_owned(),
+            change,
+            get: Box::new(get),
+            set: Some(Box::new(set)),
+        });
+        self
     }
 
-    fn signal(mut self, member: &str, signature: &str) {
+    /// Add a method taking arguments of the types in `signature` and returning those in
+    /// `result`. `handler` is called with the method call, and sends the reply (see
+    /// [`MessageRef::new_method_return()`]). Errors it returns are sent as the reply instead.
+    ///
+    /// This corresponds to `SD_BUS_METHOD()`.
+    pub fn method<H>(
+        mut self,
+        member: &MemberName,
+        signature: &Signature,
+        result: &Signature,
+        handler: H,
+    ) -> Self
+    where
+        H: Fn(&mut MessageRef) -> Result<()> + Send + Sync + 'static,
+    {
+        self.methods.push(VtableMethod {
+            member: (**member).to_owned(),
+            signature: (**signature).to_owned(),
+            result: (**result).to_owned(),
+            handler: Box::new(handler),
+        });
+        self
     }
 
-    fn create(mut self) -> Vtable {
+    /// Emit `PropertiesChanged` whenever a writable property flagged with
+    /// [`PropertyChange::EmitsChange`] or [`PropertyChange::EmitsInvalidation`] is set
+    /// successfully through `org.freedesktop.DBus.Properties.Set`.
+    ///
+    /// sd-bus leaves signalling changes to the service, without this the setters (or whatever
+    /// else changes the value) have to call [`BusRef::emit_properties_changed()`] themselves.
+    /// Failing to emit the signal doesn't fail the `Set` call, as the value was set already.
+    pub fn auto_emit_changes(mut self) -> Self {
+        self.auto_emit = true;
+        self
+    }
+
+    /// Declare a signal with arguments of the types in `signature`.
+    ///
+    /// This corresponds to `SD_BUS_SIGNAL()`.
+    pub fn signal(mut self, member: &MemberName, signature: &Signature) -> Self {
+        self.signals.push(VtableSignal {
+            member: (**member).to_owned(),
+            signature: (**signature).to_owned(),
+        });
+        self
+    }
+
+    fn method_by_name(&self, member: &CStr) -> Option<&VtableMethod> {
+        self.methods.iter().find(|m| m.member.as_c_str() == member)
+    }
+
+    fn property_by_name(&self, member: *const c_char) -> Option<&VtableProperty> {
+        let member = unsafe { CStr::from_ptr(member) };
+        self.properties
+            .iter()
+            .find(|p| p.member.as_c_str() == member)
+    }
+
+    /// Fill in `entries` pointing into the members. Neither may be modified afterwards.
+    fn build_entries(&mut self) {
+        use ffi::bus::vtable::{
+            sd_bus_table_method, sd_bus_table_property, sd_bus_table_signal, SdBusVtableType,
+        };
+        let method: extern "C" fn(
+            *mut ffi::bus::sd_bus_message,
+            *mut c_void,
+            *mut ffi::bus::sd_bus_error,
+        ) -> c_int = raw_vtable_method;
+        let get: unsafe extern "C" fn(
+            *mut ffi::bus::sd_bus,
+            *const c_char,
+            *const c_char,
+            *const c_char,
+            *mut ffi::bus::sd_bus_message,
+            *mut c_void,
+            *mut ffi::bus::sd_bus_error,
+        ) -> c_int = raw_property_get;
+        let set: unsafe extern "C" fn(
+            *mut ffi::bus::sd_bus,
+            *const c_char,
+            *const c_char,
+            *const c_char,
+            *mut ffi::bus::sd_bus_message,
+            *mut c_void,
+            *mut ffi::bus::sd_bus_error,
+        ) -> c_int = raw_property_set;
+
+        let mut entries = vec![ffi::bus::sd_bus_vtable::start(0)];
+        // every method and property gets the `Vtable` itself as userdata (offset 0) and is looked
+        // up by name
+        entries.extend(self.methods.iter().map(|m| {
+            ffi::bus::sd_bus_vtable::method(
+                0,
+                sd_bus_table_method {
+                    member: m.member.as_ptr(),
+                    signature: m.signature.as_ptr(),
+                    result: m.result.as_ptr(),
+                    handler: Some(method),
+                    offset: 0,
+                },
+            )
+        }));
+        entries.extend(self.signals.iter().map(|s| {
+            ffi::bus::sd_bus_vtable::signal(
+                0,
+                sd_bus_table_signal {
+                    member: s.member.as_ptr(),
+                    signature: s.signature.as_ptr(),
+                },
+            )
+        }));
+        entries.extend(self.properties.iter().map(|p| {
+            let typ = if p.set.is_some() {
+                SdBusVtableType::WritableProperty
+            } else {
+                SdBusVtableType::Property
+            };
+            ffi::bus::sd_bus_vtable::property(
+                typ,
+                p.change.flags(),
+                sd_bus_table_property {
+                    member: p.member.as_ptr(),
+                    signature: p.signature.as_ptr(),
+                    get: Some(get),
+                    set: p.set.as_ref().map(|_| set),
+                    offset: 0,
+                },
+            )
+        }));
+        entries.push(ffi::bus::sd_bus_vtable::end());
+        self.entries = entries;
     }
 }
-*/
+
+/// An interface of objects of the implementing type, usually generated with the
+/// `#[dbus_interface]` attribute (with the `macros` feature).
+///
+/// Objects are exported with [`BusRef::add_interface()`].
+#[cfg(feature = "bus-service")]
+pub trait Interface: Send + Sync + 'static {
+    /// The name of the interface.
+    fn name() -> &'static InterfaceName;
+
+    /// The members of the interface, with handlers calling into `object`.
+    ///
+    /// Fails if the signature of a member isn't valid D-Bus, like that of a map whose keys aren't
+    /// a basic type (see [`types::signature_of()`]).
+    fn vtable(object: std::sync::Arc<Self>) -> super::Result<Vtable>;
+}
+
+#[cfg(feature = "bus-service")]
+impl fmt::Debug for Vtable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.methods.iter().map(|m| &m.member))
+            .entries(self.signals.iter().map(|s| &s.member))
+            .entries(self.properties.iter().map(|p| &p.member))
+            .finish()
+    }
+}
+
+#[cfg(feature = "bus-service")]
+fn property_result(r: Result<()>, ret_error: *mut ffi::bus::sd_bus_error) -> c_int {
+    match r {
+        Err(e) => {
+            // sd-bus checks `ret_error` after calling property handlers and replies with it
+            unsafe { e.move_into(ret_error) }
+            0
+        }
+        Ok(_) => 0,
+    }
+}
+
+#[cfg(feature = "bus-service")]
+extern "C" fn raw_vtable_method(
+    msg: *mut ffi::bus::sd_bus_message,
+    userdata: *mut c_void,
+    ret_error: *mut ffi::bus::sd_bus_error,
+) -> c_int {
+    // the vtable is owned by the slot and freed by `raw_destroy_vtable`
+    let v: &Vtable = unsafe { &*(userdata as *const Vtable) };
+    let m = unsafe { MessageRef::from_ptr_mut(msg) };
+    let handler = match m.member().and_then(|member| v.method_by_name(member)) {
+        Some(method) => &method.handler,
+        None => return -libc::ENOENT,
+    };
+    match handler(m) {
+        Err(e) => {
+            // sd-bus replies with `ret_error`
+            unsafe { e.move_into(ret_error) }
+            0
+        }
+        // the handler replied
+        Ok(()) => 1,
+    }
+}
+
+#[cfg(feature = "bus-service")]
+unsafe extern "C" fn raw_property_get(
+    _bus: *mut ffi::bus::sd_bus,
+    _path: *const c_char,
+    _interface: *const c_char,
+    property: *const c_char,
+    reply: *mut ffi::bus::sd_bus_message,
+    userdata: *mut c_void,
+    ret_error: *mut ffi::bus::sd_bus_error,
+) -> c_int {
+    // the vtable is owned by the slot and freed by `raw_destroy_vtable`
+    let v: &Vtable = &*(userdata as *const Vtable);
+    match v.property_by_name(property) {
+        Some(p) => property_result((p.get)(MessageRef::from_ptr_mut(reply)), ret_error),
+        None => -libc::ENOENT,
+    }
+}
+
+#[cfg(feature = "bus-service")]
+unsafe extern "C" fn raw_property_set(
+    bus: *mut ffi::bus::sd_bus,
+    path: *const c_char,
+    interface: *const c_char,
+    property: *const c_char,
+    value: *mut ffi::bus::sd_bus_message,
+    userdata: *mut c_void,
+    ret_error: *mut ffi::bus::sd_bus_error,
+) -> c_int {
+    let v: &Vtable = &*(userdata as *const Vtable);
+    let p = match v.property_by_name(property) {
+        Some(p) => p,
+        None => return -libc::EPERM,
+    };
+    let set = match &p.set {
+        Some(set) => set,
+        None => return -libc::EPERM,
+    };
+    // sd-bus has already entered the variant holding the new value, so don't rewind like
+    // `MessageRef::iter()` does
+    let mut iter = MessageIter {
+        raw: value,
+        consumed: 0,
+        containers: Vec::new(),
+        life: PhantomData,
+    };
+    let r = set(&mut iter);
+    let emits = matches!(
+        p.change,
+        PropertyChange::EmitsChange | PropertyChange::EmitsInvalidation
+    );
+    if r.is_ok() && v.auto_emit && emits {
+        // the value is set, not being able to signal that doesn't make the call fail
+        let mut names = [property as *mut c_char, ptr::null_mut()];
+        ffi::bus::sd_bus_emit_properties_changed_strv(bus, path, interface, names.as_mut_ptr());
+    }
+    property_result(r, ret_error)
+}
+
+#[cfg(feature = "bus-service")]
+extern "C" fn raw_destroy_vtable(userdata: *mut c_void) {
+    let _: Box<Vtable> = unsafe { Box::from_raw(userdata as *mut Vtable) };
+}
+
+/// The userdata of a vtable registered with [`BusRef::add_fallback_vtable()`]
+#[cfg(feature = "bus-service")]
+struct FallbackVtable<F> {
+    vtable: Vtable,
+    find: F,
+}
+
+#[cfg(feature = "bus-service")]
+unsafe extern "C" fn raw_fallback_find<F>(
+    _bus: *mut ffi::bus::sd_bus,
+    path: *const c_char,
+    _interface: *const c_char,
+    userdata: *mut c_void,
+    ret_found: *mut *mut c_void,
+    ret_error: *mut ffi::bus::sd_bus_error,
+) -> c_int
+where
+    F: Fn(&ObjectPath) -> Result<bool>,
+{
+    // owned by the slot and freed by `raw_destroy_fallback_vtable`
+    let fallback: &FallbackVtable<F> = &*(userdata as *const FallbackVtable<F>);
+    match (fallback.find)(ObjectPath::from_ptr_unchecked(path)) {
+        Ok(true) => {
+            // the property handlers expect the `Vtable` as their userdata
+            *ret_found = &fallback.vtable as *const Vtable as *mut c_void;
+            1
+        }
+        Ok(false) => 0,
+        Err(e) => {
+            e.move_into(ret_error);
+            0
+        }
+    }
+}
+
+#[cfg(feature = "bus-service")]
+extern "C" fn raw_destroy_fallback_vtable<F>(userdata: *mut c_void) {
+    let _: Box<FallbackVtable<F>> = unsafe { Box::from_raw(userdata as *mut FallbackVtable<F>) };
+}
+
+#[cfg(feature = "bus-service")]
+unsafe extern "C" fn raw_node_enumerator<F>(
+    _bus: *mut ffi::bus::sd_bus,
+    prefix: *const c_char,
+    userdata: *mut c_void,
+    ret_nodes: *mut *mut *mut c_char,
+    ret_error: *mut ffi::bus::sd_bus_error,
+) -> c_int
+where
+    F: Fn(&ObjectPath) -> Result<Vec<ObjectPathBuf>>,
+{
+    // owned by the slot and freed by `raw_destroy_node_enumerator`
+    let f: &F = &*(userdata as *const F);
+    let nodes = match f(ObjectPath::from_ptr_unchecked(prefix)) {
+        Ok(nodes) => nodes,
+        Err(e) => {
+            e.move_into(ret_error);
+            return 0;
+        }
+    };
+
+    // sd-bus takes ownership of the strv and frees it (and the strings) with `free()`
+    let strv =
+        libc::calloc(nodes.len() + 1, std::mem::size_of::<*mut c_char>()) as *mut *mut c_char;
+    if strv.is_null() {
+        return -libc::ENOMEM;
+    }
+    for (i, node) in nodes.iter().enumerate() {
+        let s = libc::strdup(node.as_ptr());
+        if s.is_null() {
+            for j in 0..i {
+                libc::free(*strv.add(j) as *mut c_void);
+            }
+            libc::free(strv as *mut c_void);
+            return -libc::ENOMEM;
+        }
+        *strv.add(i) = s;
+    }
+    *ret_nodes = strv;
+    0
+}
+
+#[cfg(feature = "bus-service")]
+extern "C" fn raw_destroy_node_enumerator<F>(userdata: *mut c_void)
+where
+    F: Fn(&ObjectPath) -> Result<Vec<ObjectPathBuf>>,
+{
+    let _: Box<F> = unsafe { Box::from_raw(userdata as *mut F) };
+}
+
+/// Where a name requested with [`takeover_name()`] stands.
+#[cfg(feature = "bus-service")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameState {
+    /// The current owner didn't allow replacement, the name is acquired once it releases it.
+    Queued,
+    /// The connection is the primary owner of the name.
+    Owner,
+    /// The name was taken over by another connection.
+    Lost,
+}
+
+/// A well-known name requested with [`takeover_name()`], tracking the `NameAcquired` and
+/// `NameLost` signals for it.
+///
+/// Dropping it stops the tracking, but doesn't release the name.
+#[cfg(feature = "bus-service")]
+pub struct TakenName {
+    name: BusNameBuf,
+    state: std::sync::Arc<std::sync::Mutex<NameState>>,
+    _acquired: Slot,
+    _lost: Slot,
+}
+
+#[cfg(feature = "bus-service")]
+impl TakenName {
+    /// The requested name.
+    pub fn name(&self) -> &BusName {
+        &self.name
+    }
+
+    /// Whether the name is owned, as of the last signal processed.
+    pub fn state(&self) -> NameState {
+        *self.state.lock().unwrap()
+    }
+
+    /// Process `bus` until the name is taken over by a newer instance of the service, then finish
+    /// up so the process can exit.
+    ///
+    /// The bus delivers all calls sent to the name before the takeover ahead of `NameLost`, so
+    /// they have been dispatched by then. Afterwards, the connection leaves the queue of the name
+    /// (so it isn't handed back), processes whatever is still pending and flushes the replies.
+    /// Calls whose replies are sent later, from outside of the message handlers, need to be
+    /// finished by the caller.
+    ///
+    /// Also returns once the connection is closed.
+    pub fn run(&self, bus: &mut BusRef) -> super::Result<()> {
+        while self.state() != NameState::Lost {
+            let r = match bus.process() {
+                Ok(Some(_)) => continue,
+                Ok(None) => bus.wait(None).map(|_| ()),
+                Err(e) => Err(e),
+            };
+            match r {
+                Ok(()) => {}
+                Err(e) if e.raw_os_error() == Some(libc::EINTR) => {}
+                Err(e) if is_disconnect(&e) => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
+
+        match bus.release_name(&self.name) {
+            // not queued for the name (anymore)
+            Err(e) if matches!(e.raw_os_error(), Some(libc::EADDRINUSE) | Some(libc::ESRCH)) => {}
+            r => r?,
+        }
+        while bus.process()?.is_some() {}
+        sd_try!(ffi::bus::sd_bus_flush(bus.as_ptr()));
+        Ok(())
+    }
+}
+
+#[cfg(feature = "bus-service")]
+impl fmt::Debug for TakenName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TakenName")
+            .field("name", &self.name)
+            .field("state", &self.state())
+            .finish()
+    }
+}
+
+/// Request `name` so that a new instance of a service takes it over from the running one, for
+/// restarts without downtime.
+///
+/// The name is requested with [`NAME_REPLACE_EXISTING`], taking it from an owner which allowed
+/// that, and [`NAME_ALLOW_REPLACEMENT`], so the next instance can take it in turn. When the owner
+/// doesn't allow replacement the request is queued ([`NAME_QUEUE`]) until it releases the name.
+///
+/// The old instance notices the takeover with [`TakenName::state()`], or exits after
+/// [`TakenName::run()`] returns.
+///
+/// ```no_run
+/// use systemd::bus;
+/// let mut bus = bus::Bus::default_system().unwrap();
+/// // ... add objects ...
+/// let name = bus::BusName::from_bytes(b"org.example.Service\0").unwrap();
+/// let name = bus::takeover_name(&mut bus, name).unwrap();
+/// name.run(&mut bus).unwrap();
+/// // a newer instance handles calls to the name from now on
+/// ```
+#[cfg(feature = "bus-service")]
+pub fn takeover_name(bus: &mut BusRef, name: &BusName) -> super::Result<TakenName> {
+    let state = std::sync::Arc::new(std::sync::Mutex::new(NameState::Queued));
+    let arg0 = name.to_string();
+    let watch = |member: &[u8], new_state| {
+        let rule = MatchRule::new()
+            .type_(MessageType::Signal)
+            .sender(BusName::from_bytes(b"org.freedesktop.DBus\0").unwrap())
+            .interface(InterfaceName::from_bytes(b"org.freedesktop.DBus\0").unwrap())
+            .member(MemberName::from_bytes(member).unwrap())
+            .arg0(&arg0)?;
+        let state = state.clone();
+        bus.add_match(&rule, move |_| {
+            *state.lock().unwrap() = new_state;
+            Ok(())
+        })
+    };
+    // subscribe first, so no signal is missed
+    let acquired = watch(b"NameAcquired\0", NameState::Owner)?;
+    let lost = watch(b"NameLost\0", NameState::Lost)?;
+
+    let r = sd_try!(ffi::bus::sd_bus_request_name(
+        bus.as_ptr(),
+        name.as_ptr(),
+        NAME_REPLACE_EXISTING | NAME_ALLOW_REPLACEMENT | NAME_QUEUE
+    ));
+    if r > 0 {
+        *state.lock().unwrap() = NameState::Owner;
+    }
+    Ok(TakenName {
+        name: name.into(),
+        state,
+        _acquired: acquired,
+        _lost: lost,
+    })
+}
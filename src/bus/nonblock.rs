@@ -0,0 +1,404 @@
+//! Drive a [`Bus`] from a tokio runtime, with the bus file descriptor registered in an
+//! [`AsyncFd`].
+//!
+//! sd-bus connections aren't thread safe, so neither is [`AsyncBus`]: it is driven by whichever
+//! task owns it. [`AsyncBus::call()`] processes the connection while it waits for the reply, so
+//! callbacks keep being invoked during calls. Services run [`AsyncBus::process()`], usually in a
+//! [`tokio::task::LocalSet`] or with `block_on()` on a current thread runtime.
+//!
+//! Method calls can be answered by `async` handlers with an [`AsyncObject`], which runs them as
+//! tasks of the `LocalSet` and sends their result as the reply once they complete.
+//!
+//! ```no_run
+//! use systemd::bus::{self, nonblock::AsyncBus};
+//!
+//! # async fn f() -> bus::Result<()> {
+//! let mut bus = AsyncBus::new(bus::Bus::default_system()?)?;
+//! let mut m = bus.new_method_call(
+//!     bus::BusName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+//!     bus::ObjectPath::from_bytes(b"/\0").unwrap(),
+//!     bus::InterfaceName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+//!     bus::MemberName::from_bytes(b"GetId\0").unwrap(),
+//! )?;
+//! let mut reply = bus.call(&mut m).await?;
+//! println!("{:?}", reply.iter()?.next::<&str>()?);
+//! # Ok(())
+//! # }
+//! ```
+
+use super::{is_disconnect, Bus, BusRef, Message, MessageRef, Result, Slot};
+#[cfg(feature = "bus-service")]
+use super::{types::ToSdBusMessageArgs, Error, MemberName, ObjectPath, Signature, Vtable};
+use ffi::{c_int, c_void};
+use foreign_types::{ForeignType, ForeignTypeRef};
+use std::cell::RefCell;
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::pin::Pin;
+use std::ptr;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+use tokio::sync::Notify;
+#[cfg(feature = "bus-service")]
+use tokio::sync::Semaphore;
+#[cfg(feature = "bus-service")]
+use utf8_cstr::Utf8CStr;
+
+/// The file descriptor of the bus, registered with the runtime separately from the `Bus` so it can
+/// be waited on while the bus is processed.
+struct BusFd(RawFd);
+
+impl AsRawFd for BusFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+/// A [`Bus`] processed asynchronously on a tokio runtime.
+///
+/// Dereferences to the [`BusRef`] for everything which doesn't wait, like creating messages and
+/// adding objects and matches.
+pub struct AsyncBus {
+    // dropped (deregistered) before the bus closes the file descriptor
+    fd: AsyncFd<BusFd>,
+    bus: Bus,
+    // notified when messages were queued outside of `dispatch()`, like replies sent by tasks
+    wake: Arc<Notify>,
+}
+
+impl AsyncBus {
+    /// Register the file descriptor of `bus` with the tokio runtime of the current task.
+    ///
+    /// Fails if there is no runtime with I/O enabled, or if the bus uses separate file
+    /// descriptors for input and output.
+    pub fn new(bus: Bus) -> crate::Result<AsyncBus> {
+        let fd = AsyncFd::with_interest(BusFd(bus.fd()?), Interest::READABLE | Interest::WRITABLE)?;
+        Ok(AsyncBus {
+            fd,
+            bus,
+            wake: Arc::new(Notify::new()),
+        })
+    }
+
+    /// Handlers for the methods of one object, running at most `limit` (at least 1) calls at a
+    /// time. Calls arriving while `limit` are running wait for one of them to complete.
+    #[cfg(feature = "bus-service")]
+    pub fn object(&self, limit: usize) -> AsyncObject {
+        AsyncObject {
+            wake: self.wake.clone(),
+            permits: Arc::new(Semaphore::new(limit.max(1))),
+        }
+    }
+
+    /// Deregister the bus from the runtime and return it.
+    pub fn into_inner(self) -> Bus {
+        self.bus
+    }
+
+    /// Process the connection until it is closed, dispatching messages to callbacks.
+    ///
+    /// Messages not handled by any callback are dropped, like with [`BusRef::run_forever()`].
+    pub async fn process(&mut self) -> crate::Result<()> {
+        loop {
+            if !self.dispatch()? {
+                return Ok(());
+            }
+            self.wait().await?;
+        }
+    }
+
+    /// Call the method `m`, processing the connection until the reply arrives.
+    ///
+    /// The call times out after the [`BusRef::method_call_timeout()`] of the bus. Dropping the
+    /// returned future cancels the call.
+    ///
+    /// Seals `m`. This is the asynchronous version of [`MessageRef::call()`].
+    pub async fn call(&mut self, m: &mut MessageRef) -> Result<Message> {
+        let reply = Rc::new(RefCell::new(None));
+        let r = reply.clone();
+        let _slot = call_async(m, move |m| {
+            *r.borrow_mut() = Some(m.error().clone().into_result().map(|()| m.to_owned()));
+        })?;
+        loop {
+            if !self.dispatch()? {
+                return Err(std::io::Error::from_raw_os_error(libc::ECONNRESET).into());
+            }
+            if let Some(reply) = reply.borrow_mut().take() {
+                return reply;
+            }
+            self.wait().await?;
+        }
+    }
+
+    /// Process until there is nothing left to do, returning `false` if the connection was closed.
+    fn dispatch(&mut self) -> crate::Result<bool> {
+        loop {
+            match self.bus.process() {
+                Ok(Some(_)) => {}
+                Ok(None) => return Ok(true),
+                Err(e) if is_disconnect(&e) => return Ok(false),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Wait until the bus has something to process: I/O on its file descriptor or a timeout
+    /// expiring.
+    async fn wait(&self) -> crate::Result<()> {
+        let events = self.bus.events()?;
+        let mut interest = None;
+        if events & c_int::from(libc::POLLIN) != 0 {
+            interest = Some(Interest::READABLE);
+        }
+        if events & c_int::from(libc::POLLOUT) != 0 {
+            interest = Some(interest.map_or(Interest::WRITABLE, |i| i | Interest::WRITABLE));
+        }
+        let io = async {
+            match interest {
+                // processing reads and writes until sd-bus would block, so the readiness seen
+                // here is consumed by the next `dispatch()`
+                Some(interest) => self.fd.ready(interest).await.map(|mut g| g.clear_ready()),
+                None => std::future::pending().await,
+            }
+        };
+
+        let woken = self.wake.notified();
+        tokio::pin!(io, woken);
+        let ready = Ready { io, woken };
+
+        let timeout = self.bus.timeout()?;
+        if timeout == u64::MAX {
+            return ready.await;
+        }
+        let delay = Duration::from_micros(timeout.saturating_sub(crate::monotonic_usec()));
+        match tokio::time::timeout(delay, ready).await {
+            Ok(r) => r,
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+/// Resolves when the I/O of the bus is ready or a task queued messages on it.
+struct Ready<'a, I, W> {
+    io: Pin<&'a mut I>,
+    woken: Pin<&'a mut W>,
+}
+
+impl<I, W> Future for Ready<'_, I, W>
+where
+    I: Future<Output = crate::Result<()>>,
+    W: Future<Output = ()>,
+{
+    type Output = crate::Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.io.as_mut().poll(cx) {
+            Poll::Ready(r) => Poll::Ready(r),
+            Poll::Pending => self.woken.as_mut().poll(cx).map(Ok),
+        }
+    }
+}
+
+impl Deref for AsyncBus {
+    type Target = BusRef;
+
+    fn deref(&self) -> &BusRef {
+        &self.bus
+    }
+}
+
+impl DerefMut for AsyncBus {
+    fn deref_mut(&mut self) -> &mut BusRef {
+        &mut self.bus
+    }
+}
+
+impl AsRawFd for AsyncBus {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// Runs `async` method handlers of an object as tasks, created with [`AsyncBus::object()`].
+///
+/// The handlers are called with the method call and return the values of the reply, or an error
+/// to send instead. They run on the [`tokio::task::LocalSet`] of the task processing the bus, so
+/// the futures don't need to be `Send`; if the bus is processed outside of a `LocalSet`, calls
+/// are answered with an `org.freedesktop.DBus.Error.Failed` error instead.
+///
+/// ```no_run
+/// use systemd::bus::{self, nonblock::AsyncBus, Message};
+///
+/// async fn double(mut call: Message) -> bus::Result<(u32,)> {
+///     let n = call.iter()?.next::<u32>()?.unwrap_or(0);
+///     Ok((n * 2,))
+/// }
+///
+/// # async fn f() -> bus::Result<()> {
+/// let mut bus = AsyncBus::new(bus::Bus::default_system()?)?;
+/// let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+/// let _slot = bus.object(4).add_object(&bus, path, double)?;
+/// bus.process().await?;
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "bus-service")]
+#[derive(Clone)]
+pub struct AsyncObject {
+    wake: Arc<Notify>,
+    permits: Arc<Semaphore>,
+}
+
+#[cfg(feature = "bus-service")]
+impl AsyncObject {
+    /// Answer the method calls sent to the object at `path` with `handler`, like
+    /// [`BusRef::add_object()`].
+    pub fn add_object<H, F, R>(
+        &self,
+        bus: &BusRef,
+        path: &ObjectPath,
+        handler: H,
+    ) -> crate::Result<Slot>
+    where
+        H: Fn(Message) -> F + 'static,
+        F: Future<Output = Result<R>> + 'static,
+        R: ToSdBusMessageArgs + 'static,
+    {
+        let object = self.clone();
+        add_callback(
+            move |m| object.spawn(m, &handler),
+            |handler, userdata, slot| unsafe {
+                ffi::bus::sd_bus_add_object(bus.as_ptr(), slot, path.as_ptr(), handler, userdata)
+            },
+        )
+    }
+
+    /// Add a method to `vtable` answered by `handler`, like [`Vtable::method()`].
+    pub fn method<H, F, R>(
+        &self,
+        vtable: Vtable,
+        member: &MemberName,
+        signature: &Signature,
+        result: &Signature,
+        handler: H,
+    ) -> Vtable
+    where
+        H: Fn(Message) -> F + Send + Sync + 'static,
+        F: Future<Output = Result<R>> + 'static,
+        R: ToSdBusMessageArgs + 'static,
+    {
+        let object = self.clone();
+        vtable.method(member, signature, result, move |m| {
+            object.spawn(m, &handler);
+            Ok(())
+        })
+    }
+
+    /// Start a task running `handler` for the call `m`, and replying with its result.
+    fn spawn<H, F, R>(&self, m: &mut MessageRef, handler: &H)
+    where
+        H: Fn(Message) -> F,
+        F: Future<Output = Result<R>> + 'static,
+        R: ToSdBusMessageArgs + 'static,
+    {
+        let mut call = m.to_owned();
+        if !can_spawn_local() {
+            let e = Error::new(
+                Utf8CStr::from_bytes(b"org.freedesktop.DBus.Error.Failed\0").unwrap(),
+                Some(
+                    Utf8CStr::from_bytes(b"the bus isn't processed in a tokio LocalSet\0").unwrap(),
+                ),
+            );
+            let _ = call.new_method_error(&e).and_then(|mut m| m.send());
+            return;
+        }
+        let reply = handler(m.to_owned());
+        let object = self.clone();
+        tokio::task::spawn_local(async move {
+            let permit = object.permits.acquire().await;
+            let reply = reply.await;
+            drop(permit);
+            // nothing is left to report failures to, the caller sees the call fail or time out
+            let _ = match reply {
+                Ok(args) => call.new_method_return().and_then(|mut m| {
+                    m.append_tuple(args)?;
+                    m.send()
+                }),
+                Err(e) => call.new_method_error(&e).and_then(|mut m| m.send()),
+            };
+            object.wake.notify_one();
+        });
+    }
+}
+
+/// Whether tasks can be spawned with `tokio::task::spawn_local()`, which panics outside of a
+/// `LocalSet`. tokio has no way to find out other than trying, and this is called from sd-bus
+/// callbacks, which mustn't unwind.
+#[cfg(feature = "bus-service")]
+fn can_spawn_local() -> bool {
+    tokio::runtime::Handle::try_current().is_ok()
+        && std::panic::catch_unwind(|| drop(tokio::task::spawn_local(async {}))).is_ok()
+}
+
+/// Like [`MessageRef::call_async()`], for callbacks which stay on the thread of the bus (and so
+/// don't need to be `Send`).
+fn call_async<F>(m: &mut MessageRef, callback: F) -> crate::Result<Slot>
+where
+    F: Fn(&mut MessageRef) + 'static,
+{
+    add_callback(callback, |handler, userdata, slot| unsafe {
+        ffi::bus::sd_bus_call_async(ptr::null_mut(), slot, m.as_ptr(), handler, userdata, 0)
+    })
+}
+
+/// Register `callback` as the handler of a new slot with `add`, which is passed the handler,
+/// its userdata and where to store the slot. The callback is freed along with the slot.
+fn add_callback<F, A>(callback: F, add: A) -> crate::Result<Slot>
+where
+    F: Fn(&mut MessageRef) + 'static,
+    A: FnOnce(
+        ffi::bus::sd_bus_message_handler_t,
+        *mut c_void,
+        *mut *mut ffi::bus::sd_bus_slot,
+    ) -> c_int,
+{
+    let b = Box::into_raw(Box::new(callback));
+    let mut slot = ptr::null_mut();
+    match crate::ffi_result(add(Some(raw_handler::<F>), b as *mut c_void, &mut slot)) {
+        Err(e) => {
+            drop(unsafe { Box::from_raw(b) });
+            Err(e)
+        }
+        Ok(_) => unsafe {
+            ffi::bus::sd_bus_slot_set_destroy_callback(slot, Some(raw_destroy_handler::<F>));
+            Ok(Slot::from_ptr(slot))
+        },
+    }
+}
+
+extern "C" fn raw_handler<F>(
+    msg: *mut ffi::bus::sd_bus_message,
+    userdata: *mut c_void,
+    _ret_error: *mut ffi::bus::sd_bus_error,
+) -> c_int
+where
+    F: Fn(&mut MessageRef),
+{
+    // the closure is owned by the slot and freed by `raw_destroy_handler`
+    let f: &F = unsafe { &*(userdata as *const F) };
+    f(unsafe { MessageRef::from_ptr_mut(msg) });
+    // handled, replies are sent by the callback
+    1
+}
+
+extern "C" fn raw_destroy_handler<F>(userdata: *mut c_void)
+where
+    F: Fn(&mut MessageRef),
+{
+    let _: Box<F> = unsafe { Box::from_raw(userdata as *mut F) };
+}
@@ -0,0 +1,937 @@
+//! Values are first converted to a [`Value`] by [`Serializer`], and then appended with
+//! [`Value::append()`] (or [`Value::append_as()`] when the signature is given). The serde data
+//! model maps to D-Bus types like this:
+//!
+//! - `bool`, `u8`, `i16`, `u16`, `i32`, `u32`, `i64`, `u64` and `f64` are the D-Bus type of the
+//!   same size. D-Bus has no smaller types, so `i8` is `n` and `f32` is `d`.
+//! - `char` and strings are `s`, and bytes are `ay`.
+//! - Sequences (like slices and `Vec`) are arrays and maps are dictionaries (`a{..}`).
+//! - Structs, tuples and tuple structs are D-Bus structs, the field names aren't kept. serde
+//!   treats fixed size arrays as tuples, so they are structs too. Newtype structs are the value
+//!   they wrap.
+//! - Enum variants without data are their index as `u`. Variants with data are a struct of the
+//!   index and the data, so `(us)` for a newtype variant holding a string.
+//! - `Some(v)` is `v`. `None`, `()` and unit structs have no D-Bus representation and fail.
+//!
+//! The element type of arrays and dictionaries is taken from their first element, so empty ones
+//! can only be appended with [`append_as()`].
+//!
+//! [`Deserializer`] reads values back following the same mapping, directly from the message.
+//! Variants (`v`) are read as the value they contain, so a property can be read as its type. As
+//! an alternative to D-Bus structs, structs can also be read from dictionaries with string keys
+//! naming their fields, like the `a{sv}` maps often used for options.
+
+use super::types::{FromSdBusMessage, ToSdBusMessage};
+use super::{MessageIter, MessageRef, ObjectPath, Signature, Value};
+use ::serde::de::{self, Deserialize, DeserializeSeed, IntoDeserializer, Visitor};
+use ::serde::ser::{self, Serialize};
+use std::ffi::{CStr, CString};
+use std::fmt;
+
+/// An error converting values to or from D-Bus types.
+///
+/// Converts to an `std::io::Error` of kind `InvalidInput` when appending and `InvalidData` when
+/// reading, or to the error returned by sd-bus.
+#[derive(Debug)]
+pub struct Error(ErrorRepr);
+
+#[derive(Debug)]
+enum ErrorRepr {
+    Custom(std::io::ErrorKind, String),
+    Io(std::io::Error),
+}
+
+impl Error {
+    fn unsupported(what: &str) -> Self {
+        ser::Error::custom(format_args!("{} has no D-Bus representation", what))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            ErrorRepr::Custom(_, msg) => f.write_str(msg),
+            ErrorRepr::Io(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(ErrorRepr::Custom(
+            std::io::ErrorKind::InvalidInput,
+            msg.to_string(),
+        ))
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(ErrorRepr::Custom(
+            std::io::ErrorKind::InvalidData,
+            msg.to_string(),
+        ))
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error(ErrorRepr::Io(e))
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        match e.0 {
+            ErrorRepr::Custom(kind, msg) => std::io::Error::new(kind, msg),
+            ErrorRepr::Io(e) => e,
+        }
+    }
+}
+
+/// Convert `v` to a [`Value`].
+pub fn to_value<T: Serialize + ?Sized>(v: &T) -> Result<Value, Error> {
+    v.serialize(Serializer)
+}
+
+/// Append `v` to `m` as a single value, with the type given by [`Value::signature()`].
+pub fn append<T: Serialize + ?Sized>(m: &mut MessageRef, v: &T) -> crate::Result<()> {
+    to_value(v)?.append(m)
+}
+
+/// Append `v` to `m` as the single complete type `signature` (see [`Value::append_as()`]).
+///
+/// ```no_run
+/// # use systemd::bus;
+/// # fn f(m: &mut bus::MessageRef) -> systemd::Result<()> {
+/// let sig = bus::Signature::from_bytes(b"a(sy)\0").unwrap();
+/// let empty: &[(&str, u32)] = &[];
+/// bus::serde::append_as(m, sig, empty)?;
+/// bus::serde::append_as(m, sig, &[("a", 1u32), ("b", 2)][..])?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn append_as<T: Serialize + ?Sized>(
+    m: &mut MessageRef,
+    signature: &Signature,
+    v: &T,
+) -> crate::Result<()> {
+    to_value(v)?.append_as(m, signature)
+}
+
+/// Read the next value of `m` as a `T`, returning `None` at the end of the current container.
+pub fn read<'a, T: Deserialize<'a>>(m: &mut MessageIter<'a>) -> crate::Result<Option<T>> {
+    if m.peek_type()?.0 == 0 {
+        return Ok(None);
+    }
+    Ok(Some(T::deserialize(&mut Deserializer::new(m))?))
+}
+
+/// Appends the wrapped value with [`append()`] and reads it with [`read()`], so it can be used
+/// where a [`ToSdBusMessage`] or [`FromSdBusMessage`] is expected, like the arguments passed to
+/// [`MessageRef::append_tuple()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Serde<T>(pub T);
+
+impl<T: Serialize> ToSdBusMessage for Serde<T> {
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        append(m, &self.0)
+    }
+}
+
+impl<'a, T: Deserialize<'a>> FromSdBusMessage<'a> for Serde<T> {
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        Ok(read(m)?.map(Serde))
+    }
+}
+
+/// A `serde::Serializer` producing a [`Value`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = Error;
+
+    type SerializeSeq = ArraySerializer;
+    type SerializeTuple = StructSerializer;
+    type SerializeTupleStruct = StructSerializer;
+    type SerializeTupleVariant = VariantSerializer;
+    type SerializeMap = DictSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = VariantSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+        Ok(Value::Int16(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+        Ok(Value::Int16(v))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+        Ok(Value::Int32(v))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+        Ok(Value::Int64(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+        Ok(Value::Byte(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+        Ok(Value::UInt16(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+        Ok(Value::UInt32(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+        Ok(Value::UInt64(v))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+        Ok(Value::Double(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+        Ok(Value::Double(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, Error> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        Ok(Value::Array {
+            element: "y".into(),
+            elements: v.iter().copied().map(Value::Byte).collect(),
+        })
+    }
+
+    fn collect_str<T: fmt::Display + ?Sized>(self, v: &T) -> Result<Value, Error> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        Err(Error::unsupported("None"))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, v: &T) -> Result<Value, Error> {
+        v.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Err(Error::unsupported("()"))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Value, Error> {
+        Err(Error::unsupported(name))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+    ) -> Result<Value, Error> {
+        Ok(Value::UInt32(index))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        v: &T,
+    ) -> Result<Value, Error> {
+        v.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        v: &T,
+    ) -> Result<Value, Error> {
+        Ok(Value::Struct(vec![
+            Value::UInt32(index),
+            v.serialize(self)?,
+        ]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ArraySerializer, Error> {
+        Ok(ArraySerializer(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<StructSerializer, Error> {
+        Ok(StructSerializer(Vec::with_capacity(len)))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<StructSerializer, Error> {
+        Ok(StructSerializer(Vec::with_capacity(len)))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer, Error> {
+        Ok(VariantSerializer {
+            index,
+            fields: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<DictSerializer, Error> {
+        Ok(DictSerializer {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<StructSerializer, Error> {
+        Ok(StructSerializer(Vec::with_capacity(len)))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        index: u32,
+        _variant: &'static str,
+        len: usize,
+    ) -> Result<VariantSerializer, Error> {
+        Ok(VariantSerializer {
+            index,
+            fields: Vec::with_capacity(len),
+        })
+    }
+}
+
+/// Collects the elements of a sequence into a [`Value::Array`].
+#[derive(Debug)]
+pub struct ArraySerializer(Vec<Value>);
+
+impl ser::SerializeSeq for ArraySerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, v: &T) -> Result<(), Error> {
+        self.0.push(v.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Array {
+            element: signature_or_empty(self.0.first()),
+            elements: self.0,
+        })
+    }
+}
+
+/// Collects the fields of a struct or tuple into a [`Value::Struct`].
+#[derive(Debug)]
+pub struct StructSerializer(Vec<Value>);
+
+impl ser::SerializeTuple for StructSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, v: &T) -> Result<(), Error> {
+        self.0.push(v.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Struct(self.0))
+    }
+}
+
+impl ser::SerializeTupleStruct for StructSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, v: &T) -> Result<(), Error> {
+        self.0.push(v.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Struct(self.0))
+    }
+}
+
+impl ser::SerializeStruct for StructSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        v: &T,
+    ) -> Result<(), Error> {
+        self.0.push(v.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Struct(self.0))
+    }
+}
+
+/// Collects the fields of an enum variant into a [`Value::Struct`] following the variant index.
+#[derive(Debug)]
+pub struct VariantSerializer {
+    index: u32,
+    fields: Vec<Value>,
+}
+
+impl VariantSerializer {
+    fn finish(self) -> Value {
+        Value::Struct(vec![Value::UInt32(self.index), Value::Struct(self.fields)])
+    }
+}
+
+impl ser::SerializeTupleVariant for VariantSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, v: &T) -> Result<(), Error> {
+        self.fields.push(v.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for VariantSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        v: &T,
+    ) -> Result<(), Error> {
+        self.fields.push(v.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(self.finish())
+    }
+}
+
+/// Collects the entries of a map into a [`Value::Dict`].
+#[derive(Debug)]
+pub struct DictSerializer {
+    entries: Vec<(Value, Value)>,
+    key: Option<Value>,
+}
+
+impl ser::SerializeMap for DictSerializer {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, k: &T) -> Result<(), Error> {
+        self.key = Some(k.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, v: &T) -> Result<(), Error> {
+        let k = self
+            .key
+            .take()
+            .ok_or_else(|| <Error as ser::Error>::custom("map value serialized before its key"))?;
+        self.entries.push((k, v.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        let first = self.entries.first();
+        Ok(Value::Dict {
+            key: signature_or_empty(first.map(|(k, _)| k)),
+            value: signature_or_empty(first.map(|(_, v)| v)),
+            entries: self.entries,
+        })
+    }
+}
+
+/// The signature of the first element of a sequence or map, which is the element type. Empty if
+/// there is none or it has no signature of its own, leaving the type to [`append_as()`].
+fn signature_or_empty(first: Option<&Value>) -> String {
+    let mut sig = String::new();
+    if let Some(v) = first {
+        if v.signature(&mut sig).is_err() {
+            sig.clear();
+        }
+    }
+    sig
+}
+
+/// A `serde::Deserializer` reading values from a message.
+///
+/// Values are read from the current position of the iterator, and type mismatches fail with an
+/// error naming the expected and the actual signature.
+pub struct Deserializer<'i, 'a> {
+    iter: &'i mut MessageIter<'a>,
+}
+
+impl<'i, 'a> Deserializer<'i, 'a> {
+    /// Read values from `iter`, starting at its current position.
+    pub fn new(iter: &'i mut MessageIter<'a>) -> Self {
+        Deserializer { iter }
+    }
+
+    fn peek(&mut self) -> Result<(u8, CString), Error> {
+        let (t, contents) = self.iter.peek_type()?;
+        Ok((t as u8, CString::new(contents).unwrap()))
+    }
+
+    /// Enter any variants around the next value, until it has one of the `allowed` types.
+    ///
+    /// Returns the number of variants entered, which need to be exited with
+    /// [`Deserializer::exit_variants()`] once the value is read, and the type of the value.
+    fn expect(&mut self, expected: &str, allowed: &[u8]) -> Result<(usize, u8, CString), Error> {
+        let mut variants = 0;
+        loop {
+            let (t, contents) = self.peek()?;
+            if allowed.contains(&t) {
+                return Ok((variants, t, contents));
+            }
+            if t != b'v' {
+                return Err(mismatch(expected, t, &contents));
+            }
+            self.iter.enter_container(b'v', &contents)?;
+            variants += 1;
+        }
+    }
+
+    fn exit_variants(&mut self, variants: usize) -> Result<(), Error> {
+        for _ in 0..variants {
+            self.iter.exit_container()?;
+        }
+        Ok(())
+    }
+
+    fn basic<T: FromSdBusMessage<'a>>(&mut self, sig: &str) -> Result<T, Error> {
+        let (variants, _, _) = self.expect(sig, sig.as_bytes())?;
+        let v = self.iter.next::<T>()?.ok_or_else(end)?;
+        self.exit_variants(variants)?;
+        Ok(v)
+    }
+
+    /// Enter the array or struct `t` and visit its elements as a sequence.
+    fn visit_elements<V: Visitor<'a>>(
+        &mut self,
+        t: u8,
+        contents: &CStr,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.iter.enter_container(t, contents)?;
+        let v = visitor.visit_seq(Elements { de: &mut *self })?;
+        self.iter.exit_container()?;
+        Ok(v)
+    }
+
+    /// Enter the dictionary with the entry type `contents` and visit its entries as a map.
+    fn visit_entries<V: Visitor<'a>>(
+        &mut self,
+        contents: &CStr,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.iter.enter_container(b'a', contents)?;
+        let b = contents.to_bytes();
+        let entry = CString::new(&b[1..b.len() - 1]).unwrap();
+        let v = visitor.visit_map(Entries {
+            de: &mut *self,
+            entry,
+        })?;
+        self.iter.exit_container()?;
+        Ok(v)
+    }
+}
+
+/// The signature of a value of the type `t` with the `contents` given by `peek_type()`.
+fn type_signature(t: u8, contents: &CStr) -> String {
+    let contents = contents.to_string_lossy();
+    match t {
+        b'a' => format!("a{}", contents),
+        b'r' => format!("({})", contents),
+        b'e' => format!("{{{}}}", contents),
+        _ => (t as char).to_string(),
+    }
+}
+
+fn mismatch(expected: &str, t: u8, contents: &CStr) -> Error {
+    if t == 0 {
+        return de::Error::custom(format_args!(
+            "expected signature `{}`, found the end of the container",
+            expected
+        ));
+    }
+    de::Error::custom(format_args!(
+        "expected signature `{}`, found `{}`",
+        expected,
+        type_signature(t, contents)
+    ))
+}
+
+fn end() -> Error {
+    de::Error::custom("unexpected end of the container")
+}
+
+fn is_dict(contents: &CStr) -> bool {
+    contents.to_bytes().first() == Some(&b'{')
+}
+
+macro_rules! deserialize_basic {
+    ($($method:ident, $visit:ident, $t:ty, $sig:expr;)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            let v = self.basic::<$t>($sig)?;
+            visitor.$visit(v)
+        }
+    )*};
+}
+
+impl<'de, 'i> de::Deserializer<'de> for &mut Deserializer<'i, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let (t, contents) = self.peek()?;
+        match t {
+            b'y' => self.deserialize_u8(visitor),
+            b'b' => self.deserialize_bool(visitor),
+            b'n' => self.deserialize_i16(visitor),
+            b'q' => self.deserialize_u16(visitor),
+            b'i' => self.deserialize_i32(visitor),
+            b'u' => self.deserialize_u32(visitor),
+            b'x' => self.deserialize_i64(visitor),
+            b't' => self.deserialize_u64(visitor),
+            b'd' => self.deserialize_f64(visitor),
+            b's' | b'o' | b'g' => self.deserialize_str(visitor),
+            b'a' if is_dict(&contents) => self.visit_entries(&contents, visitor),
+            b'a' | b'r' => self.visit_elements(t, &contents, visitor),
+            b'v' => {
+                self.iter.enter_container(b'v', &contents)?;
+                let v = (&mut *self).deserialize_any(visitor)?;
+                self.iter.exit_container()?;
+                Ok(v)
+            }
+            0 => Err(end()),
+            _ => Err(de::Error::custom(format_args!(
+                "values of signature `{}` can't be deserialized",
+                t as char
+            ))),
+        }
+    }
+
+    deserialize_basic! {
+        deserialize_bool, visit_bool, bool, "b";
+        deserialize_i16, visit_i16, i16, "n";
+        deserialize_i32, visit_i32, i32, "i";
+        deserialize_i64, visit_i64, i64, "x";
+        deserialize_u8, visit_u8, u8, "y";
+        deserialize_u16, visit_u16, u16, "q";
+        deserialize_u32, visit_u32, u32, "u";
+        deserialize_u64, visit_u64, u64, "t";
+        deserialize_f32, visit_f64, f64, "d";
+        deserialize_f64, visit_f64, f64, "d";
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // serialized as `n`, the visitor checks the range
+        self.deserialize_i16(visitor)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let (variants, t, _) = self.expect("s", b"sog")?;
+        let s = match t {
+            b's' => self.iter.next::<&str>()?,
+            // object paths and signatures are ASCII
+            b'o' => self
+                .iter
+                .next::<&ObjectPath>()?
+                .map(|p| std::str::from_utf8(p.to_bytes()).unwrap()),
+            _ => self
+                .iter
+                .next::<&Signature>()?
+                .map(|g| std::str::from_utf8(g.to_bytes()).unwrap()),
+        };
+        let s = s.ok_or_else(end)?;
+        self.exit_variants(variants)?;
+        visitor.visit_borrowed_str(s)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let (variants, _, contents) = self.expect("ay", b"a")?;
+        if contents.to_bytes() != b"y" {
+            return Err(mismatch("ay", b'a', &contents));
+        }
+        let v = self.iter.read_array::<u8>()?.ok_or_else(end)?;
+        self.exit_variants(variants)?;
+        visitor.visit_borrowed_bytes(v)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        // `Some(v)` is serialized as `v`, so only a missing value is `None`
+        if self.peek()?.0 == 0 {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Error> {
+        Err(de::Error::custom("() has no D-Bus representation"))
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        _visitor: V,
+    ) -> Result<V::Value, Error> {
+        Err(de::Error::custom(format_args!(
+            "{} has no D-Bus representation",
+            name
+        )))
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let (variants, _, contents) = self.expect("a", b"a")?;
+        if is_dict(&contents) {
+            return Err(mismatch("a", b'a', &contents));
+        }
+        let v = self.visit_elements(b'a', &contents, visitor)?;
+        self.exit_variants(variants)?;
+        Ok(v)
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let (variants, _, contents) = self.expect("(..)", b"r")?;
+        let v = self.visit_elements(b'r', &contents, visitor)?;
+        self.exit_variants(variants)?;
+        Ok(v)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let (variants, _, contents) = self.expect("a{..}", b"a")?;
+        if !is_dict(&contents) {
+            return Err(mismatch("a{..}", b'a', &contents));
+        }
+        let v = self.visit_entries(&contents, visitor)?;
+        self.exit_variants(variants)?;
+        Ok(v)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let (variants, t, contents) = self.expect("(..)", b"ra")?;
+        let v = match t {
+            b'a' if is_dict(&contents) => self.visit_entries(&contents, visitor)?,
+            b'a' => return Err(mismatch("(..)", t, &contents)),
+            _ => self.visit_elements(t, &contents, visitor)?,
+        };
+        self.exit_variants(variants)?;
+        Ok(v)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let (variants, t, contents) = self.expect("u", b"ur")?;
+        let v = if t == b'u' {
+            let index = self.iter.next::<u32>()?.ok_or_else(end)?;
+            visitor.visit_enum(Enum {
+                de: &mut *self,
+                index,
+                data: false,
+            })?
+        } else {
+            self.iter.enter_container(b'r', &contents)?;
+            let index = self.basic::<u32>("u")?;
+            let v = visitor.visit_enum(Enum {
+                de: &mut *self,
+                index,
+                data: true,
+            })?;
+            self.iter.exit_container()?;
+            v
+        };
+        self.exit_variants(variants)?;
+        Ok(v)
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        Value::read(self.iter)?.ok_or_else(end)?;
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// The elements of an array or the fields of a struct.
+struct Elements<'d, 'i, 'de> {
+    de: &'d mut Deserializer<'i, 'de>,
+}
+
+impl<'de> de::SeqAccess<'de> for Elements<'_, '_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.de.peek()?.0 == 0 {
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+}
+
+/// The entries of a dictionary, each of which is entered when reading its key.
+struct Entries<'d, 'i, 'de> {
+    de: &'d mut Deserializer<'i, 'de>,
+    entry: CString,
+}
+
+impl<'de> de::MapAccess<'de> for Entries<'_, '_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        if !self.de.iter.enter_container(b'e', &self.entry)? {
+            return Ok(None);
+        }
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let v = seed.deserialize(&mut *self.de)?;
+        self.de.iter.exit_container()?;
+        Ok(v)
+    }
+}
+
+/// An enum variant: its index, followed by its data in the enclosing struct if `data` is set.
+struct Enum<'d, 'i, 'de> {
+    de: &'d mut Deserializer<'i, 'de>,
+    index: u32,
+    data: bool,
+}
+
+impl<'d, 'i, 'de> de::EnumAccess<'de> for Enum<'d, 'i, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self), Error> {
+        let index: de::value::U32Deserializer<Error> = self.index.into_deserializer();
+        Ok((seed.deserialize(index)?, self))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Enum<'_, '_, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        if self.data {
+            return Err(de::Error::custom(
+                "expected a variant without data, found `(u..)`",
+            ));
+        }
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        if !self.data {
+            return Err(mismatch("(u..)", b'u', Default::default()));
+        }
+        seed.deserialize(self.de)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, Error> {
+        if !self.data {
+            return Err(mismatch("(u..)", b'u', Default::default()));
+        }
+        de::Deserializer::deserialize_tuple(self.de, len, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.tuple_variant(fields.len(), visitor)
+    }
+}
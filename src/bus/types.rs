@@ -21,7 +21,13 @@
 use super::{MessageIter, MessageRef};
 use crate::bus;
 use ffi::{c_char, c_int};
-use std::ffi::CStr;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::ffi::{CStr, CString};
+use std::hash::{BuildHasher, Hash};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::ptr;
+use std::sync::Arc;
 use utf8_cstr::Utf8CStr;
 
 /**
@@ -48,14 +54,53 @@ pub unsafe trait SdBusMessageDirect {
     fn dbus_type() -> u8;
 }
 
+/**
+ * Provides the D-Bus type signature of a type, which is needed to place values of it in
+ * containers (like the element type of an array).
+ */
+pub trait SdBusSignature {
+    /// Append the signature of the type to `sig`
+    fn signature(sig: &mut String);
+}
+
+/// The signature of `T`, as passed to sd-bus when opening containers.
+///
+/// Fails with [`std::io::ErrorKind::InvalidInput`] if the signature isn't valid D-Bus, which for
+/// the types implemented here happens for maps whose keys aren't basic types (like
+/// `HashMap<Vec<u8>, u32>`), for types nested more than 32 deep and for signatures longer than
+/// 255 bytes.
+pub fn signature_of<T: SdBusSignature + ?Sized>() -> crate::Result<bus::SignatureBuf> {
+    let mut sig = String::new();
+    T::signature(&mut sig);
+    bus::SignatureBuf::new(sig.as_str()).map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid signature `{}`: {}", sig, e),
+        )
+    })
+}
+
 /**
  * Allows types to provide a conversion to a dbus message
  */
 pub trait ToSdBusMessage {
-    // type signature?
-    // function to do append?
     // Do we need a ToOwned bit? Check ToSql
     fn to_message(&self, m: &mut MessageRef) -> crate::Result<()>;
+
+    /// Append `v` as an array, used for `&[Self]` and `Vec<Self>`.
+    ///
+    /// By default the elements are appended one by one into an array container. Fixed size basic
+    /// types instead copy the whole slice into the message at once.
+    fn to_message_array(v: &[Self], m: &mut MessageRef) -> crate::Result<()>
+    where
+        Self: Sized + SdBusSignature,
+    {
+        m.open_container(b'a', &signature_of::<Self>()?)?;
+        for e in v {
+            e.to_message(m)?;
+        }
+        m.close_container()
+    }
 }
 
 /**
@@ -65,25 +110,72 @@ pub trait ToSdBusMessage {
  * may need to add a `from_message_to()` that takes a reference, much like `Clone`.
  */
 pub trait FromSdBusMessage<'a> {
-    fn from_message(m: &'a mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
     where
         Self: Sized;
+
+    /// Read an array of `Self`, used for `Vec<Self>`.
+    ///
+    /// By default the elements are read one by one from an array container. Fixed size basic
+    /// types instead copy the whole array out of the message at once.
+    fn from_message_array(m: &mut MessageIter<'a>) -> crate::Result<Option<Vec<Self>>>
+    where
+        Self: Sized + SdBusSignature,
+    {
+        if !m.enter_container(b'a', &signature_of::<Self>()?)? {
+            return Ok(None);
+        }
+        let mut v = Vec::new();
+        while let Some(e) = Self::from_message(m)? {
+            v.push(e);
+        }
+        m.exit_container()?;
+        Ok(Some(v))
+    }
+}
+
+impl<T: SdBusMessageDirect> SdBusSignature for T {
+    fn signature(sig: &mut String) {
+        sig.push(T::dbus_type() as char);
+    }
 }
 
 impl<T: SdBusMessageDirect> ToSdBusMessage for T {
     fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
         unsafe { m.append_basic_raw(Self::dbus_type(), self as *const _ as *const _) }
     }
+
+    fn to_message_array(v: &[Self], m: &mut MessageRef) -> crate::Result<()>
+    where
+        Self: Sized + SdBusSignature,
+    {
+        m.append_array(v)
+    }
 }
 
 impl<'a, T: SdBusMessageDirect + 'a> FromSdBusMessage<'a> for T {
-    fn from_message(m: &'a mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
     where
         Self: Sized,
     {
         let t = Self::dbus_type();
         unsafe { m.read_basic_raw(t, |x| x) }
     }
+
+    fn from_message_array(m: &mut MessageIter<'a>) -> crate::Result<Option<Vec<Self>>>
+    where
+        Self: Sized + SdBusSignature,
+    {
+        Ok(m.read_array::<T>()?.map(|a| {
+            let mut v = Vec::with_capacity(a.len());
+            // direct types are plain values, which sd-bus itself copies around bytewise
+            unsafe {
+                ptr::copy_nonoverlapping(a.as_ptr(), v.as_mut_ptr(), a.len());
+                v.set_len(a.len());
+            }
+            v
+        }))
+    }
 }
 
 // macro_rules! msg_basic {
@@ -112,11 +204,27 @@ impl<'a, T: SdBusMessageDirect + 'a> FromSdBusMessage<'a> for T {
 //     }
 // }
 
+/// Size of the value `sd_bus_message_read_basic()` and `sd_bus_message_append_basic()` access
+/// for the fixed size basic type `dbus_type`.
+const fn basic_type_size(dbus_type: u8) -> usize {
+    match dbus_type {
+        b'y' => 1,
+        b'n' | b'q' => 2,
+        b'b' | b'i' | b'u' | b'h' => 4,
+        b'x' | b't' | b'd' => 8,
+        _ => 0,
+    }
+}
+
 macro_rules! msg_basic {
     ($typ:ty : $dbus_type:expr) => {
         unsafe impl SdBusMessageDirect for $typ {
             fn dbus_type() -> u8 { $dbus_type }
         }
+
+        // sd-bus copies exactly the size of the wire type through the pointer we give it, on
+        // every target
+        const _: () = assert!(std::mem::size_of::<$typ>() == basic_type_size($dbus_type));
     };
 
     ($typ:ty : $dbus_type:expr , $($rest:tt)* ) => {
@@ -136,6 +244,12 @@ msg_basic! {
     f64: b'd'
 }
 
+impl SdBusSignature for bool {
+    fn signature(sig: &mut String) {
+        sig.push('b');
+    }
+}
+
 impl ToSdBusMessage for bool {
     fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
         let i: c_int = if *self { 1 } else { 0 };
@@ -156,9 +270,18 @@ impl<'a> FromSdBusMessage<'a> for bool {
 /**
  * A basic wrapper that simply ensures we send a Fd via the dbus file descriptor mechanisms rather
  * than as a integer
+ *
+ * Prefer [`BorrowedFd`] and [`OwnedFd`], which also take care of the ownership of the
+ * descriptor: a `UnixFd` read from a message is only valid as long as the message.
  */
 pub struct UnixFd(pub c_int);
 
+impl SdBusSignature for UnixFd {
+    fn signature(sig: &mut String) {
+        sig.push('h');
+    }
+}
+
 impl ToSdBusMessage for UnixFd {
     fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
         let i: c_int = self.0;
@@ -168,7 +291,7 @@ impl ToSdBusMessage for UnixFd {
 }
 
 impl<'a> FromSdBusMessage<'a> for UnixFd {
-    fn from_message(m: &'a mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
     where
         Self: Sized,
     {
@@ -176,6 +299,126 @@ impl<'a> FromSdBusMessage<'a> for UnixFd {
     }
 }
 
+// File descriptors (`h`) can only be passed over connections which negotiated it, which sd-bus
+// does by default (`sd_bus_negotiate_fds()`), and which support it (not remote ones). Check
+// `BusCapabilities::fds` first, appending fails otherwise.
+//
+// Appending duplicates the descriptor into the message. Reading one borrows the descriptor owned
+// by the message, or duplicates it (with `O_CLOEXEC`) into an `OwnedFd` which outlives it.
+impl SdBusSignature for BorrowedFd<'_> {
+    fn signature(sig: &mut String) {
+        sig.push('h');
+    }
+}
+
+impl ToSdBusMessage for BorrowedFd<'_> {
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        UnixFd(self.as_raw_fd()).to_message(m)
+    }
+}
+
+impl<'a> FromSdBusMessage<'a> for BorrowedFd<'a> {
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        // the message owns the descriptor and keeps it open until it is freed
+        Ok(m.next::<UnixFd>()?
+            .map(|fd| unsafe { BorrowedFd::borrow_raw(fd.0) }))
+    }
+}
+
+impl SdBusSignature for OwnedFd {
+    fn signature(sig: &mut String) {
+        sig.push('h');
+    }
+}
+
+impl ToSdBusMessage for OwnedFd {
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        self.as_fd().to_message(m)
+    }
+}
+
+impl<'a> FromSdBusMessage<'a> for OwnedFd {
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        match m.next::<BorrowedFd<'a>>()? {
+            Some(fd) => Ok(Some(fd.try_clone_to_owned()?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A file descriptor in a [`Value`], shared by the clones of the value.
+///
+/// Reading a [`Value`] duplicates descriptors (with `O_CLOEXEC`), so they outlive the message.
+/// Two `SharedFd`s are equal if they hold the same descriptor.
+#[derive(Debug, Clone)]
+pub struct SharedFd(Arc<OwnedFd>);
+
+impl SharedFd {
+    /// Take the descriptor out, or return `self` if it is still shared with other clones.
+    pub fn try_into_owned(self) -> Result<OwnedFd, Self> {
+        Arc::try_unwrap(self.0).map_err(SharedFd)
+    }
+}
+
+impl From<OwnedFd> for SharedFd {
+    fn from(fd: OwnedFd) -> Self {
+        SharedFd(Arc::new(fd))
+    }
+}
+
+impl AsFd for SharedFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl AsRawFd for SharedFd {
+    fn as_raw_fd(&self) -> c_int {
+        self.0.as_raw_fd()
+    }
+}
+
+impl PartialEq for SharedFd {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_raw_fd() == other.as_raw_fd()
+    }
+}
+
+impl Eq for SharedFd {}
+
+impl SdBusSignature for SharedFd {
+    fn signature(sig: &mut String) {
+        sig.push('h');
+    }
+}
+
+impl ToSdBusMessage for SharedFd {
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        self.as_fd().to_message(m)
+    }
+}
+
+impl<'a> FromSdBusMessage<'a> for SharedFd {
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        Ok(m.next::<OwnedFd>()?.map(SharedFd::from))
+    }
+}
+
+impl SdBusSignature for &bus::ObjectPath {
+    fn signature(sig: &mut String) {
+        sig.push('o');
+    }
+}
+
 impl<'a> ToSdBusMessage for &'a bus::ObjectPath {
     fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
         unsafe { m.append_basic_raw(b'o', self.as_ptr() as *const _) }?;
@@ -189,7 +432,7 @@ impl<'a> ToSdBusMessage for &'a bus::ObjectPath {
 //
 // If we could use &MessageRef instead this could be useful.
 impl<'a> FromSdBusMessage<'a> for &'a bus::ObjectPath {
-    fn from_message(m: &'a mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
     where
         Self: Sized,
     {
@@ -201,6 +444,40 @@ impl<'a> FromSdBusMessage<'a> for &'a bus::ObjectPath {
     }
 }
 
+const _: () = assert!(std::mem::size_of::<c_int>() == basic_type_size(b'b'));
+const _: () = assert!(std::mem::size_of::<c_int>() == basic_type_size(b'h'));
+
+impl SdBusSignature for &bus::Signature {
+    fn signature(sig: &mut String) {
+        sig.push('g');
+    }
+}
+
+impl ToSdBusMessage for &bus::Signature {
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        unsafe { m.append_basic_raw(b'g', self.as_ptr() as *const _) }
+    }
+}
+
+impl<'a> FromSdBusMessage<'a> for &'a bus::Signature {
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        unsafe {
+            m.read_basic_raw(b'g', |x: *const c_char| {
+                bus::Signature::from_ptr_unchecked(x)
+            })
+        }
+    }
+}
+
+impl SdBusSignature for &Utf8CStr {
+    fn signature(sig: &mut String) {
+        sig.push('s');
+    }
+}
+
 impl<'a> ToSdBusMessage for &'a Utf8CStr {
     fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
         unsafe { m.append_basic_raw(b's', self.as_ptr() as *const _) }
@@ -208,7 +485,7 @@ impl<'a> ToSdBusMessage for &'a Utf8CStr {
 }
 
 impl<'a> FromSdBusMessage<'a> for &'a Utf8CStr {
-    fn from_message(m: &'a mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
     where
         Self: Sized,
     {
@@ -220,10 +497,855 @@ impl<'a> FromSdBusMessage<'a> for &'a Utf8CStr {
     }
 }
 
+// Rust strings aren't nul terminated, so appending them copies them first. Use `&Utf8CStr` to
+// avoid the copy. Strings containing nul bytes fail to append with `InvalidInput`.
+impl SdBusSignature for &str {
+    fn signature(sig: &mut String) {
+        sig.push('s');
+    }
+}
+
+impl ToSdBusMessage for &str {
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        append_str(m, self)
+    }
+}
+
+impl<'a> FromSdBusMessage<'a> for &'a str {
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        Ok(m.next::<&Utf8CStr>()?.map(|s| s.as_ref()))
+    }
+}
+
+impl SdBusSignature for String {
+    fn signature(sig: &mut String) {
+        sig.push('s');
+    }
+}
+
+impl ToSdBusMessage for String {
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        append_str(m, self)
+    }
+}
+
+impl<'a> FromSdBusMessage<'a> for String {
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        Ok(m.next::<&str>()?.map(|s| s.to_owned()))
+    }
+}
+
+// Arrays of fixed size basic types (like `ay`) are copied in and out of the message in one go,
+// others go element by element through an array container. See `ToSdBusMessage::to_message_array()`.
+impl<T: SdBusSignature> SdBusSignature for [T] {
+    fn signature(sig: &mut String) {
+        sig.push('a');
+        T::signature(sig);
+    }
+}
+
+impl<T: SdBusSignature> SdBusSignature for &[T] {
+    fn signature(sig: &mut String) {
+        <[T]>::signature(sig);
+    }
+}
+
+impl<T: SdBusSignature> SdBusSignature for Vec<T> {
+    fn signature(sig: &mut String) {
+        <[T]>::signature(sig);
+    }
+}
+
+impl<T: ToSdBusMessage + SdBusSignature> ToSdBusMessage for &[T] {
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        T::to_message_array(self, m)
+    }
+}
+
+impl<T: ToSdBusMessage + SdBusSignature> ToSdBusMessage for Vec<T> {
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        T::to_message_array(self, m)
+    }
+}
+
+/// Only fixed size basic types can be borrowed from the message, use `Vec<T>` for others.
+impl<'a, T: SdBusMessageDirect + 'a> FromSdBusMessage<'a> for &'a [T] {
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        m.read_array()
+    }
+}
+
+impl<'a, T: FromSdBusMessage<'a> + SdBusSignature> FromSdBusMessage<'a> for Vec<T> {
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        T::from_message_array(m)
+    }
+}
+
+// Maps are arrays of dict entries (`a{kv}`). Keys must be basic types, which sd-bus checks when
+// the container is opened.
+fn dict_entry_signature<K: SdBusSignature + ?Sized, V: SdBusSignature + ?Sized>(sig: &mut String) {
+    K::signature(sig);
+    V::signature(sig);
+}
+
+fn map_signature<K: SdBusSignature + ?Sized, V: SdBusSignature + ?Sized>(sig: &mut String) {
+    sig.push_str("a{");
+    dict_entry_signature::<K, V>(sig);
+    sig.push('}');
+}
+
+fn append_map<'b, K, V, I>(m: &mut MessageRef, entries: I) -> crate::Result<()>
+where
+    K: ToSdBusMessage + SdBusSignature + 'b,
+    V: ToSdBusMessage + SdBusSignature + 'b,
+    I: Iterator<Item = (&'b K, &'b V)>,
+{
+    let mut entry = String::new();
+    dict_entry_signature::<K, V>(&mut entry);
+    let entry = CString::new(entry).unwrap();
+    let array = CString::new(format!("{{{}}}", entry.to_str().unwrap())).unwrap();
+
+    m.open_container(b'a', &array)?;
+    for (k, v) in entries {
+        m.open_container(b'e', &entry)?;
+        k.to_message(m)?;
+        v.to_message(m)?;
+        m.close_container()?;
+    }
+    m.close_container()
+}
+
+fn read_map<'a, K, V, M>(m: &mut MessageIter<'a>) -> crate::Result<Option<M>>
+where
+    K: FromSdBusMessage<'a> + SdBusSignature,
+    V: FromSdBusMessage<'a> + SdBusSignature,
+    M: Default + Extend<(K, V)>,
+{
+    let mut entry = String::new();
+    dict_entry_signature::<K, V>(&mut entry);
+    let entry = CString::new(entry).unwrap();
+    let array = CString::new(format!("{{{}}}", entry.to_str().unwrap())).unwrap();
+
+    if !m.enter_container(b'a', &array)? {
+        return Ok(None);
+    }
+    let mut map = M::default();
+    while m.enter_container(b'e', &entry)? {
+        let k = K::from_message(m)?.ok_or_else(bad_message)?;
+        let v = V::from_message(m)?.ok_or_else(bad_message)?;
+        m.exit_container()?;
+        map.extend(Some((k, v)));
+    }
+    m.exit_container()?;
+    Ok(Some(map))
+}
+
+impl<K: SdBusSignature, V: SdBusSignature, S> SdBusSignature for HashMap<K, V, S> {
+    fn signature(sig: &mut String) {
+        map_signature::<K, V>(sig);
+    }
+}
+
+impl<K: SdBusSignature, V: SdBusSignature> SdBusSignature for BTreeMap<K, V> {
+    fn signature(sig: &mut String) {
+        map_signature::<K, V>(sig);
+    }
+}
+
+impl<K, V, S> ToSdBusMessage for HashMap<K, V, S>
+where
+    K: ToSdBusMessage + SdBusSignature,
+    V: ToSdBusMessage + SdBusSignature,
+{
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        append_map(m, self.iter())
+    }
+}
+
+impl<K, V, S> ToSdBusMessage for &HashMap<K, V, S>
+where
+    K: ToSdBusMessage + SdBusSignature,
+    V: ToSdBusMessage + SdBusSignature,
+{
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        append_map(m, self.iter())
+    }
+}
+
+impl<K, V> ToSdBusMessage for BTreeMap<K, V>
+where
+    K: ToSdBusMessage + SdBusSignature,
+    V: ToSdBusMessage + SdBusSignature,
+{
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        append_map(m, self.iter())
+    }
+}
+
+impl<K, V> ToSdBusMessage for &BTreeMap<K, V>
+where
+    K: ToSdBusMessage + SdBusSignature,
+    V: ToSdBusMessage + SdBusSignature,
+{
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        append_map(m, self.iter())
+    }
+}
+
+impl<'a, K, V, S> FromSdBusMessage<'a> for HashMap<K, V, S>
+where
+    K: FromSdBusMessage<'a> + SdBusSignature + Eq + Hash,
+    V: FromSdBusMessage<'a> + SdBusSignature,
+    S: BuildHasher + Default,
+{
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        read_map(m)
+    }
+}
+
+impl<'a, K, V> FromSdBusMessage<'a> for BTreeMap<K, V>
+where
+    K: FromSdBusMessage<'a> + SdBusSignature + Ord,
+    V: FromSdBusMessage<'a> + SdBusSignature,
+{
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        read_map(m)
+    }
+}
+
+/// Append `s` as a D-Bus string, copying it to add the nul terminator.
+pub(crate) fn append_str(m: &mut MessageRef, s: &str) -> crate::Result<()> {
+    let s =
+        CString::new(s).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    unsafe { m.append_basic_raw(b's', s.as_ptr() as *const _) }
+}
+
+/**
+ * Allows a group of values to be appended to a message as consecutive arguments, rather than as
+ * a single value.
+ *
+ * This is implemented for tuples of up to 12 elements (and `()`, which appends nothing) so
+ * multiple arguments can be appended with one [`MessageRef::append_tuple()`] call.
+ */
+pub trait ToSdBusMessageArgs {
+    fn to_message_args(&self, m: &mut MessageRef) -> crate::Result<()>;
+}
+
+impl ToSdBusMessageArgs for () {
+    fn to_message_args(&self, _m: &mut MessageRef) -> crate::Result<()> {
+        Ok(())
+    }
+}
+
+// Tuples are either appended as consecutive arguments (`ToSdBusMessageArgs`), or as a single
+// D-Bus struct (`ToSdBusMessage`, `FromSdBusMessage`), which lets them be placed in containers
+// (`a(sv)`).
+macro_rules! tuples {
+    ($($t:ident $i:tt),+) => {
+        impl<$($t: ToSdBusMessage),+> ToSdBusMessageArgs for ($($t,)+) {
+            fn to_message_args(&self, m: &mut MessageRef) -> crate::Result<()> {
+                $(self.$i.to_message(m)?;)+
+                Ok(())
+            }
+        }
+
+        impl<$($t: SdBusSignature),+> SdBusSignature for ($($t,)+) {
+            fn signature(sig: &mut String) {
+                sig.push('(');
+                $($t::signature(sig);)+
+                sig.push(')');
+            }
+        }
+
+        impl<$($t: ToSdBusMessage + SdBusSignature),+> ToSdBusMessage for ($($t,)+) {
+            fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+                m.open_container(b'r', &struct_contents::<Self>())?;
+                $(self.$i.to_message(m)?;)+
+                m.close_container()
+            }
+        }
+
+        impl<'a, $($t: FromSdBusMessage<'a> + SdBusSignature),+> FromSdBusMessage<'a>
+            for ($($t,)+)
+        {
+            fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+            where
+                Self: Sized,
+            {
+                if !m.enter_container(b'r', &struct_contents::<Self>())? {
+                    return Ok(None);
+                }
+                let v = ($($t::from_message(m)?.ok_or_else(bad_message)?,)+);
+                m.exit_container()?;
+                Ok(Some(v))
+            }
+        }
+    };
+}
+
+/// The signature of the members of the struct `T`, without the enclosing parentheses.
+fn struct_contents<T: SdBusSignature>() -> CString {
+    let mut sig = String::new();
+    T::signature(&mut sig);
+    CString::new(&sig[1..sig.len() - 1]).unwrap()
+}
+
+tuples!(A 0);
+tuples!(A 0, B 1);
+tuples!(A 0, B 1, C 2);
+tuples!(A 0, B 1, C 2, D 3);
+tuples!(A 0, B 1, C 2, D 3, E 4);
+tuples!(A 0, B 1, C 2, D 3, E 4, F 5);
+tuples!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+tuples!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+tuples!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+tuples!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+tuples!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+tuples!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+
+/// A value of any D-Bus type, for reading and writing data whose type is only known at runtime
+/// (like the values of properties, in generic property browsers).
+///
+/// Dictionaries (`a{..}`) are read as [`Value::Dict`] rather than as arrays of dict entries.
+///
+/// Arrays and dictionaries carry the signature of their elements, so empty ones still have a
+/// type.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Value {
+    Byte(u8),
+    Bool(bool),
+    Int16(i16),
+    UInt16(u16),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    Double(f64),
+    String(String),
+    ObjectPath(bus::ObjectPathBuf),
+    Signature(String),
+    Fd(SharedFd),
+    /// An array of `elements` of the single complete type `element`, like `s` for `as`.
+    Array {
+        element: String,
+        elements: Vec<Value>,
+    },
+    Struct(Vec<Value>),
+    /// A dictionary with keys of the basic type `key` and values of the single complete type
+    /// `value`, like `s` and `v` for `a{sv}`.
+    Dict {
+        key: String,
+        value: String,
+        entries: Vec<(Value, Value)>,
+    },
+    Variant(Box<Value>),
+}
+
+impl Value {
+    /// Read a complete value of whatever type is next in `m`, returning `None` at the end of the
+    /// current container.
+    ///
+    /// Unix file descriptors (`h`) are duplicated, see [`SharedFd`].
+    pub fn read(m: &mut MessageIter<'_>) -> crate::Result<Option<Value>> {
+        let (t, contents) = m.peek_type()?;
+        if t == 0 {
+            return Ok(None);
+        }
+        let contents = CString::new(contents).unwrap();
+
+        let v = match t as u8 {
+            b'y' => m.next::<u8>()?.map(Value::Byte),
+            b'b' => m.next::<bool>()?.map(Value::Bool),
+            b'n' => m.next::<i16>()?.map(Value::Int16),
+            b'q' => m.next::<u16>()?.map(Value::UInt16),
+            b'i' => m.next::<i32>()?.map(Value::Int32),
+            b'u' => m.next::<u32>()?.map(Value::UInt32),
+            b'x' => m.next::<i64>()?.map(Value::Int64),
+            b't' => m.next::<u64>()?.map(Value::UInt64),
+            b'd' => m.next::<f64>()?.map(Value::Double),
+            b's' => m.next::<String>()?.map(Value::String),
+            b'o' => m
+                .next::<&bus::ObjectPath>()?
+                .map(|p| Value::ObjectPath(p.into())),
+            b'g' => m
+                .next::<&bus::Signature>()?
+                .map(|g| Value::Signature(g.to_string())),
+            b'h' => m.next::<SharedFd>()?.map(Value::Fd),
+            b'a' if contents.as_bytes().first() == Some(&b'{') => {
+                m.enter_container(b'a', &contents)?;
+                let mut entries = Vec::new();
+                // the contents of each entry are the signature without the braces
+                let b = contents.as_bytes();
+                let entry = CString::new(&b[1..b.len() - 1]).unwrap();
+                while m.enter_container(b'e', &entry)? {
+                    let k = Value::read(m)?.ok_or_else(bad_message)?;
+                    let v = Value::read(m)?.ok_or_else(bad_message)?;
+                    m.exit_container()?;
+                    entries.push((k, v));
+                }
+                m.exit_container()?;
+                // keys are basic types, with a single character signature
+                let entry = entry.to_str().map_err(|_| bad_message())?;
+                Some(Value::Dict {
+                    key: entry[..1].to_owned(),
+                    value: entry[1..].to_owned(),
+                    entries,
+                })
+            }
+            b'a' | b'r' => {
+                m.enter_container(t as u8, &contents)?;
+                let mut elements = Vec::new();
+                while let Some(e) = Value::read(m)? {
+                    elements.push(e);
+                }
+                m.exit_container()?;
+                Some(if t as u8 == b'a' {
+                    Value::Array {
+                        element: contents.into_string().map_err(|_| bad_message())?,
+                        elements,
+                    }
+                } else {
+                    Value::Struct(elements)
+                })
+            }
+            b'v' => {
+                m.enter_container(b'v', &contents)?;
+                let v = Value::read(m)?.ok_or_else(bad_message)?;
+                m.exit_container()?;
+                Some(Value::Variant(Box::new(v)))
+            }
+            _ => return Err(std::io::Error::from_raw_os_error(libc::EOPNOTSUPP)),
+        };
+        Ok(v)
+    }
+}
+
+impl Value {
+    /// Append the signature of the value to `sig`.
+    ///
+    /// Fails with `EINVAL` if an array or dictionary has an empty element signature.
+    pub fn signature(&self, sig: &mut String) -> crate::Result<()> {
+        match self {
+            Value::Byte(_) => sig.push('y'),
+            Value::Bool(_) => sig.push('b'),
+            Value::Int16(_) => sig.push('n'),
+            Value::UInt16(_) => sig.push('q'),
+            Value::Int32(_) => sig.push('i'),
+            Value::UInt32(_) => sig.push('u'),
+            Value::Int64(_) => sig.push('x'),
+            Value::UInt64(_) => sig.push('t'),
+            Value::Double(_) => sig.push('d'),
+            Value::String(_) => sig.push('s'),
+            Value::ObjectPath(_) => sig.push('o'),
+            Value::Signature(_) => sig.push('g'),
+            Value::Fd(_) => sig.push('h'),
+            Value::Array { element, .. } => {
+                if element.is_empty() {
+                    return Err(invalid());
+                }
+                sig.push('a');
+                sig.push_str(element);
+            }
+            Value::Struct(fields) => {
+                sig.push('(');
+                for f in fields {
+                    f.signature(sig)?;
+                }
+                sig.push(')');
+            }
+            Value::Dict { key, value, .. } => {
+                if key.is_empty() || value.is_empty() {
+                    return Err(invalid());
+                }
+                sig.push_str("a{");
+                sig.push_str(key);
+                sig.push_str(value);
+                sig.push('}');
+            }
+            Value::Variant(_) => sig.push('v'),
+        }
+        Ok(())
+    }
+
+    /// Append the value to `m`, with the type given by [`Value::signature()`].
+    pub fn append(&self, m: &mut MessageRef) -> crate::Result<()> {
+        match self {
+            Value::Byte(v) => m.append(*v),
+            Value::Bool(v) => m.append(*v),
+            Value::Int16(v) => m.append(*v),
+            Value::UInt16(v) => m.append(*v),
+            Value::Int32(v) => m.append(*v),
+            Value::UInt32(v) => m.append(*v),
+            Value::Int64(v) => m.append(*v),
+            Value::UInt64(v) => m.append(*v),
+            Value::Double(v) => m.append(*v),
+            Value::String(v) => append_str(m, v),
+            Value::ObjectPath(v) => m.append(&**v),
+            Value::Signature(v) => {
+                let v = CString::new(v.as_str()).map_err(|_| invalid())?;
+                unsafe { m.append_basic_raw(b'g', v.as_ptr() as *const _) }
+            }
+            Value::Fd(v) => m.append(v.as_fd()),
+            Value::Array { element, elements } => {
+                if element.is_empty() {
+                    return Err(invalid());
+                }
+                m.open_container(
+                    b'a',
+                    &CString::new(element.as_str()).map_err(|_| invalid())?,
+                )?;
+                for e in elements {
+                    e.append(m)?;
+                }
+                m.close_container()
+            }
+            Value::Struct(fields) => {
+                m.open_container(b'r', &struct_contents_of(fields.iter())?)?;
+                for f in fields {
+                    f.append(m)?;
+                }
+                m.close_container()
+            }
+            Value::Dict {
+                key,
+                value,
+                entries,
+            } => {
+                if key.is_empty() || value.is_empty() {
+                    return Err(invalid());
+                }
+                let entry = CString::new(format!("{}{}", key, value)).map_err(|_| invalid())?;
+                let array = CString::new(format!("{{{}{}}}", key, value)).unwrap();
+                m.open_container(b'a', &array)?;
+                for (k, v) in entries {
+                    m.open_container(b'e', &entry)?;
+                    k.append(m)?;
+                    v.append(m)?;
+                    m.close_container()?;
+                }
+                m.close_container()
+            }
+            Value::Variant(v) => {
+                m.open_container(b'v', &contents_of(v)?)?;
+                v.append(m)?;
+                m.close_container()
+            }
+        }
+    }
+
+    /// Append the value to `m` as the single complete type `signature`, rather than as the type
+    /// given by [`Value::signature()`].
+    ///
+    /// The value only needs to fit the signature: integers are converted to the integer type (or
+    /// double) given if they are in its range, strings are accepted as object paths and
+    /// signatures, values other than variants are wrapped in one for `v`, and the elements of
+    /// arrays and dictionaries are converted to the element type given by `signature`.
+    ///
+    /// Fails with `EINVAL` if the value doesn't fit.
+    pub fn append_as(&self, m: &mut MessageRef, signature: &bus::Signature) -> crate::Result<()> {
+        let sig = signature.to_bytes();
+        if sig.is_empty() || type_end(sig, 0) != sig.len() {
+            return Err(invalid());
+        }
+        self.append_as_type(m, sig)
+    }
+
+    fn append_as_type(&self, m: &mut MessageRef, sig: &[u8]) -> crate::Result<()> {
+        match (sig[0], self) {
+            (b'v', v) => {
+                let v = match v {
+                    Value::Variant(inner) => &**inner,
+                    v => v,
+                };
+                m.open_container(b'v', &contents_of(v)?)?;
+                v.append(m)?;
+                m.close_container()
+            }
+            (b'a', Value::Dict { entries, .. }) if sig[1] == b'{' => {
+                let entry = &sig[2..sig.len() - 1];
+                let key_end = type_end(entry, 0);
+                m.open_container(b'a', &cstring(&sig[1..]))?;
+                for (k, v) in entries {
+                    m.open_container(b'e', &cstring(entry))?;
+                    k.append_as_type(m, &entry[..key_end])?;
+                    v.append_as_type(m, &entry[key_end..])?;
+                    m.close_container()?;
+                }
+                m.close_container()
+            }
+            (b'a', Value::Array { elements, .. }) if sig[1] != b'{' => {
+                m.open_container(b'a', &cstring(&sig[1..]))?;
+                for e in elements {
+                    e.append_as_type(m, &sig[1..])?;
+                }
+                m.close_container()
+            }
+            (b'(', Value::Struct(fields)) => {
+                let contents = &sig[1..sig.len() - 1];
+                m.open_container(b'r', &cstring(contents))?;
+                let mut i = 0;
+                for f in fields {
+                    if i == contents.len() {
+                        return Err(invalid());
+                    }
+                    let end = type_end(contents, i);
+                    f.append_as_type(m, &contents[i..end])?;
+                    i = end;
+                }
+                if i != contents.len() {
+                    return Err(invalid());
+                }
+                m.close_container()
+            }
+            (b'o', Value::String(s)) => {
+                let path = bus::ObjectPathBuf::new(s.as_str()).map_err(|_| invalid())?;
+                m.append(&*path)
+            }
+            (b'g', Value::String(s)) => Value::Signature(s.clone()).append(m),
+            (b'd', v) => match v {
+                Value::Double(d) => m.append(*d),
+                _ => m.append(v.as_integer().ok_or_else(invalid)? as f64),
+            },
+            (b'y', v) => m.append(integer::<u8>(v)?),
+            (b'n', v) => m.append(integer::<i16>(v)?),
+            (b'q', v) => m.append(integer::<u16>(v)?),
+            (b'i', v) => m.append(integer::<i32>(v)?),
+            (b'u', v) => m.append(integer::<u32>(v)?),
+            (b'x', v) => m.append(integer::<i64>(v)?),
+            (b't', v) => m.append(integer::<u64>(v)?),
+            (_, v) => {
+                let mut own = String::new();
+                v.signature(&mut own)?;
+                if own.as_bytes() != sig {
+                    return Err(invalid());
+                }
+                v.append(m)
+            }
+        }
+    }
+
+    fn as_integer(&self) -> Option<i128> {
+        Some(match *self {
+            Value::Byte(v) => v.into(),
+            Value::Int16(v) => v.into(),
+            Value::UInt16(v) => v.into(),
+            Value::Int32(v) => v.into(),
+            Value::UInt32(v) => v.into(),
+            Value::Int64(v) => v.into(),
+            Value::UInt64(v) => v.into(),
+            _ => return None,
+        })
+    }
+}
+
+fn integer<T: TryFrom<i128>>(v: &Value) -> crate::Result<T> {
+    T::try_from(v.as_integer().ok_or_else(invalid)?).map_err(|_| invalid())
+}
+
+/// The index following the complete type starting at `i` of the valid signature `sig`.
+fn type_end(sig: &[u8], i: usize) -> usize {
+    match sig[i] {
+        b'a' => type_end(sig, i + 1),
+        b'(' | b'{' => {
+            let mut j = i + 1;
+            while sig[j] != b')' && sig[j] != b'}' {
+                j = type_end(sig, j);
+            }
+            j + 1
+        }
+        _ => i + 1,
+    }
+}
+
+fn cstring(sig: &[u8]) -> CString {
+    CString::new(sig).unwrap()
+}
+
+fn contents_of(v: &Value) -> crate::Result<CString> {
+    let mut sig = String::new();
+    v.signature(&mut sig)?;
+    Ok(CString::new(sig).unwrap())
+}
+
+fn struct_contents_of<'v>(fields: impl Iterator<Item = &'v Value>) -> crate::Result<CString> {
+    let mut sig = String::new();
+    for f in fields {
+        f.signature(&mut sig)?;
+    }
+    Ok(CString::new(sig).unwrap())
+}
+
+fn invalid() -> std::io::Error {
+    std::io::Error::from_raw_os_error(libc::EINVAL)
+}
+
+impl ToSdBusMessage for Value {
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        self.append(m)
+    }
+}
+
+impl<'a> FromSdBusMessage<'a> for Value {
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        Value::read(m)
+    }
+}
+
+/// A value of the statically known type `T` wrapped in a variant (`v`), like the values of
+/// properties, or those of the `a{sv}` maps often used for options.
+///
+/// Reading a variant containing any other type than `T` fails with `ENXIO`, use [`AnyVariant`]
+/// when the type isn't known in advance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Variant<T>(pub T);
+
+impl<T> SdBusSignature for Variant<T> {
+    fn signature(sig: &mut String) {
+        sig.push('v');
+    }
+}
+
+impl<T: ToSdBusMessage + SdBusSignature> ToSdBusMessage for Variant<T> {
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        m.open_container(b'v', &signature_of::<T>()?)?;
+        self.0.to_message(m)?;
+        m.close_container()
+    }
+}
+
+impl<'a, T: FromSdBusMessage<'a> + SdBusSignature> FromSdBusMessage<'a> for Variant<T> {
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        if !m.enter_container(b'v', &signature_of::<T>()?)? {
+            return Ok(None);
+        }
+        let v = T::from_message(m)?.ok_or_else(bad_message)?;
+        m.exit_container()?;
+        Ok(Some(Variant(v)))
+    }
+}
+
+/// A variant (`v`) containing a value of any type, which is only known at runtime.
+///
+/// When appended, the signature of the variant is taken from the value (see
+/// [`Value::signature()`]).
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnyVariant(pub Value);
+
+impl SdBusSignature for AnyVariant {
+    fn signature(sig: &mut String) {
+        sig.push('v');
+    }
+}
+
+impl ToSdBusMessage for AnyVariant {
+    fn to_message(&self, m: &mut MessageRef) -> crate::Result<()> {
+        m.open_container(b'v', &contents_of(&self.0)?)?;
+        self.0.append(m)?;
+        m.close_container()
+    }
+}
+
+impl<'a> FromSdBusMessage<'a> for AnyVariant {
+    fn from_message(m: &mut MessageIter<'a>) -> crate::Result<Option<Self>>
+    where
+        Self: Sized,
+    {
+        match m.peek_type()?.0 as u8 {
+            0 => Ok(None),
+            b'v' => match Value::read(m)? {
+                Some(Value::Variant(v)) => Ok(Some(AnyVariant(*v))),
+                _ => Err(bad_message()),
+            },
+            _ => Err(std::io::Error::from_raw_os_error(libc::ENXIO)),
+        }
+    }
+}
+
+fn bad_message() -> std::io::Error {
+    std::io::Error::from_raw_os_error(libc::EBADMSG)
+}
+
 // TODO:
-//  string-likes (string, object path, signature)
-//  array
-//  variant
-//  struct
-//  dict
+//  owned object paths and signatures
 //
+
+#[test]
+fn t_basic_types() {
+    fn check<T: SdBusMessageDirect>(dbus_type: u8) {
+        assert_eq!(T::dbus_type(), dbus_type);
+        assert_eq!(std::mem::size_of::<T>(), basic_type_size(dbus_type));
+    }
+
+    check::<u8>(b'y');
+    check::<i16>(b'n');
+    check::<u16>(b'q');
+    check::<i32>(b'i');
+    check::<u32>(b'u');
+    check::<i64>(b'x');
+    check::<u64>(b't');
+    check::<f64>(b'd');
+}
+
+#[test]
+fn t_signature() {
+    assert_eq!(signature_of::<u32>().unwrap().to_bytes(), b"u");
+    assert_eq!(signature_of::<Vec<bool>>().unwrap().to_bytes(), b"ab");
+    assert_eq!(
+        signature_of::<&[Vec<&Utf8CStr>]>().unwrap().to_bytes(),
+        b"aas"
+    );
+    assert_eq!(
+        signature_of::<Vec<&bus::ObjectPath>>().unwrap().to_bytes(),
+        b"ao"
+    );
+    assert_eq!(
+        signature_of::<HashMap<String, &str>>().unwrap().to_bytes(),
+        b"a{ss}"
+    );
+    assert_eq!(
+        signature_of::<BTreeMap<&Utf8CStr, Vec<u32>>>()
+            .unwrap()
+            .to_bytes(),
+        b"a{sau}"
+    );
+    assert_eq!(
+        signature_of::<Vec<HashMap<u8, bool>>>().unwrap().to_bytes(),
+        b"aa{yb}"
+    );
+    assert_eq!(
+        signature_of::<Vec<(&Utf8CStr, (u8, bool))>>()
+            .unwrap()
+            .to_bytes(),
+        b"a(s(yb))"
+    );
+    // dict keys have to be basic types
+    assert_eq!(
+        signature_of::<HashMap<Vec<u8>, u32>>().unwrap_err().kind(),
+        std::io::ErrorKind::InvalidInput
+    );
+}
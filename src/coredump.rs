@@ -0,0 +1,185 @@
+//! Query crashes recorded by [`systemd-coredump`].
+//!
+//! `systemd-coredump` logs a journal entry with [`MESSAGE_ID`] for every process that dumped
+//! core, describing the process in `COREDUMP_*` fields. [`coredumps()`] iterates over those
+//! entries as [`CoredumpRecord`]s.
+//!
+//! ```no_run
+//! use systemd::{coredump, journal};
+//! let mut j = journal::OpenOptions::default().system(true).open().unwrap();
+//! for c in coredump::coredumps(&mut j).unwrap() {
+//!     let c = c.unwrap();
+//!     println!("{:?} ({:?}) got signal {:?}", c.exe, c.pid, c.signal);
+//!     if let Some(path) = c.core_file() {
+//!         println!("core stored in {:?}", path);
+//!     }
+//! }
+//! ```
+//!
+//! [`systemd-coredump`]: https://www.freedesktop.org/software/systemd/man/systemd-coredump.html
+
+use crate::journal::{Filter, JournalRef};
+use crate::Result;
+use libc::{pid_t, uid_t};
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// `MESSAGE_ID` of the journal entries `systemd-coredump` logs for each crash
+pub const MESSAGE_ID: &str = "fc2e22bc6ee647b6b90729ab34a250b1";
+
+/// A crash, decoded from the `COREDUMP_*` fields of a journal entry.
+///
+/// Fields which are missing from the entry (or which don't parse) are `None`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CoredumpRecord {
+    /// PID of the crashed process (`COREDUMP_PID`)
+    pub pid: Option<pid_t>,
+    /// User the process ran as (`COREDUMP_UID`)
+    pub uid: Option<uid_t>,
+    /// Signal which caused the dump (`COREDUMP_SIGNAL`)
+    pub signal: Option<i32>,
+    /// Executable of the process (`COREDUMP_EXE`)
+    pub exe: Option<PathBuf>,
+    /// Command name of the process (`COREDUMP_COMM`)
+    pub comm: Option<String>,
+    /// Control group of the process (`COREDUMP_CGROUP`)
+    pub cgroup: Option<String>,
+    /// System unit of the process (`COREDUMP_UNIT`)
+    pub unit: Option<String>,
+    /// User unit of the process (`COREDUMP_USER_UNIT`)
+    pub user_unit: Option<String>,
+    /// Time of the crash (`COREDUMP_TIMESTAMP`)
+    pub timestamp: Option<SystemTime>,
+    /// Whether the stored core was truncated because it exceeded the configured size limit
+    /// (`COREDUMP_TRUNCATED`)
+    pub truncated: bool,
+    /// Where the core was stored, if it was stored externally (`COREDUMP_FILENAME`)
+    pub filename: Option<PathBuf>,
+}
+
+fn parse<T: FromStr>(v: &[u8]) -> Option<T> {
+    std::str::from_utf8(v).ok()?.parse().ok()
+}
+
+fn string(v: &[u8]) -> Option<String> {
+    Some(String::from_utf8_lossy(v).into_owned())
+}
+
+fn path(v: &[u8]) -> Option<PathBuf> {
+    Some(PathBuf::from(OsStr::from_bytes(v)))
+}
+
+impl CoredumpRecord {
+    /// Decode the fields of the journal's current entry.
+    pub fn from_journal(journal: &mut JournalRef) -> Result<Self> {
+        let mut r = CoredumpRecord::default();
+        journal.restart_data();
+        while let Some(f) = journal.enumerate_data()? {
+            if let Some(v) = f.value() {
+                r.set_field(f.name(), v);
+            }
+        }
+        Ok(r)
+    }
+
+    fn set_field(&mut self, name: &[u8], v: &[u8]) {
+        match name {
+            b"COREDUMP_PID" => self.pid = parse(v),
+            b"COREDUMP_UID" => self.uid = parse(v),
+            b"COREDUMP_SIGNAL" => self.signal = parse(v),
+            b"COREDUMP_EXE" => self.exe = path(v),
+            b"COREDUMP_COMM" => self.comm = string(v),
+            b"COREDUMP_CGROUP" => self.cgroup = string(v),
+            b"COREDUMP_UNIT" => self.unit = string(v),
+            b"COREDUMP_USER_UNIT" => self.user_unit = string(v),
+            b"COREDUMP_TIMESTAMP" => {
+                self.timestamp = parse(v).map(|usec| UNIX_EPOCH + Duration::from_micros(usec))
+            }
+            b"COREDUMP_TRUNCATED" => self.truncated = v == b"1",
+            b"COREDUMP_FILENAME" => self.filename = path(v),
+            _ => {}
+        }
+    }
+
+    /// The path of the stored core, if it was stored externally and still exists.
+    ///
+    /// Cores are removed by `systemd-tmpfiles` and when storage limits are reached, so older
+    /// entries often refer to cores which are gone. Cores stored in the journal itself
+    /// (`Storage=journal`) are in the entry's `COREDUMP` field instead.
+    pub fn core_file(&self) -> Option<&Path> {
+        self.filename.as_deref().filter(|p| p.exists())
+    }
+}
+
+/// A filter matching the entries `systemd-coredump` logs for crashes.
+pub fn filter() -> Filter {
+    let mut f = Filter::new();
+    f.match_add("MESSAGE_ID", MESSAGE_ID);
+    f
+}
+
+/// Iterator over crashes, created by [`coredumps()`].
+pub struct Coredumps<'a> {
+    journal: &'a mut JournalRef,
+}
+
+impl<'a> Iterator for Coredumps<'a> {
+    type Item = Result<CoredumpRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.journal.next() {
+            Ok(0) => None,
+            Ok(_) => Some(CoredumpRecord::from_journal(self.journal)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+/// Iterate over the crashes recorded in `journal`, from its current position.
+///
+/// This adds [`filter()`] to the journal's matches, so it can be combined with other matches (for
+/// example, on `COREDUMP_UNIT`) added before.
+pub fn coredumps(journal: &mut JournalRef) -> Result<Coredumps<'_>> {
+    journal.apply_filter(&filter())?;
+    Ok(Coredumps { journal })
+}
+
+#[test]
+fn t_set_field() {
+    let mut r = CoredumpRecord::default();
+    let fields: &[(&[u8], &[u8])] = &[
+        (b"COREDUMP_PID", b"1234"),
+        (b"COREDUMP_UID", b"1000"),
+        (b"COREDUMP_SIGNAL", b"11"),
+        (b"COREDUMP_EXE", b"/usr/bin/crashy"),
+        (b"COREDUMP_COMM", b"crashy"),
+        (b"COREDUMP_UNIT", b"crashy.service"),
+        (b"COREDUMP_TIMESTAMP", b"1600000000000001"),
+        (b"COREDUMP_TRUNCATED", b"1"),
+        (b"COREDUMP_FILENAME", b"/nonexistent/core.crashy.zst"),
+        (b"COREDUMP_UID_BOGUS", b"x"),
+    ];
+    for (n, v) in fields {
+        r.set_field(n, v);
+    }
+    assert_eq!(r.pid, Some(1234));
+    assert_eq!(r.uid, Some(1000));
+    assert_eq!(r.signal, Some(libc::SIGSEGV));
+    assert_eq!(r.exe.as_deref(), Some(Path::new("/usr/bin/crashy")));
+    assert_eq!(r.comm.as_deref(), Some("crashy"));
+    assert_eq!(r.unit.as_deref(), Some("crashy.service"));
+    assert_eq!(r.cgroup, None);
+    assert_eq!(
+        r.timestamp,
+        Some(UNIX_EPOCH + Duration::from_micros(1_600_000_000_000_001))
+    );
+    assert!(r.truncated);
+    assert!(r.filename.is_some());
+    assert_eq!(r.core_file(), None);
+
+    r.set_field(b"COREDUMP_PID", b"garbage");
+    assert_eq!(r.pid, None);
+}
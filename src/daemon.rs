@@ -3,16 +3,24 @@
 /// Note that most, if not all, of these APIs can be found in the pure-rust
 /// [libsystemd](https://crates.io/crates/libsystemd) crate, and you may prefer to use it instead.
 use super::ffi::{c_int, pid_t, size_t};
+use super::id128::Id128;
 use super::{Error, Result};
 use ::ffi::daemon as ffi;
 use cstr_argument::CStrArgument;
 use libc::{c_char, c_uint};
 use libc::{SOCK_DGRAM, SOCK_RAW, SOCK_STREAM};
+use std::borrow::Cow;
+use std::ffi::{CString, OsStr, OsString};
 use std::io::ErrorKind;
 use std::net::TcpListener;
-use std::os::unix::io::FromRawFd;
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::io::RawFd as Fd;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd};
 use std::ptr::null;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
 use std::{env, ptr};
 
 // XXX: this is stolen from std::old_io::net::addrinfo until we have a replacement in the standard
@@ -65,11 +73,18 @@ pub const STATE_FDSTOREREMOVE: &str = "FDSTOREREMOVE";
 pub const STATE_FDNAME: &str = "FDNAME";
 
 /// Represents the result returned by the socket dameon's sd_listen_fds
+///
+/// The passed file descriptors have `FD_CLOEXEC` set (`sd_listen_fds()` does so), so they aren't
+/// inherited by child processes unless [`leak_for_exec()`] is used.
 #[derive(Debug)]
 pub struct ListenFds {
     num_fds: c_int,
 }
 
+/// The passed file descriptors handed out by [`ListenFds::take()`]. This is kept for the whole
+/// process, as `listen_fds()` can be called more than once.
+static TAKEN: Mutex<Vec<Fd>> = Mutex::new(Vec::new());
+
 impl ListenFds {
     // Constructs a new set from the number of file_descriptors
     fn new(unset_environment: bool) -> Result<Self> {
@@ -93,6 +108,34 @@ impl ListenFds {
         self.len() == 0
     }
 
+    /// Take ownership of the file descriptor at `index` (`0` for the first one, fd 3).
+    ///
+    /// Each file descriptor can only be taken once in the whole process, through any `ListenFds`,
+    /// so that two parts of a program can't both end up owning (and closing) it: taking it
+    /// again, or taking an index past the end, fails with [`ErrorKind::InvalidInput`].
+    /// [`ListenFds::iter()`] still lists taken file descriptors.
+    pub fn take(&mut self, index: usize) -> Result<OwnedFd> {
+        if index >= self.num_fds as usize {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "no passed file descriptor {}, only {} were passed",
+                    index, self.num_fds
+                ),
+            ));
+        }
+        let fd = LISTEN_FDS_START + index as Fd;
+        let mut taken = TAKEN.lock().unwrap_or_else(|e| e.into_inner());
+        if taken.contains(&fd) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("passed file descriptor {} was already taken", index),
+            ));
+        }
+        taken.push(fd);
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+
     /// Returns an iterable range over the returned file descriptors
     pub fn iter(&self) -> ListenFdsRange {
         ListenFdsRange {
@@ -130,6 +173,20 @@ pub fn listen_fds(unset_environment: bool) -> Result<ListenFds> {
     ListenFds::new(unset_environment)
 }
 
+/// Clear `FD_CLOEXEC` on `fd` and give up ownership of it, so that it is inherited by processes
+/// executed afterwards (for example to pass a socket on to a child). Returns the raw file
+/// descriptor, which stays open in this process as well.
+pub fn leak_for_exec(fd: OwnedFd) -> Result<Fd> {
+    let raw = fd.into_raw_fd();
+    let flags = unsafe { libc::fcntl(raw, libc::F_GETFD) };
+    if flags < 0 || unsafe { libc::fcntl(raw, libc::F_SETFD, flags & !libc::FD_CLOEXEC) } < 0 {
+        let e = Error::last_os_error();
+        unsafe { libc::close(raw) };
+        return Err(e);
+    }
+    Ok(raw)
+}
+
 /// Identifies whether the passed file descriptor is a FIFO.  If a path is
 /// supplied, the file descriptor must also match the path.
 pub fn is_fifo<S: CStrArgument>(fd: Fd, path: Option<S>) -> Result<bool> {
@@ -310,6 +367,74 @@ where
     Ok(result != 0)
 }
 
+/// Returns the socket `notify()` sends to (`$NOTIFY_SOCKET`), or `None` if the process wasn't
+/// asked for notifications, in which case a service can skip preparing them altogether.
+pub fn notify_socket() -> Option<OsString> {
+    env::var_os("NOTIFY_SOCKET").filter(|s| !s.is_empty())
+}
+
+/// Similar to `notify()`, but sends the state to `socket` instead of `$NOTIFY_SOCKET`, for test
+/// harnesses and supervisors which proxy notifications.
+///
+/// `socket` is given like in `$NOTIFY_SOCKET`: either an absolute path, or a name in the
+/// abstract namespace prefixed with `@`.
+///
+/// ```no_run
+/// use std::ffi::OsStr;
+/// use systemd::daemon;
+/// daemon::notify_to(OsStr::new("@supervisor"), [(daemon::STATE_READY, "1")].iter()).unwrap();
+/// ```
+pub fn notify_to<'a, I, K, V>(socket: &OsStr, state: I) -> Result<()>
+where
+    I: Iterator<Item = &'a (K, V)>,
+    K: AsRef<str> + 'a,
+    V: AsRef<str> + 'a,
+{
+    let c_state = state_to_c_string(state);
+    let path = socket.as_bytes();
+    let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    // paths are NUL terminated, abstract names are not
+    let len = match path.first() {
+        Some(b'/') if path.len() < addr.sun_path.len() => path.len() + 1,
+        Some(b'@') if path.len() <= addr.sun_path.len() => path.len(),
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "notification socket must be an absolute path or start with '@'",
+            ))
+        }
+    };
+    for (d, s) in addr.sun_path.iter_mut().zip(path) {
+        *d = *s as c_char;
+    }
+    if path[0] == b'@' {
+        addr.sun_path[0] = 0;
+    }
+    let len = std::mem::size_of::<libc::sa_family_t>() + len;
+
+    let fd = unsafe { libc::socket(libc::AF_UNIX, SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(Error::last_os_error());
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let msg = c_state.as_bytes();
+    let sent = unsafe {
+        libc::sendto(
+            fd.as_raw_fd(),
+            msg.as_ptr() as *const libc::c_void,
+            msg.len(),
+            libc::MSG_NOSIGNAL,
+            &addr as *const libc::sockaddr_un as *const libc::sockaddr,
+            len as libc::socklen_t,
+        )
+    };
+    if sent < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
 /// Similar to `notify()`, but this sends the message on behalf of the supplied
 /// PID, if possible.
 pub fn pid_notify<'a, I, K, V>(pid: pid_t, unset_environment: bool, state: I) -> Result<bool>
@@ -350,6 +475,176 @@ where
     Ok(result != 0)
 }
 
+/// The longest `STATUS=` text that fits in a notification.
+///
+/// The service manager reads notifications into a buffer of `PIPE_BUF` (4096) bytes and drops
+/// longer ones entirely.
+pub const STATUS_MAX: usize = 4096 - "STATUS=".len();
+
+/// Make `text` suitable as a `STATUS=` value: newlines (which would end the assignment) become
+/// spaces, and it is truncated to [`STATUS_MAX`] bytes without splitting a UTF-8 character.
+pub fn status_text(text: &str) -> Cow<'_, str> {
+    let mut end = text.len().min(STATUS_MAX);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let text = &text[..end];
+    if text.contains('\n') {
+        Cow::Owned(text.replace('\n', " "))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Sends `STATUS=` updates, at most one per interval.
+///
+/// Updates arriving faster are held back, and only the latest one is sent once the interval has
+/// passed: by the next update, or by [`StatusUpdater::poll()`] once
+/// [`StatusUpdater::deadline()`] is reached. [`StatusUpdater::flush()`] sends it right away, as
+/// does dropping the `StatusUpdater`. This keeps frequent progress reports from flooding the
+/// service manager, which rate limits notifications.
+///
+/// [`status()`] and [`status_fmt!`](crate::status_fmt) use a process wide `StatusUpdater`.
+#[derive(Debug)]
+pub struct StatusUpdater {
+    interval: Duration,
+    last_sent: Option<Instant>,
+    pending: Option<String>,
+}
+
+impl StatusUpdater {
+    /// Send at most one update per `interval`.
+    pub const fn new(interval: Duration) -> Self {
+        StatusUpdater {
+            interval,
+            last_sent: None,
+            pending: None,
+        }
+    }
+
+    /// Set the status to `text` (see [`status_text()`]), returning `true` if it was sent right
+    /// away and `false` if it is held back until the interval has passed.
+    pub fn update(&mut self, text: &str) -> Result<bool> {
+        match self.last_sent {
+            Some(t) if t.elapsed() < self.interval => {
+                self.pending = Some(text.to_owned());
+                Ok(false)
+            }
+            _ => {
+                self.pending = None;
+                self.send(text)?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Send the update being held back, if any, regardless of the interval. Returns `true` if
+    /// there was one.
+    pub fn flush(&mut self) -> Result<bool> {
+        match self.pending.take() {
+            Some(text) => {
+                self.send(&text)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Send the update being held back if the interval since the last one has passed. Returns
+    /// `true` if it was sent.
+    pub fn poll(&mut self) -> Result<bool> {
+        match self.deadline() {
+            Some(deadline) if deadline <= Instant::now() => self.flush(),
+            _ => Ok(false),
+        }
+    }
+
+    /// When the update being held back is due, for waking up to [`StatusUpdater::poll()`].
+    /// `None` if there is none.
+    pub fn deadline(&self) -> Option<Instant> {
+        match (&self.pending, self.last_sent) {
+            (Some(_), Some(t)) => Some(t + self.interval),
+            _ => None,
+        }
+    }
+
+    /// The update being held back, if any.
+    pub fn pending(&self) -> Option<&str> {
+        self.pending.as_deref()
+    }
+
+    fn send(&mut self, text: &str) -> Result<()> {
+        notify(false, [(STATE_STATUS, status_text(text))].iter())?;
+        self.last_sent = Some(Instant::now());
+        Ok(())
+    }
+}
+
+impl Drop for StatusUpdater {
+    fn drop(&mut self) {
+        // the last status is the one which matters most, don't lose it
+        let _ = self.flush();
+    }
+}
+
+/// The interval of the process wide [`StatusUpdater`] used by [`status()`].
+pub const STATUS_INTERVAL: Duration = Duration::from_millis(100);
+
+static STATUS: Mutex<StatusUpdater> = Mutex::new(StatusUpdater::new(STATUS_INTERVAL));
+// set while a thread waits to send the status held back by `status()`
+static STATUS_FLUSHER: AtomicBool = AtomicBool::new(false);
+
+/// Set the status of the service (`STATUS=`) with a process wide [`StatusUpdater`], returning
+/// `true` if it was sent right away.
+///
+/// A status held back is sent by a background thread once the interval has passed. Call
+/// [`flush_status()`] before exiting, so the last status isn't left behind.
+pub fn status(text: &str) -> Result<bool> {
+    let mut updater = STATUS.lock().unwrap_or_else(|e| e.into_inner());
+    let sent = updater.update(text)?;
+    if let Some(deadline) = updater.deadline() {
+        if !STATUS_FLUSHER.swap(true, Ordering::AcqRel) {
+            let flusher = thread::Builder::new()
+                .name("sd-status".into())
+                .spawn(move || {
+                    thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                    let mut updater = STATUS.lock().unwrap_or_else(|e| e.into_inner());
+                    STATUS_FLUSHER.store(false, Ordering::Release);
+                    let _ = updater.poll();
+                });
+            if flusher.is_err() {
+                // left for the next update or `flush_status()`
+                STATUS_FLUSHER.store(false, Ordering::Release);
+            }
+        }
+    }
+    Ok(sent)
+}
+
+/// Send the status held back by [`status()`], if any. Returns `true` if there was one.
+pub fn flush_status() -> Result<bool> {
+    STATUS.lock().unwrap_or_else(|e| e.into_inner()).flush()
+}
+
+/// Set the status of the service from format arguments, using [`daemon::status()`].
+///
+/// ```no_run
+/// # let (done, total) = (1, 2);
+/// systemd::daemon::status_fmt!("Processed {} of {} items", done, total)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// [`daemon::status()`]: crate::daemon::status
+#[macro_export]
+macro_rules! status_fmt {
+    ($($arg:tt)*) => {
+        $crate::daemon::status(&::std::format!($($arg)*))
+    };
+}
+
+#[doc(inline)]
+pub use crate::status_fmt;
+
 /// Returns true if the system was booted with systemd.
 pub fn booted() -> Result<bool> {
     let result = sd_try!(ffi::sd_booted());
@@ -366,3 +661,442 @@ pub fn watchdog_enabled(unset_environment: bool) -> Result<u64> {
     ));
     Ok(timeout)
 }
+
+/// Tracks activity of a socket activated service, to exit once it has been idle for a while.
+///
+/// The service manager keeps listening on the service's sockets while it isn't running and
+/// starts it again on the next connection, so exiting when idle frees resources without losing
+/// requests. File descriptors which should survive the restart (for example, long lived
+/// connections) can be registered with [`IdleExit::store_fd()`]; they are passed to the service
+/// manager's file descriptor store before exiting (this requires `FileDescriptorStoreMax=` to be
+/// set in the service) and handed back through [`listen_fds()`] on the next start.
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use systemd::daemon::IdleExit;
+///
+/// let mut idle = IdleExit::new(Duration::from_secs(30));
+/// loop {
+///     // wait for a connection, for at most `idle.remaining()`
+///     # let connection = false;
+///     if connection {
+///         idle.activity();
+///         // ... handle it ...
+///     }
+///     idle.exit_if_idle().unwrap();
+/// }
+/// ```
+#[derive(Debug)]
+pub struct IdleExit {
+    timeout: Duration,
+    last_activity: Instant,
+    busy: usize,
+    fds: Vec<(Fd, String)>,
+}
+
+impl IdleExit {
+    /// Consider the service idle after `timeout` without activity.
+    pub fn new(timeout: Duration) -> Self {
+        IdleExit {
+            timeout,
+            last_activity: Instant::now(),
+            busy: 0,
+            fds: Vec::new(),
+        }
+    }
+
+    /// Record activity, restarting the idle period.
+    pub fn activity(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// Mark the start of work which may take longer than the idle timeout, like serving a long
+    /// lived connection. The service isn't idle until a matching [`IdleExit::end()`].
+    pub fn begin(&mut self) {
+        self.busy += 1;
+        self.activity();
+    }
+
+    /// Mark the end of work started with [`IdleExit::begin()`].
+    pub fn end(&mut self) {
+        self.busy = self.busy.saturating_sub(1);
+        self.activity();
+    }
+
+    /// Pass `fd` to the file descriptor store under `name` before exiting.
+    ///
+    /// The descriptor must stay open until the process exits.
+    pub fn store_fd(&mut self, fd: Fd, name: &str) {
+        self.fds.push((fd, name.to_owned()));
+    }
+
+    /// Stop passing `fd` to the file descriptor store, for example because it was closed.
+    pub fn forget_fd(&mut self, fd: Fd) {
+        self.fds.retain(|(f, _)| *f != fd);
+    }
+
+    /// How long until the service becomes idle, or `None` while work is in progress.
+    ///
+    /// This is zero once the service is idle, and is suitable as a timeout when waiting for new
+    /// connections.
+    pub fn remaining(&self) -> Option<Duration> {
+        if self.busy > 0 {
+            return None;
+        }
+        Some(self.timeout.saturating_sub(self.last_activity.elapsed()))
+    }
+
+    /// Whether the service has been idle for the configured timeout.
+    pub fn is_idle(&self) -> bool {
+        self.remaining() == Some(Duration::ZERO)
+    }
+
+    /// Store the registered file descriptors and notify the service manager that the service is
+    /// stopping (`STOPPING=1`).
+    ///
+    /// If file descriptors are registered but can't be passed to the service manager (because
+    /// the process wasn't started by systemd), this fails without sending `STOPPING=1`, so they
+    /// are not lost by exiting.
+    pub fn stop(&self) -> Result<()> {
+        let mut names: Vec<&str> = self.fds.iter().map(|(_, n)| n.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+        for name in names {
+            // FDNAME= applies to all descriptors of a message, so send one message per name
+            let fds: Vec<Fd> = self
+                .fds
+                .iter()
+                .filter(|(_, n)| n == name)
+                .map(|(fd, _)| *fd)
+                .collect();
+            let stored = pid_notify_with_fds(
+                0,
+                false,
+                [(STATE_FDSTORE, "1"), (STATE_FDNAME, name)].iter(),
+                &fds,
+            )?;
+            if !stored {
+                return Err(Error::new(
+                    ErrorKind::NotConnected,
+                    "can't store file descriptors, not running under systemd",
+                ));
+            }
+        }
+        notify(false, [(STATE_STOPPING, "1")].iter())?;
+        Ok(())
+    }
+
+    /// If the service is idle, [`stop()`](IdleExit::stop) it and exit the process with status
+    /// `0`. Returns `Ok(())` if the service isn't idle.
+    pub fn exit_if_idle(&self) -> Result<()> {
+        if self.is_idle() {
+            self.stop()?;
+            std::process::exit(0);
+        }
+        Ok(())
+    }
+}
+
+/// The result of a service run, from `$SERVICE_RESULT`.
+///
+/// See the description of `$SERVICE_RESULT` in [`systemd.exec(5)`].
+///
+/// [`systemd.exec(5)`]: https://www.freedesktop.org/software/systemd/man/systemd.exec.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServiceResult {
+    /// `success`
+    Success,
+    /// `protocol`: the service didn't follow the protocol of its `Type=`, like not notifying
+    /// readiness
+    Protocol,
+    /// `timeout`
+    Timeout,
+    /// `exit-code`: the main process exited with a non-zero (and not successful) status
+    ExitCode,
+    /// `signal`: the main process was killed by a signal
+    Signal,
+    /// `core-dump`: the main process dumped core
+    CoreDump,
+    /// `watchdog`: the watchdog wasn't pinged in time
+    Watchdog,
+    /// `start-limit-hit`
+    StartLimitHit,
+    /// `resources`: a resource (like a process, or the PID file) couldn't be set up
+    Resources,
+    /// `oom-kill`
+    OomKill,
+    /// Any other result
+    Other(String),
+}
+
+impl ServiceResult {
+    fn parse(s: &str) -> Self {
+        match s {
+            "success" => ServiceResult::Success,
+            "protocol" => ServiceResult::Protocol,
+            "timeout" => ServiceResult::Timeout,
+            "exit-code" => ServiceResult::ExitCode,
+            "signal" => ServiceResult::Signal,
+            "core-dump" => ServiceResult::CoreDump,
+            "watchdog" => ServiceResult::Watchdog,
+            "start-limit-hit" => ServiceResult::StartLimitHit,
+            "resources" => ServiceResult::Resources,
+            "oom-kill" => ServiceResult::OomKill,
+            s => ServiceResult::Other(s.to_owned()),
+        }
+    }
+}
+
+/// How the main process of a service exited, from `$EXIT_CODE` and `$EXIT_STATUS`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The process exited with this status
+    Exited(i32),
+    /// The process was killed by the signal with this name (without the `SIG` prefix, like
+    /// `TERM`)
+    Killed(String),
+    /// The process dumped core because of the signal with this name
+    Dumped(String),
+}
+
+impl ExitStatus {
+    fn parse(code: &str, status: &str) -> Result<Self> {
+        match code {
+            "exited" => status.parse().map(ExitStatus::Exited).map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("invalid exit status {:?}", status),
+                )
+            }),
+            "killed" => Ok(ExitStatus::Killed(status.to_owned())),
+            "dumped" => Ok(ExitStatus::Dumped(status.to_owned())),
+            _ => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("unknown exit code {:?}", code),
+            )),
+        }
+    }
+}
+
+/// The unit an `OnFailure=`/`OnSuccess=` handler was started for, from the `$MONITOR_*`
+/// variables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MonitorContext {
+    /// `$MONITOR_UNIT`
+    pub unit: Option<String>,
+    /// `$MONITOR_INVOCATION_ID`
+    pub invocation_id: Option<Id128>,
+    /// `$MONITOR_SERVICE_RESULT`
+    pub service_result: Option<ServiceResult>,
+    /// `$MONITOR_EXIT_CODE` and `$MONITOR_EXIT_STATUS`
+    pub exit_status: Option<ExitStatus>,
+}
+
+/// What the service manager tells `ExecStop=`/`ExecStopPost=` commands and `OnFailure=`/
+/// `OnSuccess=` handlers about how a service run ended, see [`exec_context()`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecContext {
+    /// `$SERVICE_RESULT`
+    pub service_result: Option<ServiceResult>,
+    /// `$EXIT_CODE` and `$EXIT_STATUS`, unset if the main process hasn't exited (or there was
+    /// none)
+    pub exit_status: Option<ExitStatus>,
+    /// Set in handlers started through `OnFailure=` or `OnSuccess=` of another unit
+    pub monitor: Option<MonitorContext>,
+}
+
+/// Read the environment variables the service manager sets for `ExecStop=` and `ExecStopPost=`
+/// commands (`$SERVICE_RESULT`, `$EXIT_CODE`, `$EXIT_STATUS`) and for units started by
+/// `OnFailure=`/`OnSuccess=` (`$MONITOR_*`, since systemd 251).
+///
+/// Variables which aren't set are reported as `None`; malformed ones are an error.
+///
+/// ```no_run
+/// use systemd::daemon::{self, ServiceResult};
+/// let ctx = daemon::exec_context().unwrap();
+/// if ctx.service_result != Some(ServiceResult::Success) {
+///     eprintln!("service failed: {:?} {:?}", ctx.service_result, ctx.exit_status);
+/// }
+/// ```
+pub fn exec_context() -> Result<ExecContext> {
+    parse_exec_context(|name| env::var(name).ok())
+}
+
+fn parse_exec_context<F: Fn(&str) -> Option<String>>(var: F) -> Result<ExecContext> {
+    let exit_status = |prefix: &str| -> Result<Option<ExitStatus>> {
+        let code = var(&format!("{}EXIT_CODE", prefix));
+        let status = var(&format!("{}EXIT_STATUS", prefix));
+        match (code, status) {
+            (Some(code), Some(status)) => ExitStatus::parse(&code, &status).map(Some),
+            _ => Ok(None),
+        }
+    };
+
+    let monitor_unit = var("MONITOR_UNIT");
+    let monitor_invocation_id = match var("MONITOR_INVOCATION_ID") {
+        Some(id) => Some(Id128::from_cstr(&CString::new(id)?)?),
+        None => None,
+    };
+    let monitor_result = var("MONITOR_SERVICE_RESULT").map(|r| ServiceResult::parse(&r));
+    let monitor_exit = exit_status("MONITOR_")?;
+    let monitor = if monitor_unit.is_some()
+        || monitor_invocation_id.is_some()
+        || monitor_result.is_some()
+        || monitor_exit.is_some()
+    {
+        Some(MonitorContext {
+            unit: monitor_unit,
+            invocation_id: monitor_invocation_id,
+            service_result: monitor_result,
+            exit_status: monitor_exit,
+        })
+    } else {
+        None
+    };
+
+    Ok(ExecContext {
+        service_result: var("SERVICE_RESULT").map(|r| ServiceResult::parse(&r)),
+        exit_status: exit_status("")?,
+        monitor,
+    })
+}
+
+#[test]
+fn t_exec_context() {
+    let vars = |vars: &'static [(&'static str, &'static str)]| {
+        move |name: &str| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        }
+    };
+
+    let ctx = parse_exec_context(vars(&[])).unwrap();
+    assert_eq!(ctx.service_result, None);
+    assert_eq!(ctx.exit_status, None);
+    assert_eq!(ctx.monitor, None);
+
+    let ctx = parse_exec_context(vars(&[
+        ("SERVICE_RESULT", "exit-code"),
+        ("EXIT_CODE", "exited"),
+        ("EXIT_STATUS", "3"),
+    ]))
+    .unwrap();
+    assert_eq!(ctx.service_result, Some(ServiceResult::ExitCode));
+    assert_eq!(ctx.exit_status, Some(ExitStatus::Exited(3)));
+
+    let ctx = parse_exec_context(vars(&[
+        ("MONITOR_UNIT", "foo.service"),
+        ("MONITOR_INVOCATION_ID", "0123456789abcdef0123456789abcdef"),
+        ("MONITOR_SERVICE_RESULT", "frobnicated"),
+        ("MONITOR_EXIT_CODE", "killed"),
+        ("MONITOR_EXIT_STATUS", "TERM"),
+    ]))
+    .unwrap();
+    assert_eq!(ctx.service_result, None);
+    let monitor = ctx.monitor.unwrap();
+    assert_eq!(monitor.unit.as_deref(), Some("foo.service"));
+    assert!(monitor.invocation_id.is_some());
+    assert_eq!(
+        monitor.service_result,
+        Some(ServiceResult::Other("frobnicated".to_owned()))
+    );
+    assert_eq!(
+        monitor.exit_status,
+        Some(ExitStatus::Killed("TERM".to_owned()))
+    );
+
+    assert!(parse_exec_context(vars(&[("EXIT_CODE", "exited"), ("EXIT_STATUS", "TERM")])).is_err());
+    assert!(parse_exec_context(vars(&[("EXIT_CODE", "gone"), ("EXIT_STATUS", "1")])).is_err());
+}
+
+/// Helpers for testing socket activated code without running under systemd.
+pub mod testing {
+    use super::{Fd, LISTEN_FDS_START, TAKEN};
+    use crate::Result;
+    use std::env;
+    use std::io;
+
+    /// Check a name the same way the service manager validates `FileDescriptorName=`.
+    fn validate_fd_name(name: &str) -> Result<()> {
+        let valid = !name.is_empty()
+            && name.len() <= 255
+            && name
+                .bytes()
+                .all(|b| (b' '..=b'~').contains(&b) && b != b':');
+        if valid {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid file descriptor name {:?}", name),
+            ))
+        }
+    }
+
+    /// Make the current process look like it was socket activated with `fds`.
+    ///
+    /// Each file descriptor is duplicated to its slot starting at fd 3, without `FD_CLOEXEC`
+    /// (as the service manager passes them), and `$LISTEN_PID`, `$LISTEN_FDS` and
+    /// `$LISTEN_FDNAMES` are set for the current process. The passed file descriptors stay owned
+    /// by the caller. Any file descriptor already open in the target slots is replaced, and can
+    /// be taken with [`ListenFds::take()`](super::ListenFds::take) again.
+    ///
+    /// [`listen_fds()`](super::listen_fds) with `unset_environment` set to `true` removes the
+    /// variables again.
+    ///
+    /// As this modifies the environment and the file descriptor table of the whole process, it
+    /// should only be used in tests which don't run in parallel with other tests doing the same.
+    ///
+    /// ```no_run
+    /// use std::os::unix::io::AsRawFd;
+    /// use std::os::unix::net::UnixListener;
+    /// use systemd::daemon;
+    ///
+    /// let l = UnixListener::bind("/tmp/example.sock").unwrap();
+    /// daemon::testing::fake_activation(&[(l.as_raw_fd(), "example")]).unwrap();
+    /// assert_eq!(daemon::listen_fds(true).unwrap().len(), 1);
+    /// ```
+    pub fn fake_activation(fds: &[(Fd, &str)]) -> Result<()> {
+        for (_, name) in fds {
+            validate_fd_name(name)?;
+        }
+
+        // Move everything out of the target range first, so sources which are themselves in the
+        // range aren't overwritten before they are duplicated.
+        let first_free = LISTEN_FDS_START + fds.len() as Fd;
+        let mut tmp = Vec::with_capacity(fds.len());
+        let mut res = Ok(());
+        for (fd, _) in fds {
+            let t = unsafe { libc::fcntl(*fd, libc::F_DUPFD_CLOEXEC, first_free) };
+            if t < 0 {
+                res = Err(io::Error::last_os_error());
+                break;
+            }
+            tmp.push(t);
+        }
+        if res.is_ok() {
+            for (i, t) in tmp.iter().enumerate() {
+                // dup2() clears FD_CLOEXEC on the new descriptor
+                if unsafe { libc::dup2(*t, LISTEN_FDS_START + i as Fd) } < 0 {
+                    res = Err(io::Error::last_os_error());
+                    break;
+                }
+            }
+        }
+        for t in tmp {
+            unsafe { libc::close(t) };
+        }
+        res?;
+        TAKEN
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|fd| *fd >= first_free);
+
+        let names: Vec<&str> = fds.iter().map(|(_, name)| *name).collect();
+        env::set_var("LISTEN_PID", std::process::id().to_string());
+        env::set_var("LISTEN_FDS", fds.len().to_string());
+        env::set_var("LISTEN_FDNAMES", names.join(":"));
+        Ok(())
+    }
+}
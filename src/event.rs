@@ -0,0 +1,856 @@
+//! Run callbacks from an [`sd-event`] event loop.
+//!
+//! An [`Event`] loop dispatches the callbacks of its event sources, such as timers added with
+//! [`EventRef::add_time()`] and [`EventRef::add_time_relative()`], file descriptors watched with
+//! [`EventRef::add_io()`] or paths watched with [`EventRef::add_inotify()`], until it is told to
+//! [`exit()`](EventRef::exit). Timer sources fire once; to do periodic work, a callback moves its
+//! source to the next deadline and enables it again:
+//!
+//! ```no_run
+//! use std::time::Duration;
+//! use systemd::event::{Clock, Enabled, Event};
+//!
+//! let mut ev = Event::new().unwrap();
+//! let period = Duration::from_secs(10);
+//! let _timer = ev
+//!     .add_time_relative(Clock::Monotonic, period, Duration::ZERO, move |s, _| {
+//!         println!("tick");
+//!         s.set_time_relative(period)?;
+//!         s.set_enabled(Enabled::OneShot)
+//!     })
+//!     .unwrap();
+//! ev.run_loop().unwrap();
+//! ```
+//!
+//! Neither [`Event`] nor [`EventSource`] is `Send`: the loop and its sources are used from the
+//! thread which created them.
+//!
+//! [`sd-event`]: https://www.freedesktop.org/software/systemd/man/sd-event.html
+
+use super::usec_from_duration;
+use crate::ffi::event as ffi;
+use crate::Result;
+use cstr_argument::CStrArgument;
+use foreign_types::{foreign_type, ForeignType, ForeignTypeRef};
+use libc::{c_int, c_void};
+use std::ffi::{CStr, CString, OsStr};
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, BorrowedFd, IntoRawFd, OwnedFd, RawFd};
+use std::path::Path;
+use std::time::Duration;
+use std::{fmt, ptr};
+
+foreign_type! {
+    /// An event loop.
+    ///
+    /// This corresponds to [`sd_event`].
+    ///
+    /// [`sd_event`]: https://www.freedesktop.org/software/systemd/man/sd-event.html
+    pub unsafe type Event {
+        type CType = ffi::sd_event;
+        fn drop = ffi::sd_event_unref;
+        fn clone = ffi::sd_event_ref;
+    }
+}
+
+foreign_type! {
+    /// A source of events added to an [`Event`] loop, such as a timer.
+    ///
+    /// Dropping the `EventSource` disables it and removes it from the loop.
+    ///
+    /// This corresponds to [`sd_event_source`].
+    ///
+    /// [`sd_event_source`]: https://www.freedesktop.org/software/systemd/man/sd-event.html
+    pub unsafe type EventSource {
+        type CType = ffi::sd_event_source;
+        fn drop = disable_unref;
+    }
+}
+
+/// Like `sd_event_source_disable_unref()`, which requires systemd v243.
+///
+/// The loop may hold another reference while the source is dispatched, disabling it makes sure
+/// it doesn't fire again.
+unsafe fn disable_unref(source: *mut ffi::sd_event_source) {
+    ffi::sd_event_source_set_enabled(source, ffi::SD_EVENT_OFF);
+    ffi::sd_event_source_unref(source);
+}
+
+/// An [`EventSource`] watching a borrowed file descriptor, created by [`EventRef::add_io()`].
+///
+/// The source borrows the file descriptor, and like an `EventSource` is disabled and removed
+/// from the loop when dropped. It has to be dropped rather than leaked, see the safety
+/// requirements of `add_io()`.
+pub struct Source<'a> {
+    source: EventSource,
+    fd: PhantomData<BorrowedFd<'a>>,
+}
+
+impl Deref for Source<'_> {
+    type Target = EventSourceRef;
+
+    fn deref(&self) -> &EventSourceRef {
+        &self.source
+    }
+}
+
+impl DerefMut for Source<'_> {
+    fn deref_mut(&mut self) -> &mut EventSourceRef {
+        &mut self.source
+    }
+}
+
+impl<'a> Source<'a> {
+    /// Watch `fd` instead of the file descriptor the source was created with. Like the first
+    /// one, it stays registered until the source is dropped.
+    ///
+    /// This corresponds to [`sd_event_source_set_io_fd`]
+    ///
+    /// [`sd_event_source_set_io_fd`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_io_fd.html
+    pub fn set_io_fd(&mut self, fd: BorrowedFd<'a>) -> Result<()> {
+        // the source can't outlive `'a`, so neither can its use of `fd`
+        unsafe { self.source.set_io_fd(fd.as_raw_fd()) }
+    }
+}
+
+impl fmt::Debug for Source<'_> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.source, fmt)
+    }
+}
+
+/// The clock a timer source is based on.
+///
+/// See [`clock_gettime(2)`] for their differences. Times are given as the duration since the
+/// epoch of the clock, for example since boot for [`Clock::Monotonic`] and since the UNIX epoch
+/// for [`Clock::Realtime`].
+///
+/// [`clock_gettime(2)`]: https://man7.org/linux/man-pages/man2/clock_gettime.2.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Clock {
+    /// Wall clock time, which may jump when it is set
+    Realtime,
+    /// Time since boot, not counting time spent suspended
+    Monotonic,
+    /// Time since boot, including time spent suspended
+    Boottime,
+    /// Like [`Clock::Realtime`], and wakes the system up from suspend
+    RealtimeAlarm,
+    /// Like [`Clock::Boottime`], and wakes the system up from suspend
+    BoottimeAlarm,
+}
+
+impl Clock {
+    fn as_raw(self) -> libc::clockid_t {
+        match self {
+            Clock::Realtime => libc::CLOCK_REALTIME,
+            Clock::Monotonic => libc::CLOCK_MONOTONIC,
+            Clock::Boottime => libc::CLOCK_BOOTTIME,
+            Clock::RealtimeAlarm => libc::CLOCK_REALTIME_ALARM,
+            Clock::BoottimeAlarm => libc::CLOCK_BOOTTIME_ALARM,
+        }
+    }
+}
+
+/// Whether an [`EventSource`] is dispatched.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Enabled {
+    /// Not dispatched
+    Off,
+    /// Dispatched every time it triggers
+    On,
+    /// Dispatched once, then switched [`Off`](Enabled::Off). Timer sources start out this way.
+    OneShot,
+}
+
+impl Enabled {
+    fn as_raw(self) -> c_int {
+        match self {
+            Enabled::Off => ffi::SD_EVENT_OFF,
+            Enabled::On => ffi::SD_EVENT_ON,
+            Enabled::OneShot => ffi::SD_EVENT_ONESHOT,
+        }
+    }
+
+    fn from_raw(raw: c_int) -> Self {
+        match raw {
+            ffi::SD_EVENT_OFF => Enabled::Off,
+            ffi::SD_EVENT_ONESHOT => Enabled::OneShot,
+            _ => Enabled::On,
+        }
+    }
+}
+
+/// Priority for sources which should be dispatched before others, see
+/// [`EventSourceRef::set_priority()`].
+pub const PRIORITY_IMPORTANT: i64 = ffi::SD_EVENT_PRIORITY_IMPORTANT as i64;
+/// Priority sources start with.
+pub const PRIORITY_NORMAL: i64 = ffi::SD_EVENT_PRIORITY_NORMAL as i64;
+/// Priority for sources which should only be dispatched when nothing else is pending.
+pub const PRIORITY_IDLE: i64 = ffi::SD_EVENT_PRIORITY_IDLE as i64;
+
+extern "C" fn raw_time_handler<F>(
+    source: *mut ffi::sd_event_source,
+    usec: u64,
+    userdata: *mut c_void,
+) -> c_int
+where
+    F: FnMut(&mut EventSourceRef, Duration) -> Result<()>,
+{
+    // the closure is owned by the source and freed by `raw_destroy_cb_handler`
+    let f = unsafe { &mut *(userdata as *mut F) };
+    let s = unsafe { EventSourceRef::from_ptr_mut(source) };
+    match f(s, Duration::from_micros(usec)) {
+        Ok(()) => 0,
+        Err(e) => -e.raw_os_error().unwrap_or(libc::EIO),
+    }
+}
+
+extern "C" fn raw_io_handler<F>(
+    source: *mut ffi::sd_event_source,
+    fd: c_int,
+    revents: u32,
+    userdata: *mut c_void,
+) -> c_int
+where
+    F: FnMut(&mut EventSourceRef, BorrowedFd<'_>, u32) -> Result<()>,
+{
+    // the closure is owned by the source and freed by `raw_destroy_cb_handler`
+    let f = unsafe { &mut *(userdata as *mut F) };
+    let s = unsafe { EventSourceRef::from_ptr_mut(source) };
+    // the fd is open for as long as the source watches it
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+    match f(s, fd, revents) {
+        Ok(()) => 0,
+        Err(e) => -e.raw_os_error().unwrap_or(libc::EIO),
+    }
+}
+
+/// A change reported by an inotify source added with [`EventRef::add_inotify()`].
+///
+/// See [`inotify(7)`] for the meaning of the fields.
+///
+/// [`inotify(7)`]: https://man7.org/linux/man-pages/man7/inotify.7.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InotifyEvent<'a> {
+    /// The events which occurred, like `libc::IN_CREATE`
+    pub mask: u32,
+    /// Connects the two halves of a rename (`IN_MOVED_FROM` and `IN_MOVED_TO`)
+    pub cookie: u32,
+    /// For a watched directory, the name of the entry in it the event is about
+    pub name: Option<&'a Path>,
+}
+
+impl<'a> InotifyEvent<'a> {
+    /// # Safety
+    ///
+    /// `event` must point to an `inotify_event` followed by its `len` bytes of name.
+    unsafe fn from_raw(event: *const libc::inotify_event) -> Self {
+        let e = &*event;
+        let name = std::slice::from_raw_parts(
+            (event as *const u8).add(std::mem::size_of::<libc::inotify_event>()),
+            e.len as usize,
+        );
+        // the name is padded with nul bytes
+        let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+        InotifyEvent {
+            mask: e.mask,
+            cookie: e.cookie,
+            name: if name.is_empty() {
+                None
+            } else {
+                Some(Path::new(OsStr::from_bytes(name)))
+            },
+        }
+    }
+}
+
+extern "C" fn raw_inotify_handler<F>(
+    source: *mut ffi::sd_event_source,
+    event: *const libc::inotify_event,
+    userdata: *mut c_void,
+) -> c_int
+where
+    F: FnMut(&mut EventSourceRef, &InotifyEvent<'_>) -> Result<()>,
+{
+    // the closure is owned by the source and freed by `raw_destroy_cb_handler`
+    let f = unsafe { &mut *(userdata as *mut F) };
+    let s = unsafe { EventSourceRef::from_ptr_mut(source) };
+    match f(s, &unsafe { InotifyEvent::from_raw(event) }) {
+        Ok(()) => 0,
+        Err(e) => -e.raw_os_error().unwrap_or(libc::EIO),
+    }
+}
+
+extern "C" fn raw_destroy_cb_handler<F>(userdata: *mut c_void) {
+    let _: Box<F> = unsafe { Box::from_raw(userdata as *mut F) };
+}
+
+/// Create a source with `add`, passing it the boxed `callback` as userdata, which the source owns
+/// from then on.
+fn add_source<F, A>(callback: F, add: A) -> Result<EventSource>
+where
+    A: FnOnce(*mut *mut ffi::sd_event_source, *mut c_void) -> c_int,
+{
+    let d: extern "C" fn(*mut c_void) = raw_destroy_cb_handler::<F>;
+    let mut source = ptr::null_mut();
+    let b = Box::into_raw(Box::new(callback));
+    match crate::ffi_result(add(&mut source, b as *mut c_void)) {
+        Err(e) => {
+            drop(unsafe { Box::from_raw(b) });
+            Err(e)
+        }
+        Ok(_) => unsafe {
+            ffi::sd_event_source_set_destroy_callback(source, Some(d));
+            Ok(EventSource::from_ptr(source))
+        },
+    }
+}
+
+impl Event {
+    /// Create a new event loop.
+    ///
+    /// This corresponds to [`sd_event_new`]
+    ///
+    /// [`sd_event_new`]: https://www.freedesktop.org/software/systemd/man/sd_event_new.html
+    #[inline]
+    pub fn new() -> Result<Event> {
+        let mut e = ptr::null_mut();
+        sd_try!(ffi::sd_event_new(&mut e));
+        Ok(unsafe { Event::from_ptr(e) })
+    }
+
+    /// Get the default event loop of the calling thread, creating it if needed.
+    ///
+    /// This corresponds to [`sd_event_default`]
+    ///
+    /// [`sd_event_default`]: https://www.freedesktop.org/software/systemd/man/sd_event_new.html
+    #[inline]
+    #[allow(clippy::should_implement_trait)]
+    pub fn default() -> Result<Event> {
+        let mut e = ptr::null_mut();
+        sd_try!(ffi::sd_event_default(&mut e));
+        Ok(unsafe { Event::from_ptr(e) })
+    }
+}
+
+impl EventRef {
+    /// The time of `clock` at the start of the current iteration of the loop, or the current time
+    /// if the loop isn't running.
+    ///
+    /// This corresponds to [`sd_event_now`]
+    ///
+    /// [`sd_event_now`]: https://www.freedesktop.org/software/systemd/man/sd_event_now.html
+    #[inline]
+    pub fn now(&self, clock: Clock) -> Result<Duration> {
+        let mut usec = 0;
+        sd_try!(ffi::sd_event_now(self.as_ptr(), clock.as_raw(), &mut usec));
+        Ok(Duration::from_micros(usec))
+    }
+
+    /// Call `callback` once `clock` reaches `when`.
+    ///
+    /// The callback may be delayed by up to `accuracy` so that wake ups can be coalesced, a zero
+    /// `accuracy` picks the default of 250ms. The source is enabled as [`Enabled::OneShot`], the
+    /// callback may move it to a new time with [`EventSourceRef::set_time()`] and enable it
+    /// again. If the callback returns an error, the source is disabled.
+    ///
+    /// This corresponds to [`sd_event_add_time`]
+    ///
+    /// [`sd_event_add_time`]: https://www.freedesktop.org/software/systemd/man/sd_event_add_time.html
+    pub fn add_time<F>(
+        &self,
+        clock: Clock,
+        when: Duration,
+        accuracy: Duration,
+        callback: F,
+    ) -> Result<EventSource>
+    where
+        F: FnMut(&mut EventSourceRef, Duration) -> Result<()> + 'static,
+    {
+        let f: extern "C" fn(*mut ffi::sd_event_source, u64, *mut c_void) -> c_int =
+            raw_time_handler::<F>;
+        add_source(callback, |source, userdata| unsafe {
+            ffi::sd_event_add_time(
+                self.as_ptr(),
+                source,
+                clock.as_raw(),
+                usec_from_duration(when),
+                usec_from_duration(accuracy),
+                Some(f),
+                userdata,
+            )
+        })
+    }
+
+    /// Call `callback` whenever `fd` is ready for one of the `events` (an `epoll(7)` mask like
+    /// `libc::EPOLLIN as u32`), with the events which occurred.
+    ///
+    /// The returned [`Source`] borrows `fd`, and removes it from the loop when dropped. The
+    /// source is enabled as [`Enabled::On`]. If the callback returns an error, the source is
+    /// disabled. [`EventRef::add_io_owned()`] is the safe alternative, handing `fd` over to the
+    /// source.
+    ///
+    /// This corresponds to [`sd_event_add_io`]
+    ///
+    /// # Safety
+    ///
+    /// The `Source` must be dropped before `fd` is closed. The borrow only ensures it doesn't
+    /// outlive `fd` if it is dropped: a leaked source (for example with [`std::mem::forget()`])
+    /// keeps watching the number `fd` had, which may refer to another file by then.
+    ///
+    /// [`sd_event_add_io`]: https://www.freedesktop.org/software/systemd/man/sd_event_add_io.html
+    pub unsafe fn add_io<'a, F>(
+        &self,
+        fd: BorrowedFd<'a>,
+        events: u32,
+        callback: F,
+    ) -> Result<Source<'a>>
+    where
+        F: FnMut(&mut EventSourceRef, BorrowedFd<'_>, u32) -> Result<()> + 'static,
+    {
+        Ok(Source {
+            source: self.add_io_raw(fd.as_raw_fd(), events, callback)?,
+            fd: PhantomData,
+        })
+    }
+
+    /// Like [`EventRef::add_io()`], but the source takes ownership of `fd` and closes it when it
+    /// is freed.
+    ///
+    /// This corresponds to [`sd_event_add_io`] and [`sd_event_source_set_io_fd_own`]
+    ///
+    /// [`sd_event_add_io`]: https://www.freedesktop.org/software/systemd/man/sd_event_add_io.html
+    /// [`sd_event_source_set_io_fd_own`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_io_fd.html
+    pub fn add_io_owned<F>(&self, fd: OwnedFd, events: u32, callback: F) -> Result<EventSource>
+    where
+        F: FnMut(&mut EventSourceRef, BorrowedFd<'_>, u32) -> Result<()> + 'static,
+    {
+        let source = self.add_io_raw(fd.as_raw_fd(), events, callback)?;
+        sd_try!(ffi::sd_event_source_set_io_fd_own(source.as_ptr(), 1));
+        let _ = fd.into_raw_fd();
+        Ok(source)
+    }
+
+    fn add_io_raw<F>(&self, fd: RawFd, events: u32, callback: F) -> Result<EventSource>
+    where
+        F: FnMut(&mut EventSourceRef, BorrowedFd<'_>, u32) -> Result<()> + 'static,
+    {
+        let f: extern "C" fn(*mut ffi::sd_event_source, c_int, u32, *mut c_void) -> c_int =
+            raw_io_handler::<F>;
+        add_source(callback, |source, userdata| unsafe {
+            ffi::sd_event_add_io(self.as_ptr(), source, fd, events, Some(f), userdata)
+        })
+    }
+
+    /// Call `callback` for each change to `path` matching `mask` (like `libc::IN_CREATE`), as
+    /// reported by [`inotify(7)`].
+    ///
+    /// The source is enabled as [`Enabled::On`]. If the callback returns an error, the source is
+    /// disabled.
+    ///
+    /// This corresponds to [`sd_event_add_inotify`]
+    ///
+    /// [`inotify(7)`]: https://man7.org/linux/man-pages/man7/inotify.7.html
+    /// [`sd_event_add_inotify`]: https://www.freedesktop.org/software/systemd/man/sd_event_add_inotify.html
+    pub fn add_inotify<P, F>(&self, path: P, mask: u32, callback: F) -> Result<EventSource>
+    where
+        P: AsRef<Path>,
+        F: FnMut(&mut EventSourceRef, &InotifyEvent<'_>) -> Result<()> + 'static,
+    {
+        let path = CString::new(path.as_ref().as_os_str().as_bytes())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let f: extern "C" fn(
+            *mut ffi::sd_event_source,
+            *const libc::inotify_event,
+            *mut c_void,
+        ) -> c_int = raw_inotify_handler::<F>;
+        add_source(callback, |source, userdata| unsafe {
+            ffi::sd_event_add_inotify(
+                self.as_ptr(),
+                source,
+                path.as_ptr(),
+                mask,
+                Some(f),
+                userdata,
+            )
+        })
+    }
+
+    /// Call `callback` once `after` has passed on `clock`, see [`EventRef::add_time()`].
+    ///
+    /// This is like [`sd_event_add_time_relative`], which requires systemd v247.
+    ///
+    /// [`sd_event_add_time_relative`]: https://www.freedesktop.org/software/systemd/man/sd_event_add_time.html
+    pub fn add_time_relative<F>(
+        &self,
+        clock: Clock,
+        after: Duration,
+        accuracy: Duration,
+        callback: F,
+    ) -> Result<EventSource>
+    where
+        F: FnMut(&mut EventSourceRef, Duration) -> Result<()> + 'static,
+    {
+        let when = self.now(clock)?.saturating_add(after);
+        self.add_time(clock, when, accuracy, callback)
+    }
+
+    /// Run a single iteration of the loop, waiting up to `timeout` (or indefinitely for `None`)
+    /// for an event. Returns whether a source was dispatched.
+    ///
+    /// This corresponds to [`sd_event_run`]
+    ///
+    /// [`sd_event_run`]: https://www.freedesktop.org/software/systemd/man/sd_event_run.html
+    #[inline]
+    pub fn run(&mut self, timeout: Option<Duration>) -> Result<bool> {
+        let timeout = timeout.map(usec_from_duration).unwrap_or(u64::MAX);
+        Ok(sd_try!(ffi::sd_event_run(self.as_ptr(), timeout)) > 0)
+    }
+
+    /// Run the loop until [`EventRef::exit()`] is called, returning the exit code passed to it.
+    ///
+    /// This corresponds to [`sd_event_loop`]
+    ///
+    /// [`sd_event_loop`]: https://www.freedesktop.org/software/systemd/man/sd_event_run.html
+    #[inline]
+    pub fn run_loop(&mut self) -> Result<c_int> {
+        Ok(sd_try!(ffi::sd_event_loop(self.as_ptr())))
+    }
+
+    /// Ask the loop to exit with `code` once the current iteration is done.
+    ///
+    /// This corresponds to [`sd_event_exit`]
+    ///
+    /// [`sd_event_exit`]: https://www.freedesktop.org/software/systemd/man/sd_event_exit.html
+    #[inline]
+    pub fn exit(&self, code: c_int) -> Result<()> {
+        sd_try!(ffi::sd_event_exit(self.as_ptr(), code));
+        Ok(())
+    }
+
+    /// Returns the file descriptor which becomes readable when the loop has events to dispatch,
+    /// to embed it into another event loop.
+    ///
+    /// This corresponds to [`sd_event_get_fd`]
+    ///
+    /// [`sd_event_get_fd`]: https://www.freedesktop.org/software/systemd/man/sd_event_get_fd.html
+    #[inline]
+    pub fn fd(&self) -> Result<c_int> {
+        Ok(sd_try!(ffi::sd_event_get_fd(self.as_ptr())))
+    }
+}
+
+impl fmt::Debug for EventRef {
+    fn fmt(&self, fmtr: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmtr.debug_struct("Event")
+            .field("raw", &self.as_ptr())
+            .finish()
+    }
+}
+
+impl EventSourceRef {
+    /// The event loop this source belongs to.
+    ///
+    /// This corresponds to [`sd_event_source_get_event`]
+    ///
+    /// [`sd_event_source_get_event`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_get_event.html
+    #[inline]
+    pub fn event(&self) -> &EventRef {
+        unsafe { EventRef::from_ptr(ffi::sd_event_source_get_event(self.as_ptr())) }
+    }
+
+    /// Set a name for the source, used in debug logging of sd-event.
+    ///
+    /// This corresponds to [`sd_event_source_set_description`]
+    ///
+    /// [`sd_event_source_set_description`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_description.html
+    #[inline]
+    pub fn set_description<S: CStrArgument>(&mut self, description: S) -> Result<()> {
+        let d = description.into_cstr();
+        sd_try!(ffi::sd_event_source_set_description(
+            self.as_ptr(),
+            d.as_ref().as_ptr()
+        ));
+        Ok(())
+    }
+
+    /// The name set with [`EventSourceRef::set_description()`].
+    ///
+    /// This corresponds to [`sd_event_source_get_description`]
+    ///
+    /// [`sd_event_source_get_description`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_description.html
+    #[inline]
+    pub fn description(&self) -> Result<&CStr> {
+        let mut d = ptr::null();
+        sd_try!(ffi::sd_event_source_get_description(self.as_ptr(), &mut d));
+        Ok(unsafe { CStr::from_ptr(d) })
+    }
+
+    /// The priority of the source, lower values are dispatched first.
+    ///
+    /// This corresponds to [`sd_event_source_get_priority`]
+    ///
+    /// [`sd_event_source_get_priority`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_priority.html
+    #[inline]
+    pub fn priority(&self) -> Result<i64> {
+        let mut priority = 0;
+        sd_try!(ffi::sd_event_source_get_priority(
+            self.as_ptr(),
+            &mut priority
+        ));
+        Ok(priority)
+    }
+
+    /// Change the priority of the source: when several sources are pending, those with the lowest
+    /// value are dispatched first. Sources start with [`PRIORITY_NORMAL`].
+    ///
+    /// This corresponds to [`sd_event_source_set_priority`]
+    ///
+    /// [`sd_event_source_set_priority`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_priority.html
+    #[inline]
+    pub fn set_priority(&mut self, priority: i64) -> Result<()> {
+        sd_try!(ffi::sd_event_source_set_priority(self.as_ptr(), priority));
+        Ok(())
+    }
+
+    /// Whether the source is dispatched.
+    ///
+    /// This corresponds to [`sd_event_source_get_enabled`]
+    ///
+    /// [`sd_event_source_get_enabled`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_enabled.html
+    #[inline]
+    pub fn enabled(&self) -> Result<Enabled> {
+        let mut enabled = 0;
+        sd_try!(ffi::sd_event_source_get_enabled(
+            self.as_ptr(),
+            &mut enabled
+        ));
+        Ok(Enabled::from_raw(enabled))
+    }
+
+    /// Enable or disable the source.
+    ///
+    /// This corresponds to [`sd_event_source_set_enabled`]
+    ///
+    /// [`sd_event_source_set_enabled`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_enabled.html
+    #[inline]
+    pub fn set_enabled(&mut self, enabled: Enabled) -> Result<()> {
+        sd_try!(ffi::sd_event_source_set_enabled(
+            self.as_ptr(),
+            enabled.as_raw()
+        ));
+        Ok(())
+    }
+
+    /// The rate limit set with [`EventSourceRef::set_ratelimit()`], if any, as the interval and
+    /// the number of dispatches allowed in it.
+    ///
+    /// This corresponds to [`sd_event_source_get_ratelimit`]
+    ///
+    /// [`sd_event_source_get_ratelimit`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_ratelimit.html
+    #[cfg(feature = "systemd_v248")]
+    #[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "systemd_v248")))]
+    pub fn ratelimit(&self) -> Result<Option<(Duration, u32)>> {
+        let mut interval = 0;
+        let mut burst = 0;
+        match crate::ffi_result(unsafe {
+            ffi::sd_event_source_get_ratelimit(self.as_ptr(), &mut interval, &mut burst)
+        }) {
+            Ok(_) => Ok(Some((Duration::from_micros(interval), burst))),
+            Err(e) if e.raw_os_error() == Some(libc::ENOEXEC) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Dispatch the source at most `burst` times per `interval`. Once the limit is hit, the
+    /// source is put to sleep until the interval is over. A zero `interval` or `burst` removes
+    /// the limit.
+    ///
+    /// This corresponds to [`sd_event_source_set_ratelimit`]
+    ///
+    /// [`sd_event_source_set_ratelimit`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_ratelimit.html
+    #[cfg(feature = "systemd_v248")]
+    #[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "systemd_v248")))]
+    #[inline]
+    pub fn set_ratelimit(&mut self, interval: Duration, burst: u32) -> Result<()> {
+        sd_try!(ffi::sd_event_source_set_ratelimit(
+            self.as_ptr(),
+            usec_from_duration(interval),
+            burst
+        ));
+        Ok(())
+    }
+
+    /// Whether the source is currently asleep because it hit its rate limit.
+    ///
+    /// This corresponds to [`sd_event_source_is_ratelimited`]
+    ///
+    /// [`sd_event_source_is_ratelimited`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_ratelimit.html
+    #[cfg(feature = "systemd_v248")]
+    #[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "systemd_v248")))]
+    #[inline]
+    pub fn is_ratelimited(&self) -> Result<bool> {
+        Ok(sd_try!(ffi::sd_event_source_is_ratelimited(self.as_ptr())) > 0)
+    }
+
+    /// The file descriptor an I/O source watches.
+    ///
+    /// This corresponds to [`sd_event_source_get_io_fd`]
+    ///
+    /// [`sd_event_source_get_io_fd`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_io_fd.html
+    #[inline]
+    pub fn io_fd(&self) -> Result<RawFd> {
+        Ok(sd_try!(ffi::sd_event_source_get_io_fd(self.as_ptr())))
+    }
+
+    /// Make an I/O source watch `fd` instead. [`Source::set_io_fd()`] does this safely for
+    /// sources watching a borrowed file descriptor.
+    ///
+    /// This corresponds to [`sd_event_source_set_io_fd`]
+    ///
+    /// # Safety
+    ///
+    /// `fd` must stay open for as long as the source exists, as the callback is handed it
+    /// borrowed. If the source owns its file descriptor (see [`EventRef::add_io_owned()`]) it
+    /// closes the previous one and takes ownership of `fd`.
+    ///
+    /// [`sd_event_source_set_io_fd`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_io_fd.html
+    #[inline]
+    pub unsafe fn set_io_fd(&mut self, fd: RawFd) -> Result<()> {
+        sd_try!(ffi::sd_event_source_set_io_fd(self.as_ptr(), fd));
+        Ok(())
+    }
+
+    /// The `epoll(7)` events an I/O source waits for.
+    ///
+    /// This corresponds to [`sd_event_source_get_io_events`]
+    ///
+    /// [`sd_event_source_get_io_events`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_io_events.html
+    #[inline]
+    pub fn io_events(&self) -> Result<u32> {
+        let mut events = 0;
+        sd_try!(ffi::sd_event_source_get_io_events(
+            self.as_ptr(),
+            &mut events
+        ));
+        Ok(events)
+    }
+
+    /// Change the `epoll(7)` events an I/O source waits for.
+    ///
+    /// This corresponds to [`sd_event_source_set_io_events`]
+    ///
+    /// [`sd_event_source_set_io_events`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_io_events.html
+    #[inline]
+    pub fn set_io_events(&mut self, events: u32) -> Result<()> {
+        sd_try!(ffi::sd_event_source_set_io_events(self.as_ptr(), events));
+        Ok(())
+    }
+
+    /// The `epoll(7)` events which occurred on an I/O source which is pending dispatch.
+    ///
+    /// This corresponds to [`sd_event_source_get_io_revents`]
+    ///
+    /// [`sd_event_source_get_io_revents`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_io_events.html
+    #[inline]
+    pub fn io_revents(&self) -> Result<u32> {
+        let mut revents = 0;
+        sd_try!(ffi::sd_event_source_get_io_revents(
+            self.as_ptr(),
+            &mut revents
+        ));
+        Ok(revents)
+    }
+
+    /// The time a timer source triggers at.
+    ///
+    /// This corresponds to [`sd_event_source_get_time`]
+    ///
+    /// [`sd_event_source_get_time`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_time.html
+    #[inline]
+    pub fn time(&self) -> Result<Duration> {
+        let mut usec = 0;
+        sd_try!(ffi::sd_event_source_get_time(self.as_ptr(), &mut usec));
+        Ok(Duration::from_micros(usec))
+    }
+
+    /// Move a timer source to trigger at `when` on its clock.
+    ///
+    /// This doesn't change whether the source is enabled: a one shot timer which already fired
+    /// must be enabled again.
+    ///
+    /// This corresponds to [`sd_event_source_set_time`]
+    ///
+    /// [`sd_event_source_set_time`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_time.html
+    #[inline]
+    pub fn set_time(&mut self, when: Duration) -> Result<()> {
+        sd_try!(ffi::sd_event_source_set_time(
+            self.as_ptr(),
+            usec_from_duration(when)
+        ));
+        Ok(())
+    }
+
+    /// Move a timer source to trigger once `after` has passed, counting from the start of the
+    /// current iteration of the loop, see [`EventSourceRef::set_time()`].
+    ///
+    /// This is like [`sd_event_source_set_time_relative`], which requires systemd v247.
+    ///
+    /// [`sd_event_source_set_time_relative`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_time.html
+    pub fn set_time_relative(&mut self, after: Duration) -> Result<()> {
+        let mut clock = 0;
+        sd_try!(ffi::sd_event_source_get_time_clock(
+            self.as_ptr(),
+            &mut clock
+        ));
+        let mut now = 0;
+        sd_try!(ffi::sd_event_now(
+            ffi::sd_event_source_get_event(self.as_ptr()),
+            clock,
+            &mut now
+        ));
+        self.set_time(Duration::from_micros(now).saturating_add(after))
+    }
+
+    /// The accuracy of a timer source.
+    ///
+    /// This corresponds to [`sd_event_source_get_time_accuracy`]
+    ///
+    /// [`sd_event_source_get_time_accuracy`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_time.html
+    #[inline]
+    pub fn accuracy(&self) -> Result<Duration> {
+        let mut usec = 0;
+        sd_try!(ffi::sd_event_source_get_time_accuracy(
+            self.as_ptr(),
+            &mut usec
+        ));
+        Ok(Duration::from_micros(usec))
+    }
+
+    /// Set how much a timer source may be delayed to coalesce wake ups, a zero `accuracy` picks
+    /// the default of 250ms.
+    ///
+    /// This corresponds to [`sd_event_source_set_time_accuracy`]
+    ///
+    /// [`sd_event_source_set_time_accuracy`]: https://www.freedesktop.org/software/systemd/man/sd_event_source_set_time.html
+    #[inline]
+    pub fn set_accuracy(&mut self, accuracy: Duration) -> Result<()> {
+        sd_try!(ffi::sd_event_source_set_time_accuracy(
+            self.as_ptr(),
+            usec_from_duration(accuracy)
+        ));
+        Ok(())
+    }
+}
+
+impl fmt::Debug for EventSourceRef {
+    fn fmt(&self, fmtr: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmtr.debug_struct("EventSource")
+            .field("raw", &self.as_ptr())
+            .field("enabled", &self.enabled())
+            .finish()
+    }
+}
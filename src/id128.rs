@@ -39,24 +39,83 @@ impl Default for Id128 {
     }
 }
 
+/// Serialized as the hex string for human-readable formats and as the 16 raw bytes otherwise.
 #[cfg(feature = "serde")]
 impl serde::Serialize for Id128 {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        serializer.collect_str(self)
+        if serializer.is_human_readable() {
+            let mut hex = [0u8; 32];
+            for (d, b) in hex.chunks_mut(2).zip(self.inner.bytes.iter()) {
+                d[0] = HEX[(b >> 4) as usize];
+                d[1] = HEX[(b & 0xf) as usize];
+            }
+            // only ASCII hex digits were written
+            serializer.serialize_str(std::str::from_utf8(&hex).unwrap())
+        } else {
+            serializer.serialize_bytes(&self.inner.bytes)
+        }
     }
 }
 
+/// Accepts both serialized forms, and IDs formatted as UUIDs.
 #[cfg(feature = "serde")]
 impl<'de> serde::Deserialize<'de> for Id128 {
     fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
     where
         D: serde::Deserializer<'de>,
     {
-        let cstr: Box<CStr> = serde::Deserialize::deserialize(deserializer)?;
-        Id128::from_cstr(&cstr).map_err(serde::de::Error::custom)
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(Id128Visitor)
+        } else {
+            deserializer.deserialize_bytes(Id128Visitor)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+#[cfg(feature = "serde")]
+struct Id128Visitor;
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::Visitor<'de> for Id128Visitor {
+    type Value = Id128;
+
+    fn expecting(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str("a 128-bit ID as a hex string or 16 bytes")
+    }
+
+    fn visit_str<E: serde::de::Error>(self, v: &str) -> std::result::Result<Id128, E> {
+        Id128::parse_str(v).ok_or_else(|| E::invalid_value(serde::de::Unexpected::Str(v), &self))
+    }
+
+    fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> std::result::Result<Id128, E> {
+        let mut r = Id128::default();
+        if v.len() != r.inner.bytes.len() {
+            return Err(E::invalid_length(v.len(), &self));
+        }
+        r.inner.bytes.copy_from_slice(v);
+        Ok(r)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Id128, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut r = Id128::default();
+        for (i, b) in r.inner.bytes.iter_mut().enumerate() {
+            *b = seq
+                .next_element()?
+                .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
+        }
+        if seq.next_element::<u8>()?.is_some() {
+            return Err(serde::de::Error::invalid_length(17, &self));
+        }
+        Ok(r)
     }
 }
 
@@ -67,6 +126,29 @@ impl Id128 {
         Ok(r)
     }
 
+    /// Parse 32 hex digits, optionally formatted as a UUID (`8-4-4-4-12` digits), like
+    /// `sd_id128_from_string()`.
+    #[cfg(feature = "serde")]
+    fn parse_str(s: &str) -> Option<Id128> {
+        let s = s.as_bytes();
+        let digits: Vec<u8> = match s.len() {
+            32 => s.to_vec(),
+            36 if [8, 13, 18, 23].iter().all(|&i| s[i] == b'-') => {
+                s.iter().copied().filter(|&c| c != b'-').collect()
+            }
+            _ => return None,
+        };
+        if digits.len() != 32 {
+            return None;
+        }
+        let mut r = Id128::default();
+        for (b, d) in r.inner.bytes.iter_mut().zip(digits.chunks(2)) {
+            let hex = |c: u8| (c as char).to_digit(16);
+            *b = ((hex(d[0])? << 4) | hex(d[1])?) as u8;
+        }
+        Some(r)
+    }
+
     pub fn from_random() -> Result<Id128> {
         let mut r = Id128::default();
         sd_try!(ffi::id128::sd_id128_randomize(&mut r.inner));
@@ -1,21 +1,69 @@
-use super::{free_cstring, usec_from_duration, Result};
+use super::Result;
+#[cfg(feature = "journal-read")]
+use super::{free_cstring, usec_from_duration};
+#[cfg(all(feature = "journal-write", not(feature = "journal-pure")))]
 use crate::ffi::const_iovec;
+#[cfg(any(feature = "journal-read", not(feature = "journal-pure")))]
 use crate::ffi::journal as ffi;
+#[cfg(feature = "journal-read")]
 use crate::id128::Id128;
 use cstr_argument::CStrArgument;
+#[cfg(feature = "journal-read")]
 use foreign_types::{foreign_type, ForeignType, ForeignTypeRef};
-use libc::{c_char, c_int, size_t};
+use libc::c_int;
+#[cfg(feature = "journal-read")]
+use libc::{c_char, size_t};
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
 use log::{self, Level, Log, Record, SetLoggerError};
 use memchr::memchr;
+#[cfg(feature = "journal-read")]
 use std::cell::RefCell;
+#[cfg(feature = "journal-read")]
+use std::cmp::Ordering;
+#[cfg(feature = "journal-read")]
 use std::collections::BTreeMap;
+#[cfg(feature = "journal-read")]
 use std::convert::TryInto;
+#[cfg(feature = "journal-read")]
 use std::io::ErrorKind::InvalidData;
+#[cfg(feature = "journal-read")]
 use std::mem::MaybeUninit;
+#[cfg(feature = "journal-read")]
 use std::os::raw::c_void;
+#[cfg(feature = "journal-read")]
 use std::os::unix::io::AsRawFd;
-use std::{fmt, io, ptr, result, slice, time};
+#[cfg(feature = "journal-read")]
+use std::sync::mpsc;
+use std::{fmt, io, result};
+#[cfg(feature = "journal-read")]
+use std::{ptr, slice, thread, time};
+
+pub mod fields;
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
+pub mod native;
+#[cfg(feature = "otel")]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "otel")))]
+pub mod otel;
+#[cfg(feature = "journal-read")]
+pub mod snapshot;
+#[cfg(feature = "journal-read")]
+pub mod stats;
+
+#[cfg(feature = "journal-pure")]
+fn collect_and_send<T, S>(args: T) -> c_int
+where
+    T: Iterator<Item = S>,
+    S: AsRef<str>,
+{
+    let args: Vec<S> = args.collect();
+    let fields: Vec<&str> = args.iter().map(|x| x.as_ref()).collect();
+    match native::with_default(|w| w.send(&fields)) {
+        Ok(()) => 0,
+        Err(e) => -e.raw_os_error().unwrap_or(libc::EINVAL),
+    }
+}
 
+#[cfg(all(feature = "journal-write", not(feature = "journal-pure")))]
 fn collect_and_send<T, S>(args: T) -> c_int
 where
     T: Iterator<Item = S>,
@@ -33,39 +81,347 @@ where
 ///
 /// This is a relatively low-level operation and probably not suitable unless
 /// you need precise control over which fields are sent to systemd.
+///
+/// Fields are passed through without validation; see [`try_send()`] for a variant which rejects
+/// malformed fields instead of producing a corrupted entry.
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
 pub fn send(args: &[&str]) -> c_int {
     collect_and_send(args.iter())
 }
 
+/// Check that `name` is a field name journald will accept from a client.
+///
+/// Names must be non-empty, at most 64 characters long, consist only of uppercase ASCII letters,
+/// digits and underscores, and must not start with a digit. Names starting with an underscore are
+/// reserved for trusted fields added by journald itself.
+fn validate_field_name(name: &str) -> result::Result<(), &'static str> {
+    if name.is_empty() {
+        return Err("empty field name");
+    }
+    if name.len() > 64 {
+        return Err("field name longer than 64 characters");
+    }
+    if name.starts_with('_') {
+        return Err("field names starting with '_' are reserved");
+    }
+    if name.as_bytes()[0].is_ascii_digit() {
+        return Err("field name starts with a digit");
+    }
+    if !name
+        .bytes()
+        .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit() || b == b'_')
+    {
+        return Err("field name may only contain 'A'-'Z', '0'-'9' and '_'");
+    }
+    Ok(())
+}
+
+/// A journal field name, checked against the rules journald applies to the fields it accepts:
+/// only `A`-`Z`, `0`-`9` and `_`, not starting with a digit or `_`, and at most 64 characters.
+///
+/// journald silently drops fields with invalid names, so validating them up front catches
+/// mistakes where they are made. Field names dereference to `str` and can be passed to
+/// [`JournalRef::get_data()`], so they work with the existing reading and matching functions:
+///
+/// ```no_run
+/// # #[cfg(feature = "journal-read")] {
+/// use systemd::journal::{self, FieldName};
+/// let unit = FieldName::new("MY_UNIT").unwrap();
+/// let mut j = journal::OpenOptions::default().open().unwrap();
+/// j.match_add(&unit, "foo.service").unwrap();
+/// if j.next().unwrap() > 0 {
+///     println!("{:?}", j.get_data(&unit).unwrap());
+/// }
+/// # }
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FieldName {
+    // always valid UTF-8, checked by `validate_field_name()`
+    name: std::ffi::CString,
+}
+
+impl FieldName {
+    fn from_validated(name: &str) -> Self {
+        FieldName {
+            // validated names contain no nul bytes
+            name: std::ffi::CString::new(name).unwrap(),
+        }
+    }
+
+    /// Check `name`, failing with [`io::ErrorKind::InvalidInput`] if it isn't a valid field name.
+    pub fn new(name: &str) -> Result<Self> {
+        validate_field_name(name).map_err(|what| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid journal field name {:?}: {}", name, what),
+            )
+        })?;
+        Ok(Self::from_validated(name))
+    }
+
+    /// Like [`FieldName::new()`], but also allow a single leading `_`, as used by the trusted
+    /// fields journald adds itself (like `_SYSTEMD_UNIT`).
+    ///
+    /// Such names can be used to read and match entries, but journald doesn't accept them from
+    /// clients.
+    pub fn trusted(name: &str) -> Result<Self> {
+        match name.strip_prefix('_') {
+            Some(rest) if !rest.starts_with('_') => {
+                FieldName::new(rest)?;
+                Ok(Self::from_validated(name))
+            }
+            _ => FieldName::new(name),
+        }
+    }
+
+    /// The field name
+    pub fn as_str(&self) -> &str {
+        unsafe { std::str::from_utf8_unchecked(self.name.as_bytes()) }
+    }
+
+    /// Whether this is a trusted field, which journald adds itself
+    pub fn is_trusted(&self) -> bool {
+        self.as_str().starts_with('_')
+    }
+}
+
+impl std::ops::Deref for FieldName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for FieldName {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<std::ffi::CStr> for FieldName {
+    fn as_ref(&self) -> &std::ffi::CStr {
+        &self.name
+    }
+}
+
+impl<'a> CStrArgument for &'a FieldName {
+    type Output = &'a std::ffi::CStr;
+
+    fn try_into_cstr(self) -> result::Result<Self::Output, cstr_argument::NulError<Self>> {
+        Ok(&self.name)
+    }
+}
+
+impl std::str::FromStr for FieldName {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        FieldName::trusted(s)
+    }
+}
+
+impl fmt::Debug for FieldName {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), fmt)
+    }
+}
+
+impl fmt::Display for FieldName {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(self.as_str())
+    }
+}
+
+/// Split a `NAME=value` pair, checking that the name is valid.
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
+fn split_field(field: &str) -> Result<(&str, &str)> {
+    let r = match memchr(b'=', field.as_bytes()) {
+        Some(eq) => validate_field_name(&field[..eq]).map(|_| (&field[..eq], &field[eq + 1..])),
+        None => Err("missing '=' separator"),
+    };
+    r.map_err(|what| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid journal field {:?}: {}", field, what),
+        )
+    })
+}
+
+/// Send preformatted fields to systemd, validating them first.
+///
+/// Unlike [`send()`], each field is checked to be a `NAME=value` pair with a valid name, and an
+/// error of kind [`io::ErrorKind::InvalidInput`] is returned (without sending anything) if any of
+/// them is not. Everything after the first `=` is the value, which may contain further `=` and
+/// newlines: values with newlines are transmitted using the length-prefixed binary encoding of the
+/// native journal protocol, so they arrive intact.
+///
+/// ```
+/// use systemd::journal;
+/// assert!(journal::try_send(&["MESSAGE\nX=a"]).is_err());
+/// assert!(journal::try_send(&["no separator"]).is_err());
+/// ```
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
+pub fn try_send(args: &[&str]) -> Result<()> {
+    for field in args {
+        split_field(field)?;
+    }
+    crate::ffi_result(collect_and_send(args.iter()))?;
+    Ok(())
+}
+
 /// Send a simple message to systemd-journald.
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
 pub fn print(lvl: u32, s: &str) -> c_int {
     send(&[&format!("PRIORITY={}", lvl), &format!("MESSAGE={}", s)])
 }
 
-enum SyslogLevel {
-    // Emerg = 0,
-    // Alert = 1,
-    // Crit = 2,
-    Err = 3,
+/// Severity of a message, the `PRIORITY=` field of journal entries.
+///
+/// See [`syslog(3)`] for their meaning.
+///
+/// [`syslog(3)`]: https://man7.org/linux/man-pages/man3/syslog.3.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Emergency = 0,
+    Alert = 1,
+    Critical = 2,
+    Error = 3,
     Warning = 4,
     Notice = 5,
     Info = 6,
     Debug = 7,
 }
 
-impl From<log::Level> for SyslogLevel {
+impl Priority {
+    /// Parse the value of a `PRIORITY=` field.
+    pub fn from_field(value: &str) -> Option<Self> {
+        Some(match value.parse::<u8>().ok()? {
+            0 => Priority::Emergency,
+            1 => Priority::Alert,
+            2 => Priority::Critical,
+            3 => Priority::Error,
+            4 => Priority::Warning,
+            5 => Priority::Notice,
+            6 => Priority::Info,
+            7 => Priority::Debug,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
+impl From<log::Level> for Priority {
     fn from(level: log::Level) -> Self {
         match level {
-            Level::Error => SyslogLevel::Err,
-            Level::Warn => SyslogLevel::Warning,
-            Level::Info => SyslogLevel::Notice,
-            Level::Debug => SyslogLevel::Info,
-            Level::Trace => SyslogLevel::Debug,
+            Level::Error => Priority::Error,
+            Level::Warn => Priority::Warning,
+            Level::Info => Priority::Notice,
+            Level::Debug => Priority::Info,
+            Level::Trace => Priority::Debug,
+        }
+    }
+}
+
+/// The kind of program which logged a message, the `SYSLOG_FACILITY=` field of journal
+/// entries.
+///
+/// See [`syslog(3)`] for their meaning.
+///
+/// [`syslog(3)`]: https://man7.org/linux/man-pages/man3/syslog.3.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Facility {
+    Kernel,
+    User,
+    Mail,
+    Daemon,
+    Auth,
+    Syslog,
+    Lpr,
+    News,
+    Uucp,
+    Cron,
+    AuthPriv,
+    Ftp,
+    /// One of the facilities not defined by Linux (12 to 15)
+    Reserved(u8),
+    /// `LOG_LOCAL0` to `LOG_LOCAL7`
+    Local(u8),
+}
+
+impl Facility {
+    /// Parse the value of a `SYSLOG_FACILITY=` field.
+    pub fn from_field(value: &str) -> Option<Self> {
+        Some(match value.parse::<u8>().ok()? {
+            0 => Facility::Kernel,
+            1 => Facility::User,
+            2 => Facility::Mail,
+            3 => Facility::Daemon,
+            4 => Facility::Auth,
+            5 => Facility::Syslog,
+            6 => Facility::Lpr,
+            7 => Facility::News,
+            8 => Facility::Uucp,
+            9 => Facility::Cron,
+            10 => Facility::AuthPriv,
+            11 => Facility::Ftp,
+            f @ 12..=15 => Facility::Reserved(f),
+            f @ 16..=23 => Facility::Local(f - 16),
+            _ => return None,
+        })
+    }
+}
+
+/// How a message reached the journal, the trusted `_TRANSPORT=` field of journal entries.
+///
+/// See [`systemd.journal-fields(7)`] for their meaning.
+///
+/// [`systemd.journal-fields(7)`]: https://www.freedesktop.org/software/systemd/man/systemd.journal-fields.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Transport {
+    /// The native journal protocol, used by `sd_journal_send()`
+    Journal,
+    /// The standard output or error of a service
+    Stdout,
+    /// The classic syslog socket
+    Syslog,
+    /// The kernel log
+    Kernel,
+    /// The kernel audit subsystem
+    Audit,
+    /// journald itself
+    Driver,
+}
+
+impl Transport {
+    /// Parse the value of a `_TRANSPORT=` field.
+    pub fn from_field(value: &str) -> Option<Self> {
+        Some(match value {
+            "journal" => Transport::Journal,
+            "stdout" => Transport::Stdout,
+            "syslog" => Transport::Syslog,
+            "kernel" => Transport::Kernel,
+            "audit" => Transport::Audit,
+            "driver" => Transport::Driver,
+            _ => return None,
+        })
+    }
+
+    /// The value of the `_TRANSPORT=` field, for matching entries with it.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Transport::Journal => "journal",
+            Transport::Stdout => "stdout",
+            Transport::Syslog => "syslog",
+            Transport::Kernel => "kernel",
+            Transport::Audit => "audit",
+            Transport::Driver => "driver",
         }
     }
 }
 
 /// Record a log entry, with custom priority and location.
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
 pub fn log(level: usize, file: &str, line: u32, module_path: &str, args: &fmt::Arguments<'_>) {
     send(&[
         &format!("PRIORITY={}", level),
@@ -77,9 +433,10 @@ pub fn log(level: usize, file: &str, line: u32, module_path: &str, args: &fmt::A
 }
 
 /// Send a `log::Record` to systemd-journald.
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
 pub fn log_record(record: &Record<'_>) {
     let keys = [
-        format!("PRIORITY={}", SyslogLevel::from(record.level()) as usize),
+        format!("PRIORITY={}", Priority::from(record.level()) as usize),
         format!("MESSAGE={}", record.args()),
         format!("TARGET={}", record.target()),
     ];
@@ -95,7 +452,9 @@ pub fn log_record(record: &Record<'_>) {
 }
 
 /// Logger implementation over systemd-journald.
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
 pub struct JournalLog;
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
 impl Log for JournalLog {
     fn enabled(&self, _metadata: &log::Metadata<'_>) -> bool {
         true
@@ -110,13 +469,112 @@ impl Log for JournalLog {
     }
 }
 
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
 static LOGGER: JournalLog = JournalLog;
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
 impl JournalLog {
     pub fn init() -> result::Result<(), SetLoggerError> {
         log::set_logger(&LOGGER)
     }
 }
 
+/// Logger writing to the journal and to another [`io::Write`] at the same time.
+///
+/// Each destination has its own level filter. Records written to the writer are formatted as
+/// `LEVEL target: message` lines. If journald isn't running when the logger is created, only the
+/// writer is used, which allows a single logging setup for environments with and without
+/// journald.
+///
+/// ```no_run
+/// use systemd::journal::TeeLog;
+/// TeeLog::new(std::io::stderr())
+///     .journal_level(log::LevelFilter::Debug)
+///     .writer_level(log::LevelFilter::Warn)
+///     .init()
+///     .unwrap();
+/// ```
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
+pub struct TeeLog<W> {
+    journal_level: log::LevelFilter,
+    writer_level: log::LevelFilter,
+    writer: std::sync::Mutex<W>,
+}
+
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
+impl<W: io::Write + Send> TeeLog<W> {
+    /// Create a logger passing all levels to both the journal and `writer`.
+    pub fn new(writer: W) -> Self {
+        let journal_level = if std::path::Path::new(native::JOURNAL_SOCKET).exists() {
+            log::LevelFilter::Trace
+        } else {
+            log::LevelFilter::Off
+        };
+        TeeLog {
+            journal_level,
+            writer_level: log::LevelFilter::Trace,
+            writer: std::sync::Mutex::new(writer),
+        }
+    }
+
+    /// Only send records up to `level` to the journal.
+    ///
+    /// This has no effect if journald wasn't running when the logger was created.
+    pub fn journal_level(mut self, level: log::LevelFilter) -> Self {
+        if self.journal_level != log::LevelFilter::Off {
+            self.journal_level = level;
+        }
+        self
+    }
+
+    /// Only write records up to `level` to the writer.
+    pub fn writer_level(mut self, level: log::LevelFilter) -> Self {
+        self.writer_level = level;
+        self
+    }
+
+    /// Install this as the global logger, and raise the global maximum level to cover both
+    /// destinations.
+    pub fn init(self) -> result::Result<(), SetLoggerError>
+    where
+        W: 'static,
+    {
+        log::set_max_level(self.journal_level.max(self.writer_level));
+        log::set_logger(Box::leak(Box::new(self)))
+    }
+}
+
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
+impl<W: io::Write + Send> Log for TeeLog<W> {
+    fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+        metadata.level() <= self.journal_level || metadata.level() <= self.writer_level
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if record.level() <= self.journal_level {
+            log_record(record);
+        }
+        if record.level() <= self.writer_level {
+            if let Ok(mut w) = self.writer.lock() {
+                // there is nowhere to report failures to write a log message
+                let _ = writeln!(
+                    w,
+                    "{} {}: {}",
+                    record.level(),
+                    record.target(),
+                    record.args()
+                );
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut w) = self.writer.lock() {
+            let _ = w.flush();
+        }
+    }
+}
+
+#[cfg(feature = "journal-read")]
 fn duration_from_usec(usec: u64) -> time::Duration {
     let secs = usec / 1_000_000;
     let sub_usec = (usec % 1_000_000) as u32;
@@ -124,11 +582,13 @@ fn duration_from_usec(usec: u64) -> time::Duration {
     time::Duration::new(secs, sub_nsec)
 }
 
+#[cfg(feature = "journal-read")]
 fn system_time_from_realtime_usec(usec: u64) -> time::SystemTime {
     let d = duration_from_usec(usec);
     time::UNIX_EPOCH + d
 }
 
+#[cfg(feature = "journal-read")]
 foreign_type! {
     /// A reader for systemd journal.
     ///
@@ -145,13 +605,17 @@ foreign_type! {
 /// A (name, value) pair formatted as a "NAME=value" byte string
 ///
 /// Internally, each journal entry includes a variety of these data entries.
+#[cfg(feature = "journal-read")]
 #[derive(Debug, PartialEq, Eq)]
 pub struct JournalEntryField<'a> {
     // TODO: this could be a CStr, which might be useful for downstream consumers
     data: &'a [u8],
     eq_offs: usize,
+    // data threshold of the journal when this was retrieved, 0 if unlimited
+    threshold: usize,
 }
 
+#[cfg(feature = "journal-read")]
 impl<'a> JournalEntryField<'a> {
     /// The entire data element
     pub fn data(&self) -> &[u8] {
@@ -177,8 +641,26 @@ impl<'a> JournalEntryField<'a> {
             None
         }
     }
+
+    /// Returns true if the data may have been truncated by the data threshold in effect when it
+    /// was retrieved.
+    ///
+    /// sd-journal never returns less than the threshold for a truncated field, so this is true
+    /// for any field at least as long as the threshold. Use [`JournalRef::get_data_full()`] to
+    /// obtain the complete value.
+    pub fn maybe_truncated(&self) -> bool {
+        self.threshold != 0 && self.data.len() >= self.threshold
+    }
+
+    fn with_threshold(data: &'a [u8], threshold: usize) -> Self {
+        Self {
+            threshold,
+            ..data.into()
+        }
+    }
 }
 
+#[cfg(feature = "journal-read")]
 impl<'a> From<&'a [u8]> for JournalEntryField<'a> {
     fn from(data: &'a [u8]) -> Self {
         // find the `=`
@@ -187,7 +669,11 @@ impl<'a> From<&'a [u8]> for JournalEntryField<'a> {
             None => data.len(),
         };
 
-        Self { data, eq_offs }
+        Self {
+            data,
+            eq_offs,
+            threshold: 0,
+        }
     }
 }
 
@@ -195,99 +681,1096 @@ impl<'a> From<&'a [u8]> for JournalEntryField<'a> {
 impl Iterator for JournalEntry<'a> {
     type Item = Result<JournalEntryEntry<'a>>;
 
-    pub fn next(&mut self) -> Option<Self::Item> {
-        let r = crate::ffi_result(unsafe { ffi::sd_journal_enumerate_data(
-            self.as_ptr(),
-            &mut data,
-            &mut sz)});
+    pub fn next(&mut self) -> Option<Self::Item> {
+        let r = crate::ffi_result(unsafe { ffi::sd_journal_enumerate_data(
+            self.as_ptr(),
+            &mut data,
+            &mut sz)});
+
+        let v = match r {
+            Err(e) => return Some(Err(e)),
+            Ok(v) => v,
+        };
+
+        if v == 0 {
+            return None;
+        }
+
+        // WARNING: slice is only valid until next call to one of `sd_journal_enumerate_data`,
+        // `sd_journal_get_data`, or `sd_journal_enumerate_avaliable_data`.
+        let b = unsafe { std::slice::from_raw_parts(data, sz as usize) };
+        let field = String::from_utf8_lossy(b);
+        let mut name_value = field.splitn(2, '=');
+        let name = name_value.next().unwrap();
+        let value = name_value.next().unwrap();
+        }
+    }
+}
+*/
+
+// A single log entry from journal.
+#[cfg(feature = "journal-read")]
+pub type JournalRecord = BTreeMap<String, String>;
+
+/// Typed accessors for the standard fields of a [`JournalRecord`].
+///
+/// ```no_run
+/// use systemd::journal::{self, JournalEntry, Transport};
+/// let mut j = journal::OpenOptions::default().open().unwrap();
+/// while let Some(r) = j.next_entry().unwrap() {
+///     if r.transport() == Some(Transport::Stdout) {
+///         println!("{}: {}", r.identifier().unwrap_or("?"), r["MESSAGE"]);
+///     }
+/// }
+/// ```
+#[cfg(feature = "journal-read")]
+pub trait JournalEntry {
+    /// How the entry reached the journal (`_TRANSPORT=`), `None` if missing or unknown.
+    fn transport(&self) -> Option<Transport>;
+
+    /// `SYSLOG_FACILITY=`, `None` if missing or invalid.
+    fn facility(&self) -> Option<Facility>;
+
+    /// The name of the program which logged the entry (`SYSLOG_IDENTIFIER=`).
+    fn identifier(&self) -> Option<&str>;
+}
+
+#[cfg(feature = "journal-read")]
+impl JournalEntry for JournalRecord {
+    fn transport(&self) -> Option<Transport> {
+        self.get(fields::_TRANSPORT)
+            .and_then(|t| Transport::from_field(t))
+    }
+
+    fn facility(&self) -> Option<Facility> {
+        self.get(fields::SYSLOG_FACILITY)
+            .and_then(|f| Facility::from_field(f))
+    }
+
+    fn identifier(&self) -> Option<&str> {
+        self.get(fields::SYSLOG_IDENTIFIER).map(String::as_str)
+    }
+}
+
+/// Represents the set of journal files to read.
+#[deprecated(
+    since = "0.8.0",
+    note = "Use `OpenOptions` instead. `JournalFiles` doesn't completely represent the filtering/inclusion options"
+)]
+#[cfg(feature = "journal-read")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum JournalFiles {
+    /// The system-wide journal.
+    System,
+    /// The current user's journal.
+    CurrentUser,
+    /// All journal files, including other users'.
+    All,
+}
+
+#[cfg(feature = "journal-read")]
+#[allow(deprecated)]
+impl JournalFiles {
+    fn as_flags(self) -> c_int {
+        match self {
+            JournalFiles::System => ffi::SD_JOURNAL_SYSTEM,
+            JournalFiles::CurrentUser => ffi::SD_JOURNAL_CURRENT_USER,
+            JournalFiles::All => 0,
+        }
+    }
+}
+
+/// A wrapper type that allows displaying a single entry in the journal
+#[cfg(feature = "journal-read")]
+pub struct DisplayEntryData<'a> {
+    // RULES:
+    //  - we can't move the cursor/position in the journal (no seeking, no
+    //   iteration/next/previous)
+    //  - we have _total_ ownership over data iteration. Do what ever necessary to get all the data
+    //    we want
+    journal: RefCell<&'a mut JournalRef>,
+}
+
+#[cfg(feature = "journal-read")]
+impl<'a> fmt::Display for DisplayEntryData<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(fmt, "{{")?;
+
+        let mut j = self.journal.borrow_mut();
+        j.restart_data();
+        loop {
+            match j.enumerate_data() {
+                Ok(Some(v)) => {
+                    writeln!(fmt, " \"{}\",", std::str::from_utf8(v.data()).unwrap())?;
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    writeln!(fmt, "E: {:?}", e)?;
+                    break;
+                }
+            }
+        }
+
+        writeln!(fmt, "}}")
+    }
+}
+
+#[cfg(feature = "journal-read")]
+impl<'a> From<&'a mut JournalRef> for DisplayEntryData<'a> {
+    fn from(v: &'a mut JournalRef) -> Self {
+        Self {
+            journal: RefCell::new(v),
+        }
+    }
+}
+
+/// Iterator over the `MESSAGE` field of the following journal entries, created by
+/// [`JournalRef::message_stream()`].
+///
+/// Only the `MESSAGE` field of each entry is read; entries without one are skipped. The value
+/// is subject to the journal's data threshold (see [`JournalRef::set_data_threshold()`]).
+///
+/// The stream also implements [`io::Read`], producing each message followed by a newline,
+/// similar to `journalctl -o cat`.
+#[cfg(feature = "journal-read")]
+pub struct MessageStream<'a> {
+    journal: &'a mut JournalRef,
+    // message not yet fully consumed by `read()`, and the position in it
+    pending: Vec<u8>,
+    pos: usize,
+}
+
+#[cfg(feature = "journal-read")]
+impl<'a> Iterator for MessageStream<'a> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.journal.next() {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            match self.journal.get_data(fields::MESSAGE) {
+                Ok(Some(f)) => return Some(Ok(f.value().unwrap_or_default().to_vec())),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "journal-read")]
+impl<'a> io::Read for MessageStream<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.pending.len() {
+            match self.next() {
+                None => return Ok(0),
+                Some(m) => {
+                    self.pending = m?;
+                    self.pending.push(b'\n');
+                    self.pos = 0;
+                }
+            }
+        }
+        let n = buf.len().min(self.pending.len() - self.pos);
+        buf[..n].copy_from_slice(&self.pending[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "journal-read")]
+impl<'a> fmt::Debug for MessageStream<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("MessageStream")
+            .field("pending", &(self.pending.len() - self.pos))
+            .finish()
+    }
+}
+
+/// Iterator following the end of the journal, created by [`tail()`].
+///
+/// The iterator never ends: once the entries present when it was created have been returned, it
+/// waits for new ones to be appended, like `journalctl --follow`.
+#[cfg(feature = "journal-read")]
+pub struct Tail<'a> {
+    journal: &'a mut JournalRef,
+    // the journal is on an entry which hasn't been returned yet
+    pending: bool,
+}
+
+#[cfg(feature = "journal-read")]
+impl<'a> Iterator for Tail<'a> {
+    type Item = Result<JournalRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pending {
+            self.pending = false;
+            return Some(self.journal.collect_entry());
+        }
+        loop {
+            match self.journal.next_entry() {
+                Ok(Some(r)) => return Some(Ok(r)),
+                Ok(None) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            if let Err(e) = self.journal.wait_resync(None) {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+#[cfg(feature = "journal-read")]
+impl<'a> fmt::Debug for Tail<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Tail")
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+/// Follow the end of the journal, starting with the last `lookback` entries, like
+/// `journalctl --lines=<lookback> --follow`.
+///
+/// Seeking to the tail places the journal *after* the last entry, and depending on the version
+/// of libsystemd, the first [`JournalRef::next()`] afterwards either returns nothing or the last
+/// entry again. To avoid relying on either behaviour, this steps back onto an entry before
+/// switching to forward iteration, so that with a `lookback` of `0` only entries appended later
+/// are returned, and otherwise exactly the last `lookback` entries (or all of them, if there are
+/// fewer) are returned first, oldest first.
+///
+/// ```no_run
+/// use systemd::journal;
+/// let mut j = journal::OpenOptions::default().open().unwrap();
+/// for r in journal::tail(&mut j, 10).unwrap() {
+///     println!("{:?}", r.unwrap().get("MESSAGE"));
+/// }
+/// ```
+#[cfg(feature = "journal-read")]
+pub fn tail(journal: &mut JournalRef, lookback: usize) -> Result<Tail<'_>> {
+    journal.seek_tail()?;
+    let moved = journal.previous_skip(lookback.max(1) as u64)?;
+    Ok(Tail {
+        journal,
+        // with no lookback, the last entry only serves as the starting point
+        pending: lookback > 0 && moved > 0,
+    })
+}
+
+/// Follow the end of `journal` from an [`Event`](crate::event::Event) loop, passing each entry
+/// appended from now on to `callback`, so that following the journal can share a loop with other
+/// work, like serving a bus attached with `BusRef::attach_event()`.
+///
+/// The journal is positioned like [`tail()`] with no lookback, and owned by the returned source:
+/// dropping the source stops following and closes the journal. If `callback` returns an error,
+/// the source is disabled.
+///
+/// ```no_run
+/// use systemd::event::Event;
+/// use systemd::journal;
+/// let mut ev = Event::new().unwrap();
+/// let j = journal::OpenOptions::default().open().unwrap();
+/// let _source = journal::add_to_event(&ev, j, |r| {
+///     println!("{:?}", r.get("MESSAGE"));
+///     Ok(())
+/// })
+/// .unwrap();
+/// ev.run_loop().unwrap();
+/// ```
+#[cfg(all(feature = "journal-read", feature = "event"))]
+pub fn add_to_event<F>(
+    event: &crate::event::EventRef,
+    mut journal: Journal,
+    mut callback: F,
+) -> Result<crate::event::EventSource>
+where
+    F: FnMut(JournalRecord) -> Result<()> + 'static,
+{
+    journal.seek_tail()?;
+    journal.previous()?;
+    // the journal closes its own fd, the source gets a duplicate of it
+    let fd =
+        unsafe { std::os::unix::io::BorrowedFd::borrow_raw(journal.fd()?) }.try_clone_to_owned()?;
+    let events = journal.events()? as u32;
+    event.add_io_owned(fd, events, move |_, _, _| {
+        let cursor = journal.cursor().ok();
+        if let (JournalWaitResult::Invalidate, Some(c)) = (journal.process()?, cursor) {
+            journal.resync(c)?;
+        }
+        while let Some(r) = journal.next_entry()? {
+            callback(r)?;
+        }
+        Ok(())
+    })
+}
+
+/// Read the entries matching `filter` on a dedicated thread, and receive them through a channel,
+/// to consume the journal from other threads although [`Journal`] isn't `Send`.
+///
+/// The thread opens the journal with `options` and sends the entries oldest first. Without
+/// `follow` it ends once it reached the end of the journal, otherwise it waits for new entries
+/// like [`tail()`]. At most `capacity` entries are queued, after which the thread waits for
+/// them to be received. The thread also ends when the receiver is dropped, noticed when the next
+/// entry is sent. It returns errors opening or reading the journal.
+///
+/// ```no_run
+/// use systemd::journal::{self, Filter, OpenOptions};
+/// let mut f = Filter::new();
+/// f.match_add("_SYSTEMD_UNIT", "sshd.service");
+/// let (reader, entries) = journal::spawn_reader(OpenOptions::default(), f, false, 64);
+/// for r in entries {
+///     println!("{:?}", r.get("MESSAGE"));
+/// }
+/// reader.join().unwrap().unwrap();
+/// ```
+#[cfg(feature = "journal-read")]
+pub fn spawn_reader(
+    options: OpenOptions,
+    filter: Filter,
+    follow: bool,
+    capacity: usize,
+) -> (
+    thread::JoinHandle<Result<()>>,
+    mpsc::Receiver<JournalRecord>,
+) {
+    let (tx, rx) = mpsc::sync_channel(capacity);
+    let reader = thread::spawn(move || {
+        let mut journal = options.open()?;
+        journal.apply_filter(&filter)?;
+        loop {
+            match journal.next_entry()? {
+                Some(r) => {
+                    if tx.send(r).is_err() {
+                        return Ok(());
+                    }
+                }
+                None if follow => {
+                    journal.wait_resync(None)?;
+                }
+                None => return Ok(()),
+            }
+        }
+    });
+    (reader, rx)
+}
+
+/// The entries of a [`Journal`] as a `Stream`, waiting for new ones on the tokio runtime once it
+/// reached the end, so services forwarding logs can read them with
+/// `while let Some(r) = stream.next().await`.
+///
+/// Entries are read from the current position of the journal onwards, so seek it first to start
+/// elsewhere; [`JournalStream::tail()`] follows only the entries appended after it was created.
+/// The stream never ends on its own. Changes signaled on [`JournalRef::fd()`] are handled with
+/// [`JournalRef::process()`], continuing after the last entry read when files are rotated or
+/// removed.
+///
+/// ```no_run
+/// use std::future::poll_fn;
+/// use std::pin::Pin;
+/// use futures_core::Stream;
+/// use systemd::journal::{self, JournalStream};
+///
+/// # async fn f() -> systemd::Result<()> {
+/// let j = journal::OpenOptions::default().open()?;
+/// let mut stream = JournalStream::tail(j)?;
+/// while let Some(r) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+///     println!("{:?}", r?.get("MESSAGE"));
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(all(feature = "journal-read", feature = "tokio"))]
+pub struct JournalStream {
+    // dropped (deregistered) before the journal closes the file descriptor
+    fd: tokio::io::unix::AsyncFd<JournalFd>,
+    journal: Journal,
+}
+
+/// The file descriptor of a journal, registered with the runtime separately from the `Journal`
+/// so it can be waited on while the journal is read.
+#[cfg(all(feature = "journal-read", feature = "tokio"))]
+struct JournalFd(c_int);
+
+#[cfg(all(feature = "journal-read", feature = "tokio"))]
+impl AsRawFd for JournalFd {
+    fn as_raw_fd(&self) -> c_int {
+        self.0
+    }
+}
+
+#[cfg(all(feature = "journal-read", feature = "tokio"))]
+impl JournalStream {
+    /// Read the entries of `journal` from its current position, registering its file descriptor
+    /// with the tokio runtime of the current task.
+    pub fn new(journal: Journal) -> Result<JournalStream> {
+        let fd = tokio::io::unix::AsyncFd::with_interest(
+            JournalFd(journal.fd()?),
+            tokio::io::Interest::READABLE,
+        )?;
+        Ok(JournalStream { fd, journal })
+    }
+
+    /// Follow the end of `journal`, positioned like [`tail()`] with no lookback.
+    pub fn tail(mut journal: Journal) -> Result<JournalStream> {
+        journal.seek_tail()?;
+        journal.previous()?;
+        JournalStream::new(journal)
+    }
+
+    /// Deregister the journal from the runtime and return it.
+    pub fn into_inner(self) -> Journal {
+        self.journal
+    }
+}
+
+#[cfg(all(feature = "journal-read", feature = "tokio"))]
+impl std::ops::Deref for JournalStream {
+    type Target = JournalRef;
+
+    fn deref(&self) -> &JournalRef {
+        &self.journal
+    }
+}
+
+#[cfg(all(feature = "journal-read", feature = "tokio"))]
+impl std::ops::DerefMut for JournalStream {
+    fn deref_mut(&mut self) -> &mut JournalRef {
+        &mut self.journal
+    }
+}
+
+#[cfg(all(feature = "journal-read", feature = "tokio"))]
+impl futures_core::Stream for JournalStream {
+    type Item = Result<JournalRecord>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<JournalRecord>>> {
+        use std::task::Poll;
+
+        let this = self.get_mut();
+        loop {
+            match this.journal.next_entry() {
+                Ok(Some(r)) => return Poll::Ready(Some(Ok(r))),
+                Ok(None) => {}
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+            let mut guard = match this.fd.poll_read_ready(cx) {
+                Poll::Ready(Ok(guard)) => guard,
+                Poll::Ready(Err(e)) => return Poll::Ready(Some(Err(e))),
+                Poll::Pending => return Poll::Pending,
+            };
+            // `process()` reads all pending notifications, so the readiness is used up
+            guard.clear_ready();
+            let cursor = this.journal.cursor().ok();
+            let changed = this.journal.process().and_then(|r| match (r, cursor) {
+                (JournalWaitResult::Invalidate, Some(c)) => this.journal.resync(c),
+                _ => Ok(()),
+            });
+            if let Err(e) = changed {
+                return Poll::Ready(Some(Err(e)));
+            }
+        }
+    }
+}
+
+/// A message of the kernel log, read from an entry with `_TRANSPORT=kernel`.
+#[cfg(feature = "journal-read")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KernelEntry {
+    /// The message (`MESSAGE=`)
+    pub message: String,
+    /// `PRIORITY=`
+    pub priority: Option<Priority>,
+    /// `SYSLOG_FACILITY=`, [`Facility::Kernel`] unless the message was written to `/dev/kmsg`
+    /// from userspace
+    pub facility: Option<Facility>,
+    /// The device the message is about, like `b8:0` or `+usb:1-1` (`_KERNEL_DEVICE=`)
+    pub device: Option<String>,
+    /// The subsystem of that device, like `block` (`_KERNEL_SUBSYSTEM=`)
+    pub subsystem: Option<String>,
+}
+
+#[cfg(feature = "journal-read")]
+impl KernelEntry {
+    /// Extract the kernel message from `record`, or `None` if it isn't from the kernel log.
+    pub fn from_record(record: &JournalRecord) -> Option<Self> {
+        if record.transport() != Some(Transport::Kernel) {
+            return None;
+        }
+        Some(KernelEntry {
+            message: record.get(fields::MESSAGE).cloned().unwrap_or_default(),
+            priority: record
+                .get(fields::PRIORITY)
+                .and_then(|p| Priority::from_field(p)),
+            facility: record.facility(),
+            device: record.get(fields::_KERNEL_DEVICE).cloned(),
+            subsystem: record.get(fields::_KERNEL_SUBSYSTEM).cloned(),
+        })
+    }
+}
+
+/// Iterator following the kernel log, created by [`dmesg_stream()`].
+#[cfg(feature = "journal-read")]
+#[derive(Debug)]
+pub struct DmesgStream<'a> {
+    tail: Tail<'a>,
+}
+
+#[cfg(feature = "journal-read")]
+impl<'a> Iterator for DmesgStream<'a> {
+    type Item = Result<KernelEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.tail.next()? {
+                Ok(r) => {
+                    if let Some(e) = KernelEntry::from_record(&r) {
+                        return Some(Ok(e));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Follow the kernel log like `dmesg --follow`, starting with the last `lookback` messages, see
+/// [`tail()`].
+///
+/// The matches of `journal` are replaced by one for `_TRANSPORT=kernel`.
+///
+/// ```no_run
+/// use systemd::journal::{self, Priority};
+/// let mut j = journal::OpenOptions::default().open().unwrap();
+/// for e in journal::dmesg_stream(&mut j, 10).unwrap() {
+///     let e = e.unwrap();
+///     if e.priority <= Some(Priority::Warning) {
+///         println!("{:?} {}", e.device, e.message);
+///     }
+/// }
+/// ```
+#[cfg(feature = "journal-read")]
+pub fn dmesg_stream(journal: &mut JournalRef, lookback: usize) -> Result<DmesgStream<'_>> {
+    journal
+        .match_flush()?
+        .match_add(fields::_TRANSPORT, Transport::Kernel.as_str())?;
+    Ok(DmesgStream {
+        tail: tail(journal, lookback)?,
+    })
+}
+
+/// Direction of the entries returned by [`page()`] and [`merge()`].
+#[cfg(feature = "journal-read")]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Oldest first, continuing with newer entries.
+    Forward,
+    /// Newest first, continuing with older entries.
+    Backward,
+}
+
+/// Read up to `limit` entries following the entry at `after` (a cursor returned by
+/// [`JournalRef::cursor()`] or a previous call) in `direction`, for paginating the journal with
+/// stable tokens, as log viewers do.
+///
+/// Without a cursor, pages start at the head (forward) or the tail (backward) of the journal.
+/// Returns the entries, in the order they were read, and the cursor to pass to get the next page:
+/// that of the last entry returned, or `after` if there were none. A page shorter than `limit`
+/// means that the end of the journal was reached, but since the journal grows, asking again later
+/// with the same cursor may return more.
+///
+/// The entry at `after` itself is never returned again. If it was removed from the journal in the
+/// meantime (by vacuuming, for example), the page starts with the closest remaining entry instead
+/// of skipping it.
+///
+/// ```no_run
+/// use systemd::journal::{self, Direction};
+/// let mut j = journal::OpenOptions::default().open().unwrap();
+/// let (first, token) = journal::page(&mut j, None, 50, Direction::Backward).unwrap();
+/// let (second, _) = journal::page(&mut j, token.as_deref(), 50, Direction::Backward).unwrap();
+/// ```
+#[cfg(feature = "journal-read")]
+pub fn page(
+    journal: &mut JournalRef,
+    after: Option<&str>,
+    limit: usize,
+    direction: Direction,
+) -> Result<(Vec<JournalRecord>, Option<String>)> {
+    let step = |j: &mut JournalRef| match direction {
+        Direction::Forward => j.next(),
+        Direction::Backward => j.previous(),
+    };
+
+    let mut entries = Vec::new();
+    let mut last = after.map(str::to_owned);
+    if limit == 0 {
+        return Ok((entries, last));
+    }
+
+    match after {
+        None => match direction {
+            Direction::Forward => journal.seek_head()?,
+            Direction::Backward => journal.seek_tail()?,
+        },
+        Some(cursor) => {
+            // Seeking only sets the location, the next step lands on the entry the cursor
+            // refers to, or if it is gone, on the closest one in `direction`.
+            journal.seek_cursor(cursor)?;
+            if step(journal)? > 0 && !journal.test_cursor(cursor)? {
+                entries.push(journal.collect_entry()?);
+                last = Some(journal.cursor()?);
+            }
+        }
+    }
+
+    while entries.len() < limit && step(journal)? > 0 {
+        entries.push(journal.collect_entry()?);
+        last = Some(journal.cursor()?);
+    }
+    Ok((entries, last))
+}
+
+/// Position of an entry in time, to interleave the entries of several journals.
+#[cfg(feature = "journal-read")]
+#[derive(Clone, Debug)]
+struct EntryOrder {
+    boot_id: Id128,
+    monotonic: u64,
+    // sequence number id and sequence number, taken from the cursor
+    seqnum: Option<(String, u64)>,
+    realtime: time::SystemTime,
+}
+
+#[cfg(feature = "journal-read")]
+impl EntryOrder {
+    fn read(journal: &JournalRef) -> Result<Self> {
+        let (monotonic, boot_id) = journal.monotonic_timestamp()?;
+        let cursor = journal.cursor()?;
+        let field = |key: &str| {
+            cursor
+                .split(';')
+                .find_map(|f| f.strip_prefix(key)?.strip_prefix('='))
+        };
+        let seqnum = match (field("s"), field("i")) {
+            (Some(id), Some(n)) => u64::from_str_radix(n, 16).ok().map(|n| (id.to_owned(), n)),
+            _ => None,
+        };
+        Ok(EntryOrder {
+            boot_id,
+            monotonic,
+            seqnum,
+            realtime: journal.timestamp()?,
+        })
+    }
+
+    /// Entries of the same boot are ordered by their monotonic timestamp, which unlike the
+    /// wallclock never jumps, then by sequence number when both were written by the same
+    /// journal; entries of different boots by their wallclock time.
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.boot_id != other.boot_id {
+            return self.realtime.cmp(&other.realtime);
+        }
+        let seqnum = match (&self.seqnum, &other.seqnum) {
+            (Some((a_id, a)), Some((b_id, b))) if a_id == b_id => a.cmp(b),
+            _ => Ordering::Equal,
+        };
+        self.monotonic
+            .cmp(&other.monotonic)
+            .then(seqnum)
+            .then(self.realtime.cmp(&other.realtime))
+    }
+}
+
+/// Iterator over the entries of several journals, interleaved in time, created by [`merge()`].
+///
+/// Each item is the index of the journal the entry was read from and the entry.
+#[cfg(feature = "journal-read")]
+pub struct Merge<'a> {
+    journals: Vec<&'a mut JournalRef>,
+    // for each journal, the entry it is on if that hasn't been returned yet, or `None` once the
+    // journal is exhausted
+    heads: Vec<Option<Option<(EntryOrder, JournalRecord)>>>,
+    direction: Direction,
+}
+
+#[cfg(feature = "journal-read")]
+impl<'a> Iterator for Merge<'a> {
+    type Item = Result<(usize, JournalRecord)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (journal, head) in self.journals.iter_mut().zip(self.heads.iter_mut()) {
+            if let Some(None) = head {
+                let moved = match self.direction {
+                    Direction::Forward => journal.next(),
+                    Direction::Backward => journal.previous(),
+                };
+                *head = match moved {
+                    Ok(0) => None,
+                    Ok(_) => match EntryOrder::read(journal)
+                        .and_then(|o| Ok((o, journal.collect_entry()?)))
+                    {
+                        Ok(e) => Some(Some(e)),
+                        Err(e) => return Some(Err(e)),
+                    },
+                    Err(e) => return Some(Err(e)),
+                };
+            }
+        }
+
+        let mut best: Option<(usize, &EntryOrder)> = None;
+        for (i, head) in self.heads.iter().enumerate() {
+            if let Some(Some((order, _))) = head {
+                let better = match best {
+                    None => true,
+                    // on ties the journal listed first wins, keeping the output stable
+                    Some((_, b)) => match self.direction {
+                        Direction::Forward => order.cmp(b) == Ordering::Less,
+                        Direction::Backward => order.cmp(b) == Ordering::Greater,
+                    },
+                };
+                if better {
+                    best = Some((i, order));
+                }
+            }
+        }
+        let i = best?.0;
+        let (_, record) = self.heads[i].as_mut()?.take()?;
+        Some(Ok((i, record)))
+    }
+}
+
+#[cfg(feature = "journal-read")]
+impl<'a> fmt::Debug for Merge<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Merge")
+            .field("journals", &self.journals.len())
+            .field("direction", &self.direction)
+            .finish()
+    }
+}
+
+/// Interleave the entries of several journals in `direction`, like `journalctl` does with the
+/// files it opens, for example to show the journals of several machines or containers newest
+/// first like `journalctl --reverse`.
+///
+/// Stepping each journal backwards and taking turns between them doesn't order the entries. The
+/// entries the journals are on are compared instead: within a boot by their monotonic timestamp
+/// and then their sequence number, and across boots by their wallclock timestamp. Entries which
+/// compare equal are returned in the order of `journals`, so that the output is stable.
+///
+/// The journals are seeked to their head (forward) or tail (backward); their matches are kept.
+///
+/// ```no_run
+/// use systemd::journal::{self, Direction};
+/// let mut system = journal::OpenDirectoryOptions::default()
+///     .open_directory("/var/log/journal")
+///     .unwrap();
+/// let mut container = journal::OpenDirectoryOptions::default()
+///     .open_directory("/var/lib/machines/web/var/log/journal")
+///     .unwrap();
+/// for r in journal::merge(vec![&mut *system, &mut *container], Direction::Backward)
+///     .unwrap()
+///     .take(100)
+/// {
+///     let (source, record) = r.unwrap();
+///     println!("{} {:?}", source, record.get("MESSAGE"));
+/// }
+/// ```
+#[cfg(feature = "journal-read")]
+pub fn merge<'a, I>(journals: I, direction: Direction) -> Result<Merge<'a>>
+where
+    I: IntoIterator<Item = &'a mut JournalRef>,
+{
+    let mut journals: Vec<_> = journals.into_iter().collect();
+    for journal in &mut journals {
+        match direction {
+            Direction::Forward => journal.seek_head()?,
+            Direction::Backward => journal.seek_tail()?,
+        }
+    }
+    Ok(Merge {
+        heads: journals.iter().map(|_| Some(None)).collect(),
+        journals,
+        direction,
+    })
+}
+
+/// Progress of a [`scan_with_progress()`].
+#[cfg(feature = "journal-read")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScanProgress {
+    /// Number of entries passed to the callback so far
+    pub entries: u64,
+    /// Estimated fraction of the scan done, from `0.0` to `1.0`
+    pub fraction: f64,
+}
 
-        let v = match r {
-            Err(e) => return Some(Err(e)),
-            Ok(v) => v,
-        };
+/// Where `at` lies between `first` and `last`, from `0.0` to `1.0`.
+#[cfg(feature = "journal-read")]
+fn time_fraction(first: time::SystemTime, last: time::SystemTime, at: time::SystemTime) -> f64 {
+    let span = last.duration_since(first).unwrap_or_default().as_secs_f64();
+    let done = at.duration_since(first).unwrap_or_default().as_secs_f64();
+    if span > 0.0 {
+        (done / span).min(1.0)
+    } else {
+        0.0
+    }
+}
 
-        if v == 0 {
-            return None;
+/// Pass each entry matching `filter` to `f`, oldest first, and report the progress of the scan to
+/// `progress`, so that interactive tools can show how far a scan of the whole journal got.
+///
+/// Counting the matching entries up front would take as long as the scan itself, so the progress
+/// is estimated from where the timestamp of the current entry lies between those of the oldest
+/// and newest entries of the journal (see [`JournalRef::cutoff_realtime()`]). `progress` is
+/// called whenever the estimate advanced by at least a percent, and once with a `fraction` of
+/// `1.0` when the scan is done. Returns the number of entries scanned; an error returned by `f`
+/// stops the scan.
+///
+/// The matches of `journal` are replaced by `filter`.
+///
+/// ```no_run
+/// use systemd::journal::{self, Filter};
+/// let mut j = journal::OpenOptions::default().open().unwrap();
+/// let mut f = Filter::new();
+/// f.match_add("PRIORITY", "3");
+/// journal::scan_with_progress(
+///     &mut j,
+///     &f,
+///     |r| Ok(println!("{:?}", r.get("MESSAGE"))),
+///     |p| eprintln!("{:.0}%", p.fraction * 100.0),
+/// )
+/// .unwrap();
+/// ```
+#[cfg(feature = "journal-read")]
+pub fn scan_with_progress<F, P>(
+    journal: &mut JournalRef,
+    filter: &Filter,
+    mut f: F,
+    mut progress: P,
+) -> Result<u64>
+where
+    F: FnMut(JournalRecord) -> Result<()>,
+    P: FnMut(ScanProgress),
+{
+    journal.match_flush()?.apply_filter(filter)?;
+    journal.seek_head()?;
+    let bounds = journal.cutoff_realtime()?;
+
+    let mut p = ScanProgress {
+        entries: 0,
+        fraction: 0.0,
+    };
+    let mut reported = None;
+    while let Some(r) = journal.next_entry()? {
+        if let Some((first, last)) = bounds {
+            p.fraction = p
+                .fraction
+                .max(time_fraction(first, last, journal.timestamp()?));
         }
-
-        // WARNING: slice is only valid until next call to one of `sd_journal_enumerate_data`,
-        // `sd_journal_get_data`, or `sd_journal_enumerate_avaliable_data`.
-        let b = unsafe { std::slice::from_raw_parts(data, sz as usize) };
-        let field = String::from_utf8_lossy(b);
-        let mut name_value = field.splitn(2, '=');
-        let name = name_value.next().unwrap();
-        let value = name_value.next().unwrap();
+        f(r)?;
+        p.entries += 1;
+        let percent = (p.fraction * 100.0) as u32;
+        if reported < Some(percent) {
+            reported = Some(percent);
+            progress(p);
         }
     }
+    p.fraction = 1.0;
+    progress(p);
+    Ok(p.entries)
 }
-*/
 
-// A single log entry from journal.
-pub type JournalRecord = BTreeMap<String, String>;
+/// Journal usage of a unit, as computed by [`usage_by_unit()`].
+#[cfg(feature = "journal-read")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UnitUsage {
+    /// Number of entries
+    pub entries: u64,
+    /// Sum of the sizes of the entries' fields (`NAME=value`), in bytes
+    pub bytes: u64,
+}
 
-/// Represents the set of journal files to read.
-#[deprecated(
-    since = "0.8.0",
-    note = "Use `OpenOptions` instead. `JournalFiles` doesn't completely represent the filtering/inclusion options"
-)]
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub enum JournalFiles {
-    /// The system-wide journal.
-    System,
-    /// The current user's journal.
-    CurrentUser,
-    /// All journal files, including other users'.
-    All,
+/// Sum up the entries logged since `since` by each unit (their `_SYSTEMD_UNIT` field), to find
+/// the units which produce the most log data.
+///
+/// Entries without a unit, like those of the kernel, are accounted under the empty string.
+///
+/// Sizes are those of the uncompressed field data, as sd-journal doesn't expose how much space
+/// an entry takes up on disk after compression and deduplication of identical fields. They are
+/// still a good measure of which units are responsible for most of the journal's size.
+///
+/// This reads all matching entries, including their complete field data (the data threshold is
+/// lifted while doing so), so matches added to the journal before restrict what is counted.
+///
+/// ```no_run
+/// use std::time::{Duration, SystemTime};
+/// use systemd::journal;
+/// let mut j = journal::OpenOptions::default().open().unwrap();
+/// let since = SystemTime::now() - Duration::from_secs(3600);
+/// let mut usage: Vec<_> = journal::usage_by_unit(&mut j, since).unwrap().into_iter().collect();
+/// usage.sort_by_key(|(_, u)| std::cmp::Reverse(u.bytes));
+/// for (unit, u) in usage.iter().take(10) {
+///     println!("{:>12} bytes {:>8} entries {}", u.bytes, u.entries, unit);
+/// }
+/// ```
+#[cfg(feature = "journal-read")]
+pub fn usage_by_unit(
+    journal: &mut JournalRef,
+    since: time::SystemTime,
+) -> Result<BTreeMap<String, UnitUsage>> {
+    let threshold = journal.data_threshold()?;
+    journal.set_data_threshold(0)?;
+    let r = sum_usage(journal, since);
+    journal.set_data_threshold(threshold)?;
+    r
 }
 
-#[allow(deprecated)]
-impl JournalFiles {
-    fn as_flags(self) -> c_int {
-        match self {
-            JournalFiles::System => ffi::SD_JOURNAL_SYSTEM,
-            JournalFiles::CurrentUser => ffi::SD_JOURNAL_CURRENT_USER,
-            JournalFiles::All => 0,
+#[cfg(feature = "journal-read")]
+fn sum_usage(
+    journal: &mut JournalRef,
+    since: time::SystemTime,
+) -> Result<BTreeMap<String, UnitUsage>> {
+    let usec = since
+        .duration_since(time::UNIX_EPOCH)
+        .map(usec_from_duration)
+        .unwrap_or(0);
+    journal.seek_realtime_usec(usec)?;
+
+    let mut usage = BTreeMap::<String, UnitUsage>::new();
+    while journal.next()? > 0 {
+        let mut unit = String::new();
+        let mut bytes = 0;
+        journal.restart_data();
+        while let Some(f) = journal.enumerate_data()? {
+            bytes += f.data().len() as u64;
+            if f.name() == fields::_SYSTEMD_UNIT.as_bytes() {
+                unit = String::from_utf8_lossy(f.value().unwrap_or_default()).into_owned();
+            }
         }
+        let u = usage.entry(unit).or_default();
+        u.entries += 1;
+        u.bytes += bytes;
     }
+    Ok(usage)
 }
 
-/// A wrapper type that allows displaying a single entry in the journal
-pub struct DisplayEntryData<'a> {
-    // RULES:
-    //  - we can't move the cursor/position in the journal (no seeking, no
-    //   iteration/next/previous)
-    //  - we have _total_ ownership over data iteration. Do what ever necessary to get all the data
-    //    we want
-    journal: RefCell<&'a mut JournalRef>,
+/// Offset of the machine id in the header of journal files
+#[cfg(feature = "journal-read")]
+const HEADER_MACHINE_ID_OFFSET: usize = 40;
+
+/// Read the id of the machine which wrote the journal file at `path`, from the file's header.
+///
+/// This is the machine id of the host the file was created on, even when the file was copied to
+/// another machine since.
+#[cfg(feature = "journal-read")]
+pub fn file_machine_id<P: AsRef<std::path::Path>>(path: P) -> Result<Id128> {
+    use std::io::Read;
+    let mut header = [0u8; HEADER_MACHINE_ID_OFFSET + 16];
+    std::fs::File::open(path)?.read_exact(&mut header)?;
+    if &header[..8] != b"LPKSHHRH" {
+        return Err(io::Error::new(InvalidData, "not a journal file"));
+    }
+    let mut id = Id128::default();
+    id.inner
+        .bytes
+        .copy_from_slice(&header[HEADER_MACHINE_ID_OFFSET..]);
+    Ok(id)
 }
 
-impl<'a> fmt::Display for DisplayEntryData<'a> {
-    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        writeln!(fmt, "{{")?;
+/// Where journal entries were read from, to tell entries from several hosts apart when reading
+/// journal directories copied from them.
+///
+/// [`EntrySource::annotate()`] adds the known values to records as `__SOURCE_DIRECTORY`,
+/// `__SOURCE_NAMESPACE`, `__SOURCE_MACHINE_ID` and `__SOURCE_HOSTNAME`. These use the double
+/// underscore prefix of the address fields (like `__CURSOR`), which never occur in stored entries,
+/// so the entries' own `_MACHINE_ID` and `_HOSTNAME` are left untouched.
+///
+/// ```no_run
+/// use systemd::journal::{EntrySource, OpenDirectoryOptions};
+/// let dir = "/srv/collected/web1/var/log/journal/0123456789abcdef0123456789abcdef";
+/// let source = EntrySource::from_directory(dir).unwrap().hostname("web1");
+/// let mut j = OpenDirectoryOptions::default().open_directory(dir).unwrap();
+/// while let Some(r) = j.next_entry_annotated(&source).unwrap() {
+///     println!("{:?} {:?}", r.get("__SOURCE_HOSTNAME"), r.get("MESSAGE"));
+/// }
+/// ```
+#[cfg(feature = "journal-read")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EntrySource {
+    /// The journal directory
+    pub directory: Option<std::path::PathBuf>,
+    /// The journal namespace
+    pub namespace: Option<String>,
+    /// The id of the machine which wrote the journal
+    pub machine_id: Option<Id128>,
+    /// The name of the host which wrote the journal
+    pub hostname: Option<String>,
+}
 
-        let mut j = self.journal.borrow_mut();
-        j.restart_data();
-        loop {
-            match j.enumerate_data() {
-                Ok(Some(v)) => {
-                    writeln!(fmt, " \"{}\",", std::str::from_utf8(v.data()).unwrap())?;
-                }
-                Ok(None) => break,
-                Err(e) => {
-                    writeln!(fmt, "E: {:?}", e)?;
+#[cfg(feature = "journal-read")]
+impl EntrySource {
+    /// Describe the journal directory `path`, like `/var/log/journal/<machine-id>[.<namespace>]`.
+    ///
+    /// The machine id is read from the header of a journal file in the directory (see
+    /// [`file_machine_id()`]), and the namespace is taken from the directory name.
+    pub fn from_directory<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut source = EntrySource {
+            directory: Some(path.to_owned()),
+            ..Default::default()
+        };
+        if let Some((_, ns)) = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| n.split_once('.'))
+        {
+            source.namespace = Some(ns.to_owned());
+        }
+        for e in std::fs::read_dir(path)? {
+            let p = e?.path();
+            if p.extension() == Some(std::ffi::OsStr::new("journal")) {
+                if let Ok(id) = file_machine_id(&p) {
+                    source.machine_id = Some(id);
                     break;
                 }
             }
         }
+        Ok(source)
+    }
 
-        writeln!(fmt, "}}")
+    /// Set the hostname, which journal files don't record outside of entries.
+    pub fn hostname<S: Into<String>>(mut self, hostname: S) -> Self {
+        self.hostname = Some(hostname.into());
+        self
     }
-}
 
-impl<'a> From<&'a mut JournalRef> for DisplayEntryData<'a> {
-    fn from(v: &'a mut JournalRef) -> Self {
-        Self {
-            journal: RefCell::new(v),
+    /// Set the machine id, overriding the one found by [`EntrySource::from_directory()`].
+    pub fn machine_id(mut self, machine_id: Id128) -> Self {
+        self.machine_id = Some(machine_id);
+        self
+    }
+
+    /// Add the known source metadata to `record`.
+    pub fn annotate(&self, record: &mut JournalRecord) {
+        if let Some(d) = &self.directory {
+            record.insert(
+                "__SOURCE_DIRECTORY".into(),
+                d.to_string_lossy().into_owned(),
+            );
+        }
+        if let Some(ns) = &self.namespace {
+            record.insert("__SOURCE_NAMESPACE".into(), ns.clone());
+        }
+        if let Some(id) = &self.machine_id {
+            record.insert("__SOURCE_MACHINE_ID".into(), id.to_string());
+        }
+        if let Some(h) = &self.hostname {
+            record.insert("__SOURCE_HOSTNAME".into(), h.clone());
         }
     }
 }
@@ -299,6 +1782,7 @@ impl<'a> From<&'a mut JournalRef> for DisplayEntryData<'a> {
 /// they complete the journal is at a specific entry. All the seek type operations don't behave as
 /// part of iteration, and don't place the journal at a specific entry (iteration must be used to
 /// move to a journal entry).
+#[cfg(feature = "journal-read")]
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum JournalSeek {
     Head,
@@ -308,6 +1792,7 @@ pub enum JournalSeek {
     Cursor { cursor: String },
 }
 
+#[cfg(feature = "journal-read")]
 #[derive(Clone, Debug)]
 pub enum JournalWaitResult {
     Nop,
@@ -315,6 +1800,72 @@ pub enum JournalWaitResult {
     Invalidate,
 }
 
+/// A reusable set of journal matches.
+///
+/// Terms are recorded in order and replayed onto a journal by [`JournalRef::apply_filter()`]
+/// using the same semantics as [`JournalRef::match_add()`], [`JournalRef::match_or()`] and
+/// [`JournalRef::match_and()`]: matches on different fields are combined with AND, matches on the
+/// same field with OR.
+///
+/// # Examples
+///
+/// ```
+/// use systemd::journal;
+/// let mut filter = journal::Filter::new();
+/// filter
+///     .match_add("_SYSTEMD_UNIT", "sshd.service")
+///     .match_or()
+///     .match_add("_COMM", "sshd");
+/// assert!(!filter.is_empty());
+/// ```
+#[cfg(feature = "journal-read")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Filter {
+    terms: Vec<FilterTerm>,
+}
+
+#[cfg(feature = "journal-read")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum FilterTerm {
+    Match(Vec<u8>),
+    Or,
+    And,
+}
+
+#[cfg(feature = "journal-read")]
+impl Filter {
+    /// Create an empty filter, which matches every entry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a `key=val` match.
+    pub fn match_add<T: Into<Vec<u8>>>(&mut self, key: &str, val: T) -> &mut Self {
+        let mut m = Vec::<u8>::from(key);
+        m.push(b'=');
+        m.extend(val.into());
+        self.terms.push(FilterTerm::Match(m));
+        self
+    }
+
+    /// Insert a disjunction (i.e. logical OR).
+    pub fn match_or(&mut self) -> &mut Self {
+        self.terms.push(FilterTerm::Or);
+        self
+    }
+
+    /// Insert a conjunction (i.e. logical AND).
+    pub fn match_and(&mut self) -> &mut Self {
+        self.terms.push(FilterTerm::And);
+        self
+    }
+
+    /// Returns `true` if no terms have been added.
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+}
+
 /// Open a [`Journal`], using custom options.
 ///
 /// This corresponds to [`sd_journal_open_namespace()`] and [`sd_journal_open()`].
@@ -330,6 +1881,7 @@ pub enum JournalWaitResult {
 ///     .current_user(true)
 ///     .open().unwrap();
 /// ```
+#[cfg(feature = "journal-read")]
 #[derive(Clone, Debug, Default)]
 pub struct OpenOptions {
     current_user: bool,
@@ -338,9 +1890,12 @@ pub struct OpenOptions {
     runtime_only: bool,
     all_namespaces: bool,
     include_default_namespace: bool,
+    user: Option<libc::uid_t>,
+    current_boot: bool,
     extra_raw_flags: libc::c_int,
 }
 
+#[cfg(feature = "journal-read")]
 impl OpenOptions {
     /// Open the journal files of the current user.
     ///
@@ -396,19 +1951,63 @@ impl OpenOptions {
         self
     }
 
+    /// Only open the journal files of the user `uid`, as found by [`user_journal_files()`].
+    ///
+    /// This allows a privileged process to read the entries of one user without those of the
+    /// system and other users. The files are opened with [`OpenFilesOptions::open_files()`], so
+    /// besides `local_only` and `runtime_only` the other options have no effect.
+    pub fn user(&mut self, uid: libc::uid_t) -> &mut Self {
+        self.user = Some(uid);
+        self
+    }
+
+    /// Only read entries of the current boot, like `journalctl -b`.
+    ///
+    /// This adds a `_BOOT_ID` match after opening the journal, so [`JournalRef::match_flush()`]
+    /// removes the restriction again.
+    pub fn current_boot(&mut self, current_boot: bool) -> &mut Self {
+        self.current_boot = current_boot;
+        self
+    }
+
     /// Supply any additional flags to the `open*()` function
     pub fn extra_raw_flags(&mut self, extra_raw_flags: libc::c_int) -> &mut Self {
         self.extra_raw_flags = extra_raw_flags;
         self
     }
 
+    fn add_boot_match(&self, mut journal: Journal) -> Result<Journal> {
+        if self.current_boot {
+            journal.match_add(fields::_BOOT_ID, current_boot_id()?.to_string())?;
+        }
+        Ok(journal)
+    }
+
     /// Open the log journal for reading. Entries included are dependent on options.
     ///
-    /// This corresponds to [`sd_journal_open()`]
+    /// This corresponds to [`sd_journal_open()`], or [`sd_journal_open_files()`] if
+    /// [`OpenOptions::user()`] was given. The latter fails with `ENOENT` if the user has no
+    /// journal files.
     ///
     /// `sd_journal_open()`: https://www.freedesktop.org/software/systemd/man/sd_journal_open.html
+    /// `sd_journal_open_files()`: https://www.freedesktop.org/software/systemd/man/sd_journal_open.html
     pub fn open(&self) -> Result<Journal> {
-        Journal::open_with_opts::<&std::ffi::CStr>(self)
+        let journal = match self.user {
+            Some(uid) => {
+                let files = find_user_journal_files(uid, self.runtime_only, self.local_only)?;
+                if files.is_empty() {
+                    return Err(io::Error::from_raw_os_error(libc::ENOENT));
+                }
+                use std::os::unix::ffi::OsStringExt;
+                let files = files
+                    .into_iter()
+                    .map(|f| std::ffi::CString::new(f.into_os_string().into_vec()))
+                    .collect::<result::Result<Vec<_>, _>>()?;
+                OpenFilesOptions::default().open_files(&files)?
+            }
+            None => Journal::open_with_opts::<&std::ffi::CStr>(self)?,
+        };
+        self.add_boot_match(journal)
     }
 
     /// Open the log journal for reading in the given namespace. Entries included are dependent on
@@ -422,14 +2021,112 @@ impl OpenOptions {
     /// This corresponds to [`sd_journal_open_namespace()`]
     ///
     /// `sd_journal_open_namespace()`: https://www.freedesktop.org/software/systemd/man/sd_journal_open.html
+    ///
+    /// This fails with `EINVAL` if [`OpenOptions::user()`] was given.
     #[cfg(feature = "systemd_v245")]
     #[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "systemd_v245")))]
     pub fn open_namespace<A: CStrArgument>(&self, namespace: A) -> Result<Journal> {
-        Journal::open_with_opts_ns(Some(namespace), self)
+        if self.user.is_some() {
+            return Err(io::Error::from_raw_os_error(libc::EINVAL));
+        }
+        self.add_boot_match(Journal::open_with_opts_ns(Some(namespace), self)?)
+    }
+}
+
+/// The id of the current boot, which can't change while we're running.
+#[cfg(feature = "journal-read")]
+fn current_boot_id() -> Result<Id128> {
+    static BOOT_ID: std::sync::Mutex<Option<Id128>> = std::sync::Mutex::new(None);
+    let mut id = BOOT_ID.lock().unwrap_or_else(|e| e.into_inner());
+    match *id {
+        Some(id) => Ok(id),
+        None => {
+            let boot = Id128::from_boot()?;
+            *id = Some(boot);
+            Ok(boot)
+        }
+    }
+}
+
+/// Returns `true` if `record` was logged during the current boot, according to its `_BOOT_ID`
+/// field. Records without the field (e.g. read without it) are never considered current.
+#[cfg(feature = "journal-read")]
+pub fn is_current_boot(record: &JournalRecord) -> Result<bool> {
+    Ok(match record.get(fields::_BOOT_ID) {
+        Some(b) => *b == current_boot_id()?.to_string(),
+        None => false,
+    })
+}
+
+/// The journal files of the user `uid` below `/var/log/journal` and `/run/log/journal`.
+///
+/// These are `user-<uid>.journal`, which `systemd-journald` writes the entries of processes of
+/// the user to, and its archived versions (`user-<uid>@….journal` and `user-<uid>@….journal~`).
+/// Files of all machines whose journals are stored below those directories are included, sorted
+/// by path. Directories that can't be read (e.g. due to missing permissions) are skipped.
+#[cfg(feature = "journal-read")]
+pub fn user_journal_files(uid: libc::uid_t) -> Result<Vec<std::path::PathBuf>> {
+    find_user_journal_files(uid, false, false)
+}
+
+#[cfg(feature = "journal-read")]
+fn find_user_journal_files(
+    uid: libc::uid_t,
+    runtime_only: bool,
+    local_only: bool,
+) -> Result<Vec<std::path::PathBuf>> {
+    let machine_id = if local_only {
+        Some(Id128::from_machine()?.to_string())
+    } else {
+        None
+    };
+    let active = format!("user-{}.journal", uid);
+    let archived = format!("user-{}@", uid);
+
+    let roots: &[&str] = if runtime_only {
+        &["/run/log/journal"]
+    } else {
+        &["/var/log/journal", "/run/log/journal"]
+    };
+    let mut files = Vec::new();
+    for root in roots {
+        let machines = match std::fs::read_dir(root) {
+            Ok(m) => m,
+            Err(_) => continue,
+        };
+        for machine in machines {
+            let machine = machine?;
+            if let Some(ref id) = machine_id {
+                if machine.file_name().to_str() != Some(id.as_str()) {
+                    continue;
+                }
+            }
+            let entries = match std::fs::read_dir(machine.path()) {
+                Ok(e) => e,
+                Err(_) => continue,
+            };
+            for e in entries {
+                let e = e?;
+                let name = e.file_name();
+                let name = match name.to_str() {
+                    Some(n) => n,
+                    None => continue,
+                };
+                if name == active
+                    || (name.starts_with(&archived)
+                        && (name.ends_with(".journal") || name.ends_with(".journal~")))
+                {
+                    files.push(e.path());
+                }
+            }
+        }
     }
+    files.sort();
+    Ok(files)
 }
 
 /// Open a journal, specifying a directory
+#[cfg(feature = "journal-read")]
 #[derive(Clone, Debug, Default)]
 pub struct OpenDirectoryOptions {
     os_root: bool,
@@ -438,6 +2135,7 @@ pub struct OpenDirectoryOptions {
     extra_raw_flags: libc::c_int,
 }
 
+#[cfg(feature = "journal-read")]
 impl OpenDirectoryOptions {
     /// If true, journal files are searched for below the usual `/var/log/journal` and
     /// `/run/log/journal` relative to the specified directory instead of directly beneath it
@@ -488,11 +2186,13 @@ impl OpenDirectoryOptions {
 ///
 /// Note that when used on a live journal, files may be rotated at any time and as a result the
 /// opening of specific files is inherently racy.
+#[cfg(feature = "journal-read")]
 #[derive(Clone, Debug, Default)]
 pub struct OpenFilesOptions {
     extra_raw_flags: libc::c_int,
 }
 
+#[cfg(feature = "journal-read")]
 impl OpenFilesOptions {
     /// Supply any additional flags to the `open*()` function
     ///
@@ -521,6 +2221,7 @@ impl OpenFilesOptions {
     */
 }
 
+#[cfg(feature = "journal-read")]
 impl Journal {
     fn open_with_opts<A: CStrArgument>(opts: &OpenOptions) -> Result<Journal> {
         let mut flags = opts.extra_raw_flags;
@@ -661,6 +2362,7 @@ impl Journal {
     }
 }
 
+#[cfg(feature = "journal-read")]
 impl JournalRef {
     /// Returns a file descriptor  a file descriptor that may be
     /// asynchronously polled in an external event loop and is signaled as
@@ -676,6 +2378,31 @@ impl JournalRef {
         Ok(sd_try!(ffi::sd_journal_get_fd(self.as_ptr())))
     }
 
+    /// Returns the `poll(2)` events to wait for on [`JournalRef::fd()`].
+    ///
+    /// This corresponds to [`sd_journal_get_events`]
+    ///
+    /// [`sd_journal_get_events`]: https://www.freedesktop.org/software/systemd/man/sd_journal_get_fd.html
+    #[inline]
+    pub fn events(&self) -> Result<c_int> {
+        Ok(sd_try!(ffi::sd_journal_get_events(self.as_ptr())))
+    }
+
+    /// Process the changes signaled on [`JournalRef::fd()`], to be called after it woke up an
+    /// external event loop. Returns what changed, like [`JournalRef::wait()`].
+    ///
+    /// This corresponds to [`sd_journal_process`]
+    ///
+    /// [`sd_journal_process`]: https://www.freedesktop.org/software/systemd/man/sd_journal_get_fd.html
+    pub fn process(&mut self) -> Result<JournalWaitResult> {
+        match sd_try!(ffi::sd_journal_process(self.as_ptr())) {
+            ffi::SD_JOURNAL_NOP => Ok(JournalWaitResult::Nop),
+            ffi::SD_JOURNAL_APPEND => Ok(JournalWaitResult::Append),
+            ffi::SD_JOURNAL_INVALIDATE => Ok(JournalWaitResult::Invalidate),
+            _ => Err(io::Error::new(InvalidData, "Failed to process changes")),
+        }
+    }
+
     /// Fields that are longer that this number of bytes _may_ be truncated when retrieved by this [`Journal`]
     /// instance.
     ///
@@ -714,6 +2441,7 @@ impl JournalRef {
     ///
     /// Corresponds to `sd_journal_get_data()`.
     pub fn get_data<A: CStrArgument>(&mut self, field: A) -> Result<Option<JournalEntryField<'_>>> {
+        let threshold = self.data_threshold()?;
         let mut data = MaybeUninit::uninit();
         let mut data_len = MaybeUninit::uninit();
         let f = field.into_cstr();
@@ -725,6 +2453,43 @@ impl JournalRef {
                 data_len.as_mut_ptr(),
             )
         }) {
+            Ok(_) => Ok(Some(JournalEntryField::with_threshold(
+                unsafe { slice::from_raw_parts(data.assume_init(), data_len.assume_init()) },
+                threshold,
+            ))),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Get the complete data associated with a particular field from the current journal entry,
+    /// regardless of the data threshold.
+    ///
+    /// The threshold is lifted for this one retrieval only, and restored afterwards.
+    pub fn get_data_full<A: CStrArgument>(
+        &mut self,
+        field: A,
+    ) -> Result<Option<JournalEntryField<'_>>> {
+        let threshold = self.data_threshold()?;
+        if threshold == 0 {
+            return self.get_data(field);
+        }
+        self.set_data_threshold(0)?;
+        let mut data = MaybeUninit::uninit();
+        let mut data_len = MaybeUninit::uninit();
+        let f = field.into_cstr();
+        let r = crate::ffi_result(unsafe {
+            ffi::sd_journal_get_data(
+                self.as_ptr(),
+                f.as_ref().as_ptr(),
+                data.as_mut_ptr(),
+                data_len.as_mut_ptr(),
+            )
+        });
+        // the returned data stays valid until the next data retrieval, changing the threshold
+        // doesn't affect it
+        self.set_data_threshold(threshold)?;
+        match r {
             Ok(_) => Ok(Some(
                 unsafe { slice::from_raw_parts(data.assume_init(), data_len.assume_init()) }.into(),
             )),
@@ -745,6 +2510,7 @@ impl JournalRef {
     ///
     /// Corresponds to `sd_journal_enumerate_data()`
     pub fn enumerate_data(&mut self) -> Result<Option<JournalEntryField<'_>>> {
+        let threshold = self.data_threshold()?;
         let mut data = MaybeUninit::uninit();
         let mut data_len = MaybeUninit::uninit();
         let r = crate::ffi_result(unsafe {
@@ -764,7 +2530,58 @@ impl JournalRef {
         // `sd_journal_get_data`, or `sd_journal_enumerate_avaliable_data`. This invariant is
         // maintained by our use of `&mut` above.
         let b = unsafe { std::slice::from_raw_parts(data.assume_init(), data_len.assume_init()) };
-        Ok(Some(b.into()))
+        Ok(Some(JournalEntryField::with_threshold(b, threshold)))
+    }
+
+    /// Select `field` for enumerating all the distinct values it has in the journal files with
+    /// [`enumerate_unique()`], regardless of the current entry and of any matches.
+    ///
+    /// Corresponds to `sd_journal_query_unique()`
+    ///
+    /// [`enumerate_unique()`]: JournalRef::enumerate_unique
+    pub fn query_unique<A: CStrArgument>(&mut self, field: A) -> Result<()> {
+        let f = field.into_cstr();
+        sd_try!(ffi::sd_journal_query_unique(
+            self.as_ptr(),
+            f.as_ref().as_ptr()
+        ));
+        Ok(())
+    }
+
+    /// Obtain the next distinct value (as `FIELD=value`) of the field selected with
+    /// [`query_unique()`].
+    ///
+    /// Corresponds to `sd_journal_enumerate_unique()`
+    ///
+    /// [`query_unique()`]: JournalRef::query_unique
+    pub fn enumerate_unique(&mut self) -> Result<Option<JournalEntryField<'_>>> {
+        let threshold = self.data_threshold()?;
+        let mut data = MaybeUninit::uninit();
+        let mut data_len = MaybeUninit::uninit();
+        let r = sd_try!(ffi::sd_journal_enumerate_unique(
+            self.as_ptr(),
+            data.as_mut_ptr(),
+            data_len.as_mut_ptr()
+        ));
+        if r == 0 {
+            return Ok(None);
+        }
+
+        // valid until the next call to `sd_journal_enumerate_unique()`, ensured by the `&mut`
+        let b = unsafe {
+            slice::from_raw_parts(data.assume_init() as *const u8, data_len.assume_init())
+        };
+        Ok(Some(JournalEntryField::with_threshold(b, threshold)))
+    }
+
+    /// Restart the iteration done by [`enumerate_unique()`] over the values of the selected
+    /// field.
+    ///
+    /// Corresponds to `sd_journal_restart_unique()`
+    ///
+    /// [`enumerate_unique()`]: JournalRef::enumerate_unique
+    pub fn restart_unique(&mut self) {
+        unsafe { ffi::sd_journal_restart_unique(self.as_ptr()) }
     }
 
     /// Obtain a display-able that display's the current entrie's fields
@@ -772,6 +2589,26 @@ impl JournalRef {
         self.into()
     }
 
+    /// Iterate over the `MESSAGE` field of the entries following the current position, advancing
+    /// the read pointer.
+    ///
+    /// This avoids enumerating and copying every field of each entry, which makes it well suited
+    /// to piping logs into other programs:
+    ///
+    /// ```no_run
+    /// use systemd::journal;
+    /// let mut j = journal::OpenOptions::default().open().unwrap();
+    /// j.match_add("_SYSTEMD_UNIT", "sshd.service").unwrap();
+    /// std::io::copy(&mut j.message_stream(), &mut std::io::stdout()).unwrap();
+    /// ```
+    pub fn message_stream(&mut self) -> MessageStream<'_> {
+        MessageStream {
+            journal: self,
+            pending: Vec::new(),
+            pos: 0,
+        }
+    }
+
     /// Collect all fields of the current journal entry into a map
     ///
     /// A convenience wrapper around [`enumerate_data()`] and [`restart_data()`].
@@ -837,6 +2674,16 @@ impl JournalRef {
         self.collect_entry().map(Some)
     }
 
+    /// Like [`JournalRef::next_entry()`], adding the metadata of `source` to the record (see
+    /// [`EntrySource::annotate()`]).
+    pub fn next_entry_annotated(&mut self, source: &EntrySource) -> Result<Option<JournalRecord>> {
+        let mut r = self.next_entry()?;
+        if let Some(r) = &mut r {
+            source.annotate(r);
+        }
+        Ok(r)
+    }
+
     /// Read the previous entry from the journal. Returns `Ok(None)` if there
     /// are no more entries to read.
     pub fn previous_entry(&mut self) -> Result<Option<JournalRecord>> {
@@ -865,19 +2712,50 @@ impl JournalRef {
     /// Wait for the next entry to appear. Returns `Ok(None)` if there were no
     /// new entries in the given wait time.
     /// Pass wait_time `None` to wait for an unlimited period for new entries.
+    ///
+    /// When journal files were added or removed while waiting, the journal is moved back to
+    /// the entry it was on with [`JournalRef::resync()`], so the next entry is the one following
+    /// it.
     pub fn await_next_entry(
         &mut self,
         wait_time: Option<time::Duration>,
     ) -> Result<Option<JournalRecord>> {
-        match self.wait(wait_time)? {
+        match self.wait_resync(wait_time)? {
             JournalWaitResult::Nop => Ok(None),
-            JournalWaitResult::Append => self.next_entry(),
+            JournalWaitResult::Append | JournalWaitResult::Invalidate => self.next_entry(),
+        }
+    }
 
-            // This is possibly wrong, but I can't generate a scenario with
-            // ..::Invalidate and neither systemd's journalctl,
-            // systemd-journal-upload, and other utilities handle that case.
-            JournalWaitResult::Invalidate => self.next_entry(),
+    /// Return to the entry identified by `cursor`, so that [`JournalRef::next()`] continues
+    /// after it, for use after the journal files changed ([`JournalWaitResult::Invalidate`]).
+    ///
+    /// If the entry is gone (its file was removed), the journal is placed so that the nearest
+    /// following entry is the next one instead, so no entry is returned twice and none is
+    /// skipped.
+    pub fn resync<A: CStrArgument>(&mut self, cursor: A) -> Result<()> {
+        let cursor = cursor.into_cstr();
+        self.seek_cursor(cursor.as_ref())?;
+        if self.next()? == 0 || self.test_cursor(cursor.as_ref())? {
+            // at the end, or on the entry which was already returned
+            return Ok(());
+        }
+        // on an entry which wasn't returned yet, step back so it is next
+        if self.previous()? == 0 {
+            self.seek_head()?;
+        }
+        Ok(())
+    }
+
+    /// Like [`JournalRef::wait()`], but on [`JournalWaitResult::Invalidate`] return to the entry
+    /// the journal was on before.
+    fn wait_resync(&mut self, wait_time: Option<time::Duration>) -> Result<JournalWaitResult> {
+        // fails if the journal isn't on an entry, in which case there is nothing to return to
+        let cursor = self.cursor().ok();
+        let r = self.wait(wait_time)?;
+        if let (JournalWaitResult::Invalidate, Some(c)) = (&r, cursor) {
+            self.resync(c)?;
         }
+        Ok(r)
     }
 
     /// Iterate through all elements from the current cursor, then await the
@@ -968,10 +2846,10 @@ impl JournalRef {
 
     /// Returns the cursor of current journal entry.
     pub fn cursor(&self) -> Result<String> {
-        let mut c_cursor: *const c_char = ptr::null_mut();
+        let mut c_cursor: *mut c_char = ptr::null_mut();
 
         sd_try!(ffi::sd_journal_get_cursor(self.as_ptr(), &mut c_cursor));
-        let cursor = unsafe { free_cstring(c_cursor as *mut _).unwrap() };
+        let cursor = unsafe { free_cstring(c_cursor).unwrap() };
         Ok(cursor)
     }
 
@@ -986,6 +2864,29 @@ impl JournalRef {
         .map(|v| v != 0)
     }
 
+    /// Returns the timestamps of the oldest and the newest entry of the journal files, or `None`
+    /// if they have no entries. Matches are not taken into account.
+    ///
+    /// This corresponds to [`sd_journal_get_cutoff_realtime_usec`]
+    ///
+    /// [`sd_journal_get_cutoff_realtime_usec`]: https://www.freedesktop.org/software/systemd/man/sd_journal_get_cutoff_realtime_usec.html
+    pub fn cutoff_realtime(&self) -> Result<Option<(time::SystemTime, time::SystemTime)>> {
+        let (mut from, mut to) = (0, 0);
+        let r = sd_try!(ffi::sd_journal_get_cutoff_realtime_usec(
+            self.as_ptr(),
+            &mut from,
+            &mut to
+        ));
+        Ok(if r > 0 {
+            Some((
+                system_time_from_realtime_usec(from),
+                system_time_from_realtime_usec(to),
+            ))
+        } else {
+            None
+        })
+    }
+
     /// Returns timestamp at which current journal entry was recorded.
     pub fn timestamp(&self) -> Result<time::SystemTime> {
         let mut timestamp_us: u64 = 0;
@@ -1008,6 +2909,12 @@ impl JournalRef {
         Ok((monotonic_timestamp_us, id))
     }
 
+    /// Returns `true` if the current journal entry was recorded during the current boot.
+    pub fn is_current_boot(&self) -> Result<bool> {
+        let (_, boot) = self.monotonic_timestamp()?;
+        Ok(boot == current_boot_id()?)
+    }
+
     /// Returns monotonic timestamp at which current journal entry was recorded. Returns an error if
     /// the current entry is not from the current system boot.
     pub fn monotonic_timestamp_current_boot(&self) -> Result<u64> {
@@ -1044,6 +2951,24 @@ impl JournalRef {
         Ok(self)
     }
 
+    /// Adds all the terms of `filter` to the match list.
+    ///
+    /// Existing matches are kept; call [`JournalRef::match_flush()`] first to replace them.
+    pub fn apply_filter(&mut self, filter: &Filter) -> Result<&mut JournalRef> {
+        for term in &filter.terms {
+            match term {
+                FilterTerm::Match(m) => sd_try!(ffi::sd_journal_add_match(
+                    self.as_ptr(),
+                    m.as_ptr() as *const c_void,
+                    m.len() as size_t
+                )),
+                FilterTerm::Or => sd_try!(ffi::sd_journal_add_disjunction(self.as_ptr())),
+                FilterTerm::And => sd_try!(ffi::sd_journal_add_conjunction(self.as_ptr())),
+            };
+        }
+        Ok(self)
+    }
+
     /// Flushes all matches, disjunction and conjunction terms.
     /// After this call all filtering is removed and all entries in
     /// the journal will be iterated again.
@@ -1053,6 +2978,7 @@ impl JournalRef {
     }
 }
 
+#[cfg(feature = "journal-read")]
 impl AsRawFd for JournalRef {
     #[inline]
     fn as_raw_fd(&self) -> c_int {
@@ -0,0 +1,143 @@
+//! Names of the standard fields of journal entries, as described in
+//! [`systemd.journal-fields(7)`].
+//!
+//! Fields starting with a single `_` are trusted: journald adds them itself and ignores them when
+//! clients send them. Those starting with `__` describe the position of an entry rather than
+//! being stored in it.
+//!
+//! ```no_run
+//! # #[cfg(feature = "journal-read")] {
+//! use systemd::journal::{self, fields};
+//! let mut j = journal::OpenOptions::default().open().unwrap();
+//! j.match_add(fields::_SYSTEMD_UNIT, "sshd.service").unwrap();
+//! while let Some(r) = j.next_entry().unwrap() {
+//!     println!("{:?}", r.get(fields::MESSAGE));
+//! }
+//! # }
+//! ```
+//!
+//! [`systemd.journal-fields(7)`]: https://www.freedesktop.org/software/systemd/man/systemd.journal-fields.html
+
+macro_rules! fields {
+    ($($(#[doc = $doc:expr])* $name:ident,)*) => {
+        $(
+            $(#[doc = $doc])*
+            #[doc = ""]
+            #[doc = concat!(
+                "See [`systemd.journal-fields(7)`](https://www.freedesktop.org/software/systemd/man/systemd.journal-fields.html#",
+                stringify!($name),
+                "=)."
+            )]
+            pub const $name: &str = stringify!($name);
+        )*
+    };
+}
+
+fields! {
+    /// The human-readable message of the entry.
+    MESSAGE,
+    /// A 128-bit identifier of the kind of message, for entries from the message catalog.
+    MESSAGE_ID,
+    /// The severity of the message, see [`Priority`](super::Priority).
+    PRIORITY,
+    /// The source file which logged the message.
+    CODE_FILE,
+    /// The line in [`CODE_FILE`] which logged the message.
+    CODE_LINE,
+    /// The function which logged the message.
+    CODE_FUNC,
+    /// The `errno` value the message is about.
+    ERRNO,
+    /// The invocation of the unit the message is about.
+    INVOCATION_ID,
+    /// The invocation of the user unit the message is about.
+    USER_INVOCATION_ID,
+    /// The syslog facility, see [`Facility`](super::Facility).
+    SYSLOG_FACILITY,
+    /// The name of the program which logged the message.
+    SYSLOG_IDENTIFIER,
+    /// The process id the client claims to have logged the message from.
+    SYSLOG_PID,
+    /// The timestamp of the original syslog message.
+    SYSLOG_TIMESTAMP,
+    /// The unparsed syslog message, when it couldn't be parsed completely.
+    SYSLOG_RAW,
+    /// A URL with documentation about the message.
+    DOCUMENTATION,
+    /// The thread id which logged the message.
+    TID,
+    /// The unit a coredump is about.
+    COREDUMP_UNIT,
+    /// The user unit a coredump is about.
+    COREDUMP_USER_UNIT,
+    /// The process id a message from a privileged program is about.
+    OBJECT_PID,
+    /// The process id of the process which logged the message.
+    _PID,
+    /// The user id of the process which logged the message.
+    _UID,
+    /// The group id of the process which logged the message.
+    _GID,
+    /// The name of the process which logged the message.
+    _COMM,
+    /// The executable path of the process which logged the message.
+    _EXE,
+    /// The command line of the process which logged the message.
+    _CMDLINE,
+    /// The effective capabilities of the process which logged the message.
+    _CAP_EFFECTIVE,
+    /// The audit session of the process which logged the message.
+    _AUDIT_SESSION,
+    /// The audit login user id of the process which logged the message.
+    _AUDIT_LOGINUID,
+    /// The control group of the process which logged the message.
+    _SYSTEMD_CGROUP,
+    /// The slice of the process which logged the message.
+    _SYSTEMD_SLICE,
+    /// The unit of the process which logged the message.
+    _SYSTEMD_UNIT,
+    /// The user unit of the process which logged the message.
+    _SYSTEMD_USER_UNIT,
+    /// The user slice of the process which logged the message.
+    _SYSTEMD_USER_SLICE,
+    /// The login session of the process which logged the message.
+    _SYSTEMD_SESSION,
+    /// The owner of the login session of the process which logged the message.
+    _SYSTEMD_OWNER_UID,
+    /// The invocation of the unit of the process which logged the message.
+    _SYSTEMD_INVOCATION_ID,
+    /// The SELinux context of the process which logged the message.
+    _SELINUX_CONTEXT,
+    /// The time the message was originally logged, in microseconds since the epoch.
+    _SOURCE_REALTIME_TIMESTAMP,
+    /// The boot the message was logged in.
+    _BOOT_ID,
+    /// The machine the message was logged on.
+    _MACHINE_ID,
+    /// The hostname of the machine the message was logged on.
+    _HOSTNAME,
+    /// How the message reached the journal, see [`Transport`](super::Transport).
+    _TRANSPORT,
+    /// The stream of a service's standard output the message was read from.
+    _STREAM_ID,
+    /// How the line of standard output was terminated, when not by a newline.
+    _LINE_BREAK,
+    /// The journal namespace the message was logged to.
+    _NAMESPACE,
+    /// The device a kernel message is about.
+    _KERNEL_DEVICE,
+    /// The subsystem of [`_KERNEL_DEVICE`].
+    _KERNEL_SUBSYSTEM,
+    /// The kernel name of [`_KERNEL_DEVICE`].
+    _UDEV_SYSNAME,
+    /// The device node of [`_KERNEL_DEVICE`].
+    _UDEV_DEVNODE,
+    /// Additional symlinks to the device node of [`_KERNEL_DEVICE`].
+    _UDEV_DEVLINK,
+    /// The cursor of the entry, see [`JournalRef::cursor()`](super::JournalRef::cursor).
+    __CURSOR,
+    /// The time the entry was received, in microseconds since the epoch.
+    __REALTIME_TIMESTAMP,
+    /// The time the entry was received, in microseconds of the monotonic clock of its boot.
+    __MONOTONIC_TIMESTAMP,
+}
@@ -0,0 +1,199 @@
+//! A writer speaking the journald [native protocol] directly, without going through
+//! `sd_journal_sendv()`.
+//!
+//! Entries are sent as a single datagram to `/run/systemd/journal/socket`. Entries too large for
+//! a datagram are written to a sealed memfd which is then passed to journald instead, the same way
+//! libsystemd does it.
+//!
+//! With the `journal-pure` feature enabled, [`send()`](super::send), [`print()`](super::print)
+//! and the logging functions of the parent module use this writer as well.
+//!
+//! [native protocol]: https://systemd.io/JOURNAL_NATIVE_PROTOCOL/
+
+use super::split_field;
+use super::validate_field_name;
+use libc::{c_char, c_int, c_void};
+use std::cell::RefCell;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, Write};
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::ptr;
+
+/// The socket journald listens on for native protocol messages.
+pub const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+
+/// Append one field to `buf` in the native protocol encoding.
+///
+/// Values containing a newline use the binary-safe form: the name, a newline, the value length
+/// as a little endian `u64`, the value and a final newline.
+fn encode_field(buf: &mut Vec<u8>, name: &str, value: &[u8]) {
+    buf.extend_from_slice(name.as_bytes());
+    if value.contains(&b'\n') {
+        buf.push(b'\n');
+        buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        buf.push(b'=');
+    }
+    buf.extend_from_slice(value);
+    buf.push(b'\n');
+}
+
+/// Writes entries to journald over its native protocol socket.
+///
+/// # Examples
+///
+/// ```no_run
+/// use systemd::journal::native::Writer;
+/// let w = Writer::new().unwrap();
+/// w.send(&["MESSAGE=hello", "PRIORITY=6"]).unwrap();
+/// w.send_fields(vec![("MESSAGE", &b"binary\0safe"[..])]).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct Writer {
+    sock: UnixDatagram,
+    path: PathBuf,
+}
+
+impl Writer {
+    /// Create a writer sending to journald's default socket.
+    pub fn new() -> io::Result<Self> {
+        Self::with_path(JOURNAL_SOCKET)
+    }
+
+    /// Create a writer sending to the socket at `path` instead of journald's default socket.
+    pub fn with_path<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Writer {
+            sock: UnixDatagram::unbound()?,
+            path: path.as_ref().to_owned(),
+        })
+    }
+
+    /// Send an entry made of preformatted `NAME=value` fields.
+    ///
+    /// Fields are validated like [`try_send()`](super::try_send) does; values may contain
+    /// arbitrary data, including newlines.
+    pub fn send(&self, args: &[&str]) -> io::Result<()> {
+        let mut buf = Vec::new();
+        for field in args {
+            let (name, value) = split_field(field)?;
+            encode_field(&mut buf, name, value.as_bytes());
+        }
+        self.send_raw(&buf)
+    }
+
+    /// Send an entry made of `(name, value)` pairs, where values are arbitrary bytes.
+    pub fn send_fields<'a, I>(&self, fields: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (&'a str, &'a [u8])>,
+    {
+        let mut buf = Vec::new();
+        for (name, value) in fields {
+            validate_field_name(name).map_err(|what| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid journal field name {:?}: {}", name, what),
+                )
+            })?;
+            encode_field(&mut buf, name, value);
+        }
+        self.send_raw(&buf)
+    }
+
+    /// Send an already encoded entry, falling back to passing a memfd if it doesn't fit in a
+    /// datagram or in the socket's send buffer, as `sd_journal_sendv()` does.
+    fn send_raw(&self, buf: &[u8]) -> io::Result<()> {
+        match self.sock.send_to(buf, &self.path) {
+            Ok(_) => Ok(()),
+            Err(e) if matches!(e.raw_os_error(), Some(libc::EMSGSIZE) | Some(libc::ENOBUFS)) => {
+                self.send_memfd(buf)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn send_memfd(&self, buf: &[u8]) -> io::Result<()> {
+        let fd = unsafe {
+            libc::memfd_create(
+                b"journal-data\0".as_ptr() as *const c_char,
+                libc::MFD_ALLOW_SEALING | libc::MFD_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut f = unsafe { File::from_raw_fd(fd) };
+        f.write_all(buf)?;
+        // journald only accepts memfds which can no longer be modified
+        let seals =
+            libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_WRITE | libc::F_SEAL_SEAL;
+        if unsafe { libc::fcntl(fd, libc::F_ADD_SEALS, seals) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.send_fd(f.as_raw_fd())
+    }
+
+    /// Send an empty datagram carrying `fd` as `SCM_RIGHTS` ancillary data.
+    fn send_fd(&self, fd: RawFd) -> io::Result<()> {
+        let (mut addr, addr_len) = sockaddr(&self.path)?;
+        let space = unsafe { libc::CMSG_SPACE(mem::size_of::<c_int>() as u32) } as usize;
+        // u64 elements to get a suitably aligned control buffer
+        let mut control = vec![0u64; space / 8 + 1];
+
+        unsafe {
+            let mut msg: libc::msghdr = mem::zeroed();
+            msg.msg_name = &mut addr as *mut _ as *mut c_void;
+            msg.msg_namelen = addr_len;
+            msg.msg_control = control.as_mut_ptr() as *mut c_void;
+            msg.msg_controllen = space as _;
+
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(mem::size_of::<c_int>() as u32) as _;
+            ptr::write_unaligned(libc::CMSG_DATA(cmsg) as *mut c_int, fd);
+
+            if libc::sendmsg(self.sock.as_raw_fd(), &msg, libc::MSG_NOSIGNAL) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn sockaddr(path: &Path) -> io::Result<(libc::sockaddr_un, libc::socklen_t)> {
+    let mut addr: libc::sockaddr_un = unsafe { mem::zeroed() };
+    addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+    let bytes = OsStr::as_bytes(path.as_os_str());
+    if bytes.len() >= addr.sun_path.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "socket path too long",
+        ));
+    }
+    for (d, s) in addr.sun_path.iter_mut().zip(bytes) {
+        *d = *s as c_char;
+    }
+    let len = mem::size_of::<libc::sa_family_t>() + bytes.len() + 1;
+    Ok((addr, len as libc::socklen_t))
+}
+
+thread_local! {
+    static DEFAULT_WRITER: RefCell<Option<Writer>> = RefCell::default();
+}
+
+/// Run `f` with a lazily created, per thread [`Writer`] for the default socket.
+#[cfg_attr(not(feature = "journal-pure"), allow(dead_code))]
+pub(super) fn with_default<T>(f: impl FnOnce(&Writer) -> io::Result<T>) -> io::Result<T> {
+    DEFAULT_WRITER.with(|w| {
+        let mut w = w.borrow_mut();
+        if w.is_none() {
+            *w = Some(Writer::new()?);
+        }
+        f(w.as_ref().unwrap())
+    })
+}
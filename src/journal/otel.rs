@@ -0,0 +1,151 @@
+//! Export journal entries as OpenTelemetry log records.
+//!
+//! ```no_run
+//! use opentelemetry::logs::LoggerProvider;
+//! use systemd::journal::{self, otel::Converter};
+//! # fn run(provider: impl LoggerProvider) {
+//! let logger = provider.logger("journal");
+//! let convert = Converter::new().trace_fields("TRACE_ID", "SPAN_ID");
+//! let mut j = journal::OpenOptions::default().open().unwrap();
+//! while let Some(r) = j.next_entry().unwrap() {
+//!     convert.emit(&logger, &r);
+//! }
+//! # }
+//! ```
+
+use super::{fields, JournalRecord};
+use opentelemetry::logs::{AnyValue, LogRecord, Logger, Severity};
+use opentelemetry::{SpanId, TraceFlags, TraceId};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Fills OpenTelemetry log records from journal entries.
+///
+/// - `PRIORITY=` sets the severity, from `Fatal` for `emerg` to `Debug` for `debug`, and the
+///   severity text to the syslog name of the level.
+/// - `_SOURCE_REALTIME_TIMESTAMP=`, or `__REALTIME_TIMESTAMP` when the entry doesn't have it,
+///   sets the timestamp, and `__REALTIME_TIMESTAMP` the observed timestamp.
+/// - `MESSAGE=` is the body.
+/// - The remaining fields are added as string attributes, except `__CURSOR` and the trace
+///   fields.
+///
+/// The journal has no standard fields for trace context: the names of the fields which hold the
+/// (hex encoded) trace and span ids are set with [`Converter::trace_fields()`].
+#[derive(Clone, Debug, Default)]
+pub struct Converter {
+    trace_id: Option<String>,
+    span_id: Option<String>,
+    trace_flags: Option<String>,
+}
+
+impl Converter {
+    /// A converter without trace correlation.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Correlate records with the trace and span whose ids are in the fields `trace_id` and
+    /// `span_id`. Both have to be present and valid for the trace context to be set.
+    pub fn trace_fields(mut self, trace_id: &str, span_id: &str) -> Self {
+        self.trace_id = Some(trace_id.to_owned());
+        self.span_id = Some(span_id.to_owned());
+        self
+    }
+
+    /// Take the (hex encoded) trace flags from the field `trace_flags`. Without it the flags of
+    /// the trace context are left unset.
+    pub fn trace_flags_field(mut self, trace_flags: &str) -> Self {
+        self.trace_flags = Some(trace_flags.to_owned());
+        self
+    }
+
+    /// Fill `record` from `entry`.
+    pub fn fill<R: LogRecord>(&self, entry: &JournalRecord, record: &mut R) {
+        if let Some((number, text)) = entry
+            .get(fields::PRIORITY)
+            .and_then(|p| severity(p.parse().ok()?))
+        {
+            record.set_severity_number(number);
+            record.set_severity_text(text);
+        }
+
+        let observed = entry
+            .get(fields::__REALTIME_TIMESTAMP)
+            .and_then(|t| time(t));
+        if let Some(t) = entry
+            .get(fields::_SOURCE_REALTIME_TIMESTAMP)
+            .and_then(|t| time(t))
+            .or(observed)
+        {
+            record.set_timestamp(t);
+        }
+        if let Some(t) = observed {
+            record.set_observed_timestamp(t);
+        }
+
+        if let Some(message) = entry.get(fields::MESSAGE) {
+            record.set_body(AnyValue::from(message.clone()));
+        }
+
+        if let (Some(trace_id), Some(span_id)) = (
+            self.field(entry, &self.trace_id)
+                .and_then(|t| TraceId::from_hex(t).ok()),
+            self.field(entry, &self.span_id)
+                .and_then(|s| SpanId::from_hex(s).ok()),
+        ) {
+            let flags = self
+                .field(entry, &self.trace_flags)
+                .and_then(|f| u8::from_str_radix(f, 16).ok())
+                .map(TraceFlags::new);
+            record.set_trace_context(trace_id, span_id, flags);
+        }
+
+        for (name, value) in entry {
+            if self.is_skipped(name) {
+                continue;
+            }
+            record.add_attribute(name.clone(), value.clone());
+        }
+    }
+
+    /// Create a record for `entry` with `logger` and emit it.
+    pub fn emit<L: Logger>(&self, logger: &L, entry: &JournalRecord) {
+        let mut record = logger.create_log_record();
+        self.fill(entry, &mut record);
+        logger.emit(record);
+    }
+
+    fn field<'a>(&self, entry: &'a JournalRecord, name: &Option<String>) -> Option<&'a str> {
+        entry.get(name.as_deref()?).map(String::as_str)
+    }
+
+    fn is_skipped(&self, name: &str) -> bool {
+        match name {
+            fields::PRIORITY
+            | fields::MESSAGE
+            | fields::_SOURCE_REALTIME_TIMESTAMP
+            | fields::__REALTIME_TIMESTAMP
+            | fields::__CURSOR => true,
+            _ => [&self.trace_id, &self.span_id, &self.trace_flags]
+                .iter()
+                .any(|f| f.as_deref() == Some(name)),
+        }
+    }
+}
+
+fn severity(priority: u8) -> Option<(Severity, &'static str)> {
+    Some(match priority {
+        0 => (Severity::Fatal, "emerg"),
+        1 => (Severity::Error3, "alert"),
+        2 => (Severity::Error2, "crit"),
+        3 => (Severity::Error, "err"),
+        4 => (Severity::Warn, "warning"),
+        5 => (Severity::Info2, "notice"),
+        6 => (Severity::Info, "info"),
+        7 => (Severity::Debug, "debug"),
+        _ => return None,
+    })
+}
+
+fn time(usec: &str) -> Option<SystemTime> {
+    UNIX_EPOCH.checked_add(Duration::from_micros(usec.parse().ok()?))
+}
@@ -0,0 +1,249 @@
+//! Reading a consistent snapshot of a journal directory, for long running exports.
+//!
+//! A journal opened on a directory follows it: files created by rotation are added and files
+//! removed by vacuuming disappear, so a long export may see entries vanish or appear halfway
+//! through. A [`Snapshot`] instead opens the journal files present when it is created by name
+//! (see [`OpenFilesOptions`]), which keeps them open and readable even if they are rotated or
+//! deleted afterwards, and ends iteration at the entry which was last when it was created.
+//!
+//! As the files are no longer watched, sd-journal doesn't report changes to them (no
+//! [`JournalWaitResult::Invalidate`](super::JournalWaitResult::Invalidate)). Use
+//! [`Snapshot::changes()`] to find out which files were added and removed in the meantime, for
+//! example to decide whether to take a new snapshot.
+//!
+//! ```no_run
+//! use systemd::journal::snapshot::Snapshot;
+//! let mut s = Snapshot::open("/var/log/journal").unwrap();
+//! while let Some(r) = s.next_entry().unwrap() {
+//!     println!("{:?}", r.get("MESSAGE"));
+//! }
+//! let changes = s.changes().unwrap();
+//! println!("{} files added, {} removed", changes.added.len(), changes.removed.len());
+//! ```
+
+use super::{Journal, JournalRecord, JournalRef, OpenFilesOptions};
+use crate::Result;
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A journal file, identified by its inode so that renaming (as done by rotation) doesn't change
+/// its identity.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct JournalFile {
+    path: PathBuf,
+    dev: u64,
+    ino: u64,
+}
+
+impl JournalFile {
+    fn same_file(&self, other: &JournalFile) -> bool {
+        self.dev == other.dev && self.ino == other.ino
+    }
+}
+
+fn is_journal_file(p: &Path) -> bool {
+    // active and archived files end in `.journal`, files which were found corrupted in `.journal~`
+    matches!(
+        p.extension().and_then(|e| e.to_str()),
+        Some("journal") | Some("journal~")
+    )
+}
+
+/// List the journal files in `dir` and its subdirectories (usually one per machine id).
+fn scan(dir: &Path, files: &mut Vec<JournalFile>) -> Result<()> {
+    for e in fs::read_dir(dir)? {
+        let e = e?;
+        let path = e.path();
+        let meta = match fs::metadata(&path) {
+            Ok(m) => m,
+            // removed while scanning
+            Err(_) => continue,
+        };
+        if meta.is_dir() {
+            scan(&path, files)?;
+        } else if meta.is_file() && is_journal_file(&path) {
+            files.push(JournalFile {
+                path,
+                dev: meta.dev(),
+                ino: meta.ino(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Where an entry is in the journal, to tell entries written after the end of a snapshot apart.
+#[derive(Clone, Debug)]
+struct Position {
+    cursor: String,
+    // the sequence number id and number of the entry, parsed from the cursor
+    seqnum: Option<(String, u64)>,
+    realtime: SystemTime,
+}
+
+impl Position {
+    /// The position of the current entry of `journal`.
+    fn current(journal: &JournalRef) -> Result<Position> {
+        let cursor = journal.cursor()?;
+        let mut seqnum_id = None;
+        let mut seqnum = None;
+        for f in cursor.split(';') {
+            if let Some(id) = f.strip_prefix("s=") {
+                seqnum_id = Some(id.to_owned());
+            } else if let Some(n) = f.strip_prefix("i=") {
+                seqnum = u64::from_str_radix(n, 16).ok();
+            }
+        }
+        Ok(Position {
+            seqnum: seqnum_id.zip(seqnum),
+            realtime: journal.timestamp()?,
+            cursor,
+        })
+    }
+
+    /// Whether the entry was written after the one at `end`.
+    ///
+    /// Entries with the same sequence number id are ordered by their sequence number, like
+    /// sd-journal does. Otherwise, as when the files come from different machines, the wallclock
+    /// time they were written at has to do.
+    fn is_after(&self, end: &Position) -> bool {
+        match (&self.seqnum, &end.seqnum) {
+            (Some((id, n)), Some((end_id, end_n))) if id == end_id => n > end_n,
+            _ => self.realtime > end.realtime,
+        }
+    }
+}
+
+/// Journal files which were added to or removed from a directory since a [`Snapshot`] of it was
+/// taken.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileChanges {
+    /// Files which are not part of the snapshot, for example those created by rotation. A file
+    /// which was part of the snapshot but renamed by rotation is not included.
+    pub added: Vec<PathBuf>,
+    /// Files of the snapshot which no longer exist in the directory, for example because they
+    /// were vacuumed. These are listed under the path they had when the snapshot was taken.
+    pub removed: Vec<PathBuf>,
+}
+
+impl FileChanges {
+    /// Whether no files were added or removed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// A consistent view of the entries of a journal directory at the time it was taken.
+///
+/// See the [module documentation](self) for details.
+pub struct Snapshot {
+    journal: Journal,
+    directory: PathBuf,
+    files: Vec<JournalFile>,
+    // the last entry when the snapshot was taken
+    end: Option<Position>,
+    done: bool,
+}
+
+impl Snapshot {
+    /// Take a snapshot of the journal files in `directory` (like `/var/log/journal`) and its
+    /// subdirectories, positioned before the first entry.
+    pub fn open<P: AsRef<Path>>(directory: P) -> Result<Snapshot> {
+        let directory = directory.as_ref().to_owned();
+        let mut files = Vec::new();
+        scan(&directory, &mut files)?;
+        let paths = files
+            .iter()
+            .map(|f| CString::new(f.path.as_os_str().as_bytes()))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let mut journal = OpenFilesOptions::default().open_files(paths)?;
+
+        journal.seek_tail()?;
+        let end = if journal.previous()? > 0 {
+            Some(Position::current(&journal)?)
+        } else {
+            None
+        };
+        journal.seek_head()?;
+
+        Ok(Snapshot {
+            journal,
+            done: end.is_none(),
+            directory,
+            files,
+            end,
+        })
+    }
+
+    /// The journal files making up the snapshot, with the paths they had when it was taken.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|f| f.path.as_path())
+    }
+
+    /// The underlying journal, for adding matches or seeking.
+    ///
+    /// Iterating it directly, rather than with [`Snapshot::next_entry()`], doesn't stop at the
+    /// end of the snapshot, so entries appended to the active file since may be returned.
+    pub fn journal(&mut self) -> &mut JournalRef {
+        &mut self.journal
+    }
+
+    /// Read the next entry, returning `Ok(None)` once the last entry of the snapshot was read.
+    ///
+    /// With matches added to the [`journal()`](Snapshot::journal), the last entry of the snapshot
+    /// is usually skipped. Iteration then ends at the first entry written after it instead.
+    pub fn next_entry(&mut self) -> Result<Option<JournalRecord>> {
+        let end = match &self.end {
+            Some(end) if !self.done => end,
+            _ => return Ok(None),
+        };
+        let r = match self.journal.next_entry()? {
+            Some(r) => r,
+            None => {
+                self.done = true;
+                return Ok(None);
+            }
+        };
+        if self.journal.test_cursor(end.cursor.as_str())? {
+            self.done = true;
+        } else if Position::current(&self.journal)?.is_after(end) {
+            self.done = true;
+            return Ok(None);
+        }
+        Ok(Some(r))
+    }
+
+    /// Compare the journal files now present in the directory with those of the snapshot.
+    pub fn changes(&self) -> Result<FileChanges> {
+        let mut now = Vec::new();
+        scan(&self.directory, &mut now)?;
+        Ok(FileChanges {
+            added: now
+                .iter()
+                .filter(|n| !self.files.iter().any(|f| f.same_file(n)))
+                .map(|n| n.path.clone())
+                .collect(),
+            removed: self
+                .files
+                .iter()
+                .filter(|f| !now.iter().any(|n| f.same_file(n)))
+                .map(|f| f.path.clone())
+                .collect(),
+        })
+    }
+}
+
+impl std::fmt::Debug for Snapshot {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("Snapshot")
+            .field("directory", &self.directory)
+            .field("files", &self.files)
+            .field("end", &self.end)
+            .finish()
+    }
+}
@@ -0,0 +1,84 @@
+//! Statistics over the values of journal fields, like which units or identifiers log the most.
+//!
+//! ```no_run
+//! use std::time::{Duration, SystemTime};
+//! use systemd::journal::{self, stats};
+//! let mut j = journal::OpenOptions::default().open().unwrap();
+//! let since = SystemTime::now() - Duration::from_secs(3600);
+//! for (unit, count) in stats::field_histogram(&mut j, "_SYSTEMD_UNIT", since)
+//!     .unwrap()
+//!     .iter()
+//!     .take(10)
+//! {
+//!     println!("{:>8} {}", count, unit);
+//! }
+//! ```
+
+use super::JournalRef;
+use crate::{usec_from_duration, Result};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Count the entries logged since `since` for each distinct value of `field` (for example
+/// `_SYSTEMD_UNIT`, `PRIORITY` or `SYSLOG_IDENTIFIER`).
+///
+/// Returns the values with at least one entry, most frequent first (values with equal counts are
+/// ordered by value). Entries without the field aren't counted. Values which aren't valid UTF-8
+/// are converted lossily.
+///
+/// The distinct values are enumerated from the journal files' indices, then the entries of each
+/// are counted by matching on it. This replaces the matches of `journal`: they are flushed before
+/// and after counting.
+pub fn field_histogram(
+    journal: &mut JournalRef,
+    field: &str,
+    since: SystemTime,
+) -> Result<Vec<(String, u64)>> {
+    // values are matched on as a whole, so they must not be truncated
+    let threshold = journal.data_threshold()?;
+    journal.set_data_threshold(0)?;
+    let values = unique_values(journal, field);
+    journal.set_data_threshold(threshold)?;
+
+    journal.match_flush()?;
+    let r = count_values(journal, field, values?, since);
+    journal.match_flush()?;
+    let mut histogram = r?;
+    histogram.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(histogram)
+}
+
+fn unique_values(journal: &mut JournalRef, field: &str) -> Result<Vec<Vec<u8>>> {
+    journal.query_unique(field)?;
+    let mut values = Vec::new();
+    while let Some(f) = journal.enumerate_unique()? {
+        values.push(f.value().unwrap_or_default().to_vec());
+    }
+    Ok(values)
+}
+
+fn count_values(
+    journal: &mut JournalRef,
+    field: &str,
+    values: Vec<Vec<u8>>,
+    since: SystemTime,
+) -> Result<Vec<(String, u64)>> {
+    let usec = since
+        .duration_since(UNIX_EPOCH)
+        .map(usec_from_duration)
+        .unwrap_or(0);
+    let mut histogram = Vec::new();
+    for value in values {
+        journal.match_flush()?;
+        let name = String::from_utf8_lossy(&value).into_owned();
+        journal.match_add(field, value)?;
+        journal.seek_realtime_usec(usec)?;
+        let mut count = 0;
+        while journal.next()? > 0 {
+            count += 1;
+        }
+        if count > 0 {
+            histogram.push((name, count));
+        }
+    }
+    Ok(histogram)
+}
@@ -1,6 +1,7 @@
 #![cfg_attr(feature = "unstable-doc-cfg", feature(doc_cfg))]
 #![warn(rust_2018_idioms)]
 
+#[cfg(feature = "libsystemd-sys")]
 extern crate libsystemd_sys as ffi;
 
 /*
@@ -9,22 +10,33 @@ extern crate enumflags2;
 extern crate enumflags2_derive;
 */
 
-#[cfg(feature = "journal")]
+#[cfg(feature = "journal-read")]
 #[allow(deprecated)]
 pub use journal::JournalFiles;
-#[cfg(feature = "journal")]
-pub use journal::{Journal, JournalLog, JournalRecord, JournalSeek, JournalWaitResult};
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
+pub use journal::JournalLog;
+#[cfg(feature = "journal-read")]
+pub use journal::{Journal, JournalRecord, JournalSeek, JournalWaitResult};
+#[cfg(any(feature = "journal-read", feature = "bus-client", feature = "login"))]
 use libc::{c_char, c_void, free, strlen};
 pub use std::io::{Error, Result};
 
-#[cfg(any(feature = "journal", feature = "bus"))]
+#[cfg(any(feature = "journal-read", feature = "bus-client", feature = "event"))]
 fn usec_from_duration(duration: std::time::Duration) -> u64 {
     let sub_usecs = duration.subsec_micros() as u64;
     duration.as_secs() * 1_000_000 + sub_usecs
 }
 
+/// The current time of `CLOCK_MONOTONIC` in usec, as used for timestamps and timeouts by systemd.
+#[cfg(any(feature = "daemon", all(feature = "tokio", feature = "bus-client")))]
+fn monotonic_usec() -> u64 {
+    let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1_000
+}
+
 /// Convert a systemd ffi return value into a Result
-pub fn ffi_result(ret: ffi::c_int) -> Result<ffi::c_int> {
+pub fn ffi_result(ret: libc::c_int) -> Result<libc::c_int> {
     if ret < 0 {
         Err(Error::from_raw_os_error(-ret))
     } else {
@@ -34,6 +46,7 @@ pub fn ffi_result(ret: ffi::c_int) -> Result<ffi::c_int> {
 
 /// Convert a malloc'd C string into a rust string and call free on it.
 /// Returns None if the pointer is null.
+#[cfg(any(feature = "journal-read", feature = "bus-client", feature = "login"))]
 unsafe fn free_cstring(ptr: *mut c_char) -> Option<String> {
     if ptr.is_null() {
         return None;
@@ -63,7 +76,19 @@ macro_rules! sd_try {
 ///
 /// The main interface for writing to the journal is `fn log()`, and the main
 /// interface for reading the journal is `struct Journal`.
-#[cfg(feature = "journal")]
+#[cfg(any(
+    feature = "journal-read",
+    feature = "journal-write",
+    feature = "journal-pure"
+))]
+#[cfg_attr(
+    feature = "unstable-doc-cfg",
+    doc(cfg(any(
+        feature = "journal-read",
+        feature = "journal-write",
+        feature = "journal-pure"
+    )))
+)]
 pub mod journal;
 
 /// Similar to `log!()`, except it accepts a func argument rather than hard
@@ -94,23 +119,62 @@ macro_rules! log_with{
     })
 }
 
-#[cfg(feature = "journal")]
+#[cfg(any(feature = "journal-write", feature = "journal-pure"))]
 #[macro_export]
 macro_rules! sd_journal_log{
     ($lvl:expr, $($arg:tt)+) => ($crate::log_with!(@raw ::systemd::journal::log, $lvl, $($arg)+))
 }
 
+/// Query crashes recorded in the journal by `systemd-coredump`.
+#[cfg(feature = "journal-read")]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "journal-read")))]
+pub mod coredump;
+
+#[cfg(feature = "daemon")]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "daemon")))]
 pub mod daemon;
 
+/// Interface to the `sd-event` event loop.
+#[cfg(feature = "event")]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "event")))]
+pub mod event;
+
+#[cfg(feature = "libsystemd-sys")]
 pub mod id128;
 
 /// Interface to introspect on seats, sessions and users.
+#[cfg(feature = "login")]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "login")))]
 pub mod login;
 
 /// An interface to work with the dbus message bus.
 ///
-#[cfg(feature = "bus")]
+#[cfg(feature = "bus-client")]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "bus-client")))]
 pub mod bus;
 
+/// Client for the systemd service manager (`org.freedesktop.systemd1`) over the bus.
+///
+/// Tools that may run without a system bus (e.g. in containers) can open it with
+/// [`bus::Bus::try_default_system`] and fall back to other code paths on `None`.
+#[cfg(feature = "bus-client")]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "bus-client")))]
+pub mod manager;
+
+/// Client for the login manager (`org.freedesktop.login1`) over the bus.
+///
+/// When logind itself isn't running calls fail with an error for which
+/// [`bus::Error::is_service_unknown`] returns `true`.
+#[cfg(all(feature = "bus-client", feature = "login"))]
+#[cfg_attr(
+    feature = "unstable-doc-cfg",
+    doc(cfg(all(feature = "bus-client", feature = "login")))
+)]
+pub mod login1;
+
+#[cfg(feature = "daemon")]
+#[cfg_attr(feature = "unstable-doc-cfg", doc(cfg(feature = "daemon")))]
+pub mod reload;
+
 /// Utilities for working with systemd units.
 pub mod unit;
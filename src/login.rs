@@ -2,7 +2,12 @@ use super::ffi::{c_char, c_uint, pid_t, uid_t};
 use super::{free_cstring, Result};
 use ::ffi::login as ffi;
 use cstr_argument::CStrArgument;
+use std::ffi::CString;
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::PathBuf;
 use std::ptr;
+use std::time::{Duration, Instant};
 
 /// Systemd slice and unit types
 pub enum UnitType {
@@ -123,3 +128,384 @@ pub fn get_owner_uid(pid: Option<pid_t>) -> Result<uid_t> {
     sd_try!(ffi::sd_pid_get_owner_uid(p, &mut c_owner_uid));
     Ok(c_owner_uid as uid_t)
 }
+
+/// The users which currently have at least one login session, in any state.
+///
+/// This corresponds to [`sd_get_uids`].
+///
+/// [`sd_get_uids`]: https://www.freedesktop.org/software/systemd/man/sd_get_seats.html
+pub fn get_uids() -> Result<Vec<uid_t>> {
+    let mut uids: *mut uid_t = ptr::null_mut();
+    let n = sd_try!(ffi::sd_get_uids(&mut uids));
+    if uids.is_null() {
+        return Ok(Vec::new());
+    }
+    let r = unsafe { std::slice::from_raw_parts(uids, n as usize) }.to_vec();
+    unsafe { libc::free(uids as *mut libc::c_void) };
+    Ok(r)
+}
+
+/// Like [`get_uids()`], paired with the name of each user when `resolve_names` is `true`.
+///
+/// Names are looked up with [`user_name()`]. Users without a name, and all users when
+/// `resolve_names` is `false`, are paired with `None`.
+pub fn get_users(resolve_names: bool) -> Result<Vec<(uid_t, Option<String>)>> {
+    get_uids()?
+        .into_iter()
+        .map(|uid| {
+            let name = if resolve_names { user_name(uid)? } else { None };
+            Ok((uid, name))
+        })
+        .collect()
+}
+
+/// The name of the user `uid` in the user database, `None` if there is no such user.
+///
+/// This uses [`getpwuid_r(3)`], so all NSS sources configured in `/etc/nsswitch.conf` are
+/// consulted, which might block on network services.
+///
+/// [`getpwuid_r(3)`]: https://man7.org/linux/man-pages/man3/getpwuid_r.3.html
+pub fn user_name(uid: uid_t) -> Result<Option<String>> {
+    let mut buf: Vec<c_char> = vec![0; 1024];
+    loop {
+        let mut pwd = std::mem::MaybeUninit::<libc::passwd>::uninit();
+        let mut result = ptr::null_mut();
+        let r = unsafe {
+            libc::getpwuid_r(
+                uid,
+                pwd.as_mut_ptr(),
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+        match r {
+            0 if result.is_null() => return Ok(None),
+            0 => {
+                let name = unsafe { std::ffi::CStr::from_ptr((*result).pw_name) };
+                return Ok(Some(name.to_string_lossy().into_owned()));
+            }
+            libc::ERANGE if buf.len() < 1 << 20 => {
+                let len = buf.len() * 2;
+                buf.resize(len, 0);
+            }
+            // these mean "not found" for some NSS modules
+            libc::ENOENT | libc::ESRCH | libc::EBADF | libc::EPERM => return Ok(None),
+            e => return Err(io::Error::from_raw_os_error(e)),
+        }
+    }
+}
+
+/// The runtime directory (`$XDG_RUNTIME_DIR`) of the user `uid`, `/run/user/<uid>`.
+///
+/// logind creates the directory when the user's first session starts (or at boot, for users with
+/// lingering enabled) and removes it when their last session ends, so it may not exist.
+pub fn user_runtime_dir(uid: uid_t) -> PathBuf {
+    PathBuf::from(format!("/run/user/{}", uid))
+}
+
+/// The D-Bus address of the user bus of the user `uid`, `unix:path=/run/user/<uid>/bus`.
+///
+/// This is where the user's bus daemon listens while the user's service manager runs. Connect to
+/// it with [`BusBuilder::address()`]; the bus usually only accepts connections from its owner, so
+/// privileged agents should switch to the user's credentials first.
+///
+/// [`BusBuilder::address()`]: crate::bus::BusBuilder::address
+pub fn user_bus_address(uid: uid_t) -> String {
+    format!("unix:path={}/bus", user_runtime_dir(uid).display())
+}
+
+/// Builds a journal [`Filter`](crate::journal::Filter) selecting the log activity of a login
+/// session.
+///
+/// The filter matches entries whose `_AUDIT_SESSION=` is the session identifier and whose `_UID=`
+/// is the session's owner. logind only uses audit session identifiers as session identifiers when
+/// audit is enabled; otherwise no entries will carry a matching `_AUDIT_SESSION=` field.
+#[cfg(feature = "journal")]
+pub fn journal_filter_for_session<S: CStrArgument>(session: S) -> Result<crate::journal::Filter> {
+    use crate::journal::fields;
+
+    let session = session.into_cstr();
+    let mut uid: uid_t = 0;
+    sd_try!(ffi::sd_session_get_uid(session.as_ref().as_ptr(), &mut uid));
+    let mut filter = crate::journal::Filter::new();
+    filter
+        .match_add(fields::_AUDIT_SESSION, session.as_ref().to_bytes())
+        .match_add(fields::_UID, uid.to_string());
+    Ok(filter)
+}
+
+/// Determines whether the session identified by `session` is active, i.e. in the foreground of
+/// its seat.
+pub fn session_is_active<S: CStrArgument>(session: S) -> Result<bool> {
+    let session = session.into_cstr();
+    let r = sd_try!(ffi::sd_session_is_active(session.as_ref().as_ptr()));
+    Ok(r > 0)
+}
+
+/// Watches a single session for becoming active (being switched to the foreground of its seat)
+/// and for staying inactive for a while, as screen savers and presence detection need.
+///
+/// Changes are picked up with a `sd_login_monitor` for sessions. State changes which are undone
+/// within the [debounce](SessionWatcher::debounce) interval (half a second by default) aren't
+/// reported, so quickly switching back and forth between sessions doesn't trigger callbacks.
+///
+/// The state of the session when the watcher is created is the starting point: `on_active` isn't
+/// called for a session that is already active, but the idle threshold of an inactive session
+/// counts from the creation of the watcher.
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use systemd::login;
+/// let session = login::get_session(None)?;
+/// let mut watcher = login::SessionWatcher::new(session)?
+///     .on_active(|| println!("back"))
+///     .on_idle(Duration::from_secs(300), || println!("away"));
+/// loop {
+///     watcher.wait(None)?;
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct SessionWatcher {
+    session: CString,
+    monitor: *mut ffi::sd_login_monitor,
+    debounce: Duration,
+    state: SessionState,
+    on_active: Option<Box<dyn FnMut()>>,
+    on_idle: Option<(Duration, Box<dyn FnMut()>)>,
+}
+
+impl SessionWatcher {
+    /// Start watching the session identified by `session`.
+    ///
+    /// Fails if the session doesn't exist.
+    pub fn new<S: CStrArgument>(session: S) -> Result<SessionWatcher> {
+        let session = session.into_cstr().as_ref().to_owned();
+        let mut monitor = ptr::null_mut();
+        sd_try!(ffi::sd_login_monitor_new(
+            b"session\0".as_ptr() as *const c_char,
+            &mut monitor
+        ));
+        let mut w = SessionWatcher {
+            session,
+            monitor,
+            debounce: Duration::from_millis(500),
+            state: SessionState::new(false, Instant::now()),
+            on_active: None,
+            on_idle: None,
+        };
+        w.state = SessionState::new(w.is_active()?, Instant::now());
+        Ok(w)
+    }
+
+    /// Call `callback` whenever the session becomes active.
+    pub fn on_active<F: FnMut() + 'static>(mut self, callback: F) -> Self {
+        self.on_active = Some(Box::new(callback));
+        self
+    }
+
+    /// Call `callback` once the session has been inactive for `threshold`. It is called again
+    /// only after the session was active in between.
+    pub fn on_idle<F: FnMut() + 'static>(mut self, threshold: Duration, callback: F) -> Self {
+        self.on_idle = Some((threshold, Box::new(callback)));
+        self
+    }
+
+    /// Only report a change of the session state once it has persisted for `interval`.
+    pub fn debounce(mut self, interval: Duration) -> Self {
+        self.debounce = interval;
+        self
+    }
+
+    /// Whether the session is active right now, regardless of debouncing.
+    pub fn is_active(&self) -> Result<bool> {
+        session_is_active(self.session.as_c_str())
+    }
+
+    /// Read the state of the session and call the callbacks which are due.
+    ///
+    /// Call this when the file descriptor of the watcher becomes readable, or when
+    /// [`SessionWatcher::timeout()`] expires.
+    pub fn process(&mut self) -> Result<()> {
+        sd_try!(ffi::sd_login_monitor_flush(self.monitor));
+        let active = self.is_active()?;
+        let idle = self.on_idle.as_ref().map(|(threshold, _)| *threshold);
+        match self
+            .state
+            .update(active, Instant::now(), self.debounce, idle)
+        {
+            Some(Transition::Active) => {
+                if let Some(f) = self.on_active.as_mut() {
+                    f();
+                }
+            }
+            Some(Transition::Idle) => {
+                if let Some((_, f)) = self.on_idle.as_mut() {
+                    f();
+                }
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// How long until [`SessionWatcher::process()`] needs to be called even if the file
+    /// descriptor doesn't become readable, or `None` if there's nothing to wait for.
+    pub fn timeout(&self) -> Option<Duration> {
+        let idle = self.on_idle.as_ref().map(|(threshold, _)| *threshold);
+        self.state
+            .deadline(self.debounce, idle)
+            .map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
+    /// Wait for up to `timeout` (or forever, if `None`) for a change, and then process it.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<()> {
+        let wait = match (timeout, self.timeout()) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let timeout_ms = match wait {
+            None => -1,
+            // round up, so waking up early doesn't turn into a busy loop
+            Some(t) => {
+                let ms = t.as_millis() + u128::from(t.subsec_nanos() % 1_000_000 != 0);
+                ms.min(libc::c_int::MAX as u128) as libc::c_int
+            }
+        };
+        let mut pfd = libc::pollfd {
+            fd: self.as_raw_fd(),
+            events: sd_try!(ffi::sd_login_monitor_get_events(self.monitor)) as libc::c_short,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut pfd, 1, timeout_ms) } < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::Interrupted {
+                return Err(e);
+            }
+        }
+        self.process()
+    }
+}
+
+impl AsRawFd for SessionWatcher {
+    /// The file descriptor to poll for changes, with the events given by
+    /// `sd_login_monitor_get_events()` (in practice, readability).
+    fn as_raw_fd(&self) -> RawFd {
+        unsafe { ffi::sd_login_monitor_get_fd(self.monitor) }
+    }
+}
+
+impl Drop for SessionWatcher {
+    fn drop(&mut self) {
+        unsafe { ffi::sd_login_monitor_unref(self.monitor) };
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Transition {
+    Active,
+    Idle,
+}
+
+/// The debounced state of a session.
+struct SessionState {
+    active: bool,
+    /// When `active` last changed
+    since: Instant,
+    /// When the session was first seen in the opposite state, if it currently is
+    pending: Option<Instant>,
+    idle_reported: bool,
+}
+
+impl SessionState {
+    fn new(active: bool, now: Instant) -> Self {
+        SessionState {
+            active,
+            since: now,
+            pending: None,
+            idle_reported: false,
+        }
+    }
+
+    fn update(
+        &mut self,
+        active: bool,
+        now: Instant,
+        debounce: Duration,
+        idle: Option<Duration>,
+    ) -> Option<Transition> {
+        if active == self.active {
+            self.pending = None;
+        } else {
+            let pending = *self.pending.get_or_insert(now);
+            if now.duration_since(pending) >= debounce {
+                self.active = active;
+                self.since = pending;
+                self.pending = None;
+                self.idle_reported = false;
+                if active {
+                    return Some(Transition::Active);
+                }
+            }
+        }
+
+        match idle {
+            Some(threshold)
+                if !self.active
+                    && !self.idle_reported
+                    && now.duration_since(self.since) >= threshold =>
+            {
+                self.idle_reported = true;
+                Some(Transition::Idle)
+            }
+            _ => None,
+        }
+    }
+
+    fn deadline(&self, debounce: Duration, idle: Option<Duration>) -> Option<Instant> {
+        let pending = self.pending.map(|p| p + debounce);
+        let idle = match idle {
+            Some(threshold) if !self.active && !self.idle_reported => Some(self.since + threshold),
+            _ => None,
+        };
+        match (pending, idle) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+#[test]
+fn t_session_state() {
+    let debounce = Duration::from_millis(500);
+    let idle = Some(Duration::from_secs(10));
+    let start = Instant::now();
+    let at = |ms| start + Duration::from_millis(ms);
+    let mut s = SessionState::new(true, start);
+    assert_eq!(s.deadline(debounce, idle), None);
+
+    // a short flap is ignored
+    assert_eq!(s.update(false, at(100), debounce, idle), None);
+    assert_eq!(s.deadline(debounce, idle), Some(at(600)));
+    assert_eq!(s.update(true, at(200), debounce, idle), None);
+    assert_eq!(s.deadline(debounce, idle), None);
+
+    // going inactive for longer than the debounce starts the idle countdown from the change
+    assert_eq!(s.update(false, at(1000), debounce, idle), None);
+    assert_eq!(s.update(false, at(1500), debounce, idle), None);
+    assert_eq!(s.deadline(debounce, idle), Some(at(11000)));
+    assert_eq!(
+        s.update(false, at(11000), debounce, idle),
+        Some(Transition::Idle)
+    );
+    // reported once
+    assert_eq!(s.update(false, at(12000), debounce, idle), None);
+    assert_eq!(s.deadline(debounce, idle), None);
+
+    assert_eq!(s.update(true, at(13000), debounce, idle), None);
+    assert_eq!(
+        s.update(true, at(13500), debounce, idle),
+        Some(Transition::Active)
+    );
+    assert_eq!(s.update(true, at(14000), debounce, idle), None);
+}
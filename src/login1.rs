@@ -0,0 +1,384 @@
+use crate::bus::types::{append_str, UnixFd};
+use crate::bus::{
+    self, BusName, BusRef, InterfaceName, MemberName, MessageIter, MessageRef, ObjectPath,
+    SignalDef, Slot,
+};
+use libc::{c_char, c_int};
+use std::ffi::CStr;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::{fmt, io, ops};
+
+fn destination() -> &'static BusName {
+    unsafe { BusName::from_bytes_unchecked(b"org.freedesktop.login1\0") }
+}
+
+fn manager_path() -> &'static ObjectPath {
+    unsafe { ObjectPath::from_bytes_unchecked(b"/org/freedesktop/login1\0") }
+}
+
+fn manager_interface() -> &'static InterfaceName {
+    unsafe { InterfaceName::from_bytes_unchecked(b"org.freedesktop.login1.Manager\0") }
+}
+
+fn member(m: &'static [u8]) -> &'static MemberName {
+    unsafe { MemberName::from_bytes_unchecked(m) }
+}
+
+/// The set of operations an inhibitor lock applies to.
+///
+/// Values can be combined with `|`:
+///
+/// ```
+/// use systemd::login1::What;
+/// let w = What::SLEEP | What::SHUTDOWN;
+/// assert_eq!(w.to_string(), "shutdown:sleep");
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct What(u32);
+
+const WHAT_NAMES: &[(What, &str)] = &[
+    (What::SHUTDOWN, "shutdown"),
+    (What::SLEEP, "sleep"),
+    (What::IDLE, "idle"),
+    (What::HANDLE_POWER_KEY, "handle-power-key"),
+    (What::HANDLE_SUSPEND_KEY, "handle-suspend-key"),
+    (What::HANDLE_HIBERNATE_KEY, "handle-hibernate-key"),
+    (What::HANDLE_LID_SWITCH, "handle-lid-switch"),
+    (What::HANDLE_REBOOT_KEY, "handle-reboot-key"),
+];
+
+impl What {
+    /// Power-off and reboot
+    pub const SHUTDOWN: What = What(1 << 0);
+    /// Suspend and hibernation
+    pub const SLEEP: What = What(1 << 1);
+    /// Automatic idle handling of the system
+    pub const IDLE: What = What(1 << 2);
+    /// Low-level handling of the power key by logind
+    pub const HANDLE_POWER_KEY: What = What(1 << 3);
+    /// Low-level handling of the suspend key by logind
+    pub const HANDLE_SUSPEND_KEY: What = What(1 << 4);
+    /// Low-level handling of the hibernate key by logind
+    pub const HANDLE_HIBERNATE_KEY: What = What(1 << 5);
+    /// Low-level handling of the lid switch by logind
+    pub const HANDLE_LID_SWITCH: What = What(1 << 6);
+    /// Low-level handling of the reboot key by logind
+    pub const HANDLE_REBOOT_KEY: What = What(1 << 7);
+
+    /// Returns true if no operation is included.
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns true if all operations in `other` are included in `self`.
+    pub fn contains(self, other: What) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl ops::BitOr for What {
+    type Output = What;
+
+    fn bitor(self, rhs: What) -> What {
+        What(self.0 | rhs.0)
+    }
+}
+
+impl ops::BitOrAssign for What {
+    fn bitor_assign(&mut self, rhs: What) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Formats the colon separated list logind expects, e.g. `sleep:idle`.
+impl fmt::Display for What {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        for (w, name) in WHAT_NAMES {
+            if self.contains(*w) {
+                if !first {
+                    f.write_str(":")?;
+                }
+                f.write_str(name)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How an inhibitor lock affects the operations it applies to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Prevent the operations from happening while the lock is held.
+    Block,
+    /// Delay the operations until the lock is released, or until logind's `InhibitDelayMaxSec=`
+    /// elapses.
+    Delay,
+}
+
+impl Mode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Mode::Block => "block",
+            Mode::Delay => "delay",
+        }
+    }
+}
+
+/// An inhibitor lock taken with [`inhibit()`].
+///
+/// logind considers the lock held as long as the file descriptor it returned is open. The lock is
+/// released when the `Inhibitor` is dropped.
+#[derive(Debug)]
+pub struct Inhibitor {
+    fd: OwnedFd,
+}
+
+/// Take an inhibitor lock from logind (`org.freedesktop.login1.Manager.Inhibit`).
+///
+/// `who` is a human readable name of the program taking the lock and `why` the reason it is
+/// taken; both are shown to users by tools like `systemd-inhibit --list`.
+///
+/// ```no_run
+/// use systemd::{bus, login1};
+/// let mut bus = bus::Bus::default_system().unwrap();
+/// let lock = login1::inhibit(&mut bus, login1::What::SLEEP, "example", "Saving state",
+///     login1::Mode::Delay).unwrap();
+/// // ... do the work the lock protects ...
+/// drop(lock);
+/// ```
+pub fn inhibit(
+    bus: &mut BusRef,
+    what: What,
+    who: &str,
+    why: &str,
+    mode: Mode,
+) -> bus::Result<Inhibitor> {
+    let mut r = bus.call_method(
+        destination(),
+        manager_path(),
+        manager_interface(),
+        member(b"Inhibit\0"),
+        |m| {
+            append_str(m, &what.to_string())?;
+            append_str(m, who)?;
+            append_str(m, why)?;
+            append_str(m, mode.as_str())
+        },
+    )?;
+    let mut i = r.iter()?;
+    let fd: Option<UnixFd> = i.next()?;
+    let fd = match fd {
+        Some(fd) => fd.0,
+        None => return Err(io::Error::from_raw_os_error(libc::EBADMSG).into()),
+    };
+    // the fd is owned by the reply message, keep our own copy
+    let fd = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 3) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(Inhibitor {
+        fd: unsafe { OwnedFd::from_raw_fd(fd) },
+    })
+}
+
+impl Inhibitor {
+    /// Release the lock. This is equivalent to dropping the `Inhibitor`.
+    pub fn release(self) {}
+
+    /// Run `f` while holding the lock, then release it (even if `f` panics).
+    ///
+    /// This is meant for delay locks, where work must be completed before a pending operation
+    /// (for example, suspend) is allowed to proceed:
+    ///
+    /// ```no_run
+    /// # use systemd::{bus, login1};
+    /// # let mut bus = bus::Bus::default_system().unwrap();
+    /// # let lock = login1::inhibit(&mut bus, login1::What::SLEEP, "example", "Saving state",
+    /// #     login1::Mode::Delay).unwrap();
+    /// lock.delay_guard(|| {
+    ///     // flush state to disk before the system goes to sleep
+    /// });
+    /// ```
+    pub fn delay_guard<F, T>(self, f: F) -> T
+    where
+        F: FnOnce() -> T,
+    {
+        let r = f();
+        self.release();
+        r
+    }
+}
+
+impl AsFd for Inhibitor {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for Inhibitor {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl From<Inhibitor> for OwnedFd {
+    fn from(i: Inhibitor) -> OwnedFd {
+        i.fd
+    }
+}
+
+/// The signal `member` emitted by the login manager object.
+fn signal(m: &'static [u8]) -> SignalDef<'static> {
+    SignalDef::new(manager_interface(), member(m))
+        .sender(destination())
+        .path(manager_path())
+}
+
+fn bad_message() -> bus::Error {
+    io::Error::from_raw_os_error(libc::EBADMSG).into()
+}
+
+fn read_str(i: &mut MessageIter<'_>, dbus_type: u8) -> bus::Result<String> {
+    let s = unsafe {
+        i.read_basic_raw(dbus_type, |p: *const c_char| {
+            CStr::from_ptr(p).to_string_lossy().into_owned()
+        })
+    }?;
+    s.ok_or_else(bad_message)
+}
+
+fn read_bool(i: &mut MessageIter<'_>) -> bus::Result<bool> {
+    let b = unsafe { i.read_basic_raw(b'b', |b: c_int| b != 0) }?;
+    b.ok_or_else(bad_message)
+}
+
+/// Decode the `(so)` arguments of `SessionNew` and `SessionRemoved`.
+fn session_args(m: &mut MessageRef) -> bus::Result<(String, String)> {
+    let mut i = m.iter()?;
+    let id = read_str(&mut i, b's')?;
+    let path = read_str(&mut i, b'o')?;
+    Ok((id, path))
+}
+
+fn on_session_signal<F>(bus: &BusRef, m: &'static [u8], f: F) -> crate::Result<Slot>
+where
+    F: Fn(&str, &ObjectPath) + Send + Sync + 'static,
+{
+    signal(m).add_match(bus, move |m| {
+        let (id, path) = session_args(m)?;
+        // logind only sends valid object paths, and the string has no interior nul
+        let path = std::ffi::CString::new(path).map_err(|_| bad_message())?;
+        f(&id, unsafe {
+            ObjectPath::from_bytes_unchecked(path.as_bytes_with_nul())
+        });
+        Ok(())
+    })
+}
+
+/// Call `f` with the id and object path of every session logind creates (the `SessionNew`
+/// signal).
+///
+/// Callbacks are invoked while processing the bus (see [`BusRef::process()`]). The subscription
+/// lasts until the returned [`Slot`] is dropped.
+///
+/// ```no_run
+/// use systemd::{bus, login1};
+/// let mut bus = bus::Bus::default_system().unwrap();
+/// let _slot = login1::on_session_new(&bus, |id, path| {
+///     println!("new session {} at {:?}", id, path);
+/// })
+/// .unwrap();
+/// loop {
+///     bus.process().unwrap();
+///     bus.wait(None).unwrap();
+/// }
+/// ```
+pub fn on_session_new<F>(bus: &BusRef, f: F) -> crate::Result<Slot>
+where
+    F: Fn(&str, &ObjectPath) + Send + Sync + 'static,
+{
+    on_session_signal(bus, b"SessionNew\0", f)
+}
+
+/// Call `f` with the id and object path of every session logind removes (the `SessionRemoved`
+/// signal).
+///
+/// See [`on_session_new()`] for how callbacks are delivered.
+pub fn on_session_removed<F>(bus: &BusRef, f: F) -> crate::Result<Slot>
+where
+    F: Fn(&str, &ObjectPath) + Send + Sync + 'static,
+{
+    on_session_signal(bus, b"SessionRemoved\0", f)
+}
+
+fn on_bool_signal<F>(bus: &BusRef, m: &'static [u8], f: F) -> crate::Result<Slot>
+where
+    F: Fn(bool) + Send + Sync + 'static,
+{
+    signal(m).add_match(bus, move |m| {
+        let mut i = m.iter()?;
+        f(read_bool(&mut i)?);
+        Ok(())
+    })
+}
+
+/// Call `f` when the system is about to suspend or hibernate (with `true`) and after it resumed
+/// (with `false`), from the `PrepareForSleep` signal.
+///
+/// Combined with a [`Mode::Delay`] lock on [`What::SLEEP`], this allows finishing work before
+/// the system goes to sleep:
+///
+/// ```no_run
+/// use std::sync::Mutex;
+/// use systemd::{bus, login1};
+/// use login1::{Inhibitor, Mode, What};
+///
+/// fn take_lock() -> Inhibitor {
+///     let mut bus = bus::Bus::default_system().unwrap();
+///     login1::inhibit(&mut bus, What::SLEEP, "example", "Saving state", Mode::Delay).unwrap()
+/// }
+///
+/// let mut bus = bus::Bus::default_system().unwrap();
+/// let lock = Mutex::new(Some(take_lock()));
+/// let _slot = login1::on_prepare_for_sleep(&bus, move |start| {
+///     let mut lock = lock.lock().unwrap();
+///     if start {
+///         if let Some(l) = lock.take() {
+///             l.delay_guard(|| { /* save state */ });
+///         }
+///     } else {
+///         *lock = Some(take_lock());
+///     }
+/// })
+/// .unwrap();
+/// ```
+pub fn on_prepare_for_sleep<F>(bus: &BusRef, f: F) -> crate::Result<Slot>
+where
+    F: Fn(bool) + Send + Sync + 'static,
+{
+    on_bool_signal(bus, b"PrepareForSleep\0", f)
+}
+
+/// Call `f` when the system is about to power off or reboot (with `true`), or when a scheduled
+/// shutdown was cancelled (with `false`), from the `PrepareForShutdown` signal.
+///
+/// See [`on_prepare_for_sleep()`] for how to combine this with a delay lock.
+pub fn on_prepare_for_shutdown<F>(bus: &BusRef, f: F) -> crate::Result<Slot>
+where
+    F: Fn(bool) + Send + Sync + 'static,
+{
+    on_bool_signal(bus, b"PrepareForShutdown\0", f)
+}
+
+#[test]
+fn t_what_display() {
+    assert_eq!(What::default().to_string(), "");
+    assert_eq!(What::IDLE.to_string(), "idle");
+    let mut w = What::HANDLE_LID_SWITCH | What::SHUTDOWN;
+    w |= What::HANDLE_POWER_KEY;
+    assert_eq!(w.to_string(), "shutdown:handle-power-key:handle-lid-switch");
+    assert!(w.contains(What::SHUTDOWN | What::HANDLE_POWER_KEY));
+    assert!(!w.contains(What::SLEEP));
+}
@@ -0,0 +1,1565 @@
+use crate::bus::types::append_str;
+use crate::bus::{
+    self, Bus, BusName, BusRef, CallPolicy, InterfaceName, MemberName, Message, MessageRef,
+    ObjectPath, SignalDef, Slot,
+};
+use foreign_types::ForeignTypeRef;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{fmt, io};
+use utf8_cstr::Utf8CStr;
+
+const UNIT_PATH_PREFIX: &str = "/org/freedesktop/systemd1/unit/";
+
+fn destination() -> &'static BusName {
+    unsafe { BusName::from_bytes_unchecked(b"org.freedesktop.systemd1\0") }
+}
+
+/// Returns the object path under which the manager exposes the unit `name`.
+///
+/// The path is computed locally using the same escaping as the manager (see
+/// [`sd_bus_path_encode`]), so the unit does not need to be loaded for this to succeed.
+///
+/// The returned value is nul terminated and always a valid [`ObjectPath`]:
+///
+/// ```
+/// use systemd::{bus, manager};
+/// let p = manager::unit_path("dbus.service");
+/// let _ = bus::ObjectPath::from_bytes(p.as_bytes_with_nul()).unwrap();
+/// ```
+///
+/// [`sd_bus_path_encode`]: https://www.freedesktop.org/software/systemd/man/sd_bus_path_encode.html
+pub fn unit_path(name: &str) -> CString {
+    let mut p = String::from(UNIT_PATH_PREFIX);
+    if name.is_empty() {
+        p.push('_');
+    }
+    for (i, b) in name.bytes().enumerate() {
+        if b.is_ascii_alphabetic() || (i > 0 && b.is_ascii_digit()) {
+            p.push(b as char);
+        } else {
+            p.push_str(&format!("_{:02x}", b));
+        }
+    }
+    // escaping leaves no nul bytes in the path
+    CString::new(p).unwrap()
+}
+
+fn manager_path() -> &'static ObjectPath {
+    unsafe { ObjectPath::from_bytes_unchecked(b"/org/freedesktop/systemd1\0") }
+}
+
+fn manager_interface() -> &'static InterfaceName {
+    unsafe { InterfaceName::from_bytes_unchecked(b"org.freedesktop.systemd1.Manager\0") }
+}
+
+fn unit_interface() -> &'static InterfaceName {
+    unsafe { InterfaceName::from_bytes_unchecked(b"org.freedesktop.systemd1.Unit\0") }
+}
+
+fn job_interface() -> &'static InterfaceName {
+    unsafe { InterfaceName::from_bytes_unchecked(b"org.freedesktop.systemd1.Job\0") }
+}
+
+fn member(m: &'static [u8]) -> &'static MemberName {
+    unsafe { MemberName::from_bytes_unchecked(m) }
+}
+
+fn owned_path(path: &ObjectPath) -> CString {
+    CString::from(&**path)
+}
+
+// `p` must have been created by `owned_path()` or `unit_path()`
+fn as_path(p: &CString) -> &ObjectPath {
+    unsafe { ObjectPath::from_bytes_unchecked(p.as_bytes_with_nul()) }
+}
+
+/// The type specific interface of units which are backed by a control group, and thus carry
+/// resource accounting properties.
+fn cgroup_interface(name: &str) -> Option<&'static InterfaceName> {
+    let i: &'static [u8] = match name.rsplit('.').next()? {
+        "service" => b"org.freedesktop.systemd1.Service\0",
+        "scope" => b"org.freedesktop.systemd1.Scope\0",
+        "slice" => b"org.freedesktop.systemd1.Slice\0",
+        "socket" => b"org.freedesktop.systemd1.Socket\0",
+        "mount" => b"org.freedesktop.systemd1.Mount\0",
+        "swap" => b"org.freedesktop.systemd1.Swap\0",
+        _ => return None,
+    };
+    Some(unsafe { InterfaceName::from_bytes_unchecked(i) })
+}
+
+/// Resource usage of a unit, as tracked by the manager.
+///
+/// Each counter is `None` if the manager reports it as not set (`u64::MAX`, shown as `[not set]`
+/// by `systemctl`), which happens when the corresponding accounting is disabled for the unit or
+/// the unit is not running. Counters unknown to the running manager version are also `None`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UnitResources {
+    /// `CPUUsageNSec`: CPU time consumed, in nanoseconds
+    pub cpu_usage_nsec: Option<u64>,
+    /// `MemoryCurrent`: memory currently in use, in bytes
+    pub memory_current: Option<u64>,
+    /// `TasksCurrent`: number of tasks (processes and threads)
+    pub tasks_current: Option<u64>,
+    /// `IPIngressBytes`: bytes received over IP
+    pub ip_ingress_bytes: Option<u64>,
+    /// `IPEgressBytes`: bytes sent over IP
+    pub ip_egress_bytes: Option<u64>,
+    /// `IOReadBytes`: bytes read from block devices
+    pub io_read_bytes: Option<u64>,
+    /// `IOWriteBytes`: bytes written to block devices
+    pub io_write_bytes: Option<u64>,
+}
+
+fn counter(
+    bus: &mut BusRef,
+    path: &ObjectPath,
+    interface: &InterfaceName,
+    m: &'static [u8],
+) -> bus::Result<Option<u64>> {
+    match bus.get_property_trivial::<u64>(destination(), path, interface, member(m)) {
+        Ok(u64::MAX) => Ok(None),
+        Ok(v) => Ok(Some(v)),
+        Err(e) if e.has_name("org.freedesktop.DBus.Error.UnknownProperty\0") => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Collect the resource usage counters of the unit `name` via the `org.freedesktop.DBus.Properties`
+/// interface of the manager.
+///
+/// Only units backed by a control group (services, scopes, slices, sockets, mounts and swaps) have
+/// resource usage; other unit types result in an `org.freedesktop.DBus.Error.InvalidArgs` error.
+pub fn unit_resources(bus: &mut BusRef, name: &str) -> bus::Result<UnitResources> {
+    let interface = match cgroup_interface(name) {
+        Some(i) => i,
+        None => {
+            return Err(bus::Error::new(
+                Utf8CStr::from_bytes(b"org.freedesktop.DBus.Error.InvalidArgs\0").unwrap(),
+                Some(Utf8CStr::from_bytes(b"Unit type has no resource accounting\0").unwrap()),
+            ))
+        }
+    };
+    let path = unit_path(name);
+    let path = as_path(&path);
+
+    Ok(UnitResources {
+        cpu_usage_nsec: counter(bus, path, interface, b"CPUUsageNSec\0")?,
+        memory_current: counter(bus, path, interface, b"MemoryCurrent\0")?,
+        tasks_current: counter(bus, path, interface, b"TasksCurrent\0")?,
+        ip_ingress_bytes: counter(bus, path, interface, b"IPIngressBytes\0")?,
+        ip_egress_bytes: counter(bus, path, interface, b"IPEgressBytes\0")?,
+        io_read_bytes: counter(bus, path, interface, b"IOReadBytes\0")?,
+        io_write_bytes: counter(bus, path, interface, b"IOWriteBytes\0")?,
+    })
+}
+
+/// How a newly queued job interacts with jobs that are already queued.
+///
+/// See the `--job-mode=` option of [`systemctl(1)`] for the meaning of each mode.
+///
+/// [`systemctl(1)`]: https://www.freedesktop.org/software/systemd/man/systemctl.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobMode {
+    Replace,
+    Fail,
+    Isolate,
+    IgnoreDependencies,
+    IgnoreRequirements,
+}
+
+impl JobMode {
+    fn as_utf8_cstr(self) -> &'static Utf8CStr {
+        let s: &'static [u8] = match self {
+            JobMode::Replace => b"replace\0",
+            JobMode::Fail => b"fail\0",
+            JobMode::Isolate => b"isolate\0",
+            JobMode::IgnoreDependencies => b"ignore-dependencies\0",
+            JobMode::IgnoreRequirements => b"ignore-requirements\0",
+        };
+        Utf8CStr::from_bytes(s).unwrap()
+    }
+}
+
+/// Which processes of a unit a signal is sent to by [`UnitProxy::kill()`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KillWho {
+    /// Only the main process
+    Main,
+    /// Only the control process (e.g. a running `ExecReload=` command)
+    Control,
+    /// All processes in the unit's control group
+    All,
+}
+
+impl KillWho {
+    fn as_utf8_cstr(self) -> &'static Utf8CStr {
+        let s: &'static [u8] = match self {
+            KillWho::Main => b"main\0",
+            KillWho::Control => b"control\0",
+            KillWho::All => b"all\0",
+        };
+        Utf8CStr::from_bytes(s).unwrap()
+    }
+}
+
+fn call_method<F>(
+    bus: &mut BusRef,
+    path: &ObjectPath,
+    interface: &InterfaceName,
+    m: &'static [u8],
+    args: F,
+) -> bus::Result<Message>
+where
+    F: FnOnce(&mut MessageRef) -> crate::Result<()>,
+{
+    bus.call_method(destination(), path, interface, member(m), args)
+}
+
+/// Like `call_method()`, retrying according to `policy` if there is one.
+fn call_method_policy<F>(
+    bus: &mut BusRef,
+    policy: Option<&CallPolicy>,
+    path: &ObjectPath,
+    interface: &InterfaceName,
+    m: &'static [u8],
+    args: F,
+) -> bus::Result<Message>
+where
+    F: Fn(&mut MessageRef) -> crate::Result<()>,
+{
+    match policy {
+        Some(policy) => {
+            bus.call_method_with_policy(destination(), path, interface, member(m), policy, args)
+        }
+        None => call_method(bus, path, interface, m, args),
+    }
+}
+
+/// Read the property `m` of `interface` on the object at `path`, retrying according to `policy`
+/// if there is one. Reading a property has no effect on the object, so it is always safe to retry.
+fn get_property_policy<T>(
+    bus: &mut BusRef,
+    policy: Option<&CallPolicy>,
+    path: &ObjectPath,
+    interface: &InterfaceName,
+    m: &'static [u8],
+) -> bus::Result<T>
+where
+    T: for<'a> bus::types::FromSdBusMessage<'a> + bus::types::SdBusSignature,
+{
+    let policy = match policy {
+        Some(policy) => policy,
+        None => return bus.get_property(destination(), path, interface, member(m)),
+    };
+    let properties =
+        unsafe { InterfaceName::from_bytes_unchecked(b"org.freedesktop.DBus.Properties\0") };
+    let mut r = bus.call_method_with_policy(
+        destination(),
+        path,
+        properties,
+        member(b"Get\0"),
+        policy,
+        |msg| {
+            // names are validated to be ASCII
+            append_str(msg, interface.to_str().unwrap())?;
+            append_str(msg, member(m).to_str().unwrap())
+        },
+    )?;
+    match r.iter()?.next::<bus::Variant<T>>()? {
+        Some(bus::Variant(v)) => Ok(v),
+        None => Err(io::Error::from_raw_os_error(libc::EBADMSG).into()),
+    }
+}
+
+/// Read the job object path returned by methods which enqueue a job.
+fn job_from_reply(bus: &BusRef, r: &mut Message) -> bus::Result<JobProxy> {
+    let mut i = r.iter()?;
+    let job: Option<&ObjectPath> = i.next()?;
+    match job {
+        Some(job) => Ok(JobProxy::new(bus, job)),
+        None => Err(io::Error::from_raw_os_error(libc::EBADMSG).into()),
+    }
+}
+
+/// A handle to a unit object of the manager (`org.freedesktop.systemd1.Unit`).
+///
+/// The handle holds a reference to the bus connection and the unit's object path, so methods can
+/// be invoked without re-specifying the destination, path and interface each time.
+pub struct UnitProxy {
+    bus: Bus,
+    path: CString,
+    policy: Option<CallPolicy>,
+    retry_non_idempotent: bool,
+}
+
+impl UnitProxy {
+    /// Create a handle for the unit exposed at `path` (for example, as returned by
+    /// [`unit_path()`]).
+    pub fn new(bus: &BusRef, path: &ObjectPath) -> Self {
+        UnitProxy {
+            bus: bus.to_owned(),
+            path: owned_path(path),
+            policy: None,
+            retry_non_idempotent: false,
+        }
+    }
+
+    /// Create a handle for the unit called `name`.
+    pub fn from_name(bus: &BusRef, name: &str) -> Self {
+        UnitProxy {
+            bus: bus.to_owned(),
+            path: unit_path(name),
+            policy: None,
+            retry_non_idempotent: false,
+        }
+    }
+
+    /// Retry reading properties of the unit and the method calls which can safely be repeated
+    /// (resetting its failed state, freezing and thawing it) according to `policy`, see
+    /// [`CallPolicy`].
+    ///
+    /// Enqueueing jobs and [`UnitProxy::kill()`] are only retried after opting in with
+    /// [`UnitProxy::retry_non_idempotent()`]. Jobs returned by the handle use the same policy.
+    pub fn with_call_policy(mut self, policy: CallPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// Also retry the method calls which enqueue jobs ([`UnitProxy::start()`],
+    /// [`UnitProxy::stop()`], [`UnitProxy::restart()`]) and [`UnitProxy::kill()`] according to the
+    /// call policy.
+    ///
+    /// A call failing with [`bus::ErrorKind::Timeout`] may still have been carried out by the
+    /// manager, only its reply is missing. Retrying it then enqueues a second job, restarting the
+    /// unit twice for example, or sends the signal again. Only opt in where that is acceptable, or
+    /// with a policy which doesn't retry timeouts.
+    pub fn retry_non_idempotent(mut self, retry: bool) -> Self {
+        self.retry_non_idempotent = retry;
+        self
+    }
+
+    /// The policy for calls which must not be repeated by accident.
+    fn non_idempotent_policy(&self) -> Option<&CallPolicy> {
+        self.policy.as_ref().filter(|_| self.retry_non_idempotent)
+    }
+
+    /// The object path of the unit
+    pub fn path(&self) -> &ObjectPath {
+        as_path(&self.path)
+    }
+
+    fn call_job(&mut self, m: &'static [u8], mode: JobMode) -> bus::Result<JobProxy> {
+        let path = as_path(&self.path);
+        let policy = self.non_idempotent_policy().cloned();
+        let mut r = call_method_policy(
+            &mut self.bus,
+            policy.as_ref(),
+            path,
+            unit_interface(),
+            m,
+            |msg| msg.append(mode.as_utf8_cstr()),
+        )?;
+        let mut job = job_from_reply(&self.bus, &mut r)?;
+        job.policy = self.policy.clone();
+        Ok(job)
+    }
+
+    /// Enqueue a start job for the unit.
+    ///
+    /// This corresponds to the `Start()` method.
+    pub fn start(&mut self, mode: JobMode) -> bus::Result<JobProxy> {
+        self.call_job(b"Start\0", mode)
+    }
+
+    /// Enqueue a stop job for the unit.
+    ///
+    /// This corresponds to the `Stop()` method.
+    pub fn stop(&mut self, mode: JobMode) -> bus::Result<JobProxy> {
+        self.call_job(b"Stop\0", mode)
+    }
+
+    /// Enqueue a restart job for the unit.
+    ///
+    /// This corresponds to the `Restart()` method.
+    pub fn restart(&mut self, mode: JobMode) -> bus::Result<JobProxy> {
+        self.call_job(b"Restart\0", mode)
+    }
+
+    fn call_unit(
+        &mut self,
+        policy: Option<CallPolicy>,
+        m: &'static [u8],
+        args: impl Fn(&mut MessageRef) -> crate::Result<()>,
+    ) -> bus::Result<()> {
+        let path = as_path(&self.path);
+        call_method_policy(
+            &mut self.bus,
+            policy.as_ref(),
+            path,
+            unit_interface(),
+            m,
+            args,
+        )?;
+        Ok(())
+    }
+
+    /// Send `signal` to processes of the unit.
+    ///
+    /// This corresponds to the `Kill()` method.
+    pub fn kill(&mut self, who: KillWho, signal: i32) -> bus::Result<()> {
+        let policy = self.non_idempotent_policy().cloned();
+        self.call_unit(policy, b"Kill\0", |msg| {
+            msg.append(who.as_utf8_cstr())?;
+            msg.append(signal)
+        })
+    }
+
+    /// Reset the failed state of the unit.
+    ///
+    /// This corresponds to the `ResetFailed()` method.
+    pub fn reset_failed(&mut self) -> bus::Result<()> {
+        self.call_unit(self.policy.clone(), b"ResetFailed\0", |_| Ok(()))
+    }
+
+    /// Freeze all processes of the unit using the cgroup freezer (since systemd v246).
+    ///
+    /// This corresponds to the `Freeze()` method.
+    pub fn freeze(&mut self) -> bus::Result<()> {
+        self.call_unit(self.policy.clone(), b"Freeze\0", |_| Ok(()))
+    }
+
+    /// Thaw a unit previously frozen with [`UnitProxy::freeze()`] (since systemd v246).
+    ///
+    /// This corresponds to the `Thaw()` method.
+    pub fn thaw(&mut self) -> bus::Result<()> {
+        self.call_unit(self.policy.clone(), b"Thaw\0", |_| Ok(()))
+    }
+
+    fn string_property(&mut self, m: &'static [u8]) -> bus::Result<String> {
+        let path = as_path(&self.path);
+        get_property_policy(
+            &mut self.bus,
+            self.policy.as_ref(),
+            path,
+            unit_interface(),
+            m,
+        )
+    }
+
+    /// The primary name of the unit (`Id` property)
+    pub fn id(&mut self) -> bus::Result<String> {
+        self.string_property(b"Id\0")
+    }
+
+    /// The human readable description of the unit (`Description` property)
+    pub fn description(&mut self) -> bus::Result<String> {
+        self.string_property(b"Description\0")
+    }
+
+    /// Whether the unit file was loaded, e.g. `loaded` or `not-found` (`LoadState` property)
+    pub fn load_state(&mut self) -> bus::Result<String> {
+        self.string_property(b"LoadState\0")
+    }
+
+    /// The high level activation state, e.g. `active` or `failed` (`ActiveState` property)
+    pub fn active_state(&mut self) -> bus::Result<String> {
+        self.string_property(b"ActiveState\0")
+    }
+
+    /// The unit type specific low level state, e.g. `running` or `exited` (`SubState` property)
+    pub fn sub_state(&mut self) -> bus::Result<String> {
+        self.string_property(b"SubState\0")
+    }
+}
+
+impl fmt::Debug for UnitProxy {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("UnitProxy")
+            .field("path", &self.path)
+            .field("policy", &self.policy)
+            .field("retry_non_idempotent", &self.retry_non_idempotent)
+            .finish()
+    }
+}
+
+/// A handle to a job object of the manager (`org.freedesktop.systemd1.Job`).
+///
+/// Jobs are transient: once a job completes its object disappears and calls on the handle fail
+/// with `org.freedesktop.DBus.Error.UnknownObject`.
+pub struct JobProxy {
+    bus: Bus,
+    path: CString,
+    policy: Option<CallPolicy>,
+}
+
+impl JobProxy {
+    /// Create a handle for the job exposed at `path`.
+    pub fn new(bus: &BusRef, path: &ObjectPath) -> Self {
+        JobProxy {
+            bus: bus.to_owned(),
+            path: owned_path(path),
+            policy: None,
+        }
+    }
+
+    /// Retry reading properties of the job and cancelling it according to `policy`, see
+    /// [`CallPolicy`].
+    pub fn with_call_policy(mut self, policy: CallPolicy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+
+    /// The object path of the job
+    pub fn path(&self) -> &ObjectPath {
+        as_path(&self.path)
+    }
+
+    /// Cancel the job.
+    ///
+    /// This corresponds to the `Cancel()` method.
+    pub fn cancel(&mut self) -> bus::Result<()> {
+        let path = as_path(&self.path);
+        call_method_policy(
+            &mut self.bus,
+            self.policy.as_ref(),
+            path,
+            job_interface(),
+            b"Cancel\0",
+            |_| Ok(()),
+        )?;
+        Ok(())
+    }
+
+    /// The numeric job id (`Id` property)
+    pub fn id(&mut self) -> bus::Result<u32> {
+        let path = as_path(&self.path);
+        get_property_policy(
+            &mut self.bus,
+            self.policy.as_ref(),
+            path,
+            job_interface(),
+            b"Id\0",
+        )
+    }
+
+    /// The job type, e.g. `start` or `stop` (`JobType` property)
+    pub fn job_type(&mut self) -> bus::Result<String> {
+        let path = as_path(&self.path);
+        get_property_policy(
+            &mut self.bus,
+            self.policy.as_ref(),
+            path,
+            job_interface(),
+            b"JobType\0",
+        )
+    }
+
+    /// The job state, `waiting` or `running` (`State` property)
+    pub fn state(&mut self) -> bus::Result<String> {
+        let path = as_path(&self.path);
+        get_property_policy(
+            &mut self.bus,
+            self.policy.as_ref(),
+            path,
+            job_interface(),
+            b"State\0",
+        )
+    }
+}
+
+impl fmt::Debug for JobProxy {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("JobProxy")
+            .field("path", &self.path)
+            .field("policy", &self.policy)
+            .finish()
+    }
+}
+
+fn signature(s: &'static [u8]) -> &'static CStr {
+    CStr::from_bytes_with_nul(s).unwrap()
+}
+
+/// A command line for properties like `ExecStart=`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExecCommand {
+    /// Absolute path of the binary to execute
+    pub path: String,
+    /// Arguments, including `argv[0]`
+    pub argv: Vec<String>,
+    /// Whether a failure of the command is ignored (the `-` prefix in unit files)
+    pub ignore_failure: bool,
+}
+
+impl ExecCommand {
+    /// Build a command from its arguments, executing `argv[0]`.
+    pub fn new<I, S>(argv: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let argv: Vec<String> = argv.into_iter().map(Into::into).collect();
+        ExecCommand {
+            path: argv.first().cloned().unwrap_or_default(),
+            argv,
+            ignore_failure: false,
+        }
+    }
+}
+
+/// The value of a unit property, as set when creating transient units.
+///
+/// See [`org.freedesktop.systemd1(5)`] for the properties each unit type accepts and their types.
+///
+/// [`org.freedesktop.systemd1(5)`]: https://www.freedesktop.org/software/systemd/man/org.freedesktop.systemd1.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PropertyValue {
+    /// `b`
+    Bool(bool),
+    /// `i`
+    I32(i32),
+    /// `u`
+    U32(u32),
+    /// `t`, also used for times in microseconds
+    U64(u64),
+    /// `s`
+    String(String),
+    /// `as`
+    Strings(Vec<String>),
+    /// `au`, used by `PIDs`
+    U32s(Vec<u32>),
+    /// `a(sbas)`, used by `ExecStart` and friends
+    ExecCommands(Vec<ExecCommand>),
+}
+
+impl PropertyValue {
+    fn signature(&self) -> &'static CStr {
+        signature(match self {
+            PropertyValue::Bool(_) => b"b\0",
+            PropertyValue::I32(_) => b"i\0",
+            PropertyValue::U32(_) => b"u\0",
+            PropertyValue::U64(_) => b"t\0",
+            PropertyValue::String(_) => b"s\0",
+            PropertyValue::Strings(_) => b"as\0",
+            PropertyValue::U32s(_) => b"au\0",
+            PropertyValue::ExecCommands(_) => b"a(sbas)\0",
+        })
+    }
+
+    fn append_to(&self, m: &mut MessageRef) -> crate::Result<()> {
+        match self {
+            PropertyValue::Bool(v) => m.append(*v),
+            PropertyValue::I32(v) => m.append(*v),
+            PropertyValue::U32(v) => m.append(*v),
+            PropertyValue::U64(v) => m.append(*v),
+            PropertyValue::String(v) => append_str(m, v),
+            PropertyValue::Strings(v) => {
+                m.open_container(b'a', signature(b"s\0"))?;
+                for s in v {
+                    append_str(m, s)?;
+                }
+                m.close_container()
+            }
+            PropertyValue::U32s(v) => {
+                m.open_container(b'a', signature(b"u\0"))?;
+                for u in v {
+                    m.append(*u)?;
+                }
+                m.close_container()
+            }
+            PropertyValue::ExecCommands(v) => {
+                m.open_container(b'a', signature(b"(sbas)\0"))?;
+                for c in v {
+                    m.open_container(b'r', signature(b"sbas\0"))?;
+                    append_str(m, &c.path)?;
+                    m.open_container(b'a', signature(b"s\0"))?;
+                    for a in &c.argv {
+                        append_str(m, a)?;
+                    }
+                    m.close_container()?;
+                    m.append(c.ignore_failure)?;
+                    m.close_container()?;
+                }
+                m.close_container()
+            }
+        }
+    }
+}
+
+/// Append properties as `a(sv)`.
+fn append_properties(
+    m: &mut MessageRef,
+    properties: &[(&str, PropertyValue)],
+) -> crate::Result<()> {
+    m.open_container(b'a', signature(b"(sv)\0"))?;
+    for (name, value) in properties {
+        m.open_container(b'r', signature(b"sv\0"))?;
+        append_str(m, name)?;
+        m.open_container(b'v', value.signature())?;
+        value.append_to(m)?;
+        m.close_container()?;
+        m.close_container()?;
+    }
+    m.close_container()
+}
+
+/// Create and start a transient unit called `name` with the given `properties`.
+///
+/// `aux` lists further transient units to create along with the main one, for example the service
+/// started by a transient timer or socket.
+///
+/// This corresponds to the `StartTransientUnit()` method of the manager.
+pub fn start_transient_unit(
+    bus: &mut BusRef,
+    name: &str,
+    mode: JobMode,
+    properties: &[(&str, PropertyValue)],
+    aux: &[(&str, &[(&str, PropertyValue)])],
+) -> bus::Result<JobProxy> {
+    let mut r = call_method(
+        bus,
+        manager_path(),
+        manager_interface(),
+        b"StartTransientUnit\0",
+        |m| {
+            append_str(m, name)?;
+            m.append(mode.as_utf8_cstr())?;
+            append_properties(m, properties)?;
+            m.open_container(b'a', signature(b"(sa(sv))\0"))?;
+            for (aux_name, aux_properties) in aux {
+                m.open_container(b'r', signature(b"sa(sv)\0"))?;
+                append_str(m, aux_name)?;
+                append_properties(m, aux_properties)?;
+                m.close_container()?;
+            }
+            m.close_container()
+        },
+    )?;
+    job_from_reply(bus, &mut r)
+}
+
+/// When a transient timer created by [`run_transient_timer()`] elapses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TimerTrigger {
+    /// A calendar event expression like `*-*-* 04:00:00`, see [`systemd.time(7)`]. The
+    /// expression is validated by the manager.
+    ///
+    /// [`systemd.time(7)`]: https://www.freedesktop.org/software/systemd/man/systemd.time.html
+    Calendar(String),
+    /// Relative to when the timer is activated (`OnActiveSec=`)
+    OnActive(Duration),
+    /// Relative to when the machine was booted (`OnBootSec=`)
+    OnBoot(Duration),
+    /// Relative to when the service manager was started (`OnStartupSec=`)
+    OnStartup(Duration),
+    /// Relative to when the service was last activated (`OnUnitActiveSec=`)
+    OnUnitActive(Duration),
+    /// Relative to when the service was last deactivated (`OnUnitInactiveSec=`)
+    OnUnitInactive(Duration),
+}
+
+impl TimerTrigger {
+    fn property(&self) -> (&'static str, PropertyValue) {
+        let usec = |d: &Duration| PropertyValue::U64(crate::usec_from_duration(*d));
+        match self {
+            TimerTrigger::Calendar(c) => ("OnCalendar", PropertyValue::String(c.clone())),
+            TimerTrigger::OnActive(d) => ("OnActiveSec", usec(d)),
+            TimerTrigger::OnBoot(d) => ("OnBootSec", usec(d)),
+            TimerTrigger::OnStartup(d) => ("OnStartupSec", usec(d)),
+            TimerTrigger::OnUnitActive(d) => ("OnUnitActiveSec", usec(d)),
+            TimerTrigger::OnUnitInactive(d) => ("OnUnitInactiveSec", usec(d)),
+        }
+    }
+}
+
+/// The units created by [`run_transient_timer()`].
+#[derive(Debug)]
+pub struct TransientTimer {
+    /// The `.timer` unit
+    pub timer: UnitProxy,
+    /// The `.service` unit activated by the timer
+    pub service: UnitProxy,
+    /// The job starting the timer
+    pub job: JobProxy,
+}
+
+/// Create a transient timer `{name}.timer` activating a transient service `{name}.service`,
+/// like `systemd-run --on-calendar=...` does.
+///
+/// `service_properties` describe the service and must at least contain an `ExecStart`:
+///
+/// ```no_run
+/// use systemd::bus::Bus;
+/// use systemd::manager::{self, ExecCommand, PropertyValue, TimerTrigger};
+/// let mut bus = Bus::default_system().unwrap();
+/// manager::run_transient_timer(
+///     &mut bus,
+///     "cleanup",
+///     TimerTrigger::Calendar("daily".into()),
+///     &[(
+///         "ExecStart",
+///         PropertyValue::ExecCommands(vec![ExecCommand::new(vec!["/usr/bin/true"])]),
+///     )],
+/// )
+/// .unwrap();
+/// ```
+pub fn run_transient_timer(
+    bus: &mut BusRef,
+    name: &str,
+    trigger: TimerTrigger,
+    service_properties: &[(&str, PropertyValue)],
+) -> bus::Result<TransientTimer> {
+    let timer = format!("{}.timer", name);
+    let service = format!("{}.service", name);
+    let timer_properties = [
+        trigger.property(),
+        ("Unit", PropertyValue::String(service.clone())),
+        // like systemd-run, let the units go away once they are no longer needed
+        ("RemainAfterElapsed", PropertyValue::Bool(false)),
+    ];
+    let job = start_transient_unit(
+        bus,
+        &timer,
+        JobMode::Fail,
+        &timer_properties,
+        &[(&service, service_properties)],
+    )?;
+    Ok(TransientTimer {
+        timer: UnitProxy::from_name(bus, &timer),
+        service: UnitProxy::from_name(bus, &service),
+        job,
+    })
+}
+
+/// Create a transient scope unit `name` (which must end in `.scope`) containing the existing
+/// processes `pids`.
+///
+/// Unlike services, scopes do not start processes: the processes are moved into the scope's
+/// control group once the returned job runs.
+pub fn start_transient_scope(
+    bus: &mut BusRef,
+    name: &str,
+    pids: &[libc::pid_t],
+    properties: &[(&str, PropertyValue)],
+) -> bus::Result<JobProxy> {
+    let mut all = vec![(
+        "PIDs",
+        PropertyValue::U32s(pids.iter().map(|&p| p as u32).collect()),
+    )];
+    all.extend(properties.iter().cloned());
+    start_transient_unit(bus, name, JobMode::Fail, &all, &[])
+}
+
+/// The job paths and results of the `JobRemoved` signals received by a match of
+/// `watch_jobs_removed()`.
+type JobResults = Arc<Mutex<Vec<(CString, String)>>>;
+
+/// Collect the results of jobs as they are removed. This has to be done before the job of
+/// interest is enqueued, so its removal can't be missed.
+fn watch_jobs_removed(bus: &BusRef) -> bus::Result<(SubscribedMatch, JobResults)> {
+    let results = JobResults::default();
+    let r = results.clone();
+    let def = SignalDef::new(manager_interface(), member(b"JobRemoved\0")).path(manager_path());
+    let watch = add_match(bus, &def, move |m| {
+        let mut i = m.iter()?;
+        let _id: Option<u32> = i.next()?;
+        let job: Option<&ObjectPath> = i.next()?;
+        let _unit: Option<&str> = i.next()?;
+        let result: Option<&str> = i.next()?;
+        if let (Some(job), Some(result)) = (job, result) {
+            r.lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push((owned_path(job), result.to_owned()));
+        }
+        Ok(())
+    })?;
+    Ok((watch, results))
+}
+
+/// Process the bus until `job` is removed, and fail unless it completed successfully.
+fn wait_job_done(
+    bus: &mut BusRef,
+    job: &JobProxy,
+    results: &JobResults,
+    timeout: Duration,
+) -> bus::Result<()> {
+    let path = owned_path(job.path());
+    let deadline = Instant::now() + timeout;
+    loop {
+        let result = results
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .find(|(p, _)| *p == path)
+            .map(|(_, result)| result.clone());
+        if let Some(result) = result {
+            return job_result(&result);
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return Err(io::Error::from_raw_os_error(libc::ETIMEDOUT).into());
+        }
+        if bus.process()?.is_none() {
+            bus.wait(Some(deadline - now))?;
+        }
+    }
+}
+
+/// Turn the result of a job, as reported by `JobRemoved`, into an error unless it is `done`.
+fn job_result(result: &str) -> bus::Result<()> {
+    let message: &[u8] = match result {
+        "done" => return Ok(()),
+        "canceled" => b"The job was canceled\0",
+        "timeout" => b"The job timed out\0",
+        "failed" => b"The job failed\0",
+        "dependency" => b"A dependency of the job failed\0",
+        "skipped" => b"The job was skipped\0",
+        _ => b"The job did not complete\0",
+    };
+    Err(bus::Error::new(
+        Utf8CStr::from_bytes(b"org.freedesktop.DBus.Error.Failed\0").unwrap(),
+        Some(Utf8CStr::from_bytes(message).unwrap()),
+    ))
+}
+
+/// Move the calling process into a new transient scope `name` with delegated control of its
+/// control group (`Delegate=yes`), so the process can create and manage sub-groups itself.
+///
+/// Returns once the process has been moved, or with an error if the job creating the scope
+/// didn't complete successfully. `properties` may set further scope properties, such as resource
+/// limits.
+///
+/// This mirrors what container runtimes do via `StartTransientUnit()`. When connected to the
+/// system manager the caller usually needs to be privileged; unprivileged processes can use the
+/// user manager (see [`Bus::default_user()`]).
+pub fn move_to_delegated_scope(
+    bus: &mut BusRef,
+    name: &str,
+    properties: &[(&str, PropertyValue)],
+) -> bus::Result<UnitProxy> {
+    let mut all = vec![("Delegate", PropertyValue::Bool(true))];
+    all.extend(properties.iter().cloned());
+    let pid = unsafe { libc::getpid() };
+    let (_watch, results) = watch_jobs_removed(bus)?;
+    let job = start_transient_scope(bus, name, &[pid], &all)?;
+    wait_job_done(bus, &job, &results, Duration::from_secs(25))?;
+    Ok(UnitProxy::from_name(bus, name))
+}
+
+/// The kind of change made to the file system by [`enable_unit_files()`] and friends.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UnitFileChangeType {
+    /// A symlink was created
+    Symlink,
+    /// A symlink (or a masking file) was removed
+    Unlink,
+    /// Another kind of change, as reported by the manager (e.g. `is-masked` on newer versions)
+    Other(String),
+}
+
+impl From<&str> for UnitFileChangeType {
+    fn from(s: &str) -> Self {
+        match s {
+            "symlink" => UnitFileChangeType::Symlink,
+            "unlink" => UnitFileChangeType::Unlink,
+            s => UnitFileChangeType::Other(s.to_owned()),
+        }
+    }
+}
+
+/// A change made to the file system while changing the enablement of unit files.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnitFileChange {
+    /// What was done
+    pub change_type: UnitFileChangeType,
+    /// The symlink (or file) which was created or removed
+    pub path: String,
+    /// For symlinks, the file the symlink points to; empty otherwise
+    pub source: String,
+}
+
+/// The enablement state of a unit file, as reported by [`get_unit_file_state()`].
+///
+/// See `systemctl is-enabled` in [`systemctl(1)`] for the meaning of each state.
+///
+/// [`systemctl(1)`]: https://www.freedesktop.org/software/systemd/man/systemctl.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UnitFileState {
+    Enabled,
+    EnabledRuntime,
+    Linked,
+    LinkedRuntime,
+    Alias,
+    Masked,
+    MaskedRuntime,
+    Static,
+    Disabled,
+    Indirect,
+    Generated,
+    Transient,
+    Bad,
+    /// A state unknown to this crate
+    Other(String),
+}
+
+impl From<&str> for UnitFileState {
+    fn from(s: &str) -> Self {
+        match s {
+            "enabled" => UnitFileState::Enabled,
+            "enabled-runtime" => UnitFileState::EnabledRuntime,
+            "linked" => UnitFileState::Linked,
+            "linked-runtime" => UnitFileState::LinkedRuntime,
+            "alias" => UnitFileState::Alias,
+            "masked" => UnitFileState::Masked,
+            "masked-runtime" => UnitFileState::MaskedRuntime,
+            "static" => UnitFileState::Static,
+            "disabled" => UnitFileState::Disabled,
+            "indirect" => UnitFileState::Indirect,
+            "generated" => UnitFileState::Generated,
+            "transient" => UnitFileState::Transient,
+            "bad" => UnitFileState::Bad,
+            s => UnitFileState::Other(s.to_owned()),
+        }
+    }
+}
+
+fn bad_message() -> bus::Error {
+    io::Error::from_raw_os_error(libc::EBADMSG).into()
+}
+
+/// Append unit file names as `as`.
+fn append_files(m: &mut MessageRef, files: &[&str]) -> crate::Result<()> {
+    m.open_container(b'a', signature(b"s\0"))?;
+    for f in files {
+        append_str(m, f)?;
+    }
+    m.close_container()
+}
+
+/// Read a change list (`a(sss)`) from a reply.
+fn read_changes(i: &mut bus::MessageIter<'_>) -> bus::Result<Vec<UnitFileChange>> {
+    if !i.enter_container(b'a', signature(b"(sss)\0"))? {
+        return Err(bad_message());
+    }
+    let mut changes = Vec::new();
+    while i.enter_container(b'r', signature(b"sss\0"))? {
+        let mut next = || -> bus::Result<String> {
+            let s: Option<&Utf8CStr> = i.next()?;
+            s.map(|s| s.to_string()).ok_or_else(bad_message)
+        };
+        let change_type = UnitFileChangeType::from(&*next()?);
+        let path = next()?;
+        let source = next()?;
+        i.exit_container()?;
+        changes.push(UnitFileChange {
+            change_type,
+            path,
+            source,
+        });
+    }
+    i.exit_container()?;
+    Ok(changes)
+}
+
+/// Call a manager method taking unit file names and flags, which replies with a change list,
+/// optionally preceded by a `carries_install_info` boolean.
+fn call_unit_files(
+    bus: &mut BusRef,
+    m: &'static [u8],
+    files: &[&str],
+    flags: &[bool],
+    install_info: bool,
+) -> bus::Result<(bool, Vec<UnitFileChange>)> {
+    let mut r = call_method(bus, manager_path(), manager_interface(), m, |msg| {
+        append_files(msg, files)?;
+        for f in flags {
+            msg.append(*f)?;
+        }
+        Ok(())
+    })?;
+    let mut i = r.iter()?;
+    let carries_install_info = if install_info {
+        i.next::<bool>()?.ok_or_else(bad_message)?
+    } else {
+        false
+    };
+    Ok((carries_install_info, read_changes(&mut i)?))
+}
+
+/// Enable the unit files `files` (names or absolute paths), like `systemctl enable`.
+///
+/// With `runtime`, the units are only enabled until the next reboot (below `/run`). With `force`,
+/// conflicting symlinks are replaced.
+///
+/// Returns whether the unit files carry install information (if not, enabling them has no effect;
+/// `systemctl` warns about this) and the changes made.
+///
+/// Like `systemctl`, call [`reload()`] afterwards for the manager to pick up the changes.
+///
+/// This corresponds to the `EnableUnitFiles()` method of the manager.
+pub fn enable_unit_files(
+    bus: &mut BusRef,
+    files: &[&str],
+    runtime: bool,
+    force: bool,
+) -> bus::Result<(bool, Vec<UnitFileChange>)> {
+    call_unit_files(bus, b"EnableUnitFiles\0", files, &[runtime, force], true)
+}
+
+/// Disable the unit files `files`, like `systemctl disable`, returning the changes made.
+///
+/// This corresponds to the `DisableUnitFiles()` method of the manager.
+pub fn disable_unit_files(
+    bus: &mut BusRef,
+    files: &[&str],
+    runtime: bool,
+) -> bus::Result<Vec<UnitFileChange>> {
+    Ok(call_unit_files(bus, b"DisableUnitFiles\0", files, &[runtime], false)?.1)
+}
+
+/// Mask the unit files `files`, like `systemctl mask`, returning the changes made.
+///
+/// This corresponds to the `MaskUnitFiles()` method of the manager.
+pub fn mask(
+    bus: &mut BusRef,
+    files: &[&str],
+    runtime: bool,
+    force: bool,
+) -> bus::Result<Vec<UnitFileChange>> {
+    Ok(call_unit_files(bus, b"MaskUnitFiles\0", files, &[runtime, force], false)?.1)
+}
+
+/// Unmask the unit files `files`, like `systemctl unmask`, returning the changes made.
+///
+/// This corresponds to the `UnmaskUnitFiles()` method of the manager.
+pub fn unmask(bus: &mut BusRef, files: &[&str], runtime: bool) -> bus::Result<Vec<UnitFileChange>> {
+    Ok(call_unit_files(bus, b"UnmaskUnitFiles\0", files, &[runtime], false)?.1)
+}
+
+/// Enable or disable the unit files `files` according to the preset policy, like
+/// `systemctl preset`.
+///
+/// Returns whether the unit files carry install information and the changes made.
+///
+/// This corresponds to the `PresetUnitFiles()` method of the manager.
+pub fn preset(
+    bus: &mut BusRef,
+    files: &[&str],
+    runtime: bool,
+    force: bool,
+) -> bus::Result<(bool, Vec<UnitFileChange>)> {
+    call_unit_files(bus, b"PresetUnitFiles\0", files, &[runtime, force], true)
+}
+
+/// Query the enablement state of the unit file `file`, like `systemctl is-enabled`.
+///
+/// This corresponds to the `GetUnitFileState()` method of the manager.
+pub fn get_unit_file_state(bus: &mut BusRef, file: &str) -> bus::Result<UnitFileState> {
+    let mut r = call_method(
+        bus,
+        manager_path(),
+        manager_interface(),
+        b"GetUnitFileState\0",
+        |m| append_str(m, file),
+    )?;
+    let mut i = r.iter()?;
+    let state: &Utf8CStr = i.next()?.ok_or_else(bad_message)?;
+    Ok(UnitFileState::from(&**state))
+}
+
+/// Reload the unit files of the manager, like `systemctl daemon-reload`.
+///
+/// This corresponds to the `Reload()` method of the manager.
+pub fn reload(bus: &mut BusRef) -> bus::Result<()> {
+    call_method(
+        bus,
+        manager_path(),
+        manager_interface(),
+        b"Reload\0",
+        |_| Ok(()),
+    )?;
+    Ok(())
+}
+
+thread_local! {
+    // number of live `Subscription`s per bus connection
+    static SUBSCRIPTIONS: RefCell<HashMap<usize, usize>> = RefCell::new(HashMap::new());
+}
+
+/// Keeps the bus connection subscribed to the signals of the manager, created by
+/// [`subscribe()`].
+///
+/// The manager only emits most of its signals (`JobNew`, `JobRemoved`, `UnitNew`, property
+/// changes of units, ...) while at least one client is subscribed. Subscriptions are counted per
+/// connection: the connection unsubscribes when its last `Subscription` is dropped.
+pub struct Subscription {
+    bus: Bus,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let key = self.bus.as_ptr() as usize;
+        let last = SUBSCRIPTIONS.with(|s| {
+            let mut s = s.borrow_mut();
+            let n = s.get_mut(&key).expect("subscription not counted");
+            *n -= 1;
+            if *n == 0 {
+                s.remove(&key);
+                true
+            } else {
+                false
+            }
+        });
+        if last {
+            // nothing to do about a failure, the subscription goes away with the connection
+            let _ = call_method(
+                &mut self.bus,
+                manager_path(),
+                manager_interface(),
+                b"Unsubscribe\0",
+                |_| Ok(()),
+            );
+        }
+    }
+}
+
+impl fmt::Debug for Subscription {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("Subscription")
+            .field("bus", &self.bus.as_ptr())
+            .finish()
+    }
+}
+
+/// Subscribe the connection to the signals of the manager for as long as the returned
+/// [`Subscription`] exists.
+///
+/// Only the first subscription of a connection calls the manager's `Subscribe()` method, so
+/// independent parts of a program can each hold one.
+pub fn subscribe(bus: &BusRef) -> bus::Result<Subscription> {
+    let key = bus.as_ptr() as usize;
+    let first = SUBSCRIPTIONS.with(|s| !s.borrow().contains_key(&key));
+    let mut bus = bus.to_owned();
+    if first {
+        match call_method(
+            &mut bus,
+            manager_path(),
+            manager_interface(),
+            b"Subscribe\0",
+            |_| Ok(()),
+        ) {
+            Ok(_) => {}
+            // subscribed by other means, the subscription is shared with those
+            Err(e) if e.has_name("org.freedesktop.systemd1.AlreadySubscribed\0") => {}
+            Err(e) => return Err(e),
+        }
+    }
+    SUBSCRIPTIONS.with(|s| *s.borrow_mut().entry(key).or_insert(0) += 1);
+    Ok(Subscription { bus })
+}
+
+/// A match on signals of the manager, created by [`add_match()`].
+///
+/// Dropping it removes the match and releases its [`Subscription`].
+pub struct SubscribedMatch {
+    slot: Slot,
+    subscription: Subscription,
+}
+
+impl fmt::Debug for SubscribedMatch {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("SubscribedMatch")
+            .field("slot", &*self.slot)
+            .field("subscription", &self.subscription)
+            .finish()
+    }
+}
+
+/// Call `callback` for each signal matching `def`, keeping the connection subscribed to the
+/// signals of the manager while the match exists.
+///
+/// ```no_run
+/// use systemd::bus::{Bus, InterfaceName, MemberName, SignalDef};
+/// use systemd::manager;
+/// let mut bus = Bus::default_system().unwrap();
+/// let def = SignalDef::new(
+///     InterfaceName::from_bytes(b"org.freedesktop.systemd1.Manager\0").unwrap(),
+///     MemberName::from_bytes(b"JobRemoved\0").unwrap(),
+/// );
+/// let _jobs = manager::add_match(&bus, &def, |m| {
+///     let mut i = m.iter()?;
+///     let id: Option<u32> = i.next()?;
+///     println!("job {:?} done", id);
+///     Ok(())
+/// })
+/// .unwrap();
+/// bus.run_forever().unwrap();
+/// ```
+pub fn add_match<F>(bus: &BusRef, def: &SignalDef<'_>, callback: F) -> bus::Result<SubscribedMatch>
+where
+    F: Fn(&mut MessageRef) -> bus::Result<()> + Send + Sync + 'static,
+{
+    // subscribe last, so no signal is emitted before the match is in place
+    let slot = def.add_match(bus, callback)?;
+    Ok(SubscribedMatch {
+        slot,
+        subscription: subscribe(bus)?,
+    })
+}
+
+/// Time spent in each phase of the boot, as shown by `systemd-analyze time`.
+///
+/// Phases which didn't happen (no initrd) or which the boot loader didn't report (firmware and
+/// loader times require EFI and a boot loader implementing the boot loader interface) are `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StartupTiming {
+    /// Time spent in the firmware before the boot loader was started
+    pub firmware: Option<Duration>,
+    /// Time spent in the boot loader before the kernel was started
+    pub loader: Option<Duration>,
+    /// Time from the start of the kernel to the start of the initrd, or of userspace without one
+    pub kernel: Duration,
+    /// Time spent in the initrd
+    pub initrd: Option<Duration>,
+    /// Time from the start of userspace until the manager finished starting up, `None` while it
+    /// is still starting up
+    pub userspace: Option<Duration>,
+}
+
+impl StartupTiming {
+    // All arguments are `CLOCK_MONOTONIC` timestamps in usec as reported by the manager, 0 if not
+    // set. The firmware and loader ones are counted backwards from the start of the kernel.
+    fn from_timestamps(
+        firmware: u64,
+        loader: u64,
+        initrd: u64,
+        userspace: u64,
+        finish: u64,
+    ) -> Self {
+        let d = Duration::from_micros;
+        StartupTiming {
+            firmware: if firmware > 0 {
+                Some(d(firmware.saturating_sub(loader)))
+            } else {
+                None
+            },
+            loader: if loader > 0 { Some(d(loader)) } else { None },
+            kernel: d(if initrd > 0 { initrd } else { userspace }),
+            initrd: if initrd > 0 {
+                Some(d(userspace.saturating_sub(initrd)))
+            } else {
+                None
+            },
+            userspace: if finish > 0 {
+                Some(d(finish.saturating_sub(userspace)))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// The total time until the manager finished starting up, `None` while it is still starting
+    /// up.
+    pub fn total(&self) -> Option<Duration> {
+        let before = self.firmware.unwrap_or_default()
+            + self.loader.unwrap_or_default()
+            + self.kernel
+            + self.initrd.unwrap_or_default();
+        self.userspace.map(|u| before + u)
+    }
+}
+
+/// Read a `CLOCK_MONOTONIC` timestamp property, 0 if it is not set or unknown to the manager.
+fn timestamp(
+    bus: &mut BusRef,
+    path: &ObjectPath,
+    interface: &InterfaceName,
+    m: &'static [u8],
+) -> bus::Result<u64> {
+    match bus.get_property_trivial::<u64>(destination(), path, interface, member(m)) {
+        Err(e) if e.has_name("org.freedesktop.DBus.Error.UnknownProperty\0") => Ok(0),
+        r => r,
+    }
+}
+
+/// Compute the boot time breakdown of the manager, like `systemd-analyze time`, from its
+/// `FirmwareTimestampMonotonic`, `LoaderTimestampMonotonic`, `InitRDTimestampMonotonic`,
+/// `UserspaceTimestampMonotonic` and `FinishTimestampMonotonic` properties.
+pub fn timing(bus: &mut BusRef) -> bus::Result<StartupTiming> {
+    let (path, interface) = (manager_path(), manager_interface());
+    Ok(StartupTiming::from_timestamps(
+        timestamp(bus, path, interface, b"FirmwareTimestampMonotonic\0")?,
+        timestamp(bus, path, interface, b"LoaderTimestampMonotonic\0")?,
+        timestamp(bus, path, interface, b"InitRDTimestampMonotonic\0")?,
+        timestamp(bus, path, interface, b"UserspaceTimestampMonotonic\0")?,
+        timestamp(bus, path, interface, b"FinishTimestampMonotonic\0")?,
+    ))
+}
+
+/// When a unit last started, as `CLOCK_MONOTONIC` times since boot.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct UnitTiming {
+    /// `InactiveExitTimestampMonotonic`: when the unit left the inactive state to start
+    pub inactive_exit: Option<Duration>,
+    /// `ActiveEnterTimestampMonotonic`: when the unit became active
+    pub active_enter: Option<Duration>,
+}
+
+impl UnitTiming {
+    /// How long the unit took to start, as shown by `systemd-analyze blame`.
+    ///
+    /// `None` if the unit didn't start, or is being started again (the unit became active before
+    /// it left the inactive state the last time).
+    pub fn activation_time(&self) -> Option<Duration> {
+        match (self.inactive_exit, self.active_enter) {
+            (Some(exit), Some(enter)) if enter >= exit => Some(enter - exit),
+            _ => None,
+        }
+    }
+}
+
+/// Read when the unit `name` last started, for the `systemd-analyze blame` view of the boot.
+pub fn unit_timing(bus: &mut BusRef, name: &str) -> bus::Result<UnitTiming> {
+    let path = unit_path(name);
+    let path = as_path(&path);
+    let t = |v: u64| {
+        if v > 0 {
+            Some(Duration::from_micros(v))
+        } else {
+            None
+        }
+    };
+    Ok(UnitTiming {
+        inactive_exit: t(timestamp(
+            bus,
+            path,
+            unit_interface(),
+            b"InactiveExitTimestampMonotonic\0",
+        )?),
+        active_enter: t(timestamp(
+            bus,
+            path,
+            unit_interface(),
+            b"ActiveEnterTimestampMonotonic\0",
+        )?),
+    })
+}
+
+#[test]
+fn t_unit_path() {
+    assert_eq!(
+        unit_path("dbus.service").to_bytes(),
+        b"/org/freedesktop/systemd1/unit/dbus_2eservice"
+    );
+    assert_eq!(
+        unit_path("getty@tty1.service").to_bytes(),
+        b"/org/freedesktop/systemd1/unit/getty_40tty1_2eservice"
+    );
+    assert_eq!(
+        unit_path("1.scope").to_bytes(),
+        b"/org/freedesktop/systemd1/unit/_31_2escope"
+    );
+    assert_eq!(
+        unit_path("").to_bytes(),
+        b"/org/freedesktop/systemd1/unit/_"
+    );
+}
+
+#[test]
+fn t_cgroup_interface() {
+    assert!(cgroup_interface("foo.service").is_some());
+    assert!(cgroup_interface("foo.target").is_none());
+    assert!(cgroup_interface("foo").is_none());
+}
+
+#[test]
+fn t_timer_trigger_property() {
+    assert_eq!(
+        TimerTrigger::OnBoot(Duration::from_secs(2)).property(),
+        ("OnBootSec", PropertyValue::U64(2_000_000))
+    );
+    assert_eq!(
+        TimerTrigger::Calendar("daily".into()).property(),
+        ("OnCalendar", PropertyValue::String("daily".into()))
+    );
+}
+
+#[test]
+fn t_job_result() {
+    assert!(job_result("done").is_ok());
+    for r in &[
+        "canceled",
+        "timeout",
+        "failed",
+        "dependency",
+        "skipped",
+        "invalid",
+    ] {
+        let e = job_result(r).unwrap_err();
+        assert!(e.has_name("org.freedesktop.DBus.Error.Failed"));
+    }
+}
+
+#[test]
+fn t_unit_file_strings() {
+    assert_eq!(
+        UnitFileChangeType::from("symlink"),
+        UnitFileChangeType::Symlink
+    );
+    assert_eq!(
+        UnitFileChangeType::from("unlink"),
+        UnitFileChangeType::Unlink
+    );
+    assert_eq!(
+        UnitFileChangeType::from("is-masked"),
+        UnitFileChangeType::Other("is-masked".into())
+    );
+    assert_eq!(
+        UnitFileState::from("enabled-runtime"),
+        UnitFileState::EnabledRuntime
+    );
+    assert_eq!(UnitFileState::from("masked"), UnitFileState::Masked);
+    assert_eq!(
+        UnitFileState::from("frobnicated"),
+        UnitFileState::Other("frobnicated".into())
+    );
+}
+
+#[test]
+fn t_startup_timing() {
+    let ms = Duration::from_millis;
+
+    // EFI boot with an initrd, finished
+    let t = StartupTiming::from_timestamps(3_000_000, 1_000_000, 500_000, 2_500_000, 7_500_000);
+    assert_eq!(t.firmware, Some(ms(2000)));
+    assert_eq!(t.loader, Some(ms(1000)));
+    assert_eq!(t.kernel, ms(500));
+    assert_eq!(t.initrd, Some(ms(2000)));
+    assert_eq!(t.userspace, Some(ms(5000)));
+    assert_eq!(t.total(), Some(ms(10500)));
+
+    // no initrd or boot loader interface, still starting up
+    let t = StartupTiming::from_timestamps(0, 0, 0, 800_000, 0);
+    assert_eq!(t.firmware, None);
+    assert_eq!(t.loader, None);
+    assert_eq!(t.kernel, ms(800));
+    assert_eq!(t.initrd, None);
+    assert_eq!(t.userspace, None);
+    assert_eq!(t.total(), None);
+
+    let u = UnitTiming {
+        inactive_exit: Some(ms(100)),
+        active_enter: Some(ms(350)),
+    };
+    assert_eq!(u.activation_time(), Some(ms(250)));
+    let u = UnitTiming {
+        inactive_exit: Some(ms(400)),
+        ..u
+    };
+    assert_eq!(u.activation_time(), None);
+}
@@ -0,0 +1,244 @@
+//! Helpers for implementing `systemctl reload` for `Type=notify` and `Type=notify-reload`
+//! services.
+//!
+//! [`reload()`] wraps reloading the configuration with the `RELOADING=1` / `READY=1` handshake
+//! with the service manager (see [`sd_notify`]), keeping `STATUS=` up to date. [`Watcher`]
+//! additionally allows reloading automatically whenever configuration files change.
+//!
+//! ```no_run
+//! use systemd::reload;
+//!
+//! fn load_config() -> std::io::Result<()> {
+//!     // read /etc/example.conf and /etc/example.conf.d/ ...
+//!     Ok(())
+//! }
+//!
+//! let mut w = reload::Watcher::new().unwrap();
+//! w.watch("/etc/example.conf.d").unwrap();
+//! w.run(load_config).unwrap();
+//! ```
+//!
+//! [`sd_notify`]: https://www.freedesktop.org/software/systemd/man/sd_notify.html
+
+use crate::daemon;
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How often removed paths are checked for again
+const LOST_RETRY: Duration = Duration::from_secs(1);
+
+/// Changes to a watched path (or to entries of a watched directory) which trigger a reload
+const WATCH_MASK: u32 = libc::IN_CLOSE_WRITE
+    | libc::IN_CREATE
+    | libc::IN_DELETE
+    | libc::IN_MOVED_FROM
+    | libc::IN_MOVED_TO
+    | libc::IN_ATTRIB
+    | libc::IN_DELETE_SELF
+    | libc::IN_MOVE_SELF;
+
+/// Reload the configuration by calling `f`, notifying the service manager around it.
+///
+/// `RELOADING=1` (with the `MONOTONIC_USEC=` timestamp `Type=notify-reload` requires) is sent
+/// before calling `f`, and `READY=1` after it returns, whether it succeeded or not, as the
+/// service keeps running either way. `STATUS=` reports a failure to reload.
+///
+/// If the process wasn't started by systemd, this just calls `f`.
+pub fn reload<F, T>(f: F) -> io::Result<T>
+where
+    F: FnOnce() -> io::Result<T>,
+{
+    let usec = crate::monotonic_usec().to_string();
+    daemon::notify(
+        false,
+        [
+            (daemon::STATE_RELOADING, "1"),
+            ("MONOTONIC_USEC", usec.as_str()),
+            (daemon::STATE_STATUS, "Reloading configuration..."),
+        ]
+        .iter(),
+    )?;
+
+    let r = f();
+    let status = match &r {
+        Ok(_) => String::new(),
+        Err(e) => format!("Reloading configuration failed: {}", e),
+    };
+    daemon::notify(
+        false,
+        [
+            (daemon::STATE_READY, "1"),
+            (daemon::STATE_STATUS, status.as_str()),
+        ]
+        .iter(),
+    )?;
+    r
+}
+
+/// Watches configuration files and directories for changes using inotify.
+///
+/// Watching a directory (for example, a `.d/` drop-in directory) also notices files in it being
+/// created, replaced or removed, which is what editors and package managers usually do. A watched
+/// path which is removed is watched again once it reappears, which [`wait()`](Watcher::wait)
+/// reports as a change.
+///
+/// The watcher can be integrated into other event loops by polling its file descriptor for
+/// readability and calling `wait(Some(Duration::ZERO))`.
+#[derive(Debug)]
+pub struct Watcher {
+    fd: OwnedFd,
+    watches: HashMap<RawFd, PathBuf>,
+    lost: Vec<PathBuf>,
+}
+
+impl Watcher {
+    /// Create a watcher with no paths.
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Watcher {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            watches: HashMap::new(),
+            lost: Vec::new(),
+        })
+    }
+
+    fn add_watch(&mut self, path: &Path) -> io::Result<()> {
+        let p = CString::new(path.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), p.as_ptr(), WATCH_MASK) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.watches.insert(wd, path.to_owned());
+        Ok(())
+    }
+
+    /// Start watching `path`, which must exist.
+    pub fn watch<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.add_watch(path.as_ref())
+    }
+
+    /// Wait for changes to the watched paths, returning `true` if any occurred.
+    ///
+    /// All pending changes are consumed, so a burst of changes results in a single `true`. With
+    /// a `timeout` of `None`, this waits until a change occurs.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> io::Result<bool> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let mut wait = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+            if !self.lost.is_empty() {
+                // removed paths don't generate events when they reappear, check periodically
+                wait = Some(wait.map_or(LOST_RETRY, |w| w.min(LOST_RETRY)));
+            }
+            let timeout_ms = match wait {
+                None => -1,
+                Some(t) => t.as_millis().min(libc::c_int::MAX as u128) as libc::c_int,
+            };
+            let mut pfd = libc::pollfd {
+                fd: self.fd.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            if unsafe { libc::poll(&mut pfd, 1, timeout_ms) } < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() != io::ErrorKind::Interrupted {
+                    return Err(e);
+                }
+            }
+
+            let mut changed = self.drain()?;
+            changed |= self.rewatch_lost();
+            if changed || matches!(deadline, Some(d) if Instant::now() >= d) {
+                return Ok(changed);
+            }
+        }
+    }
+
+    /// Read all queued inotify events, returning `true` if there were any.
+    fn drain(&mut self) -> io::Result<bool> {
+        // u64 elements to get a buffer aligned for `inotify_event`
+        let mut buf = [0u64; 512];
+        let mut changed = false;
+        loop {
+            let n = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    mem::size_of_val(&buf),
+                )
+            };
+            if n < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::WouldBlock {
+                    return Ok(changed);
+                }
+                return Err(e);
+            }
+
+            let bytes =
+                unsafe { std::slice::from_raw_parts(buf.as_ptr() as *const u8, n as usize) };
+            let mut offs = 0;
+            while offs + mem::size_of::<libc::inotify_event>() <= bytes.len() {
+                let ev = unsafe {
+                    std::ptr::read_unaligned(bytes[offs..].as_ptr() as *const libc::inotify_event)
+                };
+                offs += mem::size_of::<libc::inotify_event>() + ev.len as usize;
+                changed = true;
+                if ev.mask & libc::IN_IGNORED != 0 {
+                    // the watched path itself is gone
+                    if let Some(p) = self.watches.remove(&ev.wd) {
+                        self.lost.push(p);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Try watching removed paths again, returning `true` if any of them reappeared.
+    fn rewatch_lost(&mut self) -> bool {
+        let lost = mem::take(&mut self.lost);
+        let n = lost.len();
+        for p in lost {
+            if self.add_watch(&p).is_err() {
+                self.lost.push(p);
+            }
+        }
+        self.lost.len() != n
+    }
+
+    /// Call `f` through [`reload()`] whenever the watched paths change, forever.
+    ///
+    /// A failure of `f` is reported to the service manager, and watching continues; this only
+    /// returns if watching itself fails.
+    pub fn run<F>(&mut self, mut f: F) -> io::Result<()>
+    where
+        F: FnMut() -> io::Result<()>,
+    {
+        loop {
+            if self.wait(None)? {
+                let _ = reload(&mut f);
+            }
+        }
+    }
+}
+
+impl AsFd for Watcher {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for Watcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
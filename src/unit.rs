@@ -1,3 +1,6 @@
+#[cfg(feature = "libsystemd-sys")]
+use crate::id128::Id128;
+
 /// Escape a string for use in a systemd unit name.
 ///
 /// See [String Escaping for Inclusion in Unit Names][1] for more information.
@@ -19,3 +22,133 @@ pub fn escape_name(s: &str) -> String {
     }
     escaped
 }
+
+/// Undo [`escape_name()`], turning `-` back into `/` and decoding `\xNN` escapes.
+///
+/// Returns `None` if an escape is malformed or the result isn't valid UTF-8.
+pub fn unescape_name(s: &str) -> Option<String> {
+    let b = s.as_bytes();
+    let mut unescaped = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        match b[i] {
+            b'-' => unescaped.push(b'/'),
+            b'\\' => {
+                if b.get(i + 1) != Some(&b'x') {
+                    return None;
+                }
+                let hex = b.get(i + 2..i + 4)?;
+                if !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return None;
+                }
+                // only ASCII hex digits
+                let hex = std::str::from_utf8(hex).unwrap();
+                unescaped.push(u8::from_str_radix(hex, 16).unwrap());
+                i += 3;
+            }
+            c => unescaped.push(c),
+        }
+        i += 1;
+    }
+    String::from_utf8(unescaped).ok()
+}
+
+#[cfg(feature = "libsystemd-sys")]
+/// The values specifiers are replaced with by [`expand_specifiers()`].
+///
+/// Only `unit` is required, specifiers whose value is `None` fail to expand.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpecifierContext<'a> {
+    /// The full unit name (`%n`), like `getty@tty1.service`. The instance (`%i`) is taken from it.
+    pub unit: &'a str,
+    /// The user name (`%u`).
+    pub user: Option<&'a str>,
+    /// The home directory of the user (`%h`).
+    pub home: Option<&'a str>,
+    /// The machine ID (`%m`).
+    pub machine_id: Option<Id128>,
+    /// The boot ID (`%b`).
+    pub boot_id: Option<Id128>,
+    /// The host name (`%H`).
+    pub host: Option<&'a str>,
+}
+
+#[cfg(feature = "libsystemd-sys")]
+impl<'a> SpecifierContext<'a> {
+    /// A context for `unit`, with no other values given.
+    pub fn new(unit: &'a str) -> Self {
+        SpecifierContext {
+            unit,
+            ..Default::default()
+        }
+    }
+
+    /// The escaped instance name of the unit, which is empty unless it is an instance of a
+    /// template.
+    pub fn instance(&self) -> &'a str {
+        let unit = self.unit;
+        match (unit.find('@'), unit.rfind('.')) {
+            (Some(at), Some(dot)) if at < dot => &unit[at + 1..dot],
+            _ => "",
+        }
+    }
+}
+
+#[cfg(feature = "libsystemd-sys")]
+/// Replace the specifiers in `text` the way systemd does in unit files, so generators and
+/// drop-in writers can compute the same strings.
+///
+/// Supports `%n`, `%i`, `%I` (the instance passed through [`unescape_name()`]), `%u`, `%h`,
+/// `%m`, `%b`, `%H` and `%%` for a literal `%`. See [Specifiers][1] for their meaning.
+///
+/// Fails with an error of kind `InvalidInput` on other specifiers, a `%` at the end of `text`,
+/// or specifiers whose value isn't in `context`.
+///
+/// ```
+/// # use systemd::unit::{expand_specifiers, SpecifierContext};
+/// let context = SpecifierContext {
+///     user: Some("root"),
+///     ..SpecifierContext::new("backup@-srv-data.service")
+/// };
+/// assert_eq!(
+///     expand_specifiers("%u: %i -> %I (100%%)", &context).unwrap(),
+///     "root: -srv-data -> /srv/data (100%)"
+/// );
+/// ```
+///
+/// [1]: https://www.freedesktop.org/software/systemd/man/systemd.unit.html#Specifiers
+pub fn expand_specifiers(text: &str, context: &SpecifierContext<'_>) -> crate::Result<String> {
+    let mut expanded = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        let spec = chars
+            .next()
+            .ok_or_else(|| invalid("trailing '%'".to_owned()))?;
+        let missing = || invalid(format!("no value for specifier %{}", spec));
+        match spec {
+            '%' => expanded.push('%'),
+            'n' => expanded.push_str(context.unit),
+            'i' => expanded.push_str(context.instance()),
+            'I' => expanded.push_str(
+                &unescape_name(context.instance())
+                    .ok_or_else(|| invalid("instance name is not validly escaped".to_owned()))?,
+            ),
+            'u' => expanded.push_str(context.user.ok_or_else(missing)?),
+            'h' => expanded.push_str(context.home.ok_or_else(missing)?),
+            'm' => expanded.push_str(&context.machine_id.ok_or_else(missing)?.to_string()),
+            'b' => expanded.push_str(&context.boot_id.ok_or_else(missing)?.to_string()),
+            'H' => expanded.push_str(context.host.ok_or_else(missing)?),
+            _ => return Err(invalid(format!("unknown specifier %{}", spec))),
+        }
+    }
+    Ok(expanded)
+}
+
+#[cfg(feature = "libsystemd-sys")]
+fn invalid(msg: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, msg)
+}
@@ -0,0 +1,497 @@
+#![warn(rust_2018_idioms)]
+
+//! Procedural macros for the [`systemd`](https://docs.rs/systemd) crate.
+//!
+//! These are re-exported from `systemd::bus` when its `macros` feature is enabled, use them from
+//! there.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, AttributeArgs, FnArg, GenericArgument, ImplItem, ImplItemMethod, ItemImpl,
+    Lit, Meta, NestedMeta, Pat, PathArguments, ReturnType, Type,
+};
+
+/// Implement `systemd::bus::Interface` for the type of an `impl` block, exporting its methods on
+/// the bus.
+///
+/// With `#[dbus_interface(name = "..", auto_emit)]` setting a property which emits changes through
+/// the bus sends `PropertiesChanged` (see `systemd::bus::Vtable::auto_emit_changes()`).
+///
+/// Member names are the `CamelCase` form of the function names unless given with
+/// `#[dbus_interface(name = "..")]`. A property setter `set_x` belongs to the getter `x`, or to the
+/// property named by its own `#[dbus_interface(property, name = "..")]`.
+#[proc_macro_attribute]
+pub fn dbus_interface(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as AttributeArgs);
+    let item = parse_macro_input!(item as ItemImpl);
+    match expand(args, item) {
+        Ok(t) => t.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// What a function in the `impl` block is exported as.
+#[derive(PartialEq, Eq)]
+enum Kind {
+    Method,
+    Property,
+    Signal,
+    Skip,
+}
+
+/// The options of `#[dbus_interface(..)]` on a function.
+struct MemberAttrs {
+    kind: Kind,
+    name: Option<syn::LitStr>,
+    change: Option<syn::LitStr>,
+}
+
+impl MemberAttrs {
+    /// The member name given, or else the one derived from the name of `method`, along with the
+    /// span to report it at.
+    fn name(&self, method: &ImplItemMethod) -> (String, Span) {
+        match &self.name {
+            Some(n) => (n.value(), n.span()),
+            None => (
+                camel_case(&method.sig.ident.to_string()),
+                method.sig.ident.span(),
+            ),
+        }
+    }
+}
+
+struct Property {
+    name: String,
+    span: Span,
+    ty: Type,
+    change: TokenStream2,
+    get: TokenStream2,
+    set: Option<TokenStream2>,
+}
+
+fn expand(args: AttributeArgs, mut item: ItemImpl) -> syn::Result<TokenStream2> {
+    let (interface, auto_emit) = interface_options(&args)?;
+
+    let mut members = Vec::new();
+    let mut properties: Vec<Property> = Vec::new();
+    let mut setters = Vec::new();
+
+    for i in item.items.iter_mut() {
+        let method = match i {
+            ImplItem::Method(m) => m,
+            _ => continue,
+        };
+        let attrs = member_attrs(method)?;
+        match attrs.kind {
+            Kind::Skip => {}
+            Kind::Method => {
+                if method.sig.receiver().is_none() {
+                    continue;
+                }
+                let (name, span) = attrs.name(method);
+                members.push(expand_method(method, member_name(&name, span)?)?);
+            }
+            Kind::Signal => {
+                let (name, span) = attrs.name(method);
+                members.push(expand_signal(method, member_name(&name, span)?)?);
+            }
+            Kind::Property => {
+                let ident = method.sig.ident.to_string();
+                match ident.strip_prefix("set_") {
+                    Some(prop) => {
+                        let name = match &attrs.name {
+                            Some(n) => n.value(),
+                            None => camel_case(prop),
+                        };
+                        setters.push((name, expand_setter(method)?, method.sig.span()));
+                    }
+                    None => properties.push(expand_getter(method, attrs)?),
+                }
+            }
+        }
+    }
+
+    for (name, set, span) in setters {
+        match properties.iter_mut().find(|p| p.name == name) {
+            Some(p) => p.set = Some(set),
+            None => {
+                return Err(syn::Error::new(
+                    span,
+                    format!(
+                        "property setter without a getter for the property `{}`",
+                        name
+                    ),
+                ))
+            }
+        }
+    }
+
+    for p in properties {
+        let member = member_name(&p.name, p.span)?;
+        let sig = signature(std::slice::from_ref(&p.ty));
+        let change = p.change;
+        let get = p.get;
+        members.push(match p.set {
+            Some(set) => quote! {
+                let vtable = vtable.writable_property(#member, &*#sig, #change, #get, #set);
+            },
+            None => quote! {
+                let vtable = vtable.property(#member, &*#sig, #change, #get);
+            },
+        });
+    }
+
+    if auto_emit {
+        members.push(quote! {
+            let vtable = vtable.auto_emit_changes();
+        });
+    }
+
+    let self_ty = &item.self_ty;
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    Ok(quote! {
+        #item
+
+        impl #impl_generics ::systemd::bus::Interface for #self_ty #where_clause {
+            fn name() -> &'static ::systemd::bus::InterfaceName {
+                ::systemd::interface!(#interface)
+            }
+
+            fn vtable(
+                object: ::std::sync::Arc<Self>,
+            ) -> ::std::io::Result<::systemd::bus::Vtable> {
+                let vtable = ::systemd::bus::Vtable::new();
+                #(#members)*
+                let _ = &object;
+                ::std::result::Result::Ok(vtable)
+            }
+        }
+    })
+}
+
+/// The interface name, and whether `auto_emit` was given.
+fn interface_options(args: &[NestedMeta]) -> syn::Result<(String, bool)> {
+    let mut name = None;
+    let mut auto_emit = false;
+    for a in args {
+        match a {
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => match &nv.lit {
+                Lit::Str(s) => name = Some(s.value()),
+                l => return Err(syn::Error::new_spanned(l, "expected a string")),
+            },
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("auto_emit") => auto_emit = true,
+            a => return Err(syn::Error::new_spanned(a, "unknown option")),
+        }
+    }
+    let name = name.ok_or_else(|| {
+        syn::Error::new(
+            Span::call_site(),
+            "missing interface name, add `name = \"..\"`",
+        )
+    })?;
+    Ok((name, auto_emit))
+}
+
+/// Parse and remove `#[dbus_interface(..)]` from `method`.
+fn member_attrs(method: &mut ImplItemMethod) -> syn::Result<MemberAttrs> {
+    let mut r = MemberAttrs {
+        kind: Kind::Method,
+        name: None,
+        change: None,
+    };
+    let mut error = None;
+    method.attrs.retain(|a| {
+        if !a.path.is_ident("dbus_interface") {
+            return true;
+        }
+        if let Err(e) = parse_member_attr(a, &mut r) {
+            error.get_or_insert(e);
+        }
+        false
+    });
+    if let Some(e) = error {
+        return Err(e);
+    }
+    match &r.change {
+        Some(c) if r.kind != Kind::Property => Err(syn::Error::new_spanned(
+            c,
+            "`change` only applies to properties",
+        )),
+        _ => Ok(r),
+    }
+}
+
+fn parse_member_attr(a: &syn::Attribute, r: &mut MemberAttrs) -> syn::Result<()> {
+    let list = match a.parse_meta()? {
+        Meta::List(l) => l,
+        m => return Err(syn::Error::new_spanned(m, "expected `dbus_interface(..)`")),
+    };
+    for n in list.nested {
+        match n {
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("property") => r.kind = Kind::Property,
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("signal") => r.kind = Kind::Signal,
+            NestedMeta::Meta(Meta::Path(p)) if p.is_ident("skip") => r.kind = Kind::Skip,
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("name") => match nv.lit {
+                Lit::Str(s) => r.name = Some(s),
+                l => return Err(syn::Error::new_spanned(l, "expected a string")),
+            },
+            NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("change") => match nv.lit {
+                Lit::Str(s) => r.change = Some(s),
+                l => return Err(syn::Error::new_spanned(l, "expected a string")),
+            },
+            n => return Err(syn::Error::new_spanned(n, "unknown option")),
+        }
+    }
+    Ok(())
+}
+
+/// The arguments of `method` after the receiver, as pattern identifiers and types.
+fn arguments(method: &ImplItemMethod) -> syn::Result<Vec<(syn::Ident, Type)>> {
+    method
+        .sig
+        .inputs
+        .iter()
+        .filter_map(|i| match i {
+            FnArg::Typed(t) => Some(t),
+            FnArg::Receiver(_) => None,
+        })
+        .map(|t| match &*t.pat {
+            Pat::Ident(p) => Ok((p.ident.clone(), (*t.ty).clone())),
+            p => Err(syn::Error::new_spanned(
+                p,
+                "arguments must be plain identifiers",
+            )),
+        })
+        .collect()
+}
+
+/// The value returned by a function, and whether it is wrapped in a `Result`.
+fn output(method: &ImplItemMethod) -> (Option<Type>, bool) {
+    let ty = match &method.sig.output {
+        ReturnType::Default => return (None, false),
+        ReturnType::Type(_, ty) => &**ty,
+    };
+    if let Type::Path(p) = ty {
+        let last = p.path.segments.last().unwrap();
+        if last.ident == "Result" {
+            if let PathArguments::AngleBracketed(a) = &last.arguments {
+                if let Some(GenericArgument::Type(t)) = a.args.first() {
+                    return (Some(t.clone()), true);
+                }
+            }
+        }
+    }
+    (Some(ty.clone()), false)
+}
+
+/// The types making up a returned value: none for `()`, the elements of other tuples.
+fn output_types(ty: Option<Type>) -> Vec<Type> {
+    match ty {
+        None => Vec::new(),
+        Some(Type::Tuple(t)) => t.elems.into_iter().collect(),
+        Some(t) => vec![t],
+    }
+}
+
+fn expand_method(method: &ImplItemMethod, member: TokenStream2) -> syn::Result<TokenStream2> {
+    let ident = &method.sig.ident;
+    let args = arguments(method)?;
+    let (ret, fallible) = output(method);
+    let ret_types = output_types(ret.clone());
+
+    let arg_names: Vec<_> = args.iter().map(|(n, _)| n).collect();
+    let arg_types: Vec<_> = args.iter().map(|(_, t)| t.clone()).collect();
+    let arg_sig = signature(&arg_types);
+    let ret_sig = signature(&ret_types);
+    let try_ = if fallible { quote!(?) } else { quote!() };
+    let (bind, append) = match (&ret, ret_types.len()) {
+        (Some(Type::Tuple(_)), 0) | (None, _) => (quote!(), quote!()),
+        (Some(Type::Tuple(_)), _) => (quote!(let ret =), quote!(reply.append_tuple(ret)?;)),
+        _ => (quote!(let ret =), quote!(reply.append(ret)?;)),
+    };
+
+    Ok(quote! {
+        let vtable = vtable.method(#member, &*#arg_sig, &*#ret_sig, {
+            let object = object.clone();
+            move |call: &mut ::systemd::bus::MessageRef| -> ::systemd::bus::Result<()> {
+                #bind {
+                    #[allow(unused_mut, unused_variables)]
+                    let mut args = call.iter()?;
+                    #(
+                        let #arg_names: #arg_types = args.next()?.ok_or_else(|| {
+                            ::std::io::Error::new(
+                                ::std::io::ErrorKind::InvalidData,
+                                "missing method argument",
+                            )
+                        })?;
+                    )*
+                    object.#ident(#(#arg_names),*)#try_
+                };
+                let mut reply = call.new_method_return()?;
+                #append
+                reply.send()?;
+                Ok(())
+            }
+        });
+    })
+}
+
+fn expand_getter(method: &ImplItemMethod, attrs: MemberAttrs) -> syn::Result<Property> {
+    let ident = &method.sig.ident;
+    if method.sig.receiver().is_none() || !arguments(method)?.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &method.sig,
+            "property getters take only `&self`",
+        ));
+    }
+    let (ty, fallible) = output(method);
+    let ty = ty.ok_or_else(|| {
+        syn::Error::new_spanned(&method.sig, "property getters must return a value")
+    })?;
+    let change = match attrs.change.as_ref().map(|c| (c, c.value())) {
+        None => quote!(::systemd::bus::PropertyChange::EmitsChange),
+        Some((_, v)) if v == "none" => quote!(::systemd::bus::PropertyChange::None),
+        Some((_, v)) if v == "const" => quote!(::systemd::bus::PropertyChange::Const),
+        Some((_, v)) if v == "emits_change" => {
+            quote!(::systemd::bus::PropertyChange::EmitsChange)
+        }
+        Some((_, v)) if v == "emits_invalidation" => {
+            quote!(::systemd::bus::PropertyChange::EmitsInvalidation)
+        }
+        Some((c, _)) => {
+            return Err(syn::Error::new_spanned(
+                c,
+                "expected one of `none`, `const`, `emits_change` or `emits_invalidation`",
+            ))
+        }
+    };
+    let try_ = if fallible { quote!(?) } else { quote!() };
+    let (name, span) = attrs.name(method);
+    let get = quote! {{
+        let object = object.clone();
+        move |reply: &mut ::systemd::bus::MessageRef| -> ::systemd::bus::Result<()> {
+            reply.append(object.#ident()#try_)?;
+            Ok(())
+        }
+    }};
+    Ok(Property {
+        name,
+        span,
+        ty,
+        change,
+        get,
+        set: None,
+    })
+}
+
+fn expand_setter(method: &ImplItemMethod) -> syn::Result<TokenStream2> {
+    let ident = &method.sig.ident;
+    let args = arguments(method)?;
+    let ty = match (method.sig.receiver(), &args[..]) {
+        (Some(_), [(_, ty)]) => ty,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &method.sig,
+                "property setters take `&self` and the new value",
+            ))
+        }
+    };
+    let (_, fallible) = output(method);
+    let try_ = if fallible { quote!(?) } else { quote!() };
+    Ok(quote! {{
+        let object = object.clone();
+        move |value: &mut ::systemd::bus::MessageIter<'_>| -> ::systemd::bus::Result<()> {
+            let value: #ty = value.next()?.ok_or_else(|| {
+                ::std::io::Error::new(::std::io::ErrorKind::InvalidData, "missing property value")
+            })?;
+            object.#ident(value)#try_;
+            Ok(())
+        }
+    }})
+}
+
+/// Fill in the body of a signal, sending it with the remaining arguments, and return the vtable
+/// entry declaring it.
+fn expand_signal(method: &mut ImplItemMethod, member: TokenStream2) -> syn::Result<TokenStream2> {
+    let args = arguments(method)?;
+    if method.sig.receiver().is_some() || args.len() < 2 {
+        return Err(syn::Error::new_spanned(
+            &method.sig,
+            "signals take the bus and the object path, followed by their arguments",
+        ));
+    }
+    let bus = &args[0].0;
+    let path = &args[1].0;
+    let arg_names: Vec<_> = args[2..].iter().map(|(n, _)| n).collect();
+    let arg_types: Vec<_> = args[2..].iter().map(|(_, t)| t.clone()).collect();
+    let sig = signature(&arg_types);
+
+    let m = format_ident!("__message");
+    method.block = syn::parse_quote! {{
+        let mut #m = #bus.new_signal(
+            #path,
+            <Self as ::systemd::bus::Interface>::name(),
+            #member,
+        )?;
+        #( #m.append(#arg_names)?; )*
+        #m.send()?;
+        Ok(())
+    }};
+
+    Ok(quote! {
+        let vtable = vtable.signal(#member, &*#sig);
+    })
+}
+
+/// An expression for the concatenated signatures of `types`, returning an error from the
+/// surrounding function if they aren't valid (like a map with a non-basic key type).
+fn signature(types: &[Type]) -> TokenStream2 {
+    quote! {{
+        #[allow(unused_mut)]
+        let mut sig = ::std::string::String::new();
+        #( <#types as ::systemd::bus::types::SdBusSignature>::signature(&mut sig); )*
+        ::systemd::bus::SignatureBuf::new(sig.as_str()).map_err(|e| {
+            ::std::io::Error::new(
+                ::std::io::ErrorKind::InvalidInput,
+                format!("invalid signature `{}`: {}", sig, e),
+            )
+        })?
+    }}
+}
+
+/// An expression for the member name `name`, which is checked here so an invalid one is reported
+/// at `span` rather than when the vtable is built.
+fn member_name(name: &str, span: Span) -> syn::Result<TokenStream2> {
+    let valid = !name.is_empty()
+        && name.len() <= 255
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "`{}` isn't a valid D-Bus member name, which consists of `[A-Za-z0-9_]` and \
+                 doesn't start with a digit",
+                name
+            ),
+        ));
+    }
+    Ok(quote!(::systemd::member!(#name)))
+}
+
+/// `snake_case` to `CamelCase`, as D-Bus member names are usually written.
+fn camel_case(s: &str) -> String {
+    s.split('_')
+        .filter(|p| !p.is_empty())
+        .map(|p| {
+            let mut c = p.chars();
+            match c.next() {
+                Some(f) => f.to_uppercase().chain(c).collect(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
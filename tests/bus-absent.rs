@@ -0,0 +1,14 @@
+#![cfg(feature = "bus")]
+
+use systemd::bus;
+
+// Changes the environment, so this is kept apart from the other bus tests.
+#[test]
+fn try_default_system_without_bus() {
+    let path = std::env::temp_dir().join(format!("rust-systemd-no-bus-{}", std::process::id()));
+    std::env::set_var(
+        "DBUS_SYSTEM_BUS_ADDRESS",
+        format!("unix:path={}", path.display()),
+    );
+    assert!(bus::Bus::try_default_system().unwrap().is_none());
+}
@@ -6,6 +6,32 @@ extern crate utf8_cstr;
 use systemd::bus;
 use utf8_cstr::Utf8CStr;
 
+/// Process `service` until the `client` thread, making blocking calls to it, is done, and return
+/// the result of the thread.
+fn serve_until<T>(service: &mut bus::BusRef, client: std::thread::JoinHandle<T>) -> T {
+    serve_until_with(service, client, |_| {})
+}
+
+/// Like `serve_until()`, passing the messages no callback handled to `unhandled`.
+fn serve_until_with<T>(
+    service: &mut bus::BusRef,
+    client: std::thread::JoinHandle<T>,
+    mut unhandled: impl FnMut(bus::Message),
+) -> T {
+    while !client.is_finished() {
+        match service.process().unwrap() {
+            Some(Some(m)) => unhandled(m),
+            Some(None) => {}
+            None => {
+                service
+                    .wait(Some(std::time::Duration::from_millis(10)))
+                    .unwrap();
+            }
+        }
+    }
+    client.join().unwrap()
+}
+
 #[test]
 fn call() {
     let mut b = bus::Bus::default_system().unwrap();
@@ -22,6 +48,50 @@ fn call() {
     m.call(0).unwrap();
 }
 
+#[test]
+fn call_method_args() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    let mut b = bus::Bus::default_system().unwrap();
+    let dest = bus::BusName::from_bytes(b"org.freedesktop.DBus\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/\0").unwrap();
+    let interface = bus::InterfaceName::from_bytes(b"org.freedesktop.DBus\0").unwrap();
+
+    let mut reply = b
+        .call_method_args(
+            dest,
+            path,
+            interface,
+            bus::MemberName::from_bytes(b"NameHasOwner\0").unwrap(),
+            ("org.freedesktop.DBus",),
+        )
+        .unwrap();
+    assert_eq!(reply.iter().unwrap().next::<bool>().unwrap(), Some(true));
+
+    let done = Arc::new(AtomicBool::new(false));
+    let d = done.clone();
+    let _slot = b
+        .call_method_async(
+            dest,
+            path,
+            interface,
+            bus::MemberName::from_bytes(b"GetId\0").unwrap(),
+            (),
+            move |reply| {
+                assert!(reply.iter()?.next::<&str>()?.is_some());
+                d.store(true, Ordering::SeqCst);
+                Ok(())
+            },
+        )
+        .unwrap();
+    while !done.load(Ordering::SeqCst) {
+        if b.process().unwrap().is_none() {
+            b.wait(Some(std::time::Duration::from_millis(10))).unwrap();
+        }
+    }
+}
+
 #[test]
 fn basic_append_and_read() {
     let mut b = bus::Bus::default_system().unwrap();
@@ -44,8 +114,47 @@ fn basic_append_and_read() {
 
     assert_eq!(i.peek_type().unwrap(), (b's' as ::std::os::raw::c_char, ""));
 
+    assert_eq!(i.remaining_signature().to_bytes(), b"s");
+    assert!(!i.at_end(true).unwrap());
+    assert!(i.next::<u32>().is_err());
+
     let n: &Utf8CStr = i.next().unwrap().unwrap();
     assert_eq!(n, Utf8CStr::from_bytes(b"org.freedesktop.DBus\0").unwrap());
+
+    assert_eq!(i.remaining_signature().to_bytes(), b"");
+    assert!(i.at_end(true).unwrap());
+    assert_eq!(i.next::<&Utf8CStr>().unwrap(), None);
+}
+
+#[test]
+fn append_tuple() {
+    let mut b = bus::Bus::default_system().unwrap();
+    let mut m = b
+        .new_signal(
+            bus::ObjectPath::from_bytes(b"/org/example\0").unwrap(),
+            bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap(),
+            bus::MemberName::from_bytes(b"Tuple\0").unwrap(),
+        )
+        .unwrap();
+    m.append_tuple(()).unwrap();
+    m.append_tuple((
+        1u8,
+        Utf8CStr::from_bytes(b"a\0").unwrap(),
+        true,
+        2u16,
+        3i32,
+        4u32,
+        5i64,
+        6u64,
+        7f64,
+        -8i16,
+        bus::ObjectPath::from_bytes(b"/\0").unwrap(),
+        &[9u8][..],
+    ))
+    .unwrap();
+    assert_eq!(m.signature().to_bytes(), b"ysbqiuxtdnoay");
+    assert!(m.has_signature(bus::Signature::from_bytes(b"ysbqiuxtdnoay\0").unwrap()));
+    assert!(!m.has_signature(bus::Signature::from_bytes(b"ysbqiuxtdno\0").unwrap()));
 }
 
 #[test]
@@ -66,3 +175,1791 @@ fn bad_signature_on_call() {
     let r = m.call(0).err().unwrap();
     println!("{:?}", r);
 }
+
+#[test]
+fn capabilities() {
+    let b = bus::Bus::default_system().unwrap();
+    let c = b.capabilities().unwrap();
+    assert!(c.bus_client);
+    assert!(!c.server);
+    assert!(!c.monitor);
+    // local connections always support passing file descriptors
+    assert!(c.fds);
+    assert_eq!(c.creds_mask, b.creds_mask().unwrap());
+}
+
+#[test]
+fn read_containers() {
+    let mut b = bus::Bus::default_system().unwrap();
+    let mut r = b
+        .call_method(
+            bus::BusName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+            bus::ObjectPath::from_bytes(b"/\0").unwrap(),
+            bus::InterfaceName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+            bus::MemberName::from_bytes(b"ListNames\0").unwrap(),
+            |_| Ok(()),
+        )
+        .unwrap();
+
+    let mut i = r.iter().unwrap();
+    let s = std::ffi::CStr::from_bytes_with_nul(b"s\0").unwrap();
+    assert!(i.enter_container(b'a', s).unwrap());
+    assert_eq!(i.remaining_signature().to_bytes(), b"s");
+    let mut names = Vec::new();
+    while let Some(n) = i.next::<&Utf8CStr>().unwrap() {
+        names.push(n.to_string());
+    }
+    assert!(names.iter().any(|n| n == "org.freedesktop.DBus"));
+    assert_eq!(i.remaining_signature().to_bytes(), b"");
+    i.exit_container().unwrap();
+    assert!(i.at_end(true).unwrap());
+    assert!(!i.enter_container(b'a', s).unwrap());
+
+    // leaving a container early skips the rest of it
+    let mut i = r.iter().unwrap();
+    assert!(i.enter_container(b'a', s).unwrap());
+    let _: &Utf8CStr = i.next().unwrap().unwrap();
+    i.exit_container().unwrap();
+    assert!(i.at_end(true).unwrap());
+}
+
+#[test]
+fn signal_def() {
+    let mut b = bus::Bus::default_system().unwrap();
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Changed\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let other_path = bus::ObjectPath::from_bytes(b"/org/example/other\0").unwrap();
+    let other_member = bus::MemberName::from_bytes(b"Removed\0").unwrap();
+
+    let def = bus::SignalDef::new(interface, member)
+        .sender(bus::BusName::from_bytes(b"org.example\0").unwrap())
+        .path(path);
+    assert_eq!(
+        def.match_rule().to_bytes(),
+        &b"type='signal',sender='org.example',path='/org/example',\
+           interface='org.example.Test',member='Changed'"[..]
+    );
+
+    let m = b.new_signal(path, interface, member).unwrap();
+    assert!(def.matches(&m));
+    let m = b.new_signal(other_path, interface, member).unwrap();
+    assert!(!def.matches(&m));
+    assert!(bus::SignalDef::new(interface, member).matches(&m));
+    let m = b.new_signal(path, interface, other_member).unwrap();
+    assert!(!def.matches(&m));
+}
+
+#[test]
+fn run_until() {
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Ping\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    // a match with a callback which leaves the message unhandled
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    // sd_bus_add_match() waits for the match to be installed, and dropping the sender flushes
+    // the signal
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    let m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    assert!(def.matches(&m));
+}
+
+#[test]
+fn match_rule() {
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Arg\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+    let rule = bus::MatchRule::new()
+        .type_(bus::MessageType::Signal)
+        .interface(interface)
+        .member(member)
+        .arg0("it's")
+        .unwrap();
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let _slot = receiver.add_match(&rule, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    for arg in &[&b"other\0"[..], b"it's\0"] {
+        let mut m = sender.new_signal(path, interface, member).unwrap();
+        m.append(Utf8CStr::from_bytes(arg).unwrap()).unwrap();
+        m.send().unwrap();
+    }
+    drop(sender);
+
+    // the bus daemon only routes the second signal to us
+    let mut m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    let arg: &Utf8CStr = m.iter().unwrap().next().unwrap().unwrap();
+    assert_eq!(arg, Utf8CStr::from_bytes(b"it's\0").unwrap());
+}
+
+#[test]
+fn byte_arrays() {
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Bytes\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.append(&b"\0binary\xff"[..]).unwrap();
+    m.append(Vec::<u8>::new()).unwrap();
+    m.append_array(&[1u32, 2, 3]).unwrap();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    let mut m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    let mut i = m.iter().unwrap();
+    assert_eq!(i.remaining_signature().to_bytes(), b"ayayau");
+    assert_eq!(i.next::<&[u8]>().unwrap(), Some(&b"\0binary\xff"[..]));
+    assert_eq!(i.next::<Vec<u8>>().unwrap(), Some(Vec::new()));
+    assert!(i.read_array::<u8>().is_err());
+    assert_eq!(i.read_array::<u32>().unwrap(), Some(&[1, 2, 3][..]));
+    assert_eq!(i.next::<&[u8]>().unwrap(), None);
+}
+
+#[test]
+fn arrays() {
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Arrays\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let a = Utf8CStr::from_bytes(b"a\0").unwrap();
+    let b = Utf8CStr::from_bytes(b"b\0").unwrap();
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.append(&[1u32, 2, 3][..]).unwrap();
+    m.append(vec![true, false]).unwrap();
+    m.append(vec![a, b]).unwrap();
+    m.append(vec![vec![1i16], vec![], vec![2, 3]]).unwrap();
+    m.append(Vec::<&Utf8CStr>::new()).unwrap();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    let mut m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    let mut i = m.iter().unwrap();
+    assert_eq!(i.remaining_signature().to_bytes(), b"auabasaanas");
+    assert_eq!(i.next::<Vec<u32>>().unwrap(), Some(vec![1, 2, 3]));
+    assert_eq!(i.next::<Vec<bool>>().unwrap(), Some(vec![true, false]));
+    assert_eq!(i.next::<Vec<&Utf8CStr>>().unwrap(), Some(vec![a, b]));
+    assert_eq!(
+        i.next::<Vec<Vec<i16>>>().unwrap(),
+        Some(vec![vec![1], vec![], vec![2, 3]])
+    );
+    assert!(i.next::<Vec<u32>>().is_err());
+    assert_eq!(i.next::<Vec<&Utf8CStr>>().unwrap(), Some(vec![]));
+    assert_eq!(i.next::<Vec<&Utf8CStr>>().unwrap(), None);
+}
+
+#[test]
+fn maps() {
+    use std::collections::{BTreeMap, HashMap};
+
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Maps\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let a = bus::ObjectPath::from_bytes(b"/a\0").unwrap();
+    let b = bus::ObjectPath::from_bytes(b"/b\0").unwrap();
+    let mut names = BTreeMap::new();
+    names.insert(a, vec![1u32]);
+    names.insert(b, vec![2, 3]);
+    let mut flags = HashMap::new();
+    flags.insert(7u8, true);
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.append(&names).unwrap();
+    m.append(flags.clone()).unwrap();
+    m.append(BTreeMap::<u32, &Utf8CStr>::new()).unwrap();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    let mut m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    let mut i = m.iter().unwrap();
+    assert_eq!(i.remaining_signature().to_bytes(), b"a{oau}a{yb}a{us}");
+    assert_eq!(
+        i.next::<BTreeMap<&bus::ObjectPath, Vec<u32>>>().unwrap(),
+        Some(names)
+    );
+    assert!(i.next::<HashMap<u16, bool>>().is_err());
+    assert_eq!(i.next::<HashMap<u8, bool>>().unwrap(), Some(flags));
+    assert_eq!(
+        i.next::<BTreeMap<u32, &Utf8CStr>>().unwrap(),
+        Some(BTreeMap::new())
+    );
+    assert_eq!(i.next::<BTreeMap<u32, &Utf8CStr>>().unwrap(), None);
+}
+
+#[test]
+fn structs() {
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Structs\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let a = Utf8CStr::from_bytes(b"a\0").unwrap();
+    let b = Utf8CStr::from_bytes(b"b\0").unwrap();
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.append((a, 1u32)).unwrap();
+    m.append(vec![(a, (true, 2u8)), (b, (false, 3))]).unwrap();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    let mut m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    let mut i = m.iter().unwrap();
+    assert_eq!(i.remaining_signature().to_bytes(), b"(su)a(s(by))");
+    assert!(i.next::<(&Utf8CStr, u16)>().is_err());
+    assert_eq!(i.next::<(&Utf8CStr, u32)>().unwrap(), Some((a, 1)));
+    assert_eq!(
+        i.next::<Vec<(&Utf8CStr, (bool, u8))>>().unwrap(),
+        Some(vec![(a, (true, 2)), (b, (false, 3))])
+    );
+    assert_eq!(i.next::<(&Utf8CStr, u32)>().unwrap(), None);
+}
+
+#[test]
+fn variants() {
+    use bus::{AnyVariant, Value, Variant};
+    use std::collections::BTreeMap;
+
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Variants\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let name = Utf8CStr::from_bytes(b"name\0").unwrap();
+    let mut options = BTreeMap::new();
+    options.insert(1u32, AnyVariant(Value::String("one".into())));
+    options.insert(
+        2,
+        AnyVariant(Value::Array {
+            element: "(yb)".into(),
+            elements: vec![
+                Value::Struct(vec![Value::Byte(1), Value::Bool(true)]),
+                Value::Struct(vec![Value::Byte(2), Value::Bool(false)]),
+            ],
+        }),
+    );
+    options.insert(
+        3,
+        AnyVariant(Value::Dict {
+            key: "s".into(),
+            value: "v".into(),
+            entries: vec![],
+        }),
+    );
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.append(Variant(vec![1u16, 2])).unwrap();
+    m.append(vec![(name, Variant(7u64))]).unwrap();
+    m.append(&options).unwrap();
+    m.append(AnyVariant(Value::Array {
+        element: String::new(),
+        elements: vec![],
+    }))
+    .unwrap_err();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    let mut m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    let mut i = m.iter().unwrap();
+    assert_eq!(i.remaining_signature().to_bytes(), b"va(sv)a{uv}");
+    assert!(i.next::<Variant<u16>>().is_err());
+    assert_eq!(
+        i.next::<Variant<Vec<u16>>>().unwrap(),
+        Some(Variant(vec![1, 2]))
+    );
+    assert!(i.next::<AnyVariant>().is_err());
+    assert_eq!(
+        i.next::<Vec<(&Utf8CStr, AnyVariant)>>().unwrap(),
+        Some(vec![(name, AnyVariant(Value::UInt64(7)))])
+    );
+    // including the empty dictionary, which keeps its type
+    assert_eq!(
+        i.next::<BTreeMap<u32, AnyVariant>>().unwrap(),
+        Some(options)
+    );
+    assert_eq!(i.next::<AnyVariant>().unwrap(), None);
+}
+
+#[test]
+fn value_fds() {
+    use bus::{SharedFd, Value};
+    use std::io::{Read, Write};
+    use std::os::unix::io::OwnedFd;
+    use std::os::unix::net::UnixStream;
+
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"ValueFds\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let (a, mut b) = UnixStream::pair().unwrap();
+    let value = Value::Struct(vec![
+        Value::Fd(SharedFd::from(OwnedFd::from(a))),
+        Value::Variant(Box::new(Value::String("socket".into()))),
+    ]);
+    let mut sig = String::new();
+    value.signature(&mut sig).unwrap();
+    assert_eq!(sig, "(hv)");
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.append(value.clone()).unwrap();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    let mut m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    let read = m.iter().unwrap().next::<Value>().unwrap().unwrap();
+    drop(m);
+    // the descriptor was duplicated, so it differs from the one sent
+    assert_ne!(read, value);
+    let fd = match read {
+        Value::Struct(mut fields) => match fields.remove(0) {
+            Value::Fd(fd) => fd.try_into_owned().unwrap(),
+            v => panic!("{:?}", v),
+        },
+        v => panic!("{:?}", v),
+    };
+    UnixStream::from(fd).write_all(b"x").unwrap();
+    let mut buf = [0; 1];
+    b.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"x");
+}
+
+#[test]
+fn strings() {
+    use std::collections::BTreeMap;
+
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Strings\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let mut options = BTreeMap::new();
+    options.insert("Description".to_owned(), bus::Variant("test"));
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.append("a").unwrap();
+    m.append(String::from("b")).unwrap();
+    m.append(vec!["c", "d"]).unwrap();
+    m.append(&options).unwrap();
+    assert!(m.append("nul\0").is_err());
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    let mut m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    let mut i = m.iter().unwrap();
+    assert_eq!(i.remaining_signature().to_bytes(), b"ssasa{sv}");
+    assert_eq!(i.next::<&str>().unwrap(), Some("a"));
+    assert_eq!(i.next::<String>().unwrap(), Some("b".to_owned()));
+    assert_eq!(
+        i.next::<Vec<String>>().unwrap(),
+        Some(vec!["c".into(), "d".into()])
+    );
+    assert_eq!(
+        i.next::<BTreeMap<String, bus::Variant<String>>>().unwrap(),
+        Some(
+            vec![("Description".into(), bus::Variant("test".into()))]
+                .into_iter()
+                .collect()
+        )
+    );
+    assert_eq!(i.next::<String>().unwrap(), None);
+}
+
+#[test]
+fn fds() {
+    use std::io::{Read, Write};
+    use std::os::unix::io::{AsFd, BorrowedFd, OwnedFd};
+    use std::os::unix::net::UnixStream;
+
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Fds\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    assert!(sender.capabilities().unwrap().fds);
+    let (a, mut b) = UnixStream::pair().unwrap();
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.append(a.as_fd()).unwrap();
+    m.append(OwnedFd::from(a)).unwrap();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    let mut m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    let mut i = m.iter().unwrap();
+    assert_eq!(i.remaining_signature().to_bytes(), b"hh");
+    let borrowed = i.next::<BorrowedFd<'_>>().unwrap().unwrap();
+    let owned = i.next::<OwnedFd>().unwrap().unwrap();
+    assert!(i.next::<OwnedFd>().unwrap().is_none());
+    let mut borrowed = UnixStream::from(borrowed.try_clone_to_owned().unwrap());
+    drop(m);
+
+    // both refer to the same socket, the owned one stays valid after the message is gone
+    let mut owned = UnixStream::from(owned);
+    owned.write_all(b"x").unwrap();
+    borrowed.write_all(b"y").unwrap();
+    let mut buf = [0; 2];
+    b.read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"xy");
+}
+
+#[test]
+fn current_message() {
+    thread_local! {
+        static BUS: std::cell::RefCell<Option<bus::Bus>> = const { std::cell::RefCell::new(None) };
+    }
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Current\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    assert!(receiver.with_current_message(|m| m.is_none()));
+    assert!(receiver.current_slot().is_none());
+    assert!(unsafe { receiver.current_userdata::<()>() }.is_none());
+    BUS.with(|b| *b.borrow_mut() = Some((*receiver).to_owned()));
+    let _slot = def
+        .add_match(&receiver, |m| {
+            BUS.with(|b| {
+                let b = b.borrow();
+                let bus = b.as_ref().unwrap();
+                bus.with_current_message(|current| {
+                    assert_eq!(current.unwrap().member(), m.member());
+                });
+                assert!(bus.current_slot().is_some());
+                assert!(unsafe { bus.current_userdata::<()>() }.is_some());
+            });
+            Ok(())
+        })
+        .unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    assert!(receiver.with_current_message(|m| m.is_none()));
+    BUS.with(|b| b.borrow_mut().take());
+}
+
+#[cfg(feature = "event")]
+#[test]
+fn attach_event() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use systemd::event::Event;
+
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Attached\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut ev = Event::new().unwrap();
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    assert!(receiver.event().is_none());
+    receiver.attach_event(&ev, 0).unwrap();
+    assert!(receiver.event().is_some());
+
+    let seen = Arc::new(AtomicBool::new(false));
+    let s = seen.clone();
+    let _slot = def
+        .add_match(&receiver, move |_| {
+            s.store(true, Ordering::SeqCst);
+            Ok(())
+        })
+        .unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    for _ in 0..100 {
+        if seen.load(Ordering::SeqCst) {
+            break;
+        }
+        ev.run(Some(Duration::from_millis(100))).unwrap();
+    }
+    assert!(seen.load(Ordering::SeqCst));
+
+    receiver.detach_event().unwrap();
+    assert!(receiver.event().is_none());
+}
+
+#[test]
+fn emit_properties_changed() {
+    let b = bus::Bus::default_system().unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let name = bus::MemberName::from_bytes(b"Value\0").unwrap();
+    b.emit_properties_changed(path, interface, &[]).unwrap();
+    // nothing provides the property
+    assert!(b.emit_properties_changed(path, interface, &[name]).is_err());
+}
+
+#[test]
+fn object_vtable() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let version = bus::MemberName::from_bytes(b"Version\0").unwrap();
+    let level = bus::MemberName::from_bytes(b"Level\0").unwrap();
+
+    let gets = Arc::new(AtomicU32::new(0));
+    let value = Arc::new(AtomicU32::new(0));
+    let (g, v1, v2) = (gets.clone(), value.clone(), value.clone());
+    let vtable = bus::Vtable::new()
+        .property(
+            version,
+            bus::Signature::from_bytes(b"s\0").unwrap(),
+            bus::PropertyChange::Const,
+            |reply| Ok(reply.append(Utf8CStr::from_bytes(b"1.0\0").unwrap())?),
+        )
+        .writable_property(
+            level,
+            bus::Signature::from_bytes(b"u\0").unwrap(),
+            bus::PropertyChange::EmitsChange,
+            move |reply| {
+                g.fetch_add(1, Ordering::SeqCst);
+                Ok(reply.append(v1.load(Ordering::SeqCst))?)
+            },
+            move |m| {
+                let v = m.next::<u32>()?.unwrap();
+                v2.store(v, Ordering::SeqCst);
+                Ok(())
+            },
+        )
+        .auto_emit_changes();
+
+    let mut service = bus::BusBuilder::system().open().unwrap();
+    let slot = service.add_object_vtable(path, interface, vtable).unwrap();
+
+    // sd-bus reads the new value through the getter
+    service
+        .emit_properties_changed(path, interface, &[level])
+        .unwrap();
+    assert_eq!(gets.load(Ordering::SeqCst), 1);
+
+    let mut client = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let mut m = client
+        .new_method_call(
+            service.unique_name().unwrap(),
+            path,
+            bus::InterfaceName::from_bytes(b"org.freedesktop.DBus.Properties\0").unwrap(),
+            bus::MemberName::from_bytes(b"Set\0").unwrap(),
+        )
+        .unwrap();
+    m.append(Utf8CStr::from_bytes(b"org.example.Test\0").unwrap())
+        .unwrap();
+    m.append(Utf8CStr::from_bytes(b"Level\0").unwrap()).unwrap();
+    m.open_container(b'v', std::ffi::CStr::from_bytes_with_nul(b"u\0").unwrap())
+        .unwrap();
+    m.append(5u32).unwrap();
+    m.close_container().unwrap();
+    m.send().unwrap();
+    drop(m);
+
+    // sd-bus checks the credentials of the client before calling the setter, so keep it connected
+    // (and processing, which sends the call)
+    while value.load(Ordering::SeqCst) != 5 {
+        client.process().unwrap();
+        if service.process().unwrap().is_none() {
+            service
+                .wait(Some(std::time::Duration::from_millis(10)))
+                .unwrap();
+        }
+    }
+    drop(client);
+    // and the successful set was signalled, reading the value once more
+    assert_eq!(gets.load(Ordering::SeqCst), 2);
+
+    // unregistering removes the properties again
+    drop(slot);
+    assert!(service
+        .emit_properties_changed(path, interface, &[level])
+        .is_err());
+}
+
+#[test]
+fn method_call_timeout() {
+    use std::time::Duration;
+
+    let mut b = bus::BusBuilder::system()
+        .method_call_timeout(Duration::from_secs(3))
+        .open()
+        .unwrap();
+    assert_eq!(b.method_call_timeout().unwrap(), 3_000_000);
+    b.set_method_call_timeout(Duration::from_millis(1500))
+        .unwrap();
+    assert_eq!(b.method_call_timeout().unwrap(), 1_500_000);
+
+    let mut m = b
+        .new_method_call(
+            bus::BusName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+            bus::ObjectPath::from_bytes(b"/\0").unwrap(),
+            bus::InterfaceName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+            bus::MemberName::from_bytes(b"GetId\0").unwrap(),
+        )
+        .unwrap();
+    m.call_with_timeout(Duration::from_secs(5)).unwrap();
+
+    if std::env::var_os("SYSTEMD_BUS_TIMEOUT").is_none() {
+        assert_eq!(bus::default_call_timeout(), bus::DEFAULT_CALL_TIMEOUT);
+    }
+}
+
+#[test]
+fn object_vtable_standard_interfaces() {
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let vtable = bus::Vtable::new().property(
+        bus::MemberName::from_bytes(b"Version\0").unwrap(),
+        bus::Signature::from_bytes(b"s\0").unwrap(),
+        bus::PropertyChange::Const,
+        |reply| Ok(reply.append(Utf8CStr::from_bytes(b"1.0\0").unwrap())?),
+    );
+    let mut service = bus::BusBuilder::system().open().unwrap();
+    let _slot = service.add_object_vtable(path, interface, vtable).unwrap();
+    let dest = (**service.unique_name().unwrap()).to_owned();
+
+    // the calls block, so make them from another thread while this one serves them
+    let client = std::thread::spawn(move || {
+        let dest = bus::BusName::from_bytes(dest.to_bytes_with_nul()).unwrap();
+        let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+        let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+        let mut client = bus::BusBuilder::system().open().unwrap();
+
+        let xml = client.introspect(dest, path).unwrap();
+        client
+            .call_method(
+                dest,
+                path,
+                bus::InterfaceName::from_bytes(b"org.freedesktop.DBus.Peer\0").unwrap(),
+                bus::MemberName::from_bytes(b"Ping\0").unwrap(),
+                |_| Ok(()),
+            )
+            .unwrap();
+        let version = client
+            .get_property_string(
+                dest,
+                path,
+                interface,
+                bus::MemberName::from_bytes(b"Version\0").unwrap(),
+            )
+            .unwrap();
+        let all = client
+            .call_method(
+                dest,
+                path,
+                bus::InterfaceName::from_bytes(b"org.freedesktop.DBus.Properties\0").unwrap(),
+                bus::MemberName::from_bytes(b"GetAll\0").unwrap(),
+                |m| m.append(Utf8CStr::from_bytes(b"org.example.Test\0").unwrap()),
+            )
+            .unwrap();
+        let sig = bus::SignatureBuf::from(all.signature());
+        (xml, version, sig)
+    });
+    let (xml, version, sig) = serve_until(&mut service, client);
+    assert!(xml.contains(r#"<interface name="org.example.Test">"#));
+    assert!(xml.contains(r#"<property name="Version" type="s" access="read">"#));
+    assert!(xml.contains(r#"<interface name="org.freedesktop.DBus.Peer">"#));
+    assert!(xml.contains(r#"<interface name="org.freedesktop.DBus.Properties">"#));
+    assert_eq!(version, "1.0");
+    assert_eq!(sig.to_bytes(), b"a{sv}");
+}
+
+#[test]
+fn get_set_property() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    let level = Arc::new(AtomicU32::new(1));
+    let l = level.clone();
+    let vtable = bus::Vtable::new()
+        .property(
+            bus::MemberName::from_bytes(b"Version\0").unwrap(),
+            bus::Signature::from_bytes(b"s\0").unwrap(),
+            bus::PropertyChange::Const,
+            |reply| Ok(reply.append("1.0")?),
+        )
+        .writable_property(
+            bus::MemberName::from_bytes(b"Level\0").unwrap(),
+            bus::Signature::from_bytes(b"u\0").unwrap(),
+            bus::PropertyChange::EmitsChange,
+            move |reply| Ok(reply.append(l.load(Ordering::SeqCst))?),
+            move |value| {
+                level.store(value.next::<u32>()?.unwrap_or(0), Ordering::SeqCst);
+                Ok(())
+            },
+        );
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let mut service = bus::BusBuilder::system().open().unwrap();
+    let _slot = service.add_object_vtable(path, interface, vtable).unwrap();
+    let dest = (**service.unique_name().unwrap()).to_owned();
+
+    // the calls block, so make them from another thread while this one serves them
+    let client = std::thread::spawn(move || {
+        let dest = bus::BusName::from_bytes(dest.to_bytes_with_nul()).unwrap();
+        let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+        let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+        let level = bus::MemberName::from_bytes(b"Level\0").unwrap();
+        let mut client = bus::BusBuilder::system().open().unwrap();
+
+        let before: u32 = client.get_property(dest, path, interface, level).unwrap();
+        client
+            .set_property(dest, path, interface, level, 7u32)
+            .unwrap();
+        let after: u32 = client.get_property(dest, path, interface, level).unwrap();
+        let wrong_type = client.get_property::<String>(dest, path, interface, level);
+        let all = client.get_all_properties(dest, path, interface).unwrap();
+        (before, after, wrong_type.is_err(), all)
+    });
+    let (before, after, wrong_type, all) = serve_until(&mut service, client);
+    assert_eq!(before, 1);
+    assert_eq!(after, 7);
+    assert!(wrong_type);
+    assert_eq!(all.get("Level"), Some(&bus::Value::UInt32(7)));
+    assert_eq!(
+        all.get("Version"),
+        Some(&bus::Value::String("1.0".to_owned()))
+    );
+}
+
+#[test]
+fn fallback_vtable() {
+    let prefix = bus::ObjectPath::from_bytes(b"/org/example/device\0").unwrap();
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Device\0").unwrap();
+    let vtable = bus::Vtable::new().property(
+        bus::MemberName::from_bytes(b"Name\0").unwrap(),
+        bus::Signature::from_bytes(b"s\0").unwrap(),
+        bus::PropertyChange::Const,
+        |reply| {
+            let name = reply.bus().with_current_message(|call| {
+                let path = call.unwrap().path().unwrap().to_str().unwrap();
+                format!("{}\0", path.rsplit('/').next().unwrap())
+            });
+            Ok(reply.append(Utf8CStr::from_bytes(name.as_bytes()).unwrap())?)
+        },
+    );
+    let mut service = bus::BusBuilder::system().open().unwrap();
+    let _vtable = service
+        .add_fallback_vtable(prefix, interface, vtable, |path| {
+            Ok(path.to_bytes() == b"/org/example/device/a"
+                || path.to_bytes() == b"/org/example/device/b")
+        })
+        .unwrap();
+    let _enumerator = service
+        .add_node_enumerator(prefix, |prefix| {
+            assert_eq!(prefix.to_bytes(), b"/org/example/device");
+            Ok(vec![
+                bus::ObjectPathBuf::new("/org/example/device/a").unwrap(),
+                bus::ObjectPathBuf::new("/org/example/device/b").unwrap(),
+            ])
+        })
+        .unwrap();
+    let dest = (**service.unique_name().unwrap()).to_owned();
+
+    let client = std::thread::spawn(move || {
+        let dest = bus::BusName::from_bytes(dest.to_bytes_with_nul()).unwrap();
+        let interface = bus::InterfaceName::from_bytes(b"org.example.Device\0").unwrap();
+        let name = bus::MemberName::from_bytes(b"Name\0").unwrap();
+        let mut client = bus::BusBuilder::system().open().unwrap();
+
+        let xml = client
+            .introspect(
+                dest,
+                bus::ObjectPath::from_bytes(b"/org/example/device\0").unwrap(),
+            )
+            .unwrap();
+        let b = client
+            .get_property_string(
+                dest,
+                bus::ObjectPath::from_bytes(b"/org/example/device/b\0").unwrap(),
+                interface,
+                name,
+            )
+            .unwrap();
+        let c = client.get_property_string(
+            dest,
+            bus::ObjectPath::from_bytes(b"/org/example/device/c\0").unwrap(),
+            interface,
+            name,
+        );
+        (xml, b, c.is_err())
+    });
+    // calls to objects `find` rejects are handed back instead of being answered by sd-bus
+    let mut unhandled = Vec::new();
+    let (xml, b, c_failed) = serve_until_with(&mut service, client, |mut m| {
+        if m.type_() == bus::MessageType::MethodCall {
+            unhandled.push(m.path().unwrap().to_owned());
+            let e = bus::Error::new(
+                Utf8CStr::from_bytes(b"org.freedesktop.DBus.Error.UnknownObject\0").unwrap(),
+                None,
+            );
+            m.new_method_error(&e).unwrap().send().unwrap();
+        }
+    });
+    assert_eq!(
+        unhandled,
+        [std::ffi::CString::new("/org/example/device/c").unwrap()]
+    );
+    assert!(xml.contains(r#"<node name="a"/>"#), "{}", xml);
+    assert!(xml.contains(r#"<node name="b"/>"#), "{}", xml);
+    assert_eq!(b, "b");
+    assert!(c_failed);
+}
+
+#[test]
+fn managed_objects() {
+    let manager = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example/dev\0").unwrap();
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let vtable = bus::Vtable::new().property(
+        bus::MemberName::from_bytes(b"Version\0").unwrap(),
+        bus::Signature::from_bytes(b"u\0").unwrap(),
+        bus::PropertyChange::Const,
+        |reply| Ok(reply.append(3u32)?),
+    );
+    let mut service = bus::BusBuilder::system().open().unwrap();
+    service.add_object_manager(manager).unwrap();
+    let _slot = service.add_object_vtable(path, interface, vtable).unwrap();
+    let dest = (**service.unique_name().unwrap()).to_owned();
+
+    let client = std::thread::spawn(move || {
+        let dest = bus::BusName::from_bytes(dest.to_bytes_with_nul()).unwrap();
+        let mut client = bus::BusBuilder::system().open().unwrap();
+        client
+            .get_managed_objects(
+                dest,
+                bus::ObjectPath::from_bytes(b"/org/example\0").unwrap(),
+            )
+            .unwrap()
+    });
+    let objects = serve_until(&mut service, client);
+    assert_eq!(objects.len(), 1);
+    let interfaces = &objects[path];
+    assert_eq!(
+        interfaces[interface].get("Version"),
+        Some(&bus::Value::UInt32(3))
+    );
+    // the standard interfaces are listed too, without properties
+    assert!(interfaces
+        .keys()
+        .any(|i| i.to_bytes() == b"org.freedesktop.DBus.Peer"));
+}
+
+#[test]
+fn interfaces_removed() {
+    let mut service = bus::BusBuilder::system().open().unwrap();
+    let mut client = bus::BusBuilder::system().open().unwrap();
+    let manager = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let sender = (**service.unique_name().unwrap()).to_owned();
+    let sender = bus::BusName::from_bytes(sender.to_bytes_with_nul()).unwrap();
+
+    let removed = std::sync::Arc::new(std::sync::Mutex::new(None));
+    let r = removed.clone();
+    let _slot = client
+        .on_interfaces_removed(sender, manager, move |path, interfaces| {
+            *r.lock().unwrap() = Some((bus::ObjectPathBuf::from(path), interfaces));
+        })
+        .unwrap();
+
+    let mut m = service
+        .new_signal(
+            manager,
+            bus::InterfaceName::from_bytes(b"org.freedesktop.DBus.ObjectManager\0").unwrap(),
+            bus::MemberName::from_bytes(b"InterfacesRemoved\0").unwrap(),
+        )
+        .unwrap();
+    m.append(bus::ObjectPath::from_bytes(b"/org/example/dev\0").unwrap())
+        .unwrap();
+    m.append(&[Utf8CStr::from_bytes(b"org.example.Test\0").unwrap()][..])
+        .unwrap();
+    m.send().unwrap();
+
+    while removed.lock().unwrap().is_none() {
+        if client.process().unwrap().is_none() {
+            client
+                .wait(Some(std::time::Duration::from_millis(100)))
+                .unwrap();
+        }
+    }
+    let (path, interfaces) = removed.lock().unwrap().take().unwrap();
+    assert_eq!(path.to_bytes(), b"/org/example/dev");
+    assert_eq!(
+        interfaces,
+        [bus::InterfaceNameBuf::new("org.example.Test").unwrap()]
+    );
+}
+
+#[test]
+fn duplicate_unsealed() {
+    let mut b = bus::Bus::default_system().unwrap();
+    let dest = bus::BusName::from_bytes(b"org.freedesktop.DBus\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/freedesktop/DBus\0").unwrap();
+    let interface = bus::InterfaceName::from_bytes(b"org.freedesktop.DBus\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"NameHasOwner\0").unwrap();
+    let mut m = b.new_method_call(dest, path, interface, member).unwrap();
+    m.append(Utf8CStr::from_bytes(b"org.freedesktop.DBus\0").unwrap())
+        .unwrap();
+    m.set_auto_start(false).unwrap();
+    // not sealed yet
+    assert!(m.duplicate_unsealed().is_err());
+
+    let mut reply = m.call(0).unwrap();
+    assert_eq!(reply.iter().unwrap().next::<bool>().unwrap(), Some(true));
+    for _ in 0..2 {
+        let mut copy = m.duplicate_unsealed().unwrap();
+        assert_eq!(copy.member(), m.member());
+        assert_eq!(copy.destination(), m.destination());
+        let mut reply = copy.call(0).unwrap();
+        assert_eq!(reply.iter().unwrap().next::<bool>().unwrap(), Some(true));
+    }
+
+    let mut signal = b
+        .new_signal(
+            path,
+            interface,
+            bus::MemberName::from_bytes(b"Changed\0").unwrap(),
+        )
+        .unwrap();
+    assert!(signal.duplicate_unsealed().is_err());
+}
+
+#[test]
+fn slot_leak() {
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let name = bus::MemberName::from_bytes(b"Value\0").unwrap();
+    let vtable = bus::Vtable::new().property(
+        name,
+        bus::Signature::from_bytes(b"u\0").unwrap(),
+        bus::PropertyChange::EmitsChange,
+        |reply| Ok(reply.append(1u32)?),
+    );
+    let b = bus::Bus::default_system().unwrap();
+    let slot = b.add_object_vtable(path, interface, vtable).unwrap();
+    assert!(!slot.floating().unwrap());
+    slot.leak().unwrap();
+    // still registered
+    b.emit_properties_changed(path, interface, &[name]).unwrap();
+}
+
+#[test]
+fn body_size() {
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Sized\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.append(1u8).unwrap();
+    m.append(Utf8CStr::from_bytes(b"ab\0").unwrap()).unwrap();
+    m.append_array(&[1u32, 2, 3]).unwrap();
+    m.open_container(b'v', std::ffi::CStr::from_bytes_with_nul(b"t\0").unwrap())
+        .unwrap();
+    m.append(7u64).unwrap();
+    m.close_container().unwrap();
+    m.open_container(b'a', std::ffi::CStr::from_bytes_with_nul(b"s\0").unwrap())
+        .unwrap();
+    m.append(Utf8CStr::from_bytes(b"c\0").unwrap()).unwrap();
+    m.close_container().unwrap();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    let mut m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    // y: 1, pad 3, s: 4 + 3, pad 1, au: 4 + 12, v: 1 + 1 + 1, pad 1, t: 8, as: 4 + (4 + 2)
+    assert_eq!(m.body_size().unwrap(), bus::BodySize { bytes: 50, fds: 0 });
+    // the body can still be read afterwards
+    assert_eq!(m.iter().unwrap().next::<u8>().unwrap(), Some(1));
+}
+
+#[test]
+fn size_counters() {
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Counted\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let counters = std::sync::Arc::new(bus::SizeCounters::default());
+    let _filter = receiver.add_size_counters(counters.clone()).unwrap();
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.append_array(&[0u8; 100]).unwrap();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    assert!(counters.messages() >= 1);
+    // ay: 4 + 100, along with the replies and signals from the bus daemon
+    assert!(counters.total().bytes >= 104);
+    assert_eq!(counters.total().fds, 0);
+}
+
+#[test]
+fn reply_errno() {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut server = bus::BusBuilder::system().open().unwrap();
+        let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+        let _object = server
+            .add_object(path, |m| match m.member().map(|m| m.to_bytes()) {
+                Some(b"Context") => m.reply_errno(libc::ENOENT, "loading foo"),
+                Some(b"IoError") => {
+                    m.reply_errno_from(&std::io::Error::from_raw_os_error(libc::EACCES))
+                }
+                _ => m.reply_errno_from(&std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "custom failure",
+                )),
+            })
+            .unwrap();
+        tx.send(std::ffi::CString::from(&**server.unique_name().unwrap()))
+            .unwrap();
+        server.run_forever().unwrap();
+    });
+    let server_name: std::ffi::CString = rx.recv().unwrap();
+
+    let mut client = bus::BusBuilder::system().open().unwrap();
+    let mut call = |member: &[u8]| {
+        client
+            .call_method(
+                bus::BusName::from_bytes(server_name.as_bytes_with_nul()).unwrap(),
+                bus::ObjectPath::from_bytes(b"/org/example\0").unwrap(),
+                bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap(),
+                bus::MemberName::from_bytes(member).unwrap(),
+                |_| Ok(()),
+            )
+            .err()
+            .unwrap()
+    };
+
+    let e = call(b"Context\0");
+    assert_eq!(e.errno(), libc::ENOENT);
+    assert_eq!(
+        e.message().map(|m| m.as_ref()),
+        Some("loading foo: No such file or directory")
+    );
+    let e = call(b"IoError\0");
+    assert_eq!(e.errno(), libc::EACCES);
+    let e = call(b"Other\0");
+    assert_eq!(e.errno(), libc::EIO);
+    assert_eq!(
+        e.message().map(|m| m.as_ref()),
+        Some("custom failure: Input/output error")
+    );
+}
+
+#[test]
+fn call_policy() {
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Hello\0").unwrap();
+    let mut client = bus::BusBuilder::system().open().unwrap();
+
+    let policy = bus::CallPolicy::new()
+        .retries(2)
+        .backoff(std::time::Duration::from_millis(10));
+    let start = std::time::Instant::now();
+    let e = client
+        .call_method_with_policy(
+            bus::BusName::from_bytes(b"org.example.Missing\0").unwrap(),
+            path,
+            interface,
+            member,
+            &policy,
+            |_| Ok(()),
+        )
+        .err()
+        .unwrap();
+    assert_eq!(e.kind(), bus::ErrorKind::ServiceUnknown);
+    assert!(start.elapsed() >= std::time::Duration::from_millis(30));
+
+    // a service which shows up while the call is retried
+    std::thread::spawn(|| {
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        let mut server = bus::BusBuilder::system().open().unwrap();
+        let _object = server
+            .add_object(
+                bus::ObjectPath::from_bytes(b"/org/example\0").unwrap(),
+                |m| m.reply_errno(libc::ENOENT, "hello"),
+            )
+            .unwrap();
+        server
+            .request_name(bus::BusName::from_bytes(b"org.example.Late\0").unwrap(), 0)
+            .unwrap();
+        server.run_forever().unwrap();
+    });
+    let policy = bus::CallPolicy::new()
+        .retries(1)
+        .retry_on(&[bus::ErrorKind::ServiceUnknown])
+        .wait_for_activation(std::time::Duration::from_secs(10));
+    let e = client
+        .call_method_with_policy(
+            bus::BusName::from_bytes(b"org.example.Late\0").unwrap(),
+            path,
+            interface,
+            member,
+            &policy,
+            |_| Ok(()),
+        )
+        .err()
+        .unwrap();
+    // the error returned by the service itself, which isn't retried
+    assert_eq!(e.errno(), libc::ENOENT);
+}
+
+#[test]
+fn open_address() {
+    let system = bus::Bus::default_system().unwrap();
+    let address = system.address().unwrap().to_owned();
+    let mut b = bus::BusBuilder::address(address).open().unwrap();
+    b.call_method(
+        bus::BusName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+        bus::ObjectPath::from_bytes(b"/\0").unwrap(),
+        bus::InterfaceName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+        bus::MemberName::from_bytes(b"GetId\0").unwrap(),
+        |_| Ok(()),
+    )
+    .unwrap();
+    assert!(b.unique_name().is_ok());
+}
+
+#[test]
+fn name_traits() {
+    use std::borrow::Borrow;
+    use std::collections::{BTreeSet, HashMap};
+
+    let mut dispatch: HashMap<&bus::MemberName, u32> = HashMap::new();
+    dispatch.insert(bus::MemberName::from_bytes(b"Start\0").unwrap(), 1);
+    dispatch.insert(bus::MemberName::from_bytes(b"Stop\0").unwrap(), 2);
+    let stop = bus::MemberName::from_bytes(b"Stop\0").unwrap();
+    assert_eq!(dispatch.get(stop), Some(&2));
+
+    let a = bus::ObjectPath::from_bytes(b"/a\0").unwrap();
+    let b = bus::ObjectPath::from_bytes(b"/b\0").unwrap();
+    assert!(a < b);
+    assert_eq!(a, bus::ObjectPath::from_bytes(b"/a\0").unwrap());
+    let paths: BTreeSet<_> = vec![b, a].into_iter().collect();
+    assert_eq!(paths.into_iter().next(), Some(a));
+
+    let i = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    assert_eq!(i.to_string(), "org.example.Test");
+    let c: &std::ffi::CStr = i.borrow();
+    assert_eq!(c.to_bytes(), b"org.example.Test");
+    assert_eq!(
+        bus::BusName::from_bytes(b"org.example\0")
+            .unwrap()
+            .to_string(),
+        "org.example"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_append() {
+    use bus::serde::Serde;
+    use serde::ser::{SerializeStruct, Serializer};
+
+    struct Unit<'a> {
+        name: &'a str,
+        pid: u32,
+        tags: &'a [&'a str],
+    }
+
+    impl serde::Serialize for Unit<'_> {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            let mut st = s.serialize_struct("Unit", 3)?;
+            st.serialize_field("name", self.name)?;
+            st.serialize_field("pid", &self.pid)?;
+            st.serialize_field("tags", self.tags)?;
+            st.end()
+        }
+    }
+
+    assert_eq!(
+        bus::serde::Serializer
+            .collect_str(&format_args!("{}.{}", "a", 7))
+            .unwrap(),
+        bus::Value::String("a.7".to_owned())
+    );
+
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"Serde\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let unit = Unit {
+        name: "a.service",
+        pid: 7,
+        tags: &["x", "y"],
+    };
+    let no_tags = Unit { tags: &[], ..unit };
+    let sig = bus::Signature::from_bytes(b"(syas)\0").unwrap();
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.append_tuple((Serde(&unit), Serde(-1i8))).unwrap();
+    bus::serde::append_as(&mut m, sig, &no_tags).unwrap();
+    bus::serde::append(&mut m, &no_tags).unwrap_err();
+    bus::serde::append(&mut m, &None::<u32>).unwrap_err();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    let mut m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    let mut i = m.iter().unwrap();
+    assert_eq!(i.remaining_signature().to_bytes(), b"(suas)n(syas)");
+    assert_eq!(
+        i.next::<(&str, u32, Vec<&str>)>().unwrap(),
+        Some(("a.service", 7, vec!["x", "y"]))
+    );
+    assert_eq!(i.next::<i16>().unwrap(), Some(-1));
+    assert_eq!(
+        i.next::<(&str, u8, Vec<&str>)>().unwrap(),
+        Some(("a.service", 7, vec![]))
+    );
+    assert_eq!(i.next::<i16>().unwrap(), None);
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_bus() {
+    use bus::nonblock::AsyncBus;
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    tokio::task::LocalSet::new().block_on(&rt, async {
+        let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+        let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+        let mut server =
+            AsyncBus::new(bus::BusBuilder::system().open().unwrap().into_bus()).unwrap();
+        let object = server
+            .add_object(path, |m| {
+                let n = m.iter()?.next::<u32>()?.unwrap_or(0);
+                let mut reply = m.new_method_return()?;
+                reply.append(n * 2)?;
+                reply.send()?;
+                Ok(())
+            })
+            .unwrap();
+        let server_name = (**server.unique_name().unwrap()).to_owned();
+        tokio::task::spawn_local(async move {
+            let _object = object;
+            server.process().await.unwrap();
+        });
+
+        let mut client =
+            AsyncBus::new(bus::BusBuilder::system().open().unwrap().into_bus()).unwrap();
+        let mut m = client
+            .new_method_call(
+                bus::BusName::from_bytes(server_name.to_bytes_with_nul()).unwrap(),
+                path,
+                interface,
+                bus::MemberName::from_bytes(b"Double\0").unwrap(),
+            )
+            .unwrap();
+        m.append(21u32).unwrap();
+        let mut reply = client.call(&mut m).await.unwrap();
+        assert_eq!(reply.iter().unwrap().next::<u32>().unwrap(), Some(42));
+
+        let mut m = client
+            .new_method_call(
+                bus::BusName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+                bus::ObjectPath::from_bytes(b"/\0").unwrap(),
+                bus::InterfaceName::from_bytes(b"org.freedesktop.DBus\0").unwrap(),
+                bus::MemberName::from_bytes(b"GetNameOwner\0").unwrap(),
+            )
+            .unwrap();
+        m.append(Utf8CStr::from_bytes(b"org.example.Missing\0").unwrap())
+            .unwrap();
+        let e = client.call(&mut m).await.err().unwrap();
+        assert!(e.has_name("org.freedesktop.DBus.Error.NameHasNoOwner"));
+    });
+}
+
+#[cfg(all(feature = "tokio", feature = "bus-service"))]
+#[test]
+fn async_handlers() {
+    use bus::nonblock::AsyncBus;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    tokio::task::LocalSet::new().block_on(&rt, async {
+        let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+        let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+        let mut server =
+            AsyncBus::new(bus::BusBuilder::system().open().unwrap().into_bus()).unwrap();
+
+        // one call at a time: the second call waits for the first one to complete
+        let running = Rc::new(Cell::new(0));
+        let max_running = Rc::new(Cell::new(0));
+        let (r, max) = (running.clone(), max_running.clone());
+        let object = server
+            .object(1)
+            .add_object(&server, path, move |mut call| {
+                let (r, max) = (r.clone(), max.clone());
+                async move {
+                    r.set(r.get() + 1);
+                    max.set(max.get().max(r.get()));
+                    let n = call.iter()?.next::<u32>()?.unwrap_or(0);
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                    r.set(r.get() - 1);
+                    Ok((n * 2,))
+                }
+            })
+            .unwrap();
+        let vtable = server.object(1).method(
+            bus::Vtable::new(),
+            bus::MemberName::from_bytes(b"Fail\0").unwrap(),
+            bus::Signature::from_bytes(b"\0").unwrap(),
+            bus::Signature::from_bytes(b"\0").unwrap(),
+            |_| async {
+                Err::<(), _>(bus::Error::new(
+                    Utf8CStr::from_bytes(b"org.example.Error.Failed\0").unwrap(),
+                    None,
+                ))
+            },
+        );
+        let other = bus::ObjectPath::from_bytes(b"/org/example/other\0").unwrap();
+        let vtable = server.add_object_vtable(other, interface, vtable).unwrap();
+        let server_name = (**server.unique_name().unwrap()).to_owned();
+        tokio::task::spawn_local(async move {
+            let _slots = (object, vtable);
+            server.process().await.unwrap();
+        });
+
+        let calls = (0..2u32).map(|n| {
+            let server_name = server_name.clone();
+            tokio::task::spawn_local(async move {
+                let mut client =
+                    AsyncBus::new(bus::BusBuilder::system().open().unwrap().into_bus()).unwrap();
+                let mut m = client
+                    .new_method_call(
+                        bus::BusName::from_bytes(server_name.to_bytes_with_nul()).unwrap(),
+                        path,
+                        interface,
+                        bus::MemberName::from_bytes(b"Double\0").unwrap(),
+                    )
+                    .unwrap();
+                m.append(n).unwrap();
+                let mut reply = client.call(&mut m).await.unwrap();
+                reply.iter().unwrap().next::<u32>().unwrap()
+            })
+        });
+        for (n, call) in calls.collect::<Vec<_>>().into_iter().enumerate() {
+            assert_eq!(call.await.unwrap(), Some(n as u32 * 2));
+        }
+        assert_eq!(max_running.get(), 1);
+
+        let mut client =
+            AsyncBus::new(bus::BusBuilder::system().open().unwrap().into_bus()).unwrap();
+        let mut m = client
+            .new_method_call(
+                bus::BusName::from_bytes(server_name.to_bytes_with_nul()).unwrap(),
+                other,
+                interface,
+                bus::MemberName::from_bytes(b"Fail\0").unwrap(),
+            )
+            .unwrap();
+        let e = client.call(&mut m).await.err().unwrap();
+        assert!(e.has_name("org.example.Error.Failed"));
+    });
+}
+
+#[cfg(all(feature = "tokio", feature = "bus-service"))]
+#[test]
+fn async_handlers_without_local_set() {
+    use bus::nonblock::AsyncBus;
+
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let (tx, rx) = std::sync::mpsc::channel();
+    // processed with `block_on()` on the runtime, not in a `LocalSet`
+    std::thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut server =
+                AsyncBus::new(bus::BusBuilder::system().open().unwrap().into_bus()).unwrap();
+            let _object = server
+                .object(1)
+                .add_object(&server, path, |_| async { Ok(()) })
+                .unwrap();
+            tx.send((**server.unique_name().unwrap()).to_owned())
+                .unwrap();
+            let _ = server.process().await;
+        });
+    });
+    let server_name = rx.recv().unwrap();
+
+    let mut client = bus::Bus::default_system().unwrap();
+    let mut m = client
+        .new_method_call(
+            bus::BusName::from_bytes(server_name.to_bytes_with_nul()).unwrap(),
+            path,
+            bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap(),
+            bus::MemberName::from_bytes(b"Double\0").unwrap(),
+        )
+        .unwrap();
+    let e = m.call(0).err().unwrap();
+    assert!(e.has_name("org.freedesktop.DBus.Error.Failed"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_read() {
+    use bus::serde::Serde;
+    use bus::Variant;
+
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"SerdeRead\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    m.append(("a.service", 7u32)).unwrap();
+    m.append(&b"\0binary"[..]).unwrap();
+    m.append(Variant(("b", path))).unwrap();
+    m.append("x").unwrap();
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    let mut m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    let mut i = m.iter().unwrap();
+    assert_eq!(
+        bus::serde::read::<(&str, u32)>(&mut i).unwrap(),
+        Some(("a.service", 7))
+    );
+    assert_eq!(
+        i.next::<Serde<&[u8]>>().unwrap(),
+        Some(Serde(&b"\0binary"[..]))
+    );
+    // variants are read as their contents, and strings can be read from object paths
+    assert_eq!(
+        bus::serde::read::<(char, &str)>(&mut i).unwrap(),
+        Some(('b', "/org/example"))
+    );
+    let e = bus::serde::read::<u32>(&mut i).unwrap_err();
+    assert_eq!(e.to_string(), "expected signature `u`, found `s`");
+    assert_eq!(bus::serde::read::<&str>(&mut i).unwrap(), Some("x"));
+    assert_eq!(bus::serde::read::<Option<u32>>(&mut i).unwrap(), None);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_derive() {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum State {
+        Active,
+        Failed(String),
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Unit {
+        name: String,
+        pid: Option<u32>,
+        tags: Vec<String>,
+        state: State,
+        limits: (u64, u64),
+    }
+
+    let interface = bus::InterfaceName::from_bytes(b"org.example.Test\0").unwrap();
+    let member = bus::MemberName::from_bytes(b"SerdeDerive\0").unwrap();
+    let path = bus::ObjectPath::from_bytes(b"/org/example\0").unwrap();
+    let def = bus::SignalDef::new(interface, member).path(path);
+
+    let units = [
+        Unit {
+            name: "a.service".to_owned(),
+            pid: Some(7),
+            tags: vec!["x".to_owned(), "y".to_owned()],
+            state: State::Active,
+            limits: (1, u64::MAX),
+        },
+        Unit {
+            name: "b.service".to_owned(),
+            pid: Some(0),
+            tags: vec!["z".to_owned()],
+            state: State::Failed("exit-code".to_owned()),
+            limits: (0, 0),
+        },
+    ];
+
+    let mut receiver = bus::BusBuilder::system().open().unwrap();
+    let _slot = def.add_match(&receiver, |_| Ok(())).unwrap();
+    let mut sender = bus::BusBuilder::system()
+        .flush_close_on_drop(true)
+        .open()
+        .unwrap();
+    let mut m = sender.new_signal(path, interface, member).unwrap();
+    for unit in &units {
+        bus::serde::append(&mut m, unit).unwrap();
+    }
+    m.send().unwrap();
+    drop(m);
+    drop(sender);
+
+    let mut m = receiver.run_until(|m| def.matches(m)).unwrap().unwrap();
+    let mut i = m.iter().unwrap();
+    assert_eq!(
+        i.remaining_signature().to_bytes(),
+        b"(suasu(tt))(suas(us)(tt))"
+    );
+    for unit in &units {
+        assert_eq!(
+            bus::serde::read::<Unit>(&mut i).unwrap().as_ref(),
+            Some(unit)
+        );
+    }
+    assert_eq!(bus::serde::read::<Unit>(&mut i).unwrap(), None);
+}
+
+#[test]
+fn takeover_name() {
+    let name = bus::BusName::from_bytes(b"org.example.Takeover\0").unwrap();
+    let mut old = bus::BusBuilder::system().open().unwrap();
+    let old_name = bus::takeover_name(&mut old, name).unwrap();
+    assert_eq!(old_name.state(), bus::NameState::Owner);
+
+    let mut new = bus::BusBuilder::system().open().unwrap();
+    let new_name = bus::takeover_name(&mut new, name).unwrap();
+    assert_eq!(new_name.state(), bus::NameState::Owner);
+
+    // returns once `NameLost` is processed
+    old_name.run(&mut old).unwrap();
+    assert_eq!(old_name.state(), bus::NameState::Lost);
+}
+
+#[cfg(feature = "macros")]
+#[derive(Default)]
+struct Counter {
+    value: std::sync::atomic::AtomicU32,
+}
+
+#[cfg(feature = "macros")]
+#[bus::dbus_interface(name = "org.example.Counter", auto_emit)]
+impl Counter {
+    fn add(&self, n: u32) -> u32 {
+        self.value.fetch_add(n, std::sync::atomic::Ordering::SeqCst) + n
+    }
+
+    fn fail(&self) -> bus::Result<()> {
+        Err(bus::Error::new(
+            Utf8CStr::from_bytes(b"org.example.Error.Failed\0").unwrap(),
+            None,
+        ))
+    }
+
+    #[dbus_interface(property, change = "emits_invalidation")]
+    fn value(&self) -> u32 {
+        self.value.load(std::sync::atomic::Ordering::SeqCst)
+    }
+
+    #[dbus_interface(property)]
+    fn set_value(&self, value: u32) {
+        self.value.store(value, std::sync::atomic::Ordering::SeqCst)
+    }
+
+    // the setter names the property as well, `set_step` alone would belong to `Step`
+    #[dbus_interface(property, name = "StepSize")]
+    fn step(&self) -> u32 {
+        1
+    }
+
+    #[dbus_interface(property, name = "StepSize")]
+    fn set_step(&self, _step: u32) {}
+
+    #[dbus_interface(signal)]
+    fn overflowed(bus: &mut bus::BusRef, path: &bus::ObjectPath, value: u32) -> bus::Result<()> {}
+}
+
+#[cfg(feature = "macros")]
+#[test]
+fn dbus_interface() {
+    use bus::Interface;
+
+    assert_eq!(Counter::name().to_bytes(), b"org.example.Counter");
+    let path = bus::ObjectPath::from_bytes(b"/org/example/Counter\0").unwrap();
+    let mut service = bus::BusBuilder::system().open().unwrap();
+    let _slot = service
+        .add_interface(path, std::sync::Arc::new(Counter::default()))
+        .unwrap();
+    Counter::overflowed(&mut service, path, 1).unwrap();
+    let dest = (**service.unique_name().unwrap()).to_owned();
+
+    // the calls block, so make them from another thread while this one serves them
+    let client = std::thread::spawn(move || {
+        let dest = bus::BusName::from_bytes(dest.to_bytes_with_nul()).unwrap();
+        let path = bus::ObjectPath::from_bytes(b"/org/example/Counter\0").unwrap();
+        let interface = bus::InterfaceName::from_bytes(b"org.example.Counter\0").unwrap();
+        let mut client = bus::BusBuilder::system().open().unwrap();
+
+        let xml = client.introspect(dest, path).unwrap();
+        let mut reply = client
+            .call_method(
+                dest,
+                path,
+                interface,
+                bus::MemberName::from_bytes(b"Add\0").unwrap(),
+                |m| m.append(3u32),
+            )
+            .unwrap();
+        let sum = reply.iter().unwrap().next::<u32>().unwrap();
+        let err = client
+            .call_method(
+                dest,
+                path,
+                interface,
+                bus::MemberName::from_bytes(b"Fail\0").unwrap(),
+                |_| Ok(()),
+            )
+            .err()
+            .unwrap();
+        (xml, sum, err.name().to_string())
+    });
+    let (xml, sum, err) = serve_until(&mut service, client);
+    assert!(xml.contains(r#"<interface name="org.example.Counter">"#));
+    assert!(xml.contains(r#"<method name="Add">"#));
+    assert!(xml.contains(r#"<property name="Value" type="u" access="readwrite">"#));
+    assert!(xml.contains(r#"<property name="StepSize" type="u" access="readwrite">"#));
+    assert!(xml.contains(r#"<signal name="Overflowed">"#));
+    assert_eq!(sum, Some(3));
+    assert_eq!(err, "org.example.Error.Failed");
+}
+
+#[cfg(feature = "macros")]
+struct Blobs;
+
+#[cfg(feature = "macros")]
+#[bus::dbus_interface(name = "org.example.Blobs")]
+impl Blobs {
+    // dict keys have to be basic types, `a{ayu}` isn't a valid signature
+    #[dbus_interface(property)]
+    fn sizes(&self) -> std::collections::HashMap<Vec<u8>, u32> {
+        std::collections::HashMap::new()
+    }
+}
+
+#[cfg(feature = "macros")]
+#[test]
+fn dbus_interface_invalid_signature() {
+    use bus::Interface;
+
+    let e = Blobs::vtable(std::sync::Arc::new(Blobs)).unwrap_err();
+    assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,45 @@
+#![cfg(feature = "daemon")]
+
+extern crate systemd;
+
+use std::env;
+use std::os::unix::io::{AsRawFd, IntoRawFd};
+use std::os::unix::net::UnixDatagram;
+use systemd::daemon;
+
+// This lives in its own test binary as it changes the environment and fd table of the process.
+#[test]
+fn test_fake_activation() {
+    // The pair may itself have been opened as fds 3 and 4, which are taken below, so it isn't
+    // closed on drop.
+    let (a, b) = UnixDatagram::pair().unwrap();
+    let (a, b) = (a.into_raw_fd(), b.into_raw_fd());
+    daemon::testing::fake_activation(&[(a, "first"), (b, "second")]).unwrap();
+    assert_eq!(env::var("LISTEN_FDNAMES").unwrap(), "first:second");
+
+    let mut again = daemon::listen_fds(false).unwrap();
+    let mut fds = daemon::listen_fds(true).unwrap();
+    assert_eq!(fds.iter().collect::<Vec<_>>(), vec![3, 4]);
+    let cloexec = |fd| unsafe { libc::fcntl(fd, libc::F_GETFD) } & libc::FD_CLOEXEC != 0;
+    assert!(cloexec(3) && cloexec(4));
+    assert!(daemon::is_socket_unix(
+        3,
+        Some(daemon::SocketType::Datagram),
+        daemon::Listening::NoListeningCheck,
+        None::<&str>
+    )
+    .unwrap());
+    assert!(env::var_os("LISTEN_FDS").is_none());
+
+    let first = fds.take(0).unwrap();
+    assert_eq!(first.as_raw_fd(), 3);
+    assert!(fds.take(0).is_err());
+    assert!(again.take(0).is_err());
+    assert!(fds.take(2).is_err());
+    let second = daemon::leak_for_exec(fds.take(1).unwrap()).unwrap();
+    assert_eq!(second, 4);
+    assert!(!cloexec(4));
+    unsafe { libc::close(second) };
+
+    assert!(daemon::testing::fake_activation(&[(a, "a:b")]).is_err());
+}
@@ -0,0 +1,69 @@
+#![cfg(feature = "daemon")]
+
+extern crate systemd;
+
+use std::env;
+use std::os::unix::net::UnixDatagram;
+use std::time::{Duration, Instant};
+use systemd::daemon;
+
+fn recv(sock: &UnixDatagram) -> String {
+    let mut buf = [0u8; 8192];
+    let n = sock.recv(&mut buf).unwrap();
+    String::from_utf8(buf[..n].to_vec()).unwrap()
+}
+
+#[test]
+fn status_text() {
+    assert_eq!(daemon::status_text("one\ntwo"), "one two");
+    let long = "é".repeat(daemon::STATUS_MAX);
+    let text = daemon::status_text(&long);
+    // the limit is odd, so the last two byte character doesn't fit
+    assert_eq!(text.len(), daemon::STATUS_MAX - 1);
+}
+
+// This lives in its own test binary as it changes the environment of the process.
+#[test]
+fn status_updates() {
+    let path = env::temp_dir().join(format!("rust-systemd-status-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let notify = UnixDatagram::bind(&path).unwrap();
+    notify.set_nonblocking(true).unwrap();
+    env::set_var("NOTIFY_SOCKET", &path);
+
+    assert!(systemd::status_fmt!("{} of {}", 1, 3).unwrap());
+    assert_eq!(recv(&notify), "STATUS=1 of 3");
+    // too soon, held back and replaced by the next one
+    assert!(!daemon::status_fmt!("{} of {}", 2, 3).unwrap());
+    assert!(!daemon::status("3 of 3").unwrap());
+    assert!(notify.recv(&mut [0; 16]).is_err());
+    assert!(daemon::flush_status().unwrap());
+    assert_eq!(recv(&notify), "STATUS=3 of 3");
+    assert!(!daemon::flush_status().unwrap());
+    // sent in the background once the interval has passed
+    assert!(!daemon::status("4 of 4").unwrap());
+    std::thread::sleep(daemon::STATUS_INTERVAL * 2);
+    assert_eq!(recv(&notify), "STATUS=4 of 4");
+
+    let mut updater = daemon::StatusUpdater::new(Duration::from_millis(20));
+    assert!(updater.update(&"x".repeat(5000)).unwrap());
+    assert_eq!(recv(&notify).len(), 4096);
+    assert!(!updater.update("held").unwrap());
+    assert_eq!(updater.pending(), Some("held"));
+    std::thread::sleep(Duration::from_millis(30));
+    assert!(updater.update("sent").unwrap());
+    assert_eq!(updater.pending(), None);
+    assert_eq!(recv(&notify), "STATUS=sent");
+    assert!(!updater.update("later").unwrap());
+    assert!(!updater.poll().unwrap());
+    let deadline = updater.deadline().unwrap();
+    std::thread::sleep(deadline.saturating_duration_since(Instant::now()));
+    assert!(updater.poll().unwrap());
+    assert_eq!(updater.deadline(), None);
+    assert_eq!(recv(&notify), "STATUS=later");
+    assert!(!updater.update("last").unwrap());
+    drop(updater);
+    assert_eq!(recv(&notify), "STATUS=last");
+
+    std::fs::remove_file(&path).unwrap();
+}
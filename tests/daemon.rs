@@ -1,3 +1,5 @@
+#![cfg(feature = "daemon")]
+
 extern crate systemd;
 
 use systemd::daemon;
@@ -33,3 +35,27 @@ fn test_notify() {
     assert!(result.is_ok());
     assert!(!result.ok().unwrap()); // should fail, since this is not systemd-launched.
 }
+
+#[test]
+fn test_notify_to() {
+    use std::os::unix::net::UnixDatagram;
+
+    let path = std::env::temp_dir().join(format!("rust-systemd-notify-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let receiver = UnixDatagram::bind(&path).unwrap();
+    daemon::notify_to(
+        path.as_os_str(),
+        [(daemon::STATE_READY, "1"), (daemon::STATE_STATUS, "up")].iter(),
+    )
+    .unwrap();
+    let mut buf = [0; 64];
+    let n = receiver.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"READY=1\nSTATUS=up");
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(daemon::notify_to(
+        std::ffi::OsStr::new("relative"),
+        [(daemon::STATE_READY, "1")].iter()
+    )
+    .is_err());
+}
@@ -0,0 +1,187 @@
+#![cfg(feature = "event")]
+
+extern crate systemd;
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::Duration;
+use systemd::event::{self, Clock, Enabled, Event};
+
+#[test]
+fn periodic_timer() {
+    let mut ev = Event::new().unwrap();
+    let period = Duration::from_millis(5);
+    let start = ev.now(Clock::Monotonic).unwrap();
+    let ticks = Rc::new(Cell::new(0));
+
+    let t = ticks.clone();
+    let timer = ev
+        .add_time_relative(
+            Clock::Monotonic,
+            period,
+            Duration::from_micros(1),
+            move |s, when| {
+                assert!(when >= start + period);
+                t.set(t.get() + 1);
+                if t.get() == 3 {
+                    return s.event().exit(7);
+                }
+                s.set_time_relative(period)?;
+                s.set_enabled(Enabled::OneShot)
+            },
+        )
+        .unwrap();
+    assert_eq!(timer.enabled().unwrap(), Enabled::OneShot);
+    assert_eq!(timer.accuracy().unwrap(), Duration::from_micros(1));
+
+    assert_eq!(ev.run_loop().unwrap(), 7);
+    assert_eq!(ticks.get(), 3);
+    assert_eq!(timer.enabled().unwrap(), Enabled::Off);
+    assert!(ev.now(Clock::Monotonic).unwrap() >= start + period * 3);
+}
+
+#[test]
+fn dropped_timer_does_not_fire() {
+    let mut ev = Event::new().unwrap();
+    let fired = Rc::new(Cell::new(false));
+    let f = fired.clone();
+    let timer = ev
+        .add_time(
+            Clock::Monotonic,
+            Duration::ZERO,
+            Duration::ZERO,
+            move |_, _| {
+                f.set(true);
+                Ok(())
+            },
+        )
+        .unwrap();
+    drop(timer);
+    assert!(!ev.run(Some(Duration::from_millis(10))).unwrap());
+    assert!(!fired.get());
+}
+
+#[test]
+fn io_source() {
+    use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd};
+    use std::os::unix::net::UnixDatagram;
+
+    let mut ev = Event::new().unwrap();
+    let (a, b) = UnixDatagram::pair().unwrap();
+    let (c, d) = UnixDatagram::pair().unwrap();
+    let received = Rc::new(Cell::new(0));
+
+    let r = received.clone();
+    let callback = move |_: &mut _, fd: BorrowedFd<'_>, revents| {
+        assert_eq!(revents & libc::EPOLLIN as u32, libc::EPOLLIN as u32);
+        let mut buf = [0u8; 16];
+        let n = unsafe { libc::recv(fd.as_raw_fd(), buf.as_mut_ptr() as *mut _, buf.len(), 0) };
+        assert_eq!(&buf[..n as usize], b"ping");
+        r.set(r.get() + 1);
+        Ok(())
+    };
+    // `source` is dropped before `a` and `c` are closed
+    let mut source = unsafe { ev.add_io(a.as_fd(), libc::EPOLLIN as u32, callback) }.unwrap();
+    assert_eq!(source.io_fd().unwrap(), a.as_raw_fd());
+    assert_eq!(source.io_events().unwrap(), libc::EPOLLIN as u32);
+    assert_eq!(source.enabled().unwrap(), Enabled::On);
+
+    assert!(!ev.run(Some(Duration::ZERO)).unwrap());
+    b.send(b"ping").unwrap();
+    assert!(ev.run(Some(Duration::from_secs(1))).unwrap());
+    assert_eq!(received.get(), 1);
+
+    // not waiting for input anymore
+    source.set_io_events(0).unwrap();
+    b.send(b"ping").unwrap();
+    assert!(!ev.run(Some(Duration::from_millis(10))).unwrap());
+    assert_eq!(received.get(), 1);
+
+    source.set_io_events(libc::EPOLLIN as u32).unwrap();
+    assert!(ev.run(Some(Duration::from_secs(1))).unwrap());
+    assert_eq!(received.get(), 2);
+
+    // switched to another socket, which has to live as long as the first one
+    source.set_io_fd(c.as_fd()).unwrap();
+    assert_eq!(source.io_fd().unwrap(), c.as_raw_fd());
+    d.send(b"ping").unwrap();
+    assert!(ev.run(Some(Duration::from_secs(1))).unwrap());
+    assert_eq!(received.get(), 3);
+}
+
+#[test]
+fn owned_io_source() {
+    use std::os::unix::net::UnixDatagram;
+
+    let mut ev = Event::new().unwrap();
+    let (a, b) = UnixDatagram::pair().unwrap();
+    let received = Rc::new(Cell::new(false));
+    let r = received.clone();
+    let source = ev
+        .add_io_owned(a.into(), libc::EPOLLIN as u32, move |_, _, _| {
+            r.set(true);
+            Ok(())
+        })
+        .unwrap();
+
+    b.send(b"ping").unwrap();
+    assert!(ev.run(Some(Duration::from_secs(1))).unwrap());
+    assert!(received.get());
+
+    // the source closed its end of the pair
+    drop(source);
+    assert!(b.send(b"ping").is_err());
+}
+
+#[test]
+fn inotify_source() {
+    use std::path::{Path, PathBuf};
+
+    let dir = std::env::temp_dir().join(format!("rust-systemd-inotify-{}", std::process::id()));
+    std::fs::create_dir(&dir).unwrap();
+
+    let mut ev = Event::new().unwrap();
+    let created: Rc<Cell<Option<PathBuf>>> = Rc::new(Cell::new(None));
+    let c = created.clone();
+    let _source = ev
+        .add_inotify(&dir, libc::IN_CREATE, move |_, e| {
+            assert_eq!(e.mask & libc::IN_CREATE, libc::IN_CREATE);
+            c.set(e.name.map(Path::to_owned));
+            Ok(())
+        })
+        .unwrap();
+
+    std::fs::write(dir.join("new-file"), b"").unwrap();
+    assert!(ev.run(Some(Duration::from_secs(1))).unwrap());
+    assert_eq!(created.take(), Some(PathBuf::from("new-file")));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn source_settings() {
+    let ev = Event::new().unwrap();
+    let mut s = ev
+        .add_time_relative(
+            Clock::Monotonic,
+            Duration::from_secs(60),
+            Duration::ZERO,
+            |_, _| Ok(()),
+        )
+        .unwrap();
+
+    assert_eq!(s.priority().unwrap(), event::PRIORITY_NORMAL);
+    s.set_priority(event::PRIORITY_IDLE).unwrap();
+    assert_eq!(s.priority().unwrap(), event::PRIORITY_IDLE);
+
+    s.set_description("timer").unwrap();
+    assert_eq!(s.description().unwrap().to_bytes(), b"timer");
+
+    #[cfg(feature = "systemd_v248")]
+    {
+        assert_eq!(s.ratelimit().unwrap(), None);
+        s.set_ratelimit(Duration::from_secs(1), 5).unwrap();
+        assert_eq!(s.ratelimit().unwrap(), Some((Duration::from_secs(1), 5)));
+        assert!(!s.is_ratelimited().unwrap());
+    }
+}
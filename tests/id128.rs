@@ -0,0 +1,44 @@
+#![cfg(all(feature = "serde", feature = "libsystemd-sys"))]
+
+use serde::de::value::{BytesDeserializer, Error, SeqDeserializer};
+use serde::de::IntoDeserializer;
+use serde::Deserialize;
+use systemd::id128::Id128;
+
+const BYTES: [u8; 16] = [
+    0x2e, 0x48, 0x5d, 0x8e, 0x0c, 0x11, 0x4c, 0x0b, 0x99, 0x8b, 0xb7, 0x35, 0x4d, 0x40, 0x9a, 0x16,
+];
+
+#[test]
+fn deserialize() {
+    let from_str = |s: &str| Id128::deserialize(IntoDeserializer::<Error>::into_deserializer(s));
+    let id = from_str("2e485d8e0c114c0b998bb7354d409a16").unwrap();
+    assert_eq!(id.as_bytes(), &BYTES);
+    assert_eq!(id.to_string(), "2e485d8e0c114c0b998bb7354d409a16");
+    assert_eq!(
+        from_str("2e485d8e-0c11-4c0b-998b-b7354d409a16").unwrap(),
+        id
+    );
+    assert!(from_str("2e485d8e0c114c0b998bb7354d409a1").is_err());
+    assert!(from_str("2e485d8e0c114c0b998bb7354d409a1g").is_err());
+    assert!(from_str("2e485d8e-0c11-4c0b-998b-b7354d409a-6").is_err());
+
+    let id = Id128::deserialize(BytesDeserializer::<Error>::new(&BYTES)).unwrap();
+    assert_eq!(id.as_bytes(), &BYTES);
+    assert!(Id128::deserialize(BytesDeserializer::<Error>::new(&BYTES[1..])).is_err());
+
+    let id = Id128::deserialize(SeqDeserializer::<_, Error>::new(BYTES.iter().copied())).unwrap();
+    assert_eq!(id.as_bytes(), &BYTES);
+}
+
+#[test]
+fn round_trip() {
+    use serde_test::{assert_tokens, Configure, Token};
+
+    let id = Id128::deserialize(BytesDeserializer::<Error>::new(&BYTES)).unwrap();
+    assert_tokens(
+        &id.readable(),
+        &[Token::Str("2e485d8e0c114c0b998bb7354d409a16")],
+    );
+    assert_tokens(&id.compact(), &[Token::Bytes(&BYTES)]);
+}
@@ -0,0 +1,50 @@
+#![cfg(feature = "daemon")]
+
+extern crate systemd;
+
+use std::env;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixDatagram;
+use std::time::Duration;
+use systemd::daemon::IdleExit;
+
+fn recv(sock: &UnixDatagram) -> String {
+    let mut buf = [0u8; 256];
+    let n = sock.recv(&mut buf).unwrap();
+    String::from_utf8_lossy(&buf[..n]).into_owned()
+}
+
+// This lives in its own test binary as it changes the environment of the process.
+#[test]
+fn idle_exit() {
+    let (conn, _peer) = UnixDatagram::pair().unwrap();
+
+    let mut idle = IdleExit::new(Duration::from_millis(50));
+    assert!(!idle.is_idle());
+    idle.begin();
+    assert_eq!(idle.remaining(), None);
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(!idle.is_idle());
+    idle.end();
+    assert!(idle.remaining().unwrap() > Duration::from_millis(0));
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(idle.is_idle());
+
+    // not running under systemd, the stored descriptor would be lost
+    env::remove_var("NOTIFY_SOCKET");
+    idle.store_fd(conn.as_raw_fd(), "conn");
+    assert!(idle.stop().is_err());
+    idle.forget_fd(conn.as_raw_fd());
+    idle.stop().unwrap();
+
+    let path = env::temp_dir().join(format!("rust-systemd-idle-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let notify = UnixDatagram::bind(&path).unwrap();
+    env::set_var("NOTIFY_SOCKET", &path);
+    idle.store_fd(conn.as_raw_fd(), "conn");
+    idle.stop().unwrap();
+    assert_eq!(recv(&notify), "FDSTORE=1\nFDNAME=conn");
+    assert_eq!(recv(&notify), "STOPPING=1");
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -4,10 +4,26 @@
 use log::log;
 use systemd::sd_journal_log;
 
+use std::io::Read;
 use std::path::Path;
 use systemd::id128;
 use systemd::journal;
 
+/// Call `done` until it returns `true`, waiting for changes to the journal in between, as entries
+/// may not be visible right away. Returns `false` if that didn't happen within about 2 seconds.
+fn wait_for(
+    j: &mut journal::JournalRef,
+    mut done: impl FnMut(&mut journal::JournalRef) -> bool,
+) -> bool {
+    for _ in 0..20 {
+        if done(j) {
+            return true;
+        }
+        j.wait(Some(std::time::Duration::from_millis(100))).unwrap();
+    }
+    false
+}
+
 // Some systems don't have a running journal, which causes our tests to fail currently
 //
 // TODO: adjust tests that use this to generate a fixed journal if possible, or ship some test
@@ -164,6 +180,121 @@ fn get_data() {
     }
 }
 
+#[test]
+fn message_stream() {
+    if !have_journal() {
+        return;
+    }
+
+    let mut j = journal::OpenOptions::default().open().unwrap();
+    j.seek_tail().unwrap();
+    j.previous().unwrap();
+    journal::send(&["MESSAGE=first", "RUST_TEST_STREAM=1"]);
+    journal::send(&["RUST_TEST_STREAM=1"]);
+    journal::send(&["MESSAGE=second\nline", "RUST_TEST_STREAM=1"]);
+    j.match_add("RUST_TEST_STREAM", "1").unwrap();
+
+    let mut out = Vec::new();
+    wait_for(&mut j, |j| {
+        j.message_stream().read_to_end(&mut out).unwrap();
+        out.ends_with(b"second\nline\n")
+    });
+    assert!(out.ends_with(b"first\nsecond\nline\n"));
+}
+
+#[test]
+fn tail() {
+    if !have_journal() {
+        return;
+    }
+
+    let tag = format!("{}", std::process::id());
+    for m in &["a", "b", "c"] {
+        journal::send(&[
+            &format!("MESSAGE={}", m),
+            &format!("RUST_TEST_TAIL={}", tag),
+        ]);
+    }
+    let mut j = journal::OpenOptions::default().open().unwrap();
+    j.match_add("RUST_TEST_TAIL", tag).unwrap();
+
+    wait_for(&mut j, |j| {
+        j.seek_head().unwrap();
+        j.next_skip(3).unwrap() == 3
+    });
+
+    let messages: Vec<_> = journal::tail(&mut j, 2)
+        .unwrap()
+        .take(2)
+        .map(|r| r.unwrap().get("MESSAGE").cloned())
+        .collect();
+    assert_eq!(messages, [Some("b".to_string()), Some("c".to_string())]);
+}
+
+#[test]
+fn page() {
+    if !have_journal() {
+        return;
+    }
+
+    let tag = format!("{}", std::process::id());
+    for m in &["a", "b", "c", "d", "e"] {
+        journal::send(&[
+            &format!("MESSAGE={}", m),
+            &format!("RUST_TEST_PAGE={}", tag),
+        ]);
+    }
+    let mut j = journal::OpenOptions::default().open().unwrap();
+    j.match_add("RUST_TEST_PAGE", tag).unwrap();
+
+    wait_for(&mut j, |j| {
+        j.seek_head().unwrap();
+        j.next_skip(5).unwrap() == 5
+    });
+
+    fn messages(entries: &[journal::JournalRecord]) -> String {
+        entries.iter().map(|r| r["MESSAGE"].as_str()).collect()
+    }
+
+    let (p, token) = journal::page(&mut j, None, 2, journal::Direction::Forward).unwrap();
+    assert_eq!(messages(&p), "ab");
+    let (p, token) =
+        journal::page(&mut j, token.as_deref(), 2, journal::Direction::Forward).unwrap();
+    assert_eq!(messages(&p), "cd");
+    let (p, end) = journal::page(&mut j, token.as_deref(), 2, journal::Direction::Forward).unwrap();
+    assert_eq!(messages(&p), "e");
+    let (p, same) = journal::page(&mut j, end.as_deref(), 2, journal::Direction::Forward).unwrap();
+    assert_eq!(messages(&p), "");
+    assert_eq!(same, end);
+
+    let (p, _) = journal::page(&mut j, token.as_deref(), 10, journal::Direction::Backward).unwrap();
+    assert_eq!(messages(&p), "cba");
+    let (p, _) = journal::page(&mut j, None, 2, journal::Direction::Backward).unwrap();
+    assert_eq!(messages(&p), "ed");
+}
+
+#[test]
+fn get_data_full() {
+    if !have_journal() {
+        return;
+    }
+
+    let mut j = journal::OpenOptions::default().open().unwrap();
+    j.seek_tail().unwrap();
+    j.previous().unwrap();
+    let long = "x".repeat(4096);
+    journal::send(&[&format!("MESSAGE={}", long), "RUST_TEST_FULL=1"]);
+    j.match_add("RUST_TEST_FULL", "1").unwrap();
+    j.set_data_threshold(16).unwrap();
+
+    wait_for(&mut j, |j| j.next().unwrap() != 0);
+    assert!(j.get_data("MESSAGE").unwrap().unwrap().maybe_truncated());
+    let full = j.get_data_full("MESSAGE").unwrap().unwrap();
+    assert!(!full.maybe_truncated());
+    assert_eq!(full.value(), Some(long.as_bytes()));
+    assert_eq!(j.data_threshold().unwrap(), 16);
+}
+
 #[test]
 fn journal_entry_data_1() {
     let jrd: journal::JournalEntryField<'_> = b"HI=foo"[..].into();
@@ -171,4 +302,721 @@ fn journal_entry_data_1() {
     assert_eq!(jrd.data(), &b"HI=foo"[..]);
     assert_eq!(jrd.name(), &b"HI"[..]);
     assert_eq!(jrd.value(), Some(&b"foo"[..]));
+    assert!(!jrd.maybe_truncated());
+}
+
+#[test]
+fn apply_filter() {
+    if !have_journal() {
+        return;
+    }
+    let key = "RUST_TEST_MARKER";
+    let value = "RUST_SYSTEMD_APPLY_FILTER";
+    let mut j = journal::OpenOptions::default().open().unwrap();
+    let mut filter = journal::Filter::new();
+    filter.match_add(key, value);
+
+    j.seek(journal::JournalSeek::Tail).unwrap();
+    journal::send(&[
+        &format!("{}={}", key, value),
+        "MESSAGE=rust-systemd apply_filter",
+    ]);
+    j.apply_filter(&filter).unwrap();
+    assert!(
+        wait_for(&mut j, |j| j.next().unwrap() != 0),
+        "got to end of journal without finding our entry"
+    );
+    let entryval = j.get_data(key).unwrap().unwrap();
+    assert_eq!(entryval.value().unwrap(), value.as_bytes());
+}
+
+#[test]
+fn try_send_validation() {
+    for bad in &[
+        "MESSAGE",
+        "=value",
+        "_PID=1",
+        "1FIELD=x",
+        "lower=x",
+        "MESS\nAGE=x",
+        "A23456789012345678901234567890123456789012345678901234567890123456=x",
+    ] {
+        let e = journal::try_send(&["MESSAGE=rust-systemd try_send", bad]).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput, "{:?}", bad);
+    }
+
+    if !have_journal() {
+        return;
+    }
+    journal::try_send(&["MESSAGE=rust-systemd\ntry_send", "RUST_TEST_MARKER=a=b"]).unwrap();
+}
+
+#[test]
+fn native_writer_encoding() {
+    let dir = std::env::temp_dir().join(format!("rust-systemd-native-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("socket");
+    let _ = std::fs::remove_file(&path);
+    let server = std::os::unix::net::UnixDatagram::bind(&path).unwrap();
+
+    let w = journal::native::Writer::with_path(&path).unwrap();
+    w.send(&["MESSAGE=a=b", "MULTI=x\ny"]).unwrap();
+    let mut buf = [0u8; 128];
+    let n = server.recv(&mut buf).unwrap();
+    assert_eq!(
+        &buf[..n],
+        &b"MESSAGE=a=b\nMULTI\n\x03\x00\x00\x00\x00\x00\x00\x00x\ny\n"[..]
+    );
+
+    w.send_fields(vec![("BIN", &b"\0\x01"[..])]).unwrap();
+    let n = server.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..n], &b"BIN=\0\x01\n"[..]);
+
+    assert!(w.send(&["bad=x"]).is_err());
+    assert!(w.send_fields(vec![("_PID", &b"1"[..])]).is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn tee_log_writer() {
+    use log::Log;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buf(Arc<Mutex<Vec<u8>>>);
+    impl std::io::Write for Buf {
+        fn write(&mut self, b: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(b)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let buf = Buf::default();
+    let l = journal::TeeLog::new(buf.clone())
+        .journal_level(log::LevelFilter::Off)
+        .writer_level(log::LevelFilter::Info);
+    for (level, msg) in &[(log::Level::Warn, "shown"), (log::Level::Debug, "hidden")] {
+        l.log(
+            &log::Record::builder()
+                .args(format_args!("{}", msg))
+                .level(*level)
+                .target("tee")
+                .build(),
+        );
+    }
+    assert!(!l.enabled(&log::Metadata::builder().level(log::Level::Debug).build()));
+    assert_eq!(&*buf.0.lock().unwrap(), b"WARN tee: shown\n");
+}
+
+#[test]
+fn field_name() {
+    use journal::FieldName;
+
+    let n = FieldName::new("MESSAGE_ID").unwrap();
+    assert_eq!(&*n, "MESSAGE_ID");
+    assert!(!n.is_trusted());
+    for bad in &["", "message", "1ABC", "_PID", "A-B", &"A".repeat(65)] {
+        let e = FieldName::new(bad).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+    }
+    assert!(FieldName::new(&"A".repeat(64)).is_ok());
+
+    let t = FieldName::trusted("_SYSTEMD_UNIT").unwrap();
+    assert!(t.is_trusted());
+    assert!(FieldName::trusted("__CURSOR").is_err());
+    assert!(FieldName::trusted("_1").is_err());
+    assert_eq!("_PID".parse::<FieldName>().unwrap().as_str(), "_PID");
+}
+
+#[test]
+fn entry_source() {
+    let dir = std::env::temp_dir().join(format!(
+        "rust-systemd-source-{}/0123456789abcdef0123456789abcdef.ns",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut header = b"LPKSHHRH".to_vec();
+    header.resize(40, 0);
+    header.extend((0..16).map(|b| b * 0x11));
+    header.resize(256, 0);
+    std::fs::write(dir.join("system.journal"), &header).unwrap();
+    std::fs::write(dir.join("notes.txt"), b"not a journal").unwrap();
+
+    let id = journal::file_machine_id(dir.join("system.journal")).unwrap();
+    assert_eq!(id.to_string(), "00112233445566778899aabbccddeeff");
+    assert!(journal::file_machine_id(dir.join("notes.txt")).is_err());
+
+    let source = journal::EntrySource::from_directory(&dir)
+        .unwrap()
+        .hostname("web1");
+    assert_eq!(source.namespace.as_deref(), Some("ns"));
+    assert_eq!(source.machine_id, Some(id));
+
+    let mut r = journal::JournalRecord::new();
+    r.insert("_HOSTNAME".into(), "localhost".into());
+    source.annotate(&mut r);
+    assert_eq!(r["_HOSTNAME"], "localhost");
+    assert_eq!(r["__SOURCE_HOSTNAME"], "web1");
+    assert_eq!(r["__SOURCE_NAMESPACE"], "ns");
+    assert_eq!(r["__SOURCE_MACHINE_ID"], id.to_string());
+    assert_eq!(r["__SOURCE_DIRECTORY"], dir.to_string_lossy());
+
+    std::fs::remove_dir_all(dir.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn usage_by_unit() {
+    if !have_journal() {
+        return;
+    }
+
+    let tag = format!("usage-{}", std::process::id());
+    let since = std::time::SystemTime::now() - std::time::Duration::from_secs(1);
+    journal::send(&["MESSAGE=0123456789", &format!("RUST_TEST_USAGE={}", tag)]);
+    let mut j = journal::OpenOptions::default().open().unwrap();
+    j.match_add("RUST_TEST_USAGE", tag.clone()).unwrap();
+
+    let mut usage = Default::default();
+    wait_for(&mut j, |j| {
+        usage = journal::usage_by_unit(j, since).unwrap();
+        !usage.is_empty()
+    });
+    let total: u64 = usage.values().map(|u| u.entries).sum();
+    assert_eq!(total, 1);
+    let bytes: u64 = usage.values().map(|u| u.bytes).sum();
+    assert!(bytes as usize >= "MESSAGE=0123456789".len() + "RUST_TEST_USAGE=".len() + tag.len());
+}
+
+#[test]
+fn field_histogram() {
+    if !have_journal() {
+        return;
+    }
+
+    let field = format!("RUST_TEST_HISTOGRAM_{}", std::process::id());
+    let since = std::time::SystemTime::now() - std::time::Duration::from_secs(1);
+    for v in &["a", "b", "a"] {
+        journal::send(&["MESSAGE=histogram", &format!("{}={}", field, v)]);
+    }
+    let mut j = journal::OpenOptions::default().open().unwrap();
+
+    let mut histogram = Vec::new();
+    wait_for(&mut j, |j| {
+        histogram = journal::stats::field_histogram(j, &field, since).unwrap();
+        histogram.iter().map(|(_, c)| c).sum::<u64>() == 3
+    });
+    assert_eq!(histogram, vec![("a".to_owned(), 2), ("b".to_owned(), 1)]);
+}
+
+#[test]
+fn snapshot_changes() {
+    use journal::snapshot::Snapshot;
+
+    let dir = std::env::temp_dir().join(format!("rust-systemd-snapshot-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut s = Snapshot::open(&dir).unwrap();
+    assert_eq!(s.files().count(), 0);
+    assert!(s.next_entry().unwrap().is_none());
+    assert!(s.changes().unwrap().is_empty());
+
+    std::fs::write(dir.join("system.journal"), b"").unwrap();
+    std::fs::write(dir.join("notes.txt"), b"").unwrap();
+    let changes = s.changes().unwrap();
+    assert_eq!(changes.added, vec![dir.join("system.journal")]);
+    assert!(changes.removed.is_empty());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn snapshot_matches() {
+    use journal::snapshot::Snapshot;
+
+    let dir = match ["/var/log/journal", "/run/log/journal"]
+        .iter()
+        .find(|d| Path::new(d).exists())
+    {
+        Some(d) if have_journal() => *d,
+        _ => return,
+    };
+
+    // wait until an entry tagged `marker` is visible in the journal
+    let tag = format!("snapshot-{}", std::process::id());
+    let send = |marker: &str| {
+        journal::send(&[
+            &format!("MESSAGE={}", marker),
+            &format!("RUST_TEST_SNAPSHOT={}", tag),
+            &format!("RUST_TEST_SNAPSHOT_MARKER={}", marker),
+        ]);
+        let mut j = journal::OpenOptions::default().open().unwrap();
+        j.match_add("RUST_TEST_SNAPSHOT_MARKER", marker).unwrap();
+        assert!(
+            wait_for(&mut j, |j| j.next_entry().unwrap().is_some()),
+            "entry {} never showed up",
+            marker
+        );
+    };
+
+    send("before");
+    let mut s = match Snapshot::open(dir) {
+        Ok(s) => s,
+        // not allowed to read the system journal
+        Err(_) => return,
+    };
+    send("after");
+
+    // the last entry of the snapshot (whichever it is) doesn't match, so the end is found from
+    // the entry written after it
+    s.journal()
+        .match_add("RUST_TEST_SNAPSHOT", tag.clone())
+        .unwrap();
+    let mut markers = Vec::new();
+    while let Some(r) = s.next_entry().unwrap() {
+        markers.push(r["RUST_TEST_SNAPSHOT_MARKER"].clone());
+    }
+    assert_eq!(markers, vec!["before".to_owned()]);
+}
+
+#[test]
+fn scan_with_progress() {
+    if !have_journal() {
+        return;
+    }
+
+    let tag = format!("{}", std::process::id());
+    for m in &["a", "b", "c"] {
+        journal::send(&[
+            &format!("MESSAGE={}", m),
+            &format!("RUST_TEST_SCAN={}", tag),
+        ]);
+    }
+    let mut filter = journal::Filter::new();
+    filter.match_add("RUST_TEST_SCAN", tag.as_str());
+    let mut j = journal::OpenOptions::default().open().unwrap();
+
+    let mut messages = String::new();
+    let mut progress = Vec::new();
+    wait_for(&mut j, |j| {
+        messages.clear();
+        progress.clear();
+        let n = journal::scan_with_progress(
+            j,
+            &filter,
+            |r| {
+                messages.push_str(&r["MESSAGE"]);
+                Ok(())
+            },
+            |p| progress.push(p),
+        )
+        .unwrap();
+        n == 3
+    });
+
+    assert_eq!(messages, "abc");
+    let last = progress.last().unwrap();
+    assert_eq!((last.entries, last.fraction), (3, 1.0));
+    assert!(progress.windows(2).all(|w| w[0].fraction <= w[1].fraction));
+}
+
+#[test]
+fn merge() {
+    if !have_journal() {
+        return;
+    }
+
+    let mut a = journal::OpenOptions::default().open().unwrap();
+    let mut b = journal::OpenOptions::default().open().unwrap();
+    let merged: Vec<_> = journal::merge(vec![&mut *a, &mut *b], journal::Direction::Backward)
+        .unwrap()
+        .take(20)
+        .map(Result::unwrap)
+        .collect();
+
+    // every entry is in both journals, the first one listed is returned first
+    let (newest, _) =
+        journal::page(&mut a, None, merged.len() / 2, journal::Direction::Backward).unwrap();
+    assert_eq!(merged.len(), newest.len() * 2);
+    for (pair, expected) in merged.chunks(2).zip(&newest) {
+        assert_eq!(pair[0], (0, expected.clone()));
+        assert_eq!(pair[1], (1, expected.clone()));
+    }
+}
+
+#[cfg(feature = "event")]
+#[test]
+fn add_to_event() {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use systemd::event::Event;
+
+    if !have_journal() {
+        return;
+    }
+
+    let mut ev = Event::new().unwrap();
+    let j = journal::OpenOptions::default().open().unwrap();
+    let seen = Rc::new(Cell::new(false));
+    let s = seen.clone();
+    let _source = journal::add_to_event(&ev, j, move |r| {
+        if r.get("RUST_TEST_MARKER").map(String::as_str) == Some("add_to_event") {
+            s.set(true);
+        }
+        Ok(())
+    })
+    .unwrap();
+
+    journal::send(&[
+        "RUST_TEST_MARKER=add_to_event",
+        "MESSAGE=rust-systemd add_to_event",
+    ]);
+    for _ in 0..50 {
+        if seen.get() {
+            break;
+        }
+        ev.run(Some(std::time::Duration::from_millis(100))).unwrap();
+    }
+    assert!(seen.get());
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn journal_stream() {
+    use futures_core::Stream;
+    use std::future::poll_fn;
+    use std::pin::Pin;
+
+    if !have_journal() {
+        return;
+    }
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    rt.block_on(async {
+        let j = journal::OpenOptions::default().open().unwrap();
+        let mut stream = journal::JournalStream::tail(j).unwrap();
+        journal::send(&[
+            "RUST_TEST_MARKER=journal_stream",
+            "MESSAGE=rust-systemd journal_stream",
+        ]);
+        let found = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            while let Some(r) = poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await {
+                let r = r.unwrap();
+                if r.get("RUST_TEST_MARKER").map(String::as_str) == Some("journal_stream") {
+                    return true;
+                }
+            }
+            false
+        })
+        .await;
+        assert_eq!(found, Ok(true));
+    });
+}
+
+#[cfg(feature = "otel")]
+#[test]
+fn otel_record() {
+    use opentelemetry::logs::{AnyValue, LogRecord, Severity};
+    use opentelemetry::{Key, SpanId, TraceFlags, TraceId};
+    use std::borrow::Cow;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use systemd::journal::otel::Converter;
+
+    #[derive(Default)]
+    struct Record {
+        timestamp: Option<SystemTime>,
+        observed: Option<SystemTime>,
+        severity: Option<(Severity, &'static str)>,
+        body: Option<AnyValue>,
+        attributes: Vec<(Key, AnyValue)>,
+        trace: Option<(TraceId, SpanId, Option<TraceFlags>)>,
+    }
+
+    impl LogRecord for Record {
+        fn set_event_name(&mut self, _name: &'static str) {}
+        fn set_target<T: Into<Cow<'static, str>>>(&mut self, _target: T) {}
+        fn set_timestamp(&mut self, timestamp: SystemTime) {
+            self.timestamp = Some(timestamp);
+        }
+        fn set_observed_timestamp(&mut self, timestamp: SystemTime) {
+            self.observed = Some(timestamp);
+        }
+        fn set_severity_text(&mut self, text: &'static str) {
+            self.severity = self.severity.map(|(n, _)| (n, text));
+        }
+        fn set_severity_number(&mut self, number: Severity) {
+            self.severity = Some((number, ""));
+        }
+        fn set_body(&mut self, body: AnyValue) {
+            self.body = Some(body);
+        }
+        fn add_attributes<I, K, V>(&mut self, attributes: I)
+        where
+            I: IntoIterator<Item = (K, V)>,
+            K: Into<Key>,
+            V: Into<AnyValue>,
+        {
+            for (k, v) in attributes {
+                self.add_attribute(k, v);
+            }
+        }
+        fn add_attribute<K: Into<Key>, V: Into<AnyValue>>(&mut self, key: K, value: V) {
+            self.attributes.push((key.into(), value.into()));
+        }
+        fn set_trace_context(
+            &mut self,
+            trace_id: TraceId,
+            span_id: SpanId,
+            trace_flags: Option<TraceFlags>,
+        ) {
+            self.trace = Some((trace_id, span_id, trace_flags));
+        }
+    }
+
+    let entry: journal::JournalRecord = [
+        ("PRIORITY", "4"),
+        ("MESSAGE", "rust-systemd otel_record"),
+        ("_SOURCE_REALTIME_TIMESTAMP", "1000000"),
+        ("__REALTIME_TIMESTAMP", "2000000"),
+        ("__CURSOR", "s=0"),
+        ("TRACE_ID", "4bf92f3577b34da6a3ce929d0e0e4736"),
+        ("SPAN_ID", "00f067aa0ba902b7"),
+        ("SYSLOG_IDENTIFIER", "rust-systemd"),
+    ]
+    .iter()
+    .map(|&(k, v)| (k.to_owned(), v.to_owned()))
+    .collect();
+
+    let mut record = Record::default();
+    Converter::new().fill(&entry, &mut record);
+    assert_eq!(record.severity, Some((Severity::Warn, "warning")));
+    assert_eq!(record.timestamp, Some(UNIX_EPOCH + Duration::from_secs(1)));
+    assert_eq!(record.observed, Some(UNIX_EPOCH + Duration::from_secs(2)));
+    assert_eq!(
+        record.body,
+        Some(AnyValue::from("rust-systemd otel_record".to_owned()))
+    );
+    assert!(record.trace.is_none());
+    // Without trace fields configured, they're plain attributes.
+    assert_eq!(record.attributes.len(), 3);
+
+    let mut record = Record::default();
+    Converter::new()
+        .trace_fields("TRACE_ID", "SPAN_ID")
+        .fill(&entry, &mut record);
+    assert_eq!(
+        record.trace,
+        Some((
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap(),
+            None
+        ))
+    );
+    assert_eq!(
+        record.attributes,
+        vec![(
+            Key::from("SYSLOG_IDENTIFIER"),
+            AnyValue::from("rust-systemd".to_owned())
+        )]
+    );
+}
+
+#[test]
+fn spawn_reader() {
+    if !have_journal() {
+        return;
+    }
+
+    journal::send(&[
+        "RUST_TEST_MARKER=spawn_reader",
+        "MESSAGE=rust-systemd spawn_reader",
+    ]);
+    let mut f = journal::Filter::new();
+    f.match_add("RUST_TEST_MARKER", "spawn_reader");
+    for _ in 0..50 {
+        let (reader, entries) =
+            journal::spawn_reader(journal::OpenOptions::default(), f.clone(), false, 4);
+        let found = entries.iter().count();
+        reader.join().unwrap().unwrap();
+        if found > 0 {
+            return;
+        }
+        // the entry may not have been written yet
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    panic!("entry not found");
+}
+
+#[test]
+fn kernel_entry() {
+    use journal::{Facility, KernelEntry, Priority};
+
+    let mut r = journal::JournalRecord::new();
+    r.insert(
+        "MESSAGE".into(),
+        "sd 0:0:0:0: [sda] Attached SCSI disk".into(),
+    );
+    r.insert("PRIORITY".into(), "5".into());
+    r.insert("SYSLOG_FACILITY".into(), "0".into());
+    assert_eq!(KernelEntry::from_record(&r), None);
+
+    r.insert("_TRANSPORT".into(), "kernel".into());
+    r.insert("_KERNEL_DEVICE".into(), "b8:0".into());
+    r.insert("_KERNEL_SUBSYSTEM".into(), "block".into());
+    assert_eq!(
+        KernelEntry::from_record(&r),
+        Some(KernelEntry {
+            message: "sd 0:0:0:0: [sda] Attached SCSI disk".into(),
+            priority: Some(Priority::Notice),
+            facility: Some(Facility::Kernel),
+            device: Some("b8:0".into()),
+            subsystem: Some("block".into()),
+        })
+    );
+
+    assert_eq!(Priority::from_field("8"), None);
+    assert!(Priority::Error < Priority::Warning);
+    assert_eq!(Facility::from_field("13"), Some(Facility::Reserved(13)));
+    assert_eq!(Facility::from_field("23"), Some(Facility::Local(7)));
+    assert_eq!(Facility::from_field("24"), None);
+}
+
+#[test]
+fn journal_entry() {
+    use journal::{Facility, JournalEntry, Transport};
+
+    let mut r = journal::JournalRecord::new();
+    assert_eq!(r.transport(), None);
+    assert_eq!(r.facility(), None);
+    assert_eq!(r.identifier(), None);
+
+    r.insert("_TRANSPORT".into(), "stdout".into());
+    r.insert("SYSLOG_FACILITY".into(), "3".into());
+    r.insert("SYSLOG_IDENTIFIER".into(), "sshd".into());
+    assert_eq!(r.transport(), Some(Transport::Stdout));
+    assert_eq!(r.facility(), Some(Facility::Daemon));
+    assert_eq!(r.identifier(), Some("sshd"));
+
+    r.insert("_TRANSPORT".into(), "carrier-pigeon".into());
+    assert_eq!(r.transport(), None);
+    for t in &[
+        Transport::Journal,
+        Transport::Stdout,
+        Transport::Syslog,
+        Transport::Kernel,
+        Transport::Audit,
+        Transport::Driver,
+    ] {
+        assert_eq!(Transport::from_field(t.as_str()), Some(*t));
+    }
+}
+
+#[test]
+fn fields() {
+    use journal::fields;
+
+    if !have_journal() {
+        return;
+    }
+
+    let tag = format!("{}", std::process::id());
+    journal::send(&[
+        "MESSAGE=fields",
+        "PRIORITY=5",
+        &format!("RUST_TEST_FIELDS={}", tag),
+    ]);
+    let mut j = journal::OpenOptions::default().open().unwrap();
+    j.match_add("RUST_TEST_FIELDS", tag).unwrap();
+    let mut r = None;
+    assert!(wait_for(&mut j, |j| {
+        j.seek_head().unwrap();
+        r = j.next_entry().unwrap();
+        r.is_some()
+    }));
+
+    // the trusted fields are added by journald under the names of the constants
+    let r = r.unwrap();
+    assert_eq!(r.get(fields::MESSAGE).unwrap(), "fields");
+    assert_eq!(r.get(fields::PRIORITY).unwrap(), "5");
+    assert_eq!(
+        r.get(fields::_PID).unwrap(),
+        &std::process::id().to_string()
+    );
+    assert_eq!(r.get(fields::_TRANSPORT).unwrap(), "journal");
+    assert_eq!(
+        r.get(fields::_BOOT_ID).unwrap(),
+        &id128::Id128::from_boot().unwrap().to_string()
+    );
+}
+
+#[test]
+fn user_journal() {
+    // no user this high has any journal files
+    let uid = u32::MAX - 1;
+    assert!(journal::user_journal_files(uid).unwrap().is_empty());
+    let e = journal::OpenOptions::default()
+        .user(uid)
+        .open()
+        .err()
+        .unwrap();
+    assert_eq!(e.kind(), std::io::ErrorKind::NotFound);
+
+    if !have_journal() {
+        return;
+    }
+    let uid = unsafe { libc::getuid() };
+    for f in journal::user_journal_files(uid).unwrap() {
+        let name = f.file_name().unwrap().to_str().unwrap();
+        assert!(name.starts_with(&format!("user-{}", uid)));
+    }
+}
+
+#[test]
+fn current_boot() {
+    let mut r = journal::JournalRecord::new();
+    assert!(!journal::is_current_boot(&r).unwrap());
+    r.insert(
+        "_BOOT_ID".into(),
+        id128::Id128::from_boot().unwrap().to_string(),
+    );
+    assert!(journal::is_current_boot(&r).unwrap());
+    r.insert("_BOOT_ID".into(), id128::Id128::default().to_string());
+    assert!(!journal::is_current_boot(&r).unwrap());
+
+    if !have_journal() {
+        return;
+    }
+    let mut j = journal::OpenOptions::default()
+        .current_boot(true)
+        .open()
+        .unwrap();
+    if let Some(record) = j.next_entry().unwrap() {
+        assert!(journal::is_current_boot(&record).unwrap());
+        assert!(j.is_current_boot().unwrap());
+    }
+}
+
+#[test]
+fn resync() {
+    if !have_journal() {
+        return;
+    }
+    let mut j = journal::OpenOptions::default().open().unwrap();
+    let (first, second) = match (j.next_entry().unwrap(), j.next_entry().unwrap()) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return,
+    };
+    j.seek_head().unwrap();
+    assert_eq!(j.next_entry().unwrap(), Some(first));
+    let cursor = j.cursor().unwrap();
+
+    // from elsewhere, return to the first entry, continuing after it
+    j.seek_tail().unwrap();
+    j.resync(&cursor[..]).unwrap();
+    assert_eq!(j.next_entry().unwrap(), Some(second));
 }
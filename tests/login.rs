@@ -1,3 +1,5 @@
+#![cfg(all(feature = "login", feature = "daemon"))]
+
 extern crate systemd;
 
 use systemd::daemon::booted;
@@ -114,3 +116,35 @@ fn test_get_owner_uid() {
         false => {}
     };
 }
+
+#[test]
+fn test_user_paths() {
+    assert_eq!(
+        login::user_runtime_dir(1000),
+        std::path::Path::new("/run/user/1000")
+    );
+    assert_eq!(login::user_bus_address(0), "unix:path=/run/user/0/bus");
+}
+
+#[test]
+fn test_get_users() {
+    assert_eq!(login::user_name(0).unwrap().as_deref(), Some("root"));
+    assert_eq!(login::user_name(u32::MAX - 1).unwrap(), None);
+
+    if !booted().unwrap() {
+        return;
+    }
+    let uids = login::get_uids().unwrap();
+    let users = login::get_users(false).unwrap();
+    assert!(users.iter().all(|(_, name)| name.is_none()));
+    for (uid, name) in login::get_users(true).unwrap() {
+        assert!(uids.contains(&uid));
+        assert_eq!(name, login::user_name(uid).unwrap());
+    }
+}
+
+#[test]
+fn test_session_watcher_unknown_session() {
+    assert!(login::session_is_active("no-such-session").is_err());
+    assert!(login::SessionWatcher::new("no-such-session").is_err());
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "daemon")]
+
+extern crate systemd;
+
+use std::fs;
+use std::time::Duration;
+use systemd::reload;
+
+#[test]
+fn watch_dir() {
+    let dir = std::env::temp_dir().join(format!("rust-systemd-reload-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    let mut w = reload::Watcher::new().unwrap();
+    w.watch(&dir).unwrap();
+    assert!(!w.wait(Some(Duration::from_millis(0))).unwrap());
+
+    fs::write(dir.join("a.conf"), "x").unwrap();
+    assert!(w.wait(Some(Duration::from_secs(5))).unwrap());
+    assert!(!w.wait(Some(Duration::from_millis(0))).unwrap());
+
+    // removing and recreating the watched directory is noticed, and it is watched again
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(w.wait(Some(Duration::from_secs(5))).unwrap());
+    fs::create_dir(&dir).unwrap();
+    assert!(w.wait(Some(Duration::from_secs(5))).unwrap());
+    fs::write(dir.join("b.conf"), "x").unwrap();
+    assert!(w.wait(Some(Duration::from_secs(5))).unwrap());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn reload_result() {
+    assert_eq!(reload::reload(|| Ok(4)).unwrap(), 4);
+    assert!(reload::reload(|| -> std::io::Result<()> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "bad config",
+        ))
+    })
+    .is_err());
+}
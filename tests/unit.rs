@@ -15,3 +15,44 @@ fn escape_name() {
         assert_eq!(systemd::unit::escape_name(input), expected);
     }
 }
+
+#[test]
+fn unescape_name() {
+    for s in &["test", "/foo/", ".foo", "Hallöchen, Meister"] {
+        let escaped = systemd::unit::escape_name(s);
+        assert_eq!(systemd::unit::unescape_name(&escaped).as_deref(), Some(*s));
+    }
+    assert_eq!(systemd::unit::unescape_name("a\\x2"), None);
+    assert_eq!(systemd::unit::unescape_name("a\\y20"), None);
+    assert_eq!(systemd::unit::unescape_name("\\xff"), None);
+}
+
+#[cfg(feature = "libsystemd-sys")]
+#[test]
+fn expand_specifiers() {
+    use systemd::unit::{expand_specifiers, SpecifierContext};
+
+    let machine_id = systemd::id128::Id128::from_cstr(
+        std::ffi::CStr::from_bytes_with_nul(b"2e485d8e0c114c0b998bb7354d409a16\0").unwrap(),
+    )
+    .unwrap();
+    let context = SpecifierContext {
+        user: Some("alice"),
+        home: Some("/home/alice"),
+        machine_id: Some(machine_id),
+        host: Some("box"),
+        ..SpecifierContext::new("backup@-srv-data.service")
+    };
+    assert_eq!(
+        expand_specifiers("%n %i %I %u %h %m %H 100%%", &context).unwrap(),
+        "backup@-srv-data.service -srv-data /srv/data alice /home/alice \
+         2e485d8e0c114c0b998bb7354d409a16 box 100%"
+    );
+    assert_eq!(
+        expand_specifiers("[%i]", &SpecifierContext::new("foo.service")).unwrap(),
+        "[]"
+    );
+    assert!(expand_specifiers("%b", &context).is_err());
+    assert!(expand_specifiers("%q", &context).is_err());
+    assert!(expand_specifiers("50%", &context).is_err());
+}